@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ListWebhookDeliveriesRequest = { 
+/**
+ * Only list deliveries of the given webhook.
+ */
+webhook: string | null, 
+/**
+ * Only list deliveries with the given status, i.e. "pending", "delivered" or "failed".
+ */
+status: string | null, limit: number | null, 
+/**
+ * Id of the last delivery of the previous page.
+ */
+cursor: bigint | null, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type OrphanedFileJson = { path: string, size: number, 
+/**
+ * Unix timestamp in seconds.
+ */
+last_modified: bigint, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type OrphanedFilesRequest = { 
+/**
+ * Minimum age of unreferenced files in seconds. Defaults to 24h and must be at least 1h.
+ */
+grace_period_sec: bigint | null, };
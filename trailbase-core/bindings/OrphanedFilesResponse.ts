@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { OrphanedFileJson } from "./OrphanedFileJson";
+
+export type OrphanedFilesResponse = { files: Array<OrphanedFileJson>, 
+/**
+ * Total size in bytes.
+ */
+total_size: number, 
+/**
+ * Whether the files have been deleted or this is a dry-run.
+ */
+deleted: boolean, };
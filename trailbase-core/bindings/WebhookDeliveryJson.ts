@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type WebhookDeliveryJson = { id: bigint, webhook: string, payload: Object, status: string, attempts: bigint, last_status_code: bigint | null, last_error: string | null, next_attempt: bigint, created: bigint, updated: bigint, };
@@ -624,6 +624,102 @@ export async function dispatch(
 
 globalThis.__dispatch = dispatch;
 
+export type RecordHookEvent = "create" | "update" | "delete";
+export type RecordHookPhase = "before" | "after";
+export type RecordType = { [key: string]: unknown };
+export type RecordHookContext = {
+  /// Name of the record API.
+  api: string;
+  event: RecordHookEvent;
+  user?: UserType;
+  /// Id of the affected record. Unset for creations prior to the insert.
+  id?: string | number;
+  /// Incoming record values of creations and updates.
+  params?: RecordType;
+  /// The committed or, for deletions, deleted record. Only set for after-hooks.
+  record?: RecordType;
+};
+/// Before-hooks may reject changes by throwing an `HttpError` and alter the
+/// incoming values by either mutating `params` or returning replacements.
+export type BeforeRecordHook = (
+  ctx: RecordHookContext,
+) => MaybeResponse<RecordType>;
+export type AfterRecordHook = (ctx: RecordHookContext) => Promise<void> | void;
+export type RecordHooks = {
+  before?: BeforeRecordHook;
+  after?: AfterRecordHook;
+};
+type RecordHookResult = {
+  params?: RecordType;
+  error?: { status: number; message?: string };
+};
+
+const recordHooks = new Map<string, BeforeRecordHook | AfterRecordHook>();
+
+function addRecordHooks(
+  event: RecordHookEvent,
+  apiName: string,
+  hooks: RecordHooks,
+) {
+  const id = isolateId();
+  for (const phase of ["before", "after"] as RecordHookPhase[]) {
+    const hook = hooks[phase];
+    if (!hook) {
+      continue;
+    }
+
+    if (id === 0) {
+      rustyscript.functions.install_record_hook(phase, event, apiName);
+      console.debug("JS: Added record hook:", phase, event, apiName);
+    }
+    recordHooks.set(`${phase}:${event}:${apiName}`, hook);
+  }
+}
+
+/// Registers hooks for creations of records through the given record API.
+export function onRecordCreate(apiName: string, hooks: RecordHooks) {
+  addRecordHooks("create", apiName, hooks);
+}
+
+/// Registers hooks for updates of records through the given record API.
+export function onRecordUpdate(apiName: string, hooks: RecordHooks) {
+  addRecordHooks("update", apiName, hooks);
+}
+
+/// Registers hooks for deletions of records through the given record API.
+export function onRecordDelete(apiName: string, hooks: RecordHooks) {
+  addRecordHooks("delete", apiName, hooks);
+}
+
+export async function dispatchRecordHook(
+  phase: RecordHookPhase,
+  ctx: RecordHookContext,
+): Promise<RecordHookResult> {
+  const key = `${phase}:${ctx.event}:${ctx.api}`;
+  const hook = recordHooks.get(key);
+  if (!hook) {
+    throw Error(`Missing record hook: ${key}`);
+  }
+
+  try {
+    // After-hooks don't return anything, thus the params are simply echoed.
+    const params = (await hook(ctx)) as RecordType | undefined;
+    return { params: params ?? ctx.params };
+  } catch (err) {
+    if (err instanceof HttpError) {
+      return {
+        error: {
+          status: err.statusCode,
+          message: err.message !== "" ? err.message : undefined,
+        },
+      };
+    }
+    throw err;
+  }
+}
+
+globalThis.__dispatchRecordHook = dispatchRecordHook;
+
 export function addPeriodicCallback(
   milliseconds: number,
   cb: (cancel: () => void) => void,
@@ -0,0 +1,20 @@
+--
+-- Record history table.
+--
+-- Audit trail of changes made through record APIs with history enabled.
+CREATE TABLE _record_history (
+  id                           INTEGER PRIMARY KEY NOT NULL,
+  table_name                   TEXT NOT NULL,
+  record                       ANY NOT NULL,
+  -- One of 'insert', 'update' or 'delete'.
+  action                       TEXT NOT NULL,
+  -- Acting user, if any. Deliberately not a foreign key to retain the history
+  -- of deleted users.
+  user                         BLOB,
+  -- JSON encoded record before and after the change, respectively.
+  old_value                    TEXT CHECK(is_json(old_value)),
+  new_value                    TEXT CHECK(is_json(new_value)),
+  created                      INTEGER DEFAULT (UNIXEPOCH()) NOT NULL
+) STRICT;
+
+CREATE INDEX __record_history__record_index ON _record_history (table_name, record);
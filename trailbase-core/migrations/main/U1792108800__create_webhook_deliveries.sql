@@ -0,0 +1,23 @@
+--
+-- Webhook delivery queue.
+--
+-- Deliveries are enqueued for each record change matching a webhook and are
+-- retried with exponential backoff until they succeed or run out of attempts.
+CREATE TABLE _webhook_delivery (
+  id                           INTEGER PRIMARY KEY NOT NULL,
+  -- Name of the webhook in the config.
+  webhook                      TEXT NOT NULL,
+  -- JSON encoded event, i.e. the request body.
+  payload                      TEXT NOT NULL CHECK(is_json(payload)),
+  -- One of 'pending', 'delivered' or 'failed'.
+  status                       TEXT DEFAULT 'pending' NOT NULL,
+  attempts                     INTEGER DEFAULT 0 NOT NULL,
+  -- Outcome of the most recent attempt.
+  last_status_code             INTEGER,
+  last_error                   TEXT,
+  next_attempt                 INTEGER DEFAULT (UNIXEPOCH()) NOT NULL,
+  created                      INTEGER DEFAULT (UNIXEPOCH()) NOT NULL,
+  updated                      INTEGER DEFAULT (UNIXEPOCH()) NOT NULL
+) STRICT;
+
+CREATE INDEX __webhook_delivery__pending_index ON _webhook_delivery (status, next_attempt);
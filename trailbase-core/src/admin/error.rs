@@ -14,6 +14,8 @@ pub enum AdminError {
   JsonSerialization(#[from] serde_json::Error),
   #[error("Base64 decoding error: {0}")]
   Base64Decode(#[from] base64::DecodeError),
+  #[error("Bad request: {0}")]
+  BadRequest(&'static str),
   #[error("Already exists: {0}")]
   AlreadyExists(&'static str),
   #[error("precondition failed: {0}")]
@@ -57,6 +59,7 @@ impl IntoResponse for AdminError {
       // We should be able to use a generic for that.
       Self::Auth(err) => return err.into_response(),
       Self::Deserialization(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+      Self::BadRequest(_) => (StatusCode::BAD_REQUEST, self.to_string()),
       Self::Precondition(_) => (StatusCode::BAD_REQUEST, self.to_string()),
       Self::AlreadyExists(_) => (StatusCode::CONFLICT, self.to_string()),
       // NOTE: We can almost always leak the internal error (except for permission errors) since
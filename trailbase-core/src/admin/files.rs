@@ -0,0 +1,189 @@
+use axum::{
+  extract::{Query, State},
+  Json,
+};
+use object_store::ObjectMeta;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::constants::{ORPHANED_FILES_GRACE_PERIOD, ORPHANED_FILES_MIN_GRACE_PERIOD};
+use crate::records::files::{delete_orphaned_files, find_orphaned_files};
+
+#[derive(Debug, Default, Deserialize, TS)]
+#[ts(export)]
+pub struct OrphanedFilesRequest {
+  /// Minimum age of unreferenced files in seconds. Defaults to 24h and must be at least 1h.
+  grace_period_sec: Option<i64>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct OrphanedFileJson {
+  pub path: String,
+  pub size: usize,
+  /// Unix timestamp in seconds.
+  pub last_modified: i64,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct OrphanedFilesResponse {
+  files: Vec<OrphanedFileJson>,
+  /// Total size in bytes.
+  total_size: usize,
+  /// Whether the files have been deleted or this is a dry-run.
+  deleted: bool,
+}
+
+impl OrphanedFilesResponse {
+  fn new(orphans: Vec<ObjectMeta>, deleted: bool) -> Self {
+    return Self {
+      total_size: orphans.iter().map(|o| o.size).sum(),
+      files: orphans
+        .into_iter()
+        .map(|o| OrphanedFileJson {
+          path: o.location.to_string(),
+          size: o.size,
+          last_modified: o.last_modified.timestamp(),
+        })
+        .collect(),
+      deleted,
+    };
+  }
+}
+
+fn grace_period(request: &OrphanedFilesRequest) -> Result<chrono::Duration, Error> {
+  let Some(sec) = request.grace_period_sec else {
+    return Ok(ORPHANED_FILES_GRACE_PERIOD);
+  };
+
+  let grace_period = chrono::Duration::seconds(sec);
+  if grace_period < ORPHANED_FILES_MIN_GRACE_PERIOD {
+    return Err(Error::Precondition(format!(
+      "Grace period must be at least {}s: {sec}",
+      ORPHANED_FILES_MIN_GRACE_PERIOD.num_seconds()
+    )));
+  }
+  return Ok(grace_period);
+}
+
+/// Dry-run of the periodic garbage collection, i.e. lists uploaded files that aren't referenced by
+/// any file column, without deleting them.
+pub async fn list_orphaned_files_handler(
+  State(state): State<AppState>,
+  Query(request): Query<OrphanedFilesRequest>,
+) -> Result<Json<OrphanedFilesResponse>, Error> {
+  let orphans = find_orphaned_files(&state, grace_period(&request)?).await?;
+  return Ok(Json(OrphanedFilesResponse::new(orphans, false)));
+}
+
+/// Deletes uploaded files that aren't referenced by any file column ahead of the periodic garbage
+/// collection.
+pub async fn delete_orphaned_files_handler(
+  State(state): State<AppState>,
+  Query(request): Query<OrphanedFilesRequest>,
+) -> Result<Json<OrphanedFilesResponse>, Error> {
+  let orphans = delete_orphaned_files(&state, grace_period(&request)?).await?;
+  return Ok(Json(OrphanedFilesResponse::new(orphans, true)));
+}
+
+#[cfg(test)]
+mod tests {
+  use object_store::{path::Path, PutPayload};
+
+  use super::*;
+  use crate::app_state::test_state;
+
+  #[tokio::test]
+  async fn test_orphaned_files() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    state
+      .conn()
+      .execute_batch(
+        r#"
+          CREATE TABLE attachment (
+            id           INTEGER PRIMARY KEY,
+            files        TEXT CHECK(jsonschema('std.FileUploads', files))
+          ) STRICT;
+        "#,
+      )
+      .await?;
+    state.table_metadata().invalidate_all().await?;
+
+    let referenced = uuid::Uuid::new_v4().to_string();
+    let orphaned = uuid::Uuid::new_v4().to_string();
+    state
+      .conn()
+      .execute(
+        "INSERT INTO attachment (files) VALUES ($1)",
+        [format!(r#"[{{"id": "{referenced}"}}]"#)],
+      )
+      .await?;
+
+    let store = state.objectstore();
+    for path in [
+      referenced.clone(),
+      orphaned.clone(),
+      format!("{orphaned}.variants/100xauto-contain.png"),
+      "unmanaged".to_string(),
+    ] {
+      store
+        .put(&Path::from(path), PutPayload::from(vec![0u8; 4]))
+        .await?;
+    }
+
+    let request = |grace_period_sec: Option<i64>| Query(OrphanedFilesRequest { grace_period_sec });
+    let paths = |response: &OrphanedFilesResponse| {
+      let mut paths: Vec<String> = response.files.iter().map(|f| f.path.clone()).collect();
+      paths.sort();
+      return paths;
+    };
+
+    // Fresh uploads are protected by the grace period, which cannot be lowered arbitrarily.
+    let Json(response) = list_orphaned_files_handler(State(state.clone()), request(None)).await?;
+    assert!(response.files.is_empty());
+    let min_grace_period_sec = ORPHANED_FILES_MIN_GRACE_PERIOD.num_seconds();
+    let Json(response) =
+      list_orphaned_files_handler(State(state.clone()), request(Some(min_grace_period_sec)))
+        .await?;
+    assert!(response.files.is_empty());
+    for sec in [-1, 0, min_grace_period_sec - 1] {
+      assert!(matches!(
+        list_orphaned_files_handler(State(state.clone()), request(Some(sec))).await,
+        Err(Error::Precondition(_))
+      ));
+      assert!(matches!(
+        delete_orphaned_files_handler(State(state.clone()), request(Some(sec))).await,
+        Err(Error::Precondition(_))
+      ));
+    }
+
+    let response = OrphanedFilesResponse::new(
+      find_orphaned_files(&state, chrono::Duration::zero()).await?,
+      false,
+    );
+    assert_eq!(
+      paths(&response),
+      [
+        orphaned.clone(),
+        format!("{orphaned}.variants/100xauto-contain.png")
+      ]
+    );
+    assert_eq!(response.total_size, 8);
+    assert!(!response.deleted);
+
+    // Dry-runs retain files.
+    store.head(&Path::from(orphaned.clone())).await?;
+
+    let deleted = delete_orphaned_files(&state, chrono::Duration::zero()).await?;
+    assert_eq!(deleted.len(), 2);
+
+    assert!(store.head(&Path::from(orphaned)).await.is_err());
+    store.head(&Path::from(referenced)).await?;
+    store.head(&Path::from("unmanaged")).await?;
+
+    return Ok(());
+  }
+}
@@ -19,7 +19,7 @@ use crate::listing::{
   build_filter_where_clause, limit_or_default, parse_query, Order, WhereClause,
 };
 use crate::table_metadata::{lookup_and_parse_table_schema, TableMetadata};
-use crate::util::id_to_b64;
+use crate::util::{b64_to_id, id_to_b64};
 
 #[derive(Debug, Serialize, TS)]
 pub struct LogJson {
@@ -104,22 +104,31 @@ pub async fn list_logs_handler(
   // FIXME: we should probably return an error if the query parsing fails rather than quietly
   // falling back to defaults.
   let url_query = parse_query(raw_url_query);
-  let (filter_params, cursor, limit, order) = match url_query {
-    Some(q) => (Some(q.params), q.cursor, q.limit, q.order),
-    None => (None, None, None, None),
+  let (filter_params, filter, cursor, limit, order) = match url_query {
+    Some(q) => (
+      Some(q.params),
+      q.filter,
+      q.cursor
+        .map(|c| b64_to_id(&c).map_err(|_err| Error::BadRequest("Invalid cursor")))
+        .transpose()?,
+      q.limit,
+      q.order,
+    ),
+    None => (None, None, None, None, None),
   };
 
   // NOTE: We cannot use state.table_metadata() here, since we're working on the logs database.
   // We could cache, however this is just the admin logs handler.
   let table = lookup_and_parse_table_schema(conn, LOGS_TABLE_NAME).await?;
   let table_metadata = TableMetadata::new(table.clone(), &[table]);
-  let filter_where_clause = build_filter_where_clause(&table_metadata, filter_params)?;
+  let filter_where_clause =
+    build_filter_where_clause(&table_metadata, filter_params, filter.as_deref(), None)?;
 
   let total_row_count = {
     let row = query_one_row(
       conn,
       &format!(
-        "SELECT COUNT(*) FROM {LOGS_TABLE_NAME} WHERE {clause}",
+        "SELECT COUNT(*) FROM {LOGS_TABLE_NAME} AS _ROW_ WHERE {clause}",
         clause = filter_where_clause.clause
       ),
       Params::Named(filter_where_clause.params.clone()),
@@ -196,14 +205,14 @@ async fn fetch_logs(
 
   if let Some(cursor) = cursor {
     params.push((":cursor".to_string(), libsql::Value::Blob(cursor.to_vec())));
-    where_clause = format!("{where_clause} AND log.id < :cursor",);
+    where_clause = format!("{where_clause} AND _ROW_.id < :cursor",);
   }
 
   let order_clause = order
     .iter()
     .map(|(col, ord)| {
       format!(
-        "log.{col} {}",
+        "_ROW_.{col} {}",
         match ord {
           Order::Descending => "DESC",
           Order::Ascending => "ASC",
@@ -215,9 +224,9 @@ async fn fetch_logs(
 
   let sql_query = format!(
     r#"
-      SELECT log.*, geoip_country(log.client_ip) AS client_cc
+      SELECT _ROW_.*, geoip_country(_ROW_.client_ip) AS client_cc
       FROM
-        (SELECT * FROM {LOGS_TABLE_NAME}) AS log
+        (SELECT * FROM {LOGS_TABLE_NAME}) AS _ROW_
       WHERE
         {where_clause}
       ORDER BY
@@ -282,7 +291,7 @@ async fn fetch_aggregate_stats(
       CAST(ROUND((created - :to_seconds) / :interval_seconds) AS INTEGER) * :interval_seconds + :to_seconds AS interval_end_ts,
       COUNT(*) as count
     FROM
-      (SELECT * FROM {LOGS_TABLE_NAME} WHERE created > :from_seconds AND created < :to_seconds AND {filter_clause} ORDER BY id DESC)
+      (SELECT * FROM {LOGS_TABLE_NAME} AS _ROW_ WHERE created > :from_seconds AND created < :to_seconds AND {filter_clause} ORDER BY id DESC)
     GROUP BY
       interval_end_ts
     ORDER BY
@@ -370,6 +379,14 @@ mod tests {
   use super::*;
   use crate::migrations::apply_logs_migrations;
 
+  #[tokio::test]
+  async fn test_invalid_cursor() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+    let result =
+      list_logs_handler(State(state), RawQuery(Some("cursor=invalid".to_string()))).await;
+    assert!(matches!(result, Err(Error::BadRequest(_))));
+  }
+
   #[tokio::test]
   async fn test_aggregate_rate_computation() {
     let conn = trailbase_sqlite::connect_sqlite(None, None).await.unwrap();
@@ -1,5 +1,6 @@
 mod config;
 mod error;
+mod files;
 mod jwt;
 mod list_logs;
 mod oauth_providers;
@@ -9,6 +10,7 @@ pub(crate) mod rows;
 mod schema;
 mod table;
 pub(crate) mod user;
+mod webhooks;
 
 pub use error::AdminError;
 
@@ -53,6 +55,17 @@ pub fn router() -> Router<AppState> {
     .route("/schema", post(schema::update_schema_handler))
     // Logs
     .route("/logs", get(list_logs::list_logs_handler))
+    // Webhooks
+    .route(
+      "/webhook/deliveries",
+      get(webhooks::list_webhook_deliveries_handler),
+    )
+    // Files
+    .route("/files/orphaned", get(files::list_orphaned_files_handler))
+    .route(
+      "/files/orphaned",
+      delete(files::delete_orphaned_files_handler),
+    )
     // Query execution handler for the UI editor
     .route("/query", post(query::query_handler))
     // Parse handler for UI validation.
@@ -9,8 +9,10 @@ use crate::admin::AdminError as Error;
 use crate::api::query_one_row;
 use crate::app_state::AppState;
 use crate::listing::{
-  build_filter_where_clause, limit_or_default, parse_query, Order, WhereClause,
+  build_cursor_where_clause, build_filter_where_clause, build_keyset, build_order_clause,
+  limit_or_default, parse_query, Cursor, Order, WhereClause,
 };
+use crate::records::json_to_sql::simple_json_value_to_param;
 use crate::records::sql_to_json::rows_to_json_arrays;
 use crate::schema::Column;
 use crate::table_metadata::TableOrViewMetadata;
@@ -33,9 +35,16 @@ pub async fn list_rows_handler(
   Path(table_name): Path<String>,
   RawQuery(raw_url_query): RawQuery,
 ) -> Result<Json<ListRowsResponse>, Error> {
-  let (filter_params, cursor, offset, limit, order) = match parse_query(raw_url_query) {
-    Some(q) => (Some(q.params), q.cursor, q.offset, q.limit, q.order),
-    None => (None, None, None, None, None),
+  let (filter_params, filter, cursor, offset, limit, order) = match parse_query(raw_url_query) {
+    Some(q) => (
+      Some(q.params),
+      q.filter,
+      q.cursor,
+      q.offset,
+      q.limit,
+      q.order,
+    ),
+    None => (None, None, None, None, None, None),
   };
 
   let (virtual_table, table_or_view_metadata): (bool, Arc<dyn TableOrViewMetadata + Sync + Send>) = {
@@ -52,11 +61,16 @@ pub async fn list_rows_handler(
 
   // Where clause contains column filters and cursor depending on what's present in the url query
   // string.
-  let filter_where_clause = build_filter_where_clause(&*table_or_view_metadata, filter_params)?;
+  let filter_where_clause = build_filter_where_clause(
+    &*table_or_view_metadata,
+    filter_params,
+    filter.as_deref(),
+    None,
+  )?;
 
   let total_row_count = {
     let where_clause = &filter_where_clause.clause;
-    let count_query = format!("SELECT COUNT(*) FROM '{table_name}' WHERE {where_clause}");
+    let count_query = format!("SELECT COUNT(*) FROM '{table_name}' AS _ROW_ WHERE {where_clause}");
     let row = query_one_row(
       state.conn(),
       &count_query,
@@ -66,14 +80,26 @@ pub async fn list_rows_handler(
     row.get::<i64>(0)?
   };
 
-  let cursor_column = table_or_view_metadata.record_pk_column();
+  let pk_column = table_or_view_metadata
+    .record_pk_column()
+    .map(|(_idx, col)| col.name.clone());
+  let keyset = build_keyset(
+    &*table_or_view_metadata,
+    order.unwrap_or_else(|| match pk_column {
+      Some(ref pk_column) => vec![(pk_column.clone(), Order::Descending)],
+      None => vec![],
+    }),
+    pk_column.as_deref(),
+    &[],
+  )?;
+  let cursor = cursor.map(|c| Cursor::parse(&c)).transpose()?;
+
   let (rows, columns) = fetch_rows(
     state.conn(),
     &table_name,
     filter_where_clause,
-    order,
+    &keyset,
     Pagination {
-      cursor_column: cursor_column.map(|(_idx, c)| c),
       cursor,
       offset,
       limit: limit_or_default(limit),
@@ -81,17 +107,21 @@ pub async fn list_rows_handler(
   )
   .await?;
 
-  let next_cursor = cursor_column.and_then(|(col_idx, _col)| {
-    let row = rows.last()?;
-    assert!(row.len() > col_idx);
-    match &row[col_idx] {
-      serde_json::Value::String(id) => {
-        // Should be a base64 encoded [u8; 16] id.
-        Some(id.clone())
-      }
-      _ => None,
-    }
-  });
+  // NOTE: Only a unique key set, i.e. one including the primary key, yields stable cursors.
+  let next_cursor = match pk_column {
+    Some(_) => rows.last().and_then(|row| {
+      let values = keyset
+        .iter()
+        .map(|(col_name, _)| {
+          let index = columns.as_ref()?.iter().position(|c| c.name == *col_name)?;
+          let (col, _col_meta) = table_or_view_metadata.column_by_name(col_name)?;
+          return simple_json_value_to_param(col.data_type, row.get(index)?.clone()).ok();
+        })
+        .collect::<Option<Vec<_>>>()?;
+      return Some(Cursor(values).encode());
+    }),
+    None => None,
+  };
 
   return Ok(Json(ListRowsResponse {
     total_row_count,
@@ -109,9 +139,8 @@ pub async fn list_rows_handler(
   }));
 }
 
-struct Pagination<'a> {
-  cursor_column: Option<&'a Column>,
-  cursor: Option<[u8; 16]>,
+struct Pagination {
+  cursor: Option<Cursor>,
   offset: Option<usize>,
   limit: usize,
 }
@@ -120,8 +149,8 @@ async fn fetch_rows(
   conn: &Connection,
   table_or_view_name: &str,
   filter_where_clause: WhereClause,
-  order: Option<Vec<(String, Order)>>,
-  pagination: Pagination<'_>,
+  keyset: &[(String, Order)],
+  pagination: Pagination,
 ) -> Result<(Vec<Vec<serde_json::Value>>, Option<Vec<Column>>), Error> {
   let WhereClause {
     mut clause,
@@ -137,35 +166,21 @@ async fn fetch_rows(
   ));
 
   if let Some(cursor) = pagination.cursor {
-    params.push((":cursor".to_string(), libsql::Value::Blob(cursor.to_vec())));
-    clause = format!("{clause} AND _row_.id < :cursor",);
+    let WhereClause {
+      clause: cursor_clause,
+      params: mut cursor_params,
+    } = build_cursor_where_clause(keyset, &cursor)?;
+    params.append(&mut cursor_params);
+    clause = format!("{clause} AND {cursor_clause}");
   }
 
-  let order_clause = match order {
-    Some(order) => order
-      .iter()
-      .map(|(col, ord)| {
-        format!(
-          "_row_.{col} {}",
-          match ord {
-            Order::Descending => "DESC",
-            Order::Ascending => "ASC",
-          }
-        )
-      })
-      .collect::<Vec<_>>()
-      .join(", "),
-    None => match pagination.cursor_column {
-      Some(col) => format!("{col_name} DESC", col_name = col.name),
-      None => "NULL".to_string(),
-    },
-  };
+  let order_clause = build_order_clause(keyset);
 
   let query = format!(
     r#"
-      SELECT _row_.*
+      SELECT _ROW_.*
       FROM
-        (SELECT * FROM {table_or_view_name}) as _row_
+        (SELECT * FROM {table_or_view_name}) as _ROW_
       WHERE
         {clause}
       ORDER BY
@@ -1,5 +1,6 @@
 use axum::{
   extract::{Path, Query, State},
+  http::HeaderMap,
   response::Response,
 };
 use serde::Deserialize;
@@ -7,7 +8,7 @@ use ts_rs::TS;
 
 use crate::admin::AdminError as Error;
 use crate::app_state::AppState;
-use crate::records::files::read_file_into_response;
+use crate::records::files::{read_file_into_response, FileDisposition};
 use crate::records::json_to_sql::simple_json_value_to_param;
 use crate::records::json_to_sql::{GetFileQueryBuilder, GetFilesQueryBuilder};
 
@@ -29,6 +30,7 @@ pub async fn read_files_handler(
   State(state): State<AppState>,
   Path(table_name): Path<String>,
   Query(request): Query<ReadFilesRequest>,
+  headers: HeaderMap,
 ) -> Result<Response, Error> {
   let Some(table_metadata) = state.table_metadata().get(&table_name) else {
     return Err(Error::Precondition(format!("Table {table_name} not found")));
@@ -63,7 +65,15 @@ pub async fn read_files_handler(
       return Err(Error::Precondition(format!("Out of bounds: {file_index}")));
     }
 
-    Ok(read_file_into_response(&state, file_uploads.0.remove(file_index)).await?)
+    Ok(
+      read_file_into_response(
+        &state,
+        file_uploads.0.remove(file_index),
+        &headers,
+        FileDisposition::Attachment,
+      )
+      .await?,
+    )
   } else {
     let file_upload = GetFileQueryBuilder::run(
       &state,
@@ -74,6 +84,6 @@ pub async fn read_files_handler(
     )
     .await?;
 
-    Ok(read_file_into_response(&state, file_upload).await?)
+    Ok(read_file_into_response(&state, file_upload, &headers, FileDisposition::Attachment).await?)
   };
 }
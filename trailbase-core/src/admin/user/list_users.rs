@@ -17,7 +17,7 @@ use crate::constants::{USER_TABLE, USER_TABLE_ID_COLUMN};
 use crate::listing::{
   build_filter_where_clause, limit_or_default, parse_query, Order, WhereClause,
 };
-use crate::util::id_to_b64;
+use crate::util::{b64_to_id, id_to_b64};
 
 #[derive(Debug, Serialize, TS)]
 pub struct UserJson {
@@ -64,9 +64,17 @@ pub async fn list_users_handler(
 
   let url_query = parse_query(raw_url_query);
   info!("query: {url_query:?}");
-  let (filter_params, cursor, limit, order) = match url_query {
-    Some(q) => (Some(q.params), q.cursor, q.limit, q.order),
-    None => (None, None, None, None),
+  let (filter_params, filter, cursor, limit, order) = match url_query {
+    Some(q) => (
+      Some(q.params),
+      q.filter,
+      q.cursor
+        .map(|c| b64_to_id(&c).map_err(|_err| Error::BadRequest("Invalid cursor")))
+        .transpose()?,
+      q.limit,
+      q.order,
+    ),
+    None => (None, None, None, None, None),
   };
 
   let Some(table_metadata) = state.table_metadata().get(USER_TABLE) else {
@@ -74,13 +82,14 @@ pub async fn list_users_handler(
   };
   // Where clause contains column filters and cursor depending on what's present in the url query
   // string.
-  let filter_where_clause = build_filter_where_clause(&*table_metadata, filter_params)?;
+  let filter_where_clause =
+    build_filter_where_clause(&*table_metadata, filter_params, filter.as_deref(), None)?;
 
   let total_row_count = {
     let where_clause = &filter_where_clause.clause;
     let row = query_one_row(
       conn,
-      &format!("SELECT COUNT(*) FROM {USER_TABLE} WHERE {where_clause}"),
+      &format!("SELECT COUNT(*) FROM {USER_TABLE} AS _ROW_ WHERE {where_clause}"),
       Params::Named(filter_where_clause.params.clone()),
     )
     .await?;
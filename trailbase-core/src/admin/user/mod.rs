@@ -65,4 +65,18 @@ mod tests {
 
     assert!(user_by_email(&state, email).await.is_err());
   }
+
+  #[tokio::test]
+  async fn test_list_users_invalid_cursor() {
+    let state = test_state(None).await.unwrap();
+    let result = super::list_users_handler(
+      State(state),
+      axum::extract::RawQuery(Some("cursor=invalid".to_string())),
+    )
+    .await;
+    assert!(matches!(
+      result,
+      Err(crate::admin::AdminError::BadRequest(_))
+    ));
+  }
 }
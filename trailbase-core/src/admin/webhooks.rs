@@ -0,0 +1,181 @@
+use axum::{
+  extract::{Query, State},
+  Json,
+};
+use libsql::params::Params;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::admin::AdminError as Error;
+use crate::app_state::AppState;
+use crate::constants::WEBHOOK_DELIVERY_TABLE;
+use crate::listing::limit_or_default;
+
+#[derive(Debug, Default, Deserialize, TS)]
+#[ts(export)]
+pub struct ListWebhookDeliveriesRequest {
+  /// Only list deliveries of the given webhook.
+  webhook: Option<String>,
+  /// Only list deliveries with the given status, i.e. "pending", "delivered" or "failed".
+  status: Option<String>,
+  limit: Option<usize>,
+  /// Id of the last delivery of the previous page.
+  cursor: Option<i64>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct WebhookDeliveryJson {
+  pub id: i64,
+  pub webhook: String,
+  #[ts(type = "Object")]
+  pub payload: serde_json::Value,
+  pub status: String,
+  pub attempts: i64,
+  pub last_status_code: Option<i64>,
+  pub last_error: Option<String>,
+  pub next_attempt: i64,
+  pub created: i64,
+  pub updated: i64,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ListWebhookDeliveriesResponse {
+  cursor: Option<i64>,
+  deliveries: Vec<WebhookDeliveryJson>,
+}
+
+/// Lists webhook deliveries, most recent first, including their attempts and last errors.
+pub async fn list_webhook_deliveries_handler(
+  State(state): State<AppState>,
+  Query(request): Query<ListWebhookDeliveriesRequest>,
+) -> Result<Json<ListWebhookDeliveriesResponse>, Error> {
+  let mut clauses = vec!["TRUE".to_string()];
+  let mut params: Vec<(String, libsql::Value)> = vec![];
+
+  if let Some(webhook) = request.webhook {
+    clauses.push("webhook = :webhook".to_string());
+    params.push((":webhook".to_string(), libsql::Value::Text(webhook)));
+  }
+  if let Some(status) = request.status {
+    clauses.push("status = :status".to_string());
+    params.push((":status".to_string(), libsql::Value::Text(status)));
+  }
+  if let Some(cursor) = request.cursor {
+    clauses.push("id < :cursor".to_string());
+    params.push((":cursor".to_string(), libsql::Value::Integer(cursor)));
+  }
+  params.push((
+    ":limit".to_string(),
+    libsql::Value::Integer(limit_or_default(request.limit) as i64),
+  ));
+
+  let mut rows = state
+    .conn()
+    .query(
+      &format!(
+        r#"
+          SELECT id, webhook, payload, status, attempts, last_status_code, last_error,
+                 next_attempt, created, updated
+          FROM {WEBHOOK_DELIVERY_TABLE}
+          WHERE {clause}
+          ORDER BY id DESC LIMIT :limit
+        "#,
+        clause = clauses.join(" AND ")
+      ),
+      Params::Named(params),
+    )
+    .await?;
+
+  let mut deliveries: Vec<WebhookDeliveryJson> = vec![];
+  while let Some(row) = rows.next().await? {
+    deliveries.push(WebhookDeliveryJson {
+      id: row.get(0)?,
+      webhook: row.get(1)?,
+      payload: serde_json::from_str(&row.get::<String>(2)?)?,
+      status: row.get(3)?,
+      attempts: row.get(4)?,
+      last_status_code: row.get(5)?,
+      last_error: row.get(6)?,
+      next_attempt: row.get(7)?,
+      created: row.get(8)?,
+      updated: row.get(9)?,
+    });
+  }
+
+  return Ok(Json(ListWebhookDeliveriesResponse {
+    cursor: deliveries.last().map(|d| d.id),
+    deliveries,
+  }));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app_state::test_state;
+
+  #[tokio::test]
+  async fn test_list_webhook_deliveries() {
+    let state = test_state(None).await.unwrap();
+    state
+      .conn()
+      .execute_batch(&format!(
+        r#"
+          INSERT INTO {WEBHOOK_DELIVERY_TABLE} (webhook, payload, status) VALUES ('a', '{{"n":1}}', 'delivered');
+          INSERT INTO {WEBHOOK_DELIVERY_TABLE} (webhook, payload, status) VALUES ('a', '{{"n":2}}', 'failed');
+          INSERT INTO {WEBHOOK_DELIVERY_TABLE} (webhook, payload) VALUES ('b', '{{"n":3}}');
+        "#,
+      ))
+      .await
+      .unwrap();
+
+    let list = |request: ListWebhookDeliveriesRequest| {
+      let state = state.clone();
+      async move {
+        let Json(response) = list_webhook_deliveries_handler(State(state), Query(request))
+          .await
+          .unwrap();
+        return response;
+      }
+    };
+
+    // Most recent first.
+    let all = list(ListWebhookDeliveriesRequest::default()).await;
+    assert_eq!(
+      all
+        .deliveries
+        .iter()
+        .map(|d| d.webhook.as_str())
+        .collect::<Vec<_>>(),
+      vec!["b", "a", "a"]
+    );
+    assert_eq!(all.deliveries[0].status, "pending");
+    assert_eq!(all.deliveries[0].payload, serde_json::json!({"n": 3}));
+
+    let failed = list(ListWebhookDeliveriesRequest {
+      webhook: Some("a".to_string()),
+      status: Some("failed".to_string()),
+      ..Default::default()
+    })
+    .await;
+    assert_eq!(failed.deliveries.len(), 1);
+    assert_eq!(failed.deliveries[0].payload, serde_json::json!({"n": 2}));
+
+    // Paginate using the cursor.
+    let first_page = list(ListWebhookDeliveriesRequest {
+      limit: Some(2),
+      ..Default::default()
+    })
+    .await;
+    assert_eq!(first_page.deliveries.len(), 2);
+    let second_page = list(ListWebhookDeliveriesRequest {
+      limit: Some(2),
+      cursor: first_page.cursor,
+      ..Default::default()
+    })
+    .await;
+    assert_eq!(second_page.deliveries.len(), 1);
+    assert_eq!(second_page.deliveries[0].id, all.deliveries[2].id);
+  }
+}
@@ -13,6 +13,9 @@ use crate::data_dir::DataDir;
 use crate::email::Mailer;
 use crate::js::RuntimeHandle;
 use crate::query::QueryApi;
+use crate::records::geo::sync_geo_indexes;
+use crate::records::search::sync_search_tables;
+use crate::records::subscribe::SubscriptionManager;
 use crate::records::RecordApi;
 use crate::table_metadata::TableMetadataCache;
 use crate::value_notifier::{Computed, ValueNotifier};
@@ -32,11 +35,16 @@ struct InternalState {
 
   logs_conn: Connection,
   conn: Connection,
+  /// Separate connection to the main DB for write transactions, which would otherwise capture
+  /// concurrent queries on the shared `conn`. Guarded by `write_lock` to serialize transactions.
+  write_conn: Connection,
+  write_lock: tokio::sync::Mutex<()>,
 
   jwt: JwtHelper,
 
   table_metadata: TableMetadataCache,
   object_store: Box<dyn ObjectStore + Send + Sync>,
+  subscription_manager: SubscriptionManager,
 
   runtime: RuntimeHandle,
 
@@ -52,6 +60,8 @@ pub(crate) struct AppStateArgs {
   pub table_metadata: TableMetadataCache,
   pub config: Config,
   pub conn: Connection,
+  /// Second connection to the main DB, see [AppState::write_transaction].
+  pub write_conn: Connection,
   pub logs_conn: Connection,
   pub jwt: JwtHelper,
   pub object_store: Box<dyn ObjectStore + Send + Sync>,
@@ -123,10 +133,13 @@ impl AppState {
         }),
         config,
         conn: args.conn.clone(),
+        write_conn: args.write_conn,
+        write_lock: tokio::sync::Mutex::new(()),
         logs_conn: args.logs_conn,
         jwt: args.jwt,
         table_metadata: args.table_metadata,
         object_store: args.object_store,
+        subscription_manager: SubscriptionManager::default(),
         runtime,
         #[cfg(test)]
         cleanup: vec![],
@@ -160,6 +173,19 @@ impl AppState {
     return &self.state.logs_conn;
   }
 
+  /// Begins a transaction on the dedicated write connection, i.e. queries on [Self::conn] issued
+  /// concurrently aren't affected by it. Transactions are serialized, thus they should only contain
+  /// DB operations, e.g. no hooks.
+  pub(crate) async fn write_transaction(&self) -> Result<WriteTransaction<'_>, libsql::Error> {
+    let guard = self.state.write_lock.lock().await;
+    let tx = self
+      .state
+      .write_conn
+      .transaction_with_behavior(libsql::TransactionBehavior::Immediate)
+      .await?;
+    return Ok(WriteTransaction { tx, _guard: guard });
+  }
+
   pub(crate) fn table_metadata(&self) -> &TableMetadataCache {
     return &self.state.table_metadata;
   }
@@ -172,6 +198,10 @@ impl AppState {
     return &*self.state.object_store;
   }
 
+  pub(crate) fn subscription_manager(&self) -> &SubscriptionManager {
+    return &self.state.subscription_manager;
+  }
+
   pub(crate) fn get_oauth_provider(&self, name: &str) -> Option<Arc<OAuthProviderType>> {
     return self.state.oauth.load().lookup(name).cloned();
   }
@@ -210,6 +240,26 @@ impl AppState {
     return None;
   }
 
+  /// Returns the first RecordApi exposing the given table, if any.
+  pub(crate) fn lookup_record_api_for_table(&self, table_name: &str) -> Option<RecordApi> {
+    for (_record_api_name, record_api) in self.state.record_apis.load().iter() {
+      if record_api.table_name() == table_name {
+        return Some(record_api.clone());
+      }
+    }
+    return None;
+  }
+
+  pub(crate) fn record_apis(&self) -> Vec<RecordApi> {
+    return self
+      .state
+      .record_apis
+      .load()
+      .iter()
+      .map(|(_record_api_name, record_api)| record_api.clone())
+      .collect();
+  }
+
   pub(crate) fn lookup_query_api(&self, name: &str) -> Option<QueryApi> {
     for (query_api_name, query_api) in self.state.query_apis.load().iter() {
       if query_api_name == name {
@@ -261,20 +311,56 @@ impl AppState {
     };
 
     // Write new config to the file system.
-    return write_config_and_vault_textproto(
-      self.data_dir(),
-      self.table_metadata(),
-      &self.get_config(),
-    )
-    .await;
+    write_config_and_vault_textproto(self.data_dir(), self.table_metadata(), &self.get_config())
+      .await?;
+
+    // Derived schema changes are only applied once the config has been committed, i.e. rejected
+    // configs don't leave behind search tables or geo indexes.
+    let config = self.get_config();
+    sync_search_tables(self.conn(), &config)
+      .await
+      .map_err(|err| crate::config::ConfigError::Update(format!("Search tables: {err}")))?;
+    sync_geo_indexes(self.conn(), &config)
+      .await
+      .map_err(|err| crate::config::ConfigError::Update(format!("Geo indexes: {err}")))?;
+
+    return self
+      .table_metadata()
+      .invalidate_all()
+      .await
+      .map_err(|err| crate::config::ConfigError::Update(format!("Table metadata: {err}")));
   }
 
-  #[cfg(feature = "v8")]
   pub(crate) fn script_runtime(&self) -> RuntimeHandle {
     return self.state.runtime.clone();
   }
 }
 
+/// Transaction on the dedicated write connection, which is rolled back unless committed.
+pub(crate) struct WriteTransaction<'a> {
+  // NOTE: Dropped, i.e. rolled back, before releasing the lock.
+  tx: libsql::Transaction,
+  _guard: tokio::sync::MutexGuard<'a, ()>,
+}
+
+impl WriteTransaction<'_> {
+  pub(crate) async fn commit(self) -> Result<(), libsql::Error> {
+    return self.tx.commit().await;
+  }
+
+  pub(crate) async fn rollback(self) -> Result<(), libsql::Error> {
+    return self.tx.rollback().await;
+  }
+}
+
+impl std::ops::Deref for WriteTransaction<'_> {
+  type Target = Connection;
+
+  fn deref(&self) -> &Connection {
+    return &self.tx;
+  }
+}
+
 fn build_mailer(
   config: &ValueNotifier<Config>,
   mailer: Option<Mailer>,
@@ -306,13 +392,16 @@ pub async fn test_state(options: Option<TestStateOptions>) -> anyhow::Result<App
   let temp_dir = temp_dir::TempDir::new()?;
   tokio::fs::create_dir_all(temp_dir.child("uploads")).await?;
 
+  // NOTE: Unlike in-memory DBs, a file can be shared with the dedicated write connection.
+  let main_db_path = temp_dir.child("main.db");
   let main_conn = {
-    let conn = trailbase_sqlite::connect_sqlite(None, None).await?;
+    let conn = trailbase_sqlite::connect_sqlite(Some(main_db_path.clone()), None).await?;
     apply_user_migrations(conn.clone()).await?;
     let _new_db = apply_main_migrations(conn.clone(), None).await?;
 
     conn
   };
+  let write_conn = trailbase_sqlite::connect_sqlite(Some(main_db_path), None).await?;
 
   let logs_conn = {
     let conn = trailbase_sqlite::connect_sqlite(None, None).await?;
@@ -429,10 +518,13 @@ pub async fn test_state(options: Option<TestStateOptions>) -> anyhow::Result<App
       }),
       config,
       conn: main_conn.clone(),
+      write_conn,
+      write_lock: tokio::sync::Mutex::new(()),
       logs_conn,
       jwt: jwt::test_jwt_helper(),
       table_metadata,
       object_store,
+      subscription_manager: SubscriptionManager::default(),
       runtime,
       cleanup: vec![Box::new(temp_dir)],
     }),
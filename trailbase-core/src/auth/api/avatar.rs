@@ -116,6 +116,7 @@ mod tests {
     create_record_handler, CreateRecordQuery, CreateRecordResponse,
   };
   use crate::records::read_record::get_uploaded_file_from_record_handler;
+  use crate::records::thumbnail::ThumbnailQuery;
   use crate::test::unpack_json_response;
   use crate::util::{b64_to_uuid, id_to_b64, uuid_to_b64};
 
@@ -179,6 +180,7 @@ mod tests {
         id_to_b64(record_id),
         COL_NAME.to_string(),
       )),
+      Query(ThumbnailQuery::default()),
       None,
     )
     .await
@@ -1,5 +1,5 @@
 use crate::rand::generate_random_string;
-use crate::util::uuid_to_b64;
+use crate::util::{hmac_sha256, uuid_to_b64};
 use ed25519_dalek::pkcs8::spki::der::pem::LineEnding;
 use ed25519_dalek::pkcs8::{EncodePrivateKey, EncodePublicKey};
 use ed25519_dalek::{SigningKey, VerifyingKey};
@@ -74,6 +74,9 @@ pub struct JwtHelper {
   // The public key used for validating provided JWTs.
   decoding_key: DecodingKey,
   public_key: Vec<u8>,
+
+  // Symmetric key derived from the private key, used e.g. for signing file URLs.
+  signing_key: [u8; 32],
 }
 
 impl JwtHelper {
@@ -84,6 +87,7 @@ impl JwtHelper {
       encoding_key: EncodingKey::from_ed_pem(&private_key)?,
       decoding_key: DecodingKey::from_ed_pem(&public_key)?,
       public_key,
+      signing_key: hmac_sha256(&private_key, b"trailbase-signing-key"),
     });
   }
 
@@ -126,6 +130,12 @@ impl JwtHelper {
   pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String, JwtError> {
     return jsonwebtoken::encode::<T>(&self.header, claims, &self.encoding_key);
   }
+
+  /// HMAC-SHA256 of the message. The key is derived from the private key and thus rotates with the
+  /// keys in [DataDir::key_path].
+  pub(crate) fn sign(&self, message: &[u8]) -> [u8; 32] {
+    return hmac_sha256(&self.signing_key, message);
+  }
 }
 
 fn generate_new_key_pair() -> (SigningKey, VerifyingKey) {
@@ -197,6 +207,16 @@ mod tests {
 
     assert_eq!(claims, jwt.decode(&token).unwrap());
   }
+
+  #[test]
+  fn test_sign() {
+    let jwt = test_jwt_helper();
+    assert_eq!(jwt.sign(b"message"), jwt.sign(b"message"));
+    assert_ne!(jwt.sign(b"message"), jwt.sign(b"other"));
+
+    // Rotated keys invalidate signatures.
+    assert_ne!(jwt.sign(b"message"), test_jwt_helper().sign(b"message"));
+  }
 }
 
 const PRIVATE_KEY_FILE: &str = "private_key.pem";
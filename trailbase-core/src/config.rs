@@ -91,7 +91,7 @@ pub mod proto {
   use prost_reflect::{DynamicMessage, MessageDescriptor, ReflectMessage};
   use std::hash::{DefaultHasher, Hash, Hasher};
 
-  use crate::config::ConfigError;
+  use crate::config::{ConfigError, CONFIG_VERSION};
   use crate::constants::{
     AVATAR_TABLE, DEFAULT_AUTH_TOKEN_TTL, DEFAULT_REFRESH_TOKEN_TTL, LOGS_RETENTION_DEFAULT,
     SITE_URL_DEFAULT,
@@ -133,6 +133,7 @@ pub mod proto {
       // however it lets us tie into the set update-config Admin UI flow to let users change the
       // templates.
       let mut config = Config {
+        version: Some(CONFIG_VERSION),
         server: ServerConfig {
           application_name: Some("TrailBase".to_string()),
           site_url: Some(SITE_URL_DEFAULT.to_string()),
@@ -158,18 +159,31 @@ pub mod proto {
         table_name: Some(AVATAR_TABLE.to_string()),
         conflict_resolution: Some(ConflictResolutionStrategy::Replace.into()),
         autofill_missing_user_id_columns: Some(true),
-        acl_world: vec![PermissionFlag::Read as i32],
+        acl_world: vec![PermissionFlag::Read as i32, PermissionFlag::List as i32],
         acl_authenticated: vec![
           PermissionFlag::Create as i32,
           PermissionFlag::Read as i32,
           PermissionFlag::Update as i32,
           PermissionFlag::Delete as i32,
+          PermissionFlag::List as i32,
         ],
         read_access_rule: None,
         create_access_rule: Some("_REQ_.user IS NULL OR _REQ_.user = _USER_.id".to_string()),
         update_access_rule: Some("_ROW_.user = _USER_.id".to_string()),
         delete_access_rule: Some("_ROW_.user = _USER_.id".to_string()),
         schema_access_rule: None,
+        list_access_rule: None,
+        etag_column: None,
+        search_columns: vec![],
+        geo_latitude_column: None,
+        geo_longitude_column: None,
+        soft_delete_column: None,
+        soft_delete_retention_sec: None,
+        enable_history: None,
+        column_acl_world: None,
+        column_acl_authenticated: None,
+        computed_fields: vec![],
+        file_upload_limits: vec![],
       }];
 
       return config;
@@ -407,13 +421,40 @@ async fn load_vault_textproto_or_default(data_dir: &DataDir) -> Result<proto::Va
   return Ok(vault);
 }
 
+/// Migrates configs written by prior versions to the current [CONFIG_VERSION]. Returns true if the
+/// config was changed.
+fn migrate_config(config: &mut proto::Config) -> bool {
+  let version = config.version.unwrap_or(0);
+  if version >= CONFIG_VERSION {
+    return false;
+  }
+
+  if version < 1 {
+    // Listing used to be covered by READ, thus grant LIST wherever READ was granted.
+    let (read, list) = (
+      proto::PermissionFlag::Read as i32,
+      proto::PermissionFlag::List as i32,
+    );
+    for api in &mut config.record_apis {
+      for acl in [&mut api.acl_world, &mut api.acl_authenticated] {
+        if acl.contains(&read) && !acl.contains(&list) {
+          acl.push(list);
+        }
+      }
+    }
+  }
+
+  config.version = Some(CONFIG_VERSION);
+  return true;
+}
+
 pub async fn load_or_init_config_textproto(
   data_dir: &DataDir,
   table_metadata: &TableMetadataCache,
 ) -> Result<proto::Config, ConfigError> {
   let vault = load_vault_textproto_or_default(data_dir).await?;
 
-  let config: proto::Config =
+  let mut config: proto::Config =
     match fs::read_to_string(data_dir.config_path().join(CONFIG_FILENAME)).await {
       Ok(contents) => proto::Config::from_text(&contents)?,
       Err(err) => match err.kind() {
@@ -429,6 +470,14 @@ pub async fn load_or_init_config_textproto(
       },
     };
 
+  if migrate_config(&mut config) {
+    info!("Migrated config to version {CONFIG_VERSION}");
+    if cfg!(not(test)) {
+      let config_path = data_dir.config_path().join(CONFIG_FILENAME);
+      fs::write(&config_path, config.to_text()?.as_bytes()).await?;
+    }
+  }
+
   let merged_config = merge_vault_and_env(config, vault)?;
   validate_config(table_metadata, &merged_config)?;
 
@@ -453,7 +502,9 @@ pub async fn write_config_and_vault_textproto(
 ) -> Result<(), ConfigError> {
   validate_config(table_metadata, config)?;
 
-  let (stripped_config, vault) = split_config(config)?;
+  let (mut stripped_config, vault) = split_config(config)?;
+  // Configs written by this version are current, even if submitted by clients unaware of versions.
+  stripped_config.version = Some(CONFIG_VERSION);
 
   if cfg!(test) {
     debug!("Skip writing config for tests.");
@@ -556,6 +607,25 @@ pub(crate) fn validate_config(
     }
   }
 
+  // Check webhooks.
+  for (name, webhook) in &config.webhooks {
+    match &webhook.api_name {
+      Some(api_name) if api_names.contains(api_name) => {}
+      _ => {
+        return ierr(&format!("Webhook '{name}' references missing API"));
+      }
+    };
+
+    let valid_url = webhook
+      .url
+      .as_deref()
+      .and_then(|url| url::Url::parse(url).ok())
+      .is_some_and(|url| matches!(url.scheme(), "http" | "https"));
+    if !valid_url {
+      return ierr(&format!("Webhook '{name}' requires a http(s) URL"));
+    }
+  }
+
   // Check email config.
   {
     let email = &config.email;
@@ -604,6 +674,36 @@ mod test {
     validate_config(&table_metadata, &config).unwrap();
   }
 
+  #[test]
+  fn test_config_migration() {
+    use crate::config::proto::{PermissionFlag, RecordApiConfig};
+
+    let (read, list) = (PermissionFlag::Read as i32, PermissionFlag::List as i32);
+    let mut config = proto::Config {
+      record_apis: vec![RecordApiConfig {
+        acl_world: vec![read],
+        acl_authenticated: vec![PermissionFlag::Create as i32, read, list],
+        ..Default::default()
+      }],
+      ..Default::default()
+    };
+
+    assert!(migrate_config(&mut config));
+    assert_eq!(config.version, Some(CONFIG_VERSION));
+    assert_eq!(config.record_apis[0].acl_world, vec![read, list]);
+    assert_eq!(
+      config.record_apis[0].acl_authenticated,
+      vec![PermissionFlag::Create as i32, read, list]
+    );
+
+    // Current configs are left untouched, i.e. READ without LIST is retained.
+    config.record_apis[0].acl_world = vec![read];
+    assert!(!migrate_config(&mut config));
+    assert_eq!(config.record_apis[0].acl_world, vec![read]);
+
+    assert!(!migrate_config(&mut Config::new_with_custom_defaults()));
+  }
+
   fn test_config_merging() -> anyhow::Result<()> {
     let config = proto::Config {
       email: proto::EmailConfig {
@@ -723,5 +823,7 @@ mod test {
   }
 }
 
+/// Version of the config format, see [migrate_config].
+const CONFIG_VERSION: u32 = 1;
 const CONFIG_FILENAME: &str = "config.textproto";
 const VAULT_FILENAME: &str = "secrets.textproto";
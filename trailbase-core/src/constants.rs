@@ -7,10 +7,31 @@ pub(crate) const USER_TABLE_ID_COLUMN: &str = "id";
 
 pub(crate) const SESSION_TABLE: &str = "_session";
 pub(crate) const AVATAR_TABLE: &str = "_user_avatar";
+pub(crate) const RECORD_HISTORY_TABLE: &str = "_record_history";
+pub(crate) const WEBHOOK_DELIVERY_TABLE: &str = "_webhook_delivery";
 
 pub(crate) const LOGS_TABLE_ID_COLUMN: &str = "id";
 pub const LOGS_RETENTION_DEFAULT: Duration = Duration::days(7);
 
+/// Minimum age of unreferenced uploaded files before they're garbage collected, which protects
+/// uploads that haven't been committed yet.
+pub const ORPHANED_FILES_GRACE_PERIOD: Duration = Duration::hours(24);
+/// Lower bound for custom grace periods. Files are streamed to the object store before their
+/// record gets written, i.e. shorter periods risk deleting files of uploads still in flight.
+pub const ORPHANED_FILES_MIN_GRACE_PERIOD: Duration = Duration::hours(1);
+pub const SOFT_DELETE_RETENTION_DEFAULT: Duration = Duration::days(30);
+/// Age of delivered or failed webhook deliveries before they're purged from the queue.
+pub const WEBHOOK_DELIVERY_RETENTION: Duration = Duration::days(7);
+
+/// Max size of request bodies. Multipart uploads to record APIs are exempt, since files are streamed
+/// to the object store subject to per-file and per-request limits.
+pub(crate) const MAX_REQUEST_BODY_SIZE: usize = 10 * 1024 * 1024;
+pub const FILE_UPLOAD_MAX_SIZE_DEFAULT: usize = 10 * 1024 * 1024;
+/// Max number of files uploaded with a single request.
+pub const FILE_UPLOAD_MAX_FILES_PER_REQUEST: usize = 32;
+/// Max total size of files streamed with a single request, unless a single file may be larger.
+pub const FILE_UPLOAD_MAX_REQUEST_SIZE: usize = 100 * 1024 * 1024;
+
 pub const COOKIE_AUTH_TOKEN: &str = "auth_token";
 pub const COOKIE_REFRESH_TOKEN: &str = "refresh_token";
 pub const COOKIE_OAUTH_STATE: &str = "oauth_state";
@@ -27,6 +48,10 @@ pub const DEFAULT_AUTH_TOKEN_TTL: Duration = Duration::minutes(60);
 
 pub const DEFAULT_REFRESH_TOKEN_TTL: Duration = Duration::days(30);
 
+/// Validity of signed file URLs unless requested otherwise, and the upper bound on it.
+pub const SIGNED_FILE_URL_DEFAULT_TTL: Duration = Duration::minutes(15);
+pub const SIGNED_FILE_URL_MAX_TTL: Duration = Duration::days(7);
+
 pub const SITE_URL_DEFAULT: &str = "http://localhost:4000";
 
 pub(crate) const PASSWORD_OPTIONS: PasswordOptions = PasswordOptions::default();
@@ -36,4 +61,5 @@ pub(crate) const REFRESH_TOKEN_LENGTH: usize = 32;
 // Public APIs
 pub const RECORD_API_PATH: &str = "api/records/v1";
 pub const QUERY_API_PATH: &str = "api/query/v1";
+pub const TRANSACTION_API_PATH: &str = "api/transaction/v1";
 pub const AUTH_API_PATH: &str = "api/auth/v1";
@@ -8,9 +8,10 @@ use log::*;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use thiserror::Error;
-use trailbase_sqlite::schema::FileUploadInput;
 
-use crate::extract::multipart::{parse_multipart, Rejection as MultipartRejection};
+use crate::extract::multipart::{
+  parse_multipart, FileUploadTarget, MultipartFile, Rejection as MultipartRejection,
+};
 
 #[derive(Debug, Error)]
 pub enum EitherRejection {
@@ -28,17 +29,24 @@ pub enum EitherRejection {
 
 impl IntoResponse for EitherRejection {
   fn into_response(self) -> Response {
-    return (StatusCode::BAD_REQUEST, format!("{self:?}")).into_response();
+    let status = match self {
+      Self::Multipart(ref err) => err.status(),
+      _ => StatusCode::BAD_REQUEST,
+    };
+    return (status, format!("{self:?}")).into_response();
   }
 }
 
 // NOTE: For serde_json::Value as T, the different formats will produce very different results,
 // e.g. json has a notion of types, whereas Multipart and Form don't. They're s practically a:
 //   Map<String, String | Vec<String>>
+//
+// Multipart file uploads are streamed to the object store if a `FileUploadTarget` request extension
+// was installed, e.g. by the record API router, and buffered otherwise.
 #[derive(Debug)]
 pub enum Either<T> {
   Json(T),
-  Multipart(T, Vec<FileUploadInput>),
+  Multipart(T, Vec<MultipartFile>),
   Form(T),
   // Proto(DynamicMessage),
 }
@@ -51,7 +59,7 @@ where
 {
   type Rejection = EitherRejection;
 
-  async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+  async fn from_request(mut req: Request, state: &S) -> Result<Self, Self::Rejection> {
     return match req.headers().get(CONTENT_TYPE) {
       Some(x) if x.as_ref().starts_with(b"application/json") => {
         let Json(value): Json<T> = Json::from_request(req, state).await?;
@@ -62,7 +70,8 @@ where
         Ok(Either::Form(value))
       }
       Some(x) if x.as_ref().starts_with(b"multipart/form-data") => {
-        let (value, files) = parse_multipart(req).await?;
+        let target = req.extensions_mut().remove::<FileUploadTarget>();
+        let (value, files) = parse_multipart(req, target).await?;
         Ok(Either::Multipart(value, files))
       }
       // Some(x) if x == "application/x-protobuf" => {
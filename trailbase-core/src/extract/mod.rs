@@ -2,3 +2,7 @@ mod either;
 mod multipart;
 
 pub use either::Either;
+pub(crate) use multipart::is_multipart;
+pub use multipart::{
+  FileUploadLimit, FileUploadLimitError, FileUploadTarget, MultipartFile, StoredFileUpload,
+};
@@ -1,12 +1,27 @@
 //! Parse multipart form requests
 use axum::{
   body::Body,
-  extract::{FromRequest, Request},
+  extract::{multipart::Field, FromRequest, Request},
+  http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
 };
+use log::*;
+use object_store::{path::Path, WriteMultipart};
 use serde::de::DeserializeOwned;
 use serde_json::json;
+use std::collections::HashMap;
 use thiserror::Error;
-use trailbase_sqlite::schema::FileUploadInput;
+use trailbase_sqlite::schema::{FileUpload, FileUploadInput};
+
+use crate::app_state::AppState;
+use crate::constants::{
+  FILE_UPLOAD_MAX_FILES_PER_REQUEST, FILE_UPLOAD_MAX_REQUEST_SIZE, FILE_UPLOAD_MAX_SIZE_DEFAULT,
+  MAX_REQUEST_BODY_SIZE,
+};
+
+/// Number of leading bytes buffered to infer a streamed file's MIME type.
+const MIME_TYPE_SNIFF_LENGTH: usize = 8192;
+/// Number of concurrently uploaded parts per streamed file.
+const MAX_CONCURRENT_PARTS: usize = 2;
 
 #[derive(Debug, Error)]
 pub enum Rejection {
@@ -20,31 +35,251 @@ pub enum Rejection {
   Serde(#[from] serde_path_to_error::Error<serde_json::Error>),
   #[error("Precondition error: {0}")]
   Precondition(&'static str),
+  #[error("File upload error: {0}")]
+  FileUpload(#[from] FileUploadLimitError),
+  #[error("Storage error: {0}")]
+  Storage(#[from] object_store::Error),
+}
+
+impl Rejection {
+  pub fn status(&self) -> StatusCode {
+    return match self {
+      Self::FileUpload(err) => err.status(),
+      Self::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+      _ => StatusCode::BAD_REQUEST,
+    };
+  }
+}
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum FileUploadLimitError {
+  #[error("File too large")]
+  TooLarge,
+  #[error("Unsupported MIME type")]
+  UnsupportedMimeType,
+  #[error("Too many files")]
+  TooManyFiles,
+}
+
+impl FileUploadLimitError {
+  pub fn status(&self) -> StatusCode {
+    return match self {
+      Self::TooLarge | Self::TooManyFiles => StatusCode::PAYLOAD_TOO_LARGE,
+      Self::UnsupportedMimeType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+    };
+  }
+}
+
+/// Size and type constraints for an uploaded file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileUploadLimit {
+  /// Max size in bytes.
+  pub max_size: usize,
+  /// Allowed inferred MIME types, e.g. "image/png" or "image/*". Any type is allowed if empty.
+  pub allowed_mime_types: Vec<String>,
+}
+
+impl Default for FileUploadLimit {
+  fn default() -> Self {
+    return Self {
+      max_size: FILE_UPLOAD_MAX_SIZE_DEFAULT,
+      allowed_mime_types: vec![],
+    };
+  }
+}
+
+impl FileUploadLimit {
+  pub fn check_size(&self, size: usize) -> Result<(), FileUploadLimitError> {
+    if size > self.max_size {
+      return Err(FileUploadLimitError::TooLarge);
+    }
+    return Ok(());
+  }
+
+  pub fn check_mime_type(&self, mime_type: Option<&str>) -> Result<(), FileUploadLimitError> {
+    if self.allowed_mime_types.is_empty() {
+      return Ok(());
+    }
+
+    let allowed = mime_type.is_some_and(|mime_type| {
+      self
+        .allowed_mime_types
+        .iter()
+        .any(|allowed| match allowed.strip_suffix("/*") {
+          Some(prefix) => mime_type.split_once('/').is_some_and(|(t, _)| t == prefix),
+          None => allowed == mime_type,
+        })
+    });
+    if !allowed {
+      return Err(FileUploadLimitError::UnsupportedMimeType);
+    }
+    return Ok(());
+  }
+
+  pub fn check(&self, size: usize, mime_type: Option<&str>) -> Result<(), FileUploadLimitError> {
+    self.check_mime_type(mime_type)?;
+    return self.check_size(size);
+  }
+}
+
+/// Request extension instructing [parse_multipart] to stream file parts straight to the object
+/// store rather than buffering them in memory.
+///
+/// Installed, e.g., by the record API router, which knows the targeted API's file columns and their
+/// limits.
+#[derive(Clone)]
+pub struct FileUploadTarget {
+  state: AppState,
+  /// Limits by form field name. Files for other fields are skipped.
+  limits: HashMap<String, FileUploadLimit>,
+  /// Max total size of all files streamed with the request.
+  max_total_size: usize,
+}
+
+impl FileUploadTarget {
+  pub fn new(state: AppState, limits: HashMap<String, FileUploadLimit>) -> Self {
+    let max_total_size = limits
+      .values()
+      .map(|limit| limit.max_size)
+      .fold(FILE_UPLOAD_MAX_REQUEST_SIZE, usize::max);
+
+    return Self {
+      state,
+      limits,
+      max_total_size,
+    };
+  }
+}
+
+/// A file, which has already been streamed to the object store.
+///
+/// Unless persisted, e.g. because the request got rejected or writing the record failed, the file
+/// is deleted again when dropped.
+pub struct StoredFileUpload {
+  /// The name of the form's file control.
+  pub name: Option<String>,
+  pub file_upload: FileUpload,
+
+  state: Option<AppState>,
+}
+
+impl StoredFileUpload {
+  /// Keeps the file around for good.
+  pub fn persist(mut self) {
+    self.state = None;
+  }
+}
+
+impl Drop for StoredFileUpload {
+  fn drop(&mut self) {
+    let Some(state) = self.state.take() else {
+      return;
+    };
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+      warn!("Failed to cleanup unused file (leak): no runtime");
+      return;
+    };
+
+    let path = Path::from(self.file_upload.path());
+    runtime.spawn(async move {
+      if let Err(err) = state.objectstore().delete(&path).await {
+        warn!("Failed to cleanup unused file (leak): {err}");
+      }
+    });
+  }
+}
+
+impl std::fmt::Debug for StoredFileUpload {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return f
+      .debug_struct("StoredFileUpload")
+      .field("name", &self.name)
+      .field("file_upload", &self.file_upload)
+      .field("persisted", &self.state.is_none())
+      .finish();
+  }
+}
+
+/// A file uploaded as part of a multipart form.
+#[derive(Debug)]
+pub enum MultipartFile {
+  /// Contents buffered in memory, i.e. if no [FileUploadTarget] was installed.
+  Buffered(FileUploadInput),
+  /// Already streamed to the object store.
+  Stored(StoredFileUpload),
+}
+
+pub(crate) fn is_multipart(headers: &HeaderMap) -> bool {
+  return headers
+    .get(CONTENT_TYPE)
+    .is_some_and(|c| c.as_bytes().starts_with(b"multipart/form-data"));
 }
 
 /// Parse a multipart form submission into the specified type and a list of files uploaded with it.
 ///
+/// Given a [FileUploadTarget], files are streamed straight to the object store and checked against
+/// the target's limits along the way. Otherwise, they're buffered in memory.
+///
 /// Note, when encountering stream errors one should check the tower limit layers. The error is
 /// pretty cryptic when the stream gets cut off.
-pub async fn parse_multipart<T>(req: Request<Body>) -> Result<(T, Vec<FileUploadInput>), Rejection>
+pub async fn parse_multipart<T>(
+  req: Request<Body>,
+  target: Option<FileUploadTarget>,
+) -> Result<(T, Vec<MultipartFile>), Rejection>
 where
   T: DeserializeOwned + Send + Sync + 'static,
 {
   let mut multipart = axum::extract::Multipart::from_request(req, &()).await?;
 
   let mut data = serde_json::Map::<String, serde_json::Value>::new();
-  let mut files: Vec<FileUploadInput> = vec![];
+  let mut files: Vec<MultipartFile> = vec![];
+  // Size of text fields and buffered files.
+  let mut buffered_size: usize = 0;
+  let mut stored_size: usize = 0;
 
   while let Some(mut field) = multipart.next_field().await? {
     if field.file_name().is_some() {
-      // We
-
       let content_type = field.content_type().map(|s| s.to_string());
       let name = field.name().map(|s| s.to_string());
       let filename = field.file_name().map(|s| s.to_string());
 
+      if files.len() >= FILE_UPLOAD_MAX_FILES_PER_REQUEST {
+        return Err(FileUploadLimitError::TooManyFiles.into());
+      }
+
+      if let Some(ref target) = target {
+        // We simply skip files for unknown columns, similar to unknown fields.
+        let Some(limit) = name.as_ref().and_then(|name| target.limits.get(name)) else {
+          continue;
+        };
+
+        // Files are subject to their own limit as well as the remaining per-request budget.
+        let limit = FileUploadLimit {
+          max_size: limit
+            .max_size
+            .min(target.max_total_size.saturating_sub(stored_size)),
+          allowed_mime_types: limit.allowed_mime_types.clone(),
+        };
+
+        let stored =
+          stream_file_to_store(&target.state, &mut field, &limit, filename, content_type).await?;
+        if let Some((file_upload, size)) = stored {
+          stored_size += size;
+          files.push(MultipartFile::Stored(StoredFileUpload {
+            name,
+            file_upload,
+            state: Some(target.state.clone()),
+          }));
+        }
+        continue;
+      }
+
       let mut buffer: Vec<u8> = vec![];
       while let Some(chunk) = field.chunk().await? {
+        buffered_size += chunk.len();
+        if buffered_size > MAX_REQUEST_BODY_SIZE {
+          return Err(FileUploadLimitError::TooLarge.into());
+        }
         buffer.extend_from_slice(&chunk);
       }
 
@@ -53,14 +288,16 @@ where
         continue;
       }
 
-      files.push(FileUploadInput {
+      files.push(MultipartFile::Buffered(FileUploadInput {
         name,
         filename,
         content_type,
         data: buffer,
-      });
+      }));
     } else if let Some(name) = field.name() {
-      coerce_and_push_array(&mut data, name.to_string(), json!(field.text().await?));
+      let name = name.to_string();
+      let text = read_text(&mut field, &mut buffered_size).await?;
+      coerce_and_push_array(&mut data, name, json!(text));
     } else {
       // We consider form fields that neither have a filename nor a name to be invalid.
       return Err(Rejection::Precondition("Neither name nor filename"));
@@ -73,6 +310,79 @@ where
   ));
 }
 
+/// Reads a text field. Since uploads may be exempt from request body limits, the total size of
+/// buffered fields is capped separately.
+async fn read_text(field: &mut Field<'_>, buffered_size: &mut usize) -> Result<String, Rejection> {
+  let mut buffer: Vec<u8> = vec![];
+  while let Some(chunk) = field.chunk().await? {
+    *buffered_size += chunk.len();
+    if *buffered_size > MAX_REQUEST_BODY_SIZE {
+      return Err(Rejection::Precondition("Form fields too large"));
+    }
+    buffer.extend_from_slice(&chunk);
+  }
+
+  return String::from_utf8(buffer).map_err(|_err| Rejection::Precondition("Invalid UTF-8"));
+}
+
+/// Streams a file part to the object store using a multipart put, checking it against the given
+/// limit along the way.
+///
+/// Returns the stored file and its size or None for empty parts.
+async fn stream_file_to_store(
+  state: &AppState,
+  field: &mut Field<'_>,
+  limit: &FileUploadLimit,
+  filename: Option<String>,
+  content_type: Option<String>,
+) -> Result<Option<(FileUpload, usize)>, Rejection> {
+  // Buffer the head of the file to infer its type. We don't trust user provided types.
+  let mut head: Vec<u8> = vec![];
+  while head.len() < MIME_TYPE_SNIFF_LENGTH {
+    let Some(chunk) = field.chunk().await? else {
+      break;
+    };
+    head.extend_from_slice(&chunk);
+  }
+
+  // Forms submit an empty string for optional file inputs :/.
+  if head.is_empty() {
+    return Ok(None);
+  }
+
+  let mime_type = infer::get(&head).map(|t| t.mime_type().to_string());
+  limit.check(head.len(), mime_type.as_deref())?;
+
+  let file_upload = FileUpload::new(uuid::Uuid::new_v4(), filename, content_type, mime_type);
+  let path = Path::from(file_upload.path());
+  let mut writer = WriteMultipart::new(state.objectstore().put_multipart(&path).await?);
+
+  let mut size = head.len();
+  writer.write(&head);
+
+  let result: Result<(), Rejection> = async {
+    while let Some(chunk) = field.chunk().await? {
+      size += chunk.len();
+      limit.check_size(size)?;
+
+      writer.wait_for_capacity(MAX_CONCURRENT_PARTS).await?;
+      writer.write(&chunk);
+    }
+    return Ok(());
+  }
+  .await;
+
+  if let Err(err) = result {
+    if let Err(err) = writer.abort().await {
+      warn!("Failed to abort upload: {err}");
+    }
+    return Err(err);
+  }
+  writer.finish().await?;
+
+  return Ok(Some((file_upload, size)));
+}
+
 /// Adds ([key], [value]) to [map], first as value and subsequently as an array, i.e.
 ///   `map[key]=[v0, v1, ...]`.
 fn coerce_and_push_array(
@@ -133,7 +443,7 @@ mod test {
   #[tokio::test]
   async fn parse_multipart_jsonvalue() {
     let data = get_req();
-    let (value, files) = super::parse_multipart::<serde_json::Value>(data)
+    let (value, files) = super::parse_multipart::<serde_json::Value>(data, None)
       .await
       .unwrap();
     assert_eq!(
@@ -144,6 +454,13 @@ mod test {
       })
     );
 
+    let files: Vec<FileUploadInput> = files
+      .into_iter()
+      .map(|file| match file {
+        MultipartFile::Buffered(file) => file,
+        MultipartFile::Stored(_) => panic!("Expected buffered file"),
+      })
+      .collect();
     assert_eq!(
       files,
       vec![
@@ -162,4 +479,190 @@ mod test {
       ]
     );
   }
+
+  fn file_req(content: &str) -> axum::http::Request<axum::body::Body> {
+    let body = format!(
+      "--fieldB\r\n\
+       Content-Disposition: form-data; name=\"name\"\r\n\r\n\
+       test\r\n\
+       --fieldB\r\n\
+       Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+       Content-Type: text/plain\r\n\r\n\
+       {content}\r\n\
+       --fieldB\r\n\
+       Content-Disposition: form-data; name=\"unknown\"; filename=\"b.txt\"\r\n\
+       Content-Type: text/plain\r\n\r\n\
+       ignored\r\n\
+       --fieldB--\r\n"
+    );
+
+    axum::http::Request::builder()
+      .header("content-type", "multipart/form-data; boundary=fieldB")
+      .header("content-length", body.len())
+      .body(axum::body::Body::from(body))
+      .unwrap()
+  }
+
+  #[tokio::test]
+  async fn parse_multipart_streaming() {
+    let state = crate::app_state::test_state(None).await.unwrap();
+    let target = |limit: FileUploadLimit| {
+      FileUploadTarget::new(state.clone(), HashMap::from([("file".to_string(), limit)]))
+    };
+    let exists = |file_upload: &FileUpload| {
+      let (state, path) = (state.clone(), Path::from(file_upload.path()));
+      async move { state.objectstore().head(&path).await.is_ok() }
+    };
+
+    let content = "x".repeat(3 * MIME_TYPE_SNIFF_LENGTH);
+    let (value, mut files) = parse_multipart::<serde_json::Value>(
+      file_req(&content),
+      Some(target(FileUploadLimit::default())),
+    )
+    .await
+    .unwrap();
+    assert_eq!(value, json!({"name": "test"}));
+
+    // The file for the unknown field is skipped.
+    assert_eq!(files.len(), 1);
+    let Some(MultipartFile::Stored(stored)) = files.pop() else {
+      panic!("Expected stored file");
+    };
+    assert_eq!(stored.name.as_deref(), Some("file"));
+
+    let file_upload = stored.file_upload.clone();
+    let contents = state
+      .objectstore()
+      .get(&Path::from(file_upload.path()))
+      .await
+      .unwrap()
+      .bytes()
+      .await
+      .unwrap();
+    assert_eq!(contents, content.as_bytes());
+
+    // Stored files are cleaned up unless persisted.
+    drop(stored);
+    for _ in 0..100 {
+      if !exists(&file_upload).await {
+        break;
+      }
+      tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert!(!exists(&file_upload).await);
+
+    let (_value, mut files) = parse_multipart::<serde_json::Value>(
+      file_req("persisted"),
+      Some(target(FileUploadLimit::default())),
+    )
+    .await
+    .unwrap();
+    let Some(MultipartFile::Stored(stored)) = files.pop() else {
+      panic!("Expected stored file");
+    };
+    let file_upload = stored.file_upload.clone();
+    stored.persist();
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert!(exists(&file_upload).await);
+
+    // Limits are enforced while streaming.
+    let err = parse_multipart::<serde_json::Value>(
+      file_req(&content),
+      Some(target(FileUploadLimit {
+        max_size: 2 * MIME_TYPE_SNIFF_LENGTH,
+        ..Default::default()
+      })),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let err = parse_multipart::<serde_json::Value>(
+      file_req("text"),
+      Some(target(FileUploadLimit {
+        allowed_mime_types: vec!["image/*".to_string()],
+        ..Default::default()
+      })),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+  }
+
+  fn files_req(count: usize, content: &str) -> axum::http::Request<axum::body::Body> {
+    let mut body = String::new();
+    for _ in 0..count {
+      body.push_str(&format!(
+        "--fieldB\r\n\
+         Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+         Content-Type: text/plain\r\n\r\n\
+         {content}\r\n"
+      ));
+    }
+    body.push_str("--fieldB--\r\n");
+
+    axum::http::Request::builder()
+      .header("content-type", "multipart/form-data; boundary=fieldB")
+      .body(axum::body::Body::from(body))
+      .unwrap()
+  }
+
+  #[tokio::test]
+  async fn parse_multipart_request_limits() {
+    let (_value, files) =
+      parse_multipart::<serde_json::Value>(files_req(FILE_UPLOAD_MAX_FILES_PER_REQUEST, "x"), None)
+        .await
+        .unwrap();
+    assert_eq!(files.len(), FILE_UPLOAD_MAX_FILES_PER_REQUEST);
+
+    let err = parse_multipart::<serde_json::Value>(
+      files_req(FILE_UPLOAD_MAX_FILES_PER_REQUEST + 1, "x"),
+      None,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    // The total size of streamed files is capped, even if each file is within its own limit.
+    let state = crate::app_state::test_state(None).await.unwrap();
+    let target = FileUploadTarget {
+      state: state.clone(),
+      limits: HashMap::from([("file".to_string(), FileUploadLimit::default())]),
+      max_total_size: 10,
+    };
+    let (_value, files) =
+      parse_multipart::<serde_json::Value>(files_req(2, "12345"), Some(target.clone()))
+        .await
+        .unwrap();
+    assert_eq!(files.len(), 2);
+
+    let err = parse_multipart::<serde_json::Value>(files_req(3, "12345"), Some(target))
+      .await
+      .unwrap_err();
+    assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+  }
+
+  #[test]
+  fn test_file_upload_limit() {
+    let limit = FileUploadLimit {
+      max_size: 10,
+      allowed_mime_types: vec!["image/*".to_string(), "video/mp4".to_string()],
+    };
+
+    assert!(limit.check(10, Some("image/png")).is_ok());
+    assert!(limit.check(10, Some("video/mp4")).is_ok());
+    assert_eq!(
+      limit.check(11, Some("image/png")),
+      Err(FileUploadLimitError::TooLarge)
+    );
+    assert_eq!(
+      limit.check(1, Some("video/webm")),
+      Err(FileUploadLimitError::UnsupportedMimeType)
+    );
+    assert_eq!(
+      limit.check(1, None),
+      Err(FileUploadLimitError::UnsupportedMimeType)
+    );
+    assert!(FileUploadLimit::default().check(1, None).is_ok());
+  }
 }
@@ -1,11 +1,84 @@
+use serde::{Deserialize, Serialize};
+
+use crate::auth::user::User;
+
 #[cfg(feature = "v8")]
 mod import_provider;
 
 #[cfg(feature = "v8")]
 mod runtime;
 
+/// Record API operations JS hooks can be registered for, e.g. via `onRecordCreate`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RecordHookEvent {
+  Create,
+  Update,
+  Delete,
+}
+
+/// Before-hooks run prior to a change and may reject it or alter the incoming values, after-hooks
+/// run once the change has been committed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RecordHookPhase {
+  Before,
+  After,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct JsUser {
+  // Base64 encoded user id.
+  pub id: String,
+  pub email: String,
+  pub csrf: String,
+}
+
+impl From<&User> for JsUser {
+  fn from(user: &User) -> Self {
+    return JsUser {
+      id: user.id.clone(),
+      email: user.email.clone(),
+      csrf: user.csrf_token.clone(),
+    };
+  }
+}
+
+/// Arguments passed to JS record hooks.
+#[derive(Debug, Serialize)]
+pub(crate) struct RecordHookContext {
+  pub api: String,
+  pub event: RecordHookEvent,
+  pub user: Option<JsUser>,
+  /// Id of the affected record. Unset for creations prior to the insert.
+  pub id: Option<serde_json::Value>,
+  /// Incoming record values of creations and updates.
+  pub params: Option<serde_json::Value>,
+  /// The committed or, for deletions, deleted record. Only set for after-hooks.
+  pub record: Option<serde_json::Value>,
+}
+
+/// Outcome of a before-hook.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct RecordHookResult {
+  /// Replacement for the incoming record values, if any.
+  pub params: Option<serde_json::Value>,
+  /// Set if the hook rejected the change by throwing an `HttpError`.
+  pub error: Option<RecordHookRejection>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct RecordHookRejection {
+  pub status: u16,
+  pub message: Option<String>,
+}
+
 #[cfg(not(feature = "v8"))]
 mod fallback {
+  use super::*;
+
+  type AnyError = Box<dyn std::error::Error + Send + Sync>;
+
   #[derive(Clone)]
   pub(crate) struct RuntimeHandle {}
 
@@ -19,6 +92,23 @@ mod fallback {
     pub(crate) fn new_with_threads(n_threads: usize) -> Self {
       return Self {};
     }
+
+    pub(crate) fn has_record_hook(
+      &self,
+      _phase: RecordHookPhase,
+      _event: RecordHookEvent,
+      _api_name: &str,
+    ) -> bool {
+      return false;
+    }
+
+    pub(crate) async fn dispatch_record_hook(
+      &self,
+      _phase: RecordHookPhase,
+      _context: RecordHookContext,
+    ) -> Result<RecordHookResult, AnyError> {
+      return Ok(RecordHookResult::default());
+    }
   }
 }
 
@@ -8,7 +8,7 @@ use parking_lot::Mutex;
 use rustyscript::{
   deno_core::PollEventLoopOptions, init_platform, js_value::Promise, json_args, Module, Runtime,
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use serde_json::from_value;
 use std::collections::HashSet;
 use std::str::FromStr;
@@ -19,6 +19,7 @@ use tokio::sync::oneshot;
 use crate::assets::cow_to_string;
 use crate::auth::user::User;
 use crate::js::import_provider::JsRuntimeAssets;
+use crate::js::{JsUser, RecordHookContext, RecordHookEvent, RecordHookPhase, RecordHookResult};
 use crate::records::sql_to_json::rows_to_json_arrays;
 use crate::{AppState, DataDir};
 
@@ -39,14 +40,6 @@ pub enum JsResponseError {
   Internal(Box<dyn std::error::Error + Send + Sync>),
 }
 
-#[derive(Serialize)]
-struct JsUser {
-  // Base64 encoded user id.
-  id: String,
-  email: String,
-  csrf: String,
-}
-
 struct DispatchArgs {
   method: String,
   route_path: String,
@@ -407,9 +400,14 @@ fn get_runtime(n_threads: Option<usize>) -> &'static RuntimeSingleton {
   return RUNTIME.get_or_init(move || RuntimeSingleton::new_with_threads(n_threads));
 }
 
+type RecordHookKey = (RecordHookPhase, RecordHookEvent, String);
+
 #[derive(Clone)]
 pub(crate) struct RuntimeHandle {
   runtime: &'static RuntimeSingleton,
+
+  // Record hooks registered by JS modules, which lets us skip dispatching to JS otherwise.
+  record_hooks: Arc<Mutex<HashSet<RecordHookKey>>>,
 }
 
 impl RuntimeHandle {
@@ -450,20 +448,48 @@ impl RuntimeHandle {
   pub(crate) fn new() -> Self {
     return Self {
       runtime: get_runtime(None),
+      record_hooks: Arc::new(Mutex::new(HashSet::new())),
     };
   }
 
   pub(crate) fn new_with_threads(n_threads: usize) -> Self {
     return Self {
       runtime: get_runtime(Some(n_threads)),
+      record_hooks: Arc::new(Mutex::new(HashSet::new())),
     };
   }
 
+  pub(crate) fn has_record_hook(
+    &self,
+    phase: RecordHookPhase,
+    event: RecordHookEvent,
+    api_name: &str,
+  ) -> bool {
+    return self
+      .record_hooks
+      .lock()
+      .contains(&(phase, event, api_name.to_string()));
+  }
+
+  /// Calls the JS hook registered for the given phase, event and API.
+  pub(crate) async fn dispatch_record_hook(
+    &self,
+    phase: RecordHookPhase,
+    context: RecordHookContext,
+  ) -> Result<RecordHookResult, AnyError> {
+    return self
+      .call_function::<RecordHookResult>(
+        None,
+        "__dispatchRecordHook",
+        vec![serde_json::to_value(phase)?, serde_json::to_value(context)?],
+      )
+      .await;
+  }
+
   fn state(&self) -> &'static Vec<State> {
     return &self.runtime.state;
   }
 
-  #[allow(unused)]
   async fn call_function<T>(
     &self,
     module: Option<Module>,
@@ -570,11 +596,7 @@ fn add_route_to_router(
       })
       .collect();
 
-    let js_user: Option<JsUser> = user.map(|u| JsUser {
-      id: u.id,
-      email: u.email,
-      csrf: u.csrf_token,
-    });
+    let js_user: Option<JsUser> = user.as_ref().map(JsUser::from);
 
     let (sender, receiver) = tokio::sync::oneshot::channel::<Result<JsResponse, JsResponseError>>();
 
@@ -678,6 +700,8 @@ async fn install_routes(
         if let Err(err) = state
           .sender
           .send(Message::Run(Box::new(move |runtime: &mut Runtime| {
+            let record_hooks = runtime_handle.record_hooks.clone();
+
             // First install a native callback that builds an axum router.
             let router_clone = router_clone.clone();
             runtime
@@ -691,6 +715,22 @@ async fn install_routes(
                 Ok(serde_json::Value::Null)
               })
               .expect("Failed to register 'route' function");
+
+            // And one that keeps track of registered record hooks.
+            runtime
+              .register_function(
+                "install_record_hook",
+                move |args: &[serde_json::Value]| {
+                  let phase: RecordHookPhase = get_arg(args, 0)?;
+                  let event: RecordHookEvent = get_arg(args, 1)?;
+                  let api_name: String = get_arg(args, 2)?;
+
+                  record_hooks.lock().insert((phase, event, api_name));
+
+                  Ok(serde_json::Value::Null)
+                },
+              )
+              .expect("Failed to register 'install_record_hook' function");
           })))
           .await
         {
@@ -51,6 +51,7 @@ pub mod openapi {
         nest(
             (path = "/api/auth/v1", api = crate::auth::AuthAPI),
             (path = "/api/records/v1", api = crate::records::RecordOpenApi),
+            (path = "/api/transaction/v1", api = crate::records::TransactionOpenApi),
         ),
         tags()
     )]
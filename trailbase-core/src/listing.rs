@@ -1,9 +1,13 @@
+use base64::prelude::*;
 use lazy_static::lazy_static;
 use log::*;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use thiserror::Error;
 
-use crate::records::json_to_sql::simple_json_value_to_param;
+use crate::records::geo::GeoIndex;
+use crate::records::json_to_sql::{simple_json_value_to_param, ParamsError};
+use crate::schema::Column;
 use crate::table_metadata::TableOrViewMetadata;
 use crate::util::b64_to_id;
 
@@ -40,6 +44,10 @@ pub enum Qualifier {
   LessThan,
   Like,
   Regexp,
+  In,
+  NotIn,
+  Is,
+  IsNot,
 }
 
 impl Qualifier {
@@ -53,6 +61,10 @@ impl Qualifier {
       Some("ne") => Some(Self::NotEqual),
       Some("like") => Some(Self::Like),
       Some("re") => Some(Self::Regexp),
+      Some("in") => Some(Self::In),
+      Some("nin") => Some(Self::NotIn),
+      Some("is") => Some(Self::Is),
+      Some("isnot") => Some(Self::IsNot),
       None => Some(Self::Equal),
       _ => None,
     };
@@ -69,6 +81,10 @@ impl Qualifier {
       Self::Like => "LIKE",
       Self::Regexp => "REGEXP",
       Self::Equal => "=",
+      Self::In => "IN",
+      Self::NotIn => "NOT IN",
+      Self::Is => "IS",
+      Self::IsNot => "IS NOT",
     };
   }
 }
@@ -81,14 +97,42 @@ pub enum Order {
 
 #[derive(Default, Debug)]
 pub struct QueryParseResult {
-  // Pagination parameters.
+  // Pagination parameters. The cursor is opaque, see [Cursor].
   pub limit: Option<usize>,
-  pub cursor: Option<[u8; 16]>,
+  pub cursor: Option<String>,
   pub offset: Option<usize>,
 
   // Ordering. It's a vector for &order=-col0,+col1,col2
   pub order: Option<Vec<(String, Order)>>,
 
+  // Foreign keys to expand, e.g. &expand=author,comments.author.
+  pub expand: Option<String>,
+
+  // Columns to project onto, e.g. &fields=id,title.
+  pub fields: Option<String>,
+
+  // Opt-in response envelope, optionally including the total count, e.g. &count=true.
+  pub envelope: bool,
+  pub count: bool,
+
+  // Composite filter expression, e.g. &filter=(status = 'open' OR owner IS NULL) AND prio > 2.
+  pub filter: Option<String>,
+
+  // Full-text search query, e.g. &q=hello world.
+  pub search: Option<String>,
+
+  // Geospatial filters, e.g. &near=<lat>,<lng>,<radius> and
+  // &bbox=<min_lat>,<min_lng>,<max_lat>,<max_lng>. See [GeoNear] and [GeoBoundingBox].
+  pub near: Option<String>,
+  pub bbox: Option<String>,
+
+  // Streaming export format, e.g. &format=csv.
+  pub format: Option<String>,
+
+  // Aggregation, e.g. &group_by=status,month(created)&aggregate=count,sum(amount).
+  pub group_by: Option<String>,
+  pub aggregate: Option<String>,
+
   // Map from filter params to filter value. It's a vector in cases like
   // "col0[gte]=2&col0[lte]=10".
   pub params: HashMap<String, Vec<QueryParam>>,
@@ -105,6 +149,14 @@ pub fn limit_or_default(limit: Option<usize>) -> usize {
 ///
 /// An example query may look like:
 ///  ?cursor=[0:16]&limit=50&order=price,-date&price[lte]=100&date[gte]=<timestamp>.
+///
+/// Simple column filters are AND-ed. More complex filters can be expressed using `filter`, see
+/// [FilterExpr].
+///
+/// Keys of the above list operations, i.e. limit, cursor, offset, order, expand, fields, envelope,
+/// count, filter, q, near, bbox, format, group_by and aggregate, are reserved and shadow columns
+/// of the same name. Columns can always be filtered unambiguously using `filter[col]=value` or
+/// `filter[col][op]=value`.
 pub fn parse_query(query: Option<String>) -> Option<QueryParseResult> {
   let q = query?;
   if q.is_empty() {
@@ -115,8 +167,19 @@ pub fn parse_query(query: Option<String>) -> Option<QueryParseResult> {
   for (key, value) in form_urlencoded::parse(q.as_bytes()) {
     match key.as_ref() {
       "limit" => result.limit = value.parse::<usize>().ok(),
-      "cursor" => result.cursor = b64_to_id(value.as_ref()).ok(),
+      "cursor" => result.cursor = Some(value.to_string()),
       "offset" => result.offset = value.parse::<usize>().ok(),
+      "expand" => result.expand = Some(value.to_string()),
+      "fields" => result.fields = Some(value.to_string()),
+      "envelope" => result.envelope = parse_bool(&value),
+      "count" => result.count = parse_bool(&value),
+      "filter" => result.filter = Some(value.to_string()),
+      "q" => result.search = Some(value.to_string()),
+      "near" => result.near = Some(value.to_string()),
+      "bbox" => result.bbox = Some(value.to_string()),
+      "format" => result.format = Some(value.to_string()),
+      "group_by" => result.group_by = Some(value.to_string()),
+      "aggregate" => result.aggregate = Some(value.to_string()),
       "order" => {
         let order: Vec<(String, Order)> = value
           .split(",")
@@ -163,54 +226,247 @@ pub fn parse_query(query: Option<String>) -> Option<QueryParseResult> {
   return Some(result);
 }
 
+fn parse_bool(value: &str) -> bool {
+  return matches!(value, "true" | "TRUE" | "1");
+}
+
 #[derive(Debug, Clone)]
 pub struct WhereClause {
   pub clause: String,
   pub params: Vec<(String, libsql::Value)>,
 }
 
+/// Circle around a center given in degrees with a radius in meters, i.e. "<lat>,<lng>,<radius>".
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeoNear {
+  pub lat: f64,
+  pub lng: f64,
+  pub radius: f64,
+}
+
+impl GeoNear {
+  pub fn parse(near: &str) -> Result<Self, WhereClauseError> {
+    let [lat, lng, radius] = parse_coordinates::<3>(near)?;
+    if !valid_lat(lat) || !valid_lng(lng) || radius < 0.0 {
+      return Err(WhereClauseError::Parse(format!("Invalid near: {near}")));
+    }
+    return Ok(GeoNear { lat, lng, radius });
+  }
+
+  /// Smallest bounding box containing the circle. Circles covering a pole span all longitudes.
+  fn bounding_box(&self) -> GeoBoundingBox {
+    // Mean earth radius in meters, same as `geo_distance`.
+    const EARTH_RADIUS: f64 = 6_371_008.8;
+
+    let angle = self.radius / EARTH_RADIUS;
+    let min_lat = self.lat - angle.to_degrees();
+    let max_lat = self.lat + angle.to_degrees();
+
+    let cos_lat = self.lat.to_radians().cos();
+    if min_lat <= -90.0 || max_lat >= 90.0 || angle.sin() >= cos_lat {
+      return GeoBoundingBox {
+        min_lat: min_lat.max(-90.0),
+        min_lng: -180.0,
+        max_lat: max_lat.min(90.0),
+        max_lng: 180.0,
+      };
+    }
+
+    let d_lng = (angle.sin() / cos_lat).asin().to_degrees();
+    let wrap = |lng: f64| match lng {
+      lng if lng < -180.0 => lng + 360.0,
+      lng if lng > 180.0 => lng - 360.0,
+      lng => lng,
+    };
+
+    return GeoBoundingBox {
+      min_lat,
+      min_lng: wrap(self.lng - d_lng),
+      max_lat,
+      max_lng: wrap(self.lng + d_lng),
+    };
+  }
+}
+
+/// Bounding box given in degrees, i.e. "<min_lat>,<min_lng>,<max_lat>,<max_lng>".
+///
+/// Boxes crossing the anti-meridian have a `min_lng` greater than their `max_lng`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeoBoundingBox {
+  pub min_lat: f64,
+  pub min_lng: f64,
+  pub max_lat: f64,
+  pub max_lng: f64,
+}
+
+impl GeoBoundingBox {
+  pub fn parse(bbox: &str) -> Result<Self, WhereClauseError> {
+    let [min_lat, min_lng, max_lat, max_lng] = parse_coordinates::<4>(bbox)?;
+    if !valid_lat(min_lat)
+      || !valid_lat(max_lat)
+      || !valid_lng(min_lng)
+      || !valid_lng(max_lng)
+      || min_lat > max_lat
+    {
+      return Err(WhereClauseError::Parse(format!("Invalid bbox: {bbox}")));
+    }
+    return Ok(GeoBoundingBox {
+      min_lat,
+      min_lng,
+      max_lat,
+      max_lng,
+    });
+  }
+}
+
+/// Geospatial filters backed by a [GeoIndex].
+///
+/// NOTE: Filtered rows are expected to expose their rowid as `_ROW_._rowid`, which is what the
+/// R*-tree index is keyed by.
+#[derive(Clone, Debug)]
+pub struct GeoFilter<'a> {
+  pub index: &'a GeoIndex,
+  pub near: Option<GeoNear>,
+  pub bbox: Option<GeoBoundingBox>,
+}
+
+fn parse_coordinates<const N: usize>(value: &str) -> Result<[f64; N], WhereClauseError> {
+  let err = || WhereClauseError::Parse(format!("Invalid coordinates: {value}"));
+
+  let values = value
+    .split(',')
+    .map(|v| v.trim().parse::<f64>().ok().filter(|v| v.is_finite()))
+    .collect::<Option<Vec<_>>>()
+    .ok_or_else(err)?;
+  return values.try_into().map_err(|_| err());
+}
+
+fn valid_lat(lat: f64) -> bool {
+  return (-90.0..=90.0).contains(&lat);
+}
+
+fn valid_lng(lng: f64) -> bool {
+  return (-180.0..=180.0).contains(&lng);
+}
+
+/// Builds the clause for rows within `bbox` using the R*-tree index for a coarse pre-selection,
+/// since it stores 32-bit floats, followed by exact comparisons.
+fn build_geo_bbox_clause(
+  index: &GeoIndex,
+  bbox: &GeoBoundingBox,
+  exact: bool,
+  params: &mut Vec<(String, libsql::Value)>,
+) -> String {
+  let mut bind = |value: f64| {
+    let placeholder = format!(":__geo{}", params.len());
+    params.push((placeholder.clone(), libsql::Value::Real(value)));
+    return placeholder;
+  };
+  let (min_lat, min_lng) = (bind(bbox.min_lat), bind(bbox.min_lng));
+  let (max_lat, max_lng) = (bind(bbox.max_lat), bind(bbox.max_lng));
+
+  let GeoIndex {
+    table,
+    latitude_column: lat,
+    longitude_column: lng,
+  } = index;
+  let lng_joiner = match bbox.min_lng <= bbox.max_lng {
+    true => "AND",
+    // Crossing the anti-meridian.
+    false => "OR",
+  };
+
+  let index_clause = format!(
+    r#"_ROW_._rowid IN (SELECT id FROM "{table}" WHERE "{lat}_max" >= {min_lat} AND "{lat}_min" <= {max_lat} AND ("{lng}_max" >= {min_lng} {lng_joiner} "{lng}_min" <= {max_lng}))"#
+  );
+  if !exact {
+    return index_clause;
+  }
+
+  return format!(
+    r#"{index_clause} AND _ROW_."{lat}" BETWEEN {min_lat} AND {max_lat} AND (_ROW_."{lng}" >= {min_lng} {lng_joiner} _ROW_."{lng}" <= {max_lng})"#
+  );
+}
+
+fn build_geo_clauses(geo: &GeoFilter, params: &mut Vec<(String, libsql::Value)>) -> Vec<String> {
+  let mut clauses: Vec<String> = vec![];
+
+  if let Some(ref near) = geo.near {
+    // Pre-select using the circle's bounding box and then filter by exact distance.
+    let index_clause = build_geo_bbox_clause(geo.index, &near.bounding_box(), false, params);
+
+    let mut bind = |value: f64| {
+      let placeholder = format!(":__geo{}", params.len());
+      params.push((placeholder.clone(), libsql::Value::Real(value)));
+      return placeholder;
+    };
+    let (lat, lng, radius) = (bind(near.lat), bind(near.lng), bind(near.radius));
+
+    let GeoIndex {
+      latitude_column,
+      longitude_column,
+      ..
+    } = geo.index;
+    clauses.push(format!(
+      r#"{index_clause} AND geo_distance(_ROW_."{latitude_column}", _ROW_."{longitude_column}", {lat}, {lng}) <= {radius}"#
+    ));
+  }
+
+  if let Some(ref bbox) = geo.bbox {
+    clauses.push(build_geo_bbox_clause(geo.index, bbox, true, params));
+  }
+
+  return clauses;
+}
+
 pub fn build_filter_where_clause(
   table_metadata: &dyn TableOrViewMetadata,
   filter_params: Option<HashMap<String, Vec<QueryParam>>>,
+  filter: Option<&str>,
+  geo: Option<&GeoFilter>,
 ) -> Result<WhereClause, WhereClauseError> {
   let mut where_clauses: Vec<String> = vec![];
   let mut params: Vec<(String, libsql::Value)> = vec![];
 
   if let Some(filter_params) = filter_params {
     for (column_name, query_params) in filter_params {
-      if column_name.starts_with("_") {
-        return Err(WhereClauseError::UnrecognizedParam(format!(
-          "Invalid parameter: {column_name}"
-        )));
-      }
-
-      let Some((col, _col_meta)) = table_metadata.column_by_name(&column_name) else {
-        return Err(WhereClauseError::UnrecognizedParam(format!(
-          "Unrecognized parameter: {column_name}"
-        )));
-      };
+      let col = lookup_filter_column(table_metadata, &column_name)?;
 
       for query_param in query_params {
-        let Some(op) = query_param.qualifier.map(|q| q.to_sql()) else {
+        let Some(qualifier) = query_param.qualifier else {
           info!("No op for: {column_name}={query_param:?}");
           continue;
         };
 
-        match simple_json_value_to_param(
-          col.data_type,
-          serde_json::Value::String(query_param.value.clone()),
-        ) {
-          Ok(value) => {
-            let clause = format!("{column_name} {op} :{column_name}");
-            where_clauses.push(clause);
-            params.push((format!(":{column_name}"), value));
+        let values: Vec<serde_json::Value> = match qualifier {
+          Qualifier::In | Qualifier::NotIn => query_param
+            .value
+            .split(",")
+            .map(|v| serde_json::Value::String(v.to_string()))
+            .collect(),
+          Qualifier::Is | Qualifier::IsNot if query_param.value.eq_ignore_ascii_case("null") => {
+            vec![serde_json::Value::Null]
           }
+          _ => vec![serde_json::Value::String(query_param.value.clone())],
+        };
+
+        match build_predicate(col, qualifier, values, &mut params) {
+          Ok(clause) => where_clauses.push(clause),
           Err(err) => debug!("Parameter conversion for {column_name} failed: {err}"),
         };
       }
     }
   }
 
+  if let Some(filter) = filter {
+    let expr = FilterExpr::parse(filter)?;
+    where_clauses.push(build_expression(table_metadata, &expr, &mut params)?);
+  }
+
+  if let Some(geo) = geo {
+    where_clauses.append(&mut build_geo_clauses(geo, &mut params));
+  }
+
   let clause = match where_clauses.len() {
     0 => "TRUE".to_string(),
     _ => where_clauses.join(" AND "),
@@ -219,8 +475,632 @@ pub fn build_filter_where_clause(
   return Ok(WhereClause { clause, params });
 }
 
+fn lookup_filter_column<'a>(
+  table_metadata: &'a dyn TableOrViewMetadata,
+  column_name: &str,
+) -> Result<&'a Column, WhereClauseError> {
+  if column_name.starts_with("_") {
+    return Err(WhereClauseError::UnrecognizedParam(format!(
+      "Invalid parameter: {column_name}"
+    )));
+  }
+
+  let Some((col, _col_meta)) = table_metadata.column_by_name(column_name) else {
+    return Err(WhereClauseError::UnrecognizedParam(format!(
+      "Unrecognized parameter: {column_name}"
+    )));
+  };
+
+  return Ok(col);
+}
+
+/// Builds a single "column op value" clause. Values are always bound as parameters using unique
+/// placeholders, which allows filtering the same column more than once.
+fn build_predicate(
+  col: &Column,
+  qualifier: Qualifier,
+  values: Vec<serde_json::Value>,
+  params: &mut Vec<(String, libsql::Value)>,
+) -> Result<String, ParamsError> {
+  let values = values
+    .into_iter()
+    .map(|value| simple_json_value_to_param(col.data_type, value))
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let placeholders: Vec<String> = values
+    .into_iter()
+    .map(|value| {
+      let placeholder = format!(":__filter{}", params.len());
+      params.push((placeholder.clone(), value));
+      return placeholder;
+    })
+    .collect();
+
+  let column_name = &col.name;
+  let op = qualifier.to_sql();
+  return Ok(match qualifier {
+    Qualifier::In | Qualifier::NotIn => {
+      format!(
+        r#"_ROW_."{column_name}" {op} ({})"#,
+        placeholders.join(", ")
+      )
+    }
+    _ => format!(r#"_ROW_."{column_name}" {op} {}"#, placeholders.join(", ")),
+  });
+}
+
+fn build_expression(
+  table_metadata: &dyn TableOrViewMetadata,
+  expr: &FilterExpr,
+  params: &mut Vec<(String, libsql::Value)>,
+) -> Result<String, WhereClauseError> {
+  return match expr {
+    FilterExpr::And(exprs) | FilterExpr::Or(exprs) => {
+      let clauses = exprs
+        .iter()
+        .map(|e| build_expression(table_metadata, e, params))
+        .collect::<Result<Vec<_>, _>>()?;
+      let separator = match expr {
+        FilterExpr::And(_) => " AND ",
+        _ => " OR ",
+      };
+      Ok(format!("({})", clauses.join(separator)))
+    }
+    FilterExpr::Not(expr) => Ok(format!(
+      "(NOT {})",
+      build_expression(table_metadata, expr, params)?
+    )),
+    FilterExpr::Predicate {
+      column,
+      qualifier,
+      values,
+    } => {
+      let col = lookup_filter_column(table_metadata, column)?;
+      build_predicate(col, *qualifier, values.clone(), params)
+        .map_err(|err| WhereClauseError::Parse(format!("Invalid value for {column}: {err}")))
+    }
+  };
+}
+
+/// Maximum nesting of parentheses and NOTs in a filter expression.
+const MAX_FILTER_DEPTH: usize = 16;
+
+/// Composite filter expression.
+///
+/// Syntax, with keywords being case-insensitive:
+///
+///   expr      := and ("OR" and)*
+///   and       := unary ("AND" unary)*
+///   unary     := "NOT" unary | "(" expr ")" | predicate
+///   predicate := column ("=" | "!=" | "<>" | "<" | "<=" | ">" | ">=" | "LIKE" | "REGEXP") value
+///              | column "IS" ["NOT"] "NULL"
+///              | column ["NOT"] "IN" "(" value ("," value)* ")"
+///   value     := 'string' | number | "TRUE" | "FALSE"
+///
+/// Single quotes within strings are escaped by doubling them, e.g. 'it''s'.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FilterExpr {
+  And(Vec<FilterExpr>),
+  Or(Vec<FilterExpr>),
+  Not(Box<FilterExpr>),
+  Predicate {
+    column: String,
+    qualifier: Qualifier,
+    values: Vec<serde_json::Value>,
+  },
+}
+
+impl FilterExpr {
+  pub fn parse(filter: &str) -> Result<Self, WhereClauseError> {
+    let mut parser = FilterParser {
+      tokens: tokenize_filter(filter)?,
+      pos: 0,
+      depth: 0,
+    };
+
+    let expr = parser.parse_or()?;
+    if parser.pos != parser.tokens.len() {
+      return Err(WhereClauseError::Parse(
+        "Unexpected trailing filter input".to_string(),
+      ));
+    }
+    return Ok(expr);
+  }
+
+  /// Columns referenced by the expression's predicates.
+  pub fn columns(&self) -> Vec<&str> {
+    return match self {
+      FilterExpr::And(exprs) | FilterExpr::Or(exprs) => {
+        exprs.iter().flat_map(|e| e.columns()).collect()
+      }
+      FilterExpr::Not(expr) => expr.columns(),
+      FilterExpr::Predicate { column, .. } => vec![column.as_str()],
+    };
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+  // Identifiers and keywords.
+  Word(String),
+  String(String),
+  Number(serde_json::Number),
+  Op(&'static str),
+  LParen,
+  RParen,
+  Comma,
+}
+
+const KEYWORDS: [&str; 10] = [
+  "AND", "OR", "NOT", "IN", "IS", "NULL", "LIKE", "REGEXP", "TRUE", "FALSE",
+];
+
+fn tokenize_filter(input: &str) -> Result<Vec<Token>, WhereClauseError> {
+  let mut tokens: Vec<Token> = vec![];
+  let mut chars = input.chars().peekable();
+
+  while let Some(&c) = chars.peek() {
+    match c {
+      c if c.is_whitespace() => {
+        chars.next();
+      }
+      '(' | ')' | ',' => {
+        chars.next();
+        tokens.push(match c {
+          '(' => Token::LParen,
+          ')' => Token::RParen,
+          _ => Token::Comma,
+        });
+      }
+      '\'' => {
+        chars.next();
+        let mut str = String::new();
+        loop {
+          match chars.next() {
+            Some('\'') if chars.peek() == Some(&'\'') => {
+              chars.next();
+              str.push('\'');
+            }
+            Some('\'') => break,
+            Some(c) => str.push(c),
+            None => {
+              return Err(WhereClauseError::Parse(
+                "Unterminated string in filter".to_string(),
+              ));
+            }
+          }
+        }
+        tokens.push(Token::String(str));
+      }
+      '=' | '!' | '<' | '>' => {
+        chars.next();
+        let op = match (c, chars.peek().copied()) {
+          ('=', Some('=')) | ('!', Some('=')) | ('<', Some('=' | '>')) | ('>', Some('=')) => {
+            let next = chars.next();
+            match (c, next) {
+              ('=', _) => "=",
+              ('!', _) | ('<', Some('>')) => "<>",
+              ('<', _) => "<=",
+              _ => ">=",
+            }
+          }
+          ('=', _) => "=",
+          ('<', _) => "<",
+          ('>', _) => ">",
+          _ => {
+            return Err(WhereClauseError::Parse(format!(
+              "Unexpected '{c}' in filter"
+            )));
+          }
+        };
+        tokens.push(Token::Op(op));
+      }
+      c if c == '-' || c.is_ascii_digit() => {
+        let mut number = String::new();
+        while let Some(&c) = chars.peek() {
+          if !(c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+')) {
+            break;
+          }
+          number.push(c);
+          chars.next();
+        }
+        let Ok(number) = number.parse::<serde_json::Number>() else {
+          return Err(WhereClauseError::Parse(format!(
+            "Invalid number in filter: {number}"
+          )));
+        };
+        tokens.push(Token::Number(number));
+      }
+      c if c.is_alphabetic() || c == '_' => {
+        let mut word = String::new();
+        while let Some(&c) = chars.peek() {
+          if !(c.is_alphanumeric() || c == '_') {
+            break;
+          }
+          word.push(c);
+          chars.next();
+        }
+        tokens.push(Token::Word(word));
+      }
+      c => {
+        return Err(WhereClauseError::Parse(format!(
+          "Unexpected '{c}' in filter"
+        )));
+      }
+    }
+  }
+
+  return Ok(tokens);
+}
+
+struct FilterParser {
+  tokens: Vec<Token>,
+  pos: usize,
+  depth: usize,
+}
+
+impl FilterParser {
+  fn next(&mut self) -> Option<Token> {
+    let token = self.tokens.get(self.pos).cloned();
+    if token.is_some() {
+      self.pos += 1;
+    }
+    return token;
+  }
+
+  fn consume(&mut self, expected: &Token) -> bool {
+    if self.tokens.get(self.pos) == Some(expected) {
+      self.pos += 1;
+      return true;
+    }
+    return false;
+  }
+
+  fn consume_keyword(&mut self, keyword: &str) -> bool {
+    if let Some(Token::Word(word)) = self.tokens.get(self.pos) {
+      if word.eq_ignore_ascii_case(keyword) {
+        self.pos += 1;
+        return true;
+      }
+    }
+    return false;
+  }
+
+  fn expect(&mut self, expected: Token) -> Result<(), WhereClauseError> {
+    if !self.consume(&expected) {
+      return Err(WhereClauseError::Parse(format!(
+        "Expected {expected:?} in filter"
+      )));
+    }
+    return Ok(());
+  }
+
+  fn descend(&mut self) -> Result<(), WhereClauseError> {
+    self.depth += 1;
+    if self.depth > MAX_FILTER_DEPTH {
+      return Err(WhereClauseError::Parse(
+        "Filter nested too deeply".to_string(),
+      ));
+    }
+    return Ok(());
+  }
+
+  fn parse_or(&mut self) -> Result<FilterExpr, WhereClauseError> {
+    let mut exprs = vec![self.parse_and()?];
+    while self.consume_keyword("OR") {
+      exprs.push(self.parse_and()?);
+    }
+
+    return Ok(match exprs.len() {
+      1 => exprs.swap_remove(0),
+      _ => FilterExpr::Or(exprs),
+    });
+  }
+
+  fn parse_and(&mut self) -> Result<FilterExpr, WhereClauseError> {
+    let mut exprs = vec![self.parse_unary()?];
+    while self.consume_keyword("AND") {
+      exprs.push(self.parse_unary()?);
+    }
+
+    return Ok(match exprs.len() {
+      1 => exprs.swap_remove(0),
+      _ => FilterExpr::And(exprs),
+    });
+  }
+
+  fn parse_unary(&mut self) -> Result<FilterExpr, WhereClauseError> {
+    if self.consume_keyword("NOT") {
+      self.descend()?;
+      let expr = self.parse_unary()?;
+      self.depth -= 1;
+      return Ok(FilterExpr::Not(Box::new(expr)));
+    }
+
+    if self.consume(&Token::LParen) {
+      self.descend()?;
+      let expr = self.parse_or()?;
+      self.expect(Token::RParen)?;
+      self.depth -= 1;
+      return Ok(expr);
+    }
+
+    return self.parse_predicate();
+  }
+
+  fn parse_predicate(&mut self) -> Result<FilterExpr, WhereClauseError> {
+    let column = match self.next() {
+      Some(Token::Word(word)) if !KEYWORDS.iter().any(|k| word.eq_ignore_ascii_case(k)) => word,
+      _ => {
+        return Err(WhereClauseError::Parse(
+          "Expected column in filter".to_string(),
+        ));
+      }
+    };
+
+    if self.consume_keyword("IS") {
+      let qualifier = match self.consume_keyword("NOT") {
+        true => Qualifier::IsNot,
+        false => Qualifier::Is,
+      };
+      if !self.consume_keyword("NULL") {
+        return Err(WhereClauseError::Parse(
+          "Expected NULL in filter".to_string(),
+        ));
+      }
+      return Ok(FilterExpr::Predicate {
+        column,
+        qualifier,
+        values: vec![serde_json::Value::Null],
+      });
+    }
+
+    let negated = self.consume_keyword("NOT");
+    if self.consume_keyword("IN") {
+      self.expect(Token::LParen)?;
+      let mut values = vec![self.parse_value()?];
+      while self.consume(&Token::Comma) {
+        values.push(self.parse_value()?);
+      }
+      self.expect(Token::RParen)?;
+
+      return Ok(FilterExpr::Predicate {
+        column,
+        qualifier: match negated {
+          true => Qualifier::NotIn,
+          false => Qualifier::In,
+        },
+        values,
+      });
+    } else if negated {
+      return Err(WhereClauseError::Parse("Expected IN in filter".to_string()));
+    }
+
+    let qualifier = match self.next() {
+      Some(Token::Op("=")) => Qualifier::Equal,
+      Some(Token::Op("<>")) => Qualifier::NotEqual,
+      Some(Token::Op("<")) => Qualifier::LessThan,
+      Some(Token::Op("<=")) => Qualifier::LessThanEqual,
+      Some(Token::Op(">")) => Qualifier::GreaterThan,
+      Some(Token::Op(">=")) => Qualifier::GreaterThanEqual,
+      Some(Token::Word(word)) if word.eq_ignore_ascii_case("LIKE") => Qualifier::Like,
+      Some(Token::Word(word)) if word.eq_ignore_ascii_case("REGEXP") => Qualifier::Regexp,
+      _ => {
+        return Err(WhereClauseError::Parse(
+          "Expected operator in filter".to_string(),
+        ));
+      }
+    };
+
+    return Ok(FilterExpr::Predicate {
+      column,
+      qualifier,
+      values: vec![self.parse_value()?],
+    });
+  }
+
+  fn parse_value(&mut self) -> Result<serde_json::Value, WhereClauseError> {
+    return match self.next() {
+      Some(Token::String(str)) => Ok(serde_json::Value::String(str)),
+      Some(Token::Number(number)) => Ok(serde_json::Value::Number(number)),
+      Some(Token::Word(word)) if word.eq_ignore_ascii_case("TRUE") => {
+        Ok(serde_json::Value::Bool(true))
+      }
+      Some(Token::Word(word)) if word.eq_ignore_ascii_case("FALSE") => {
+        Ok(serde_json::Value::Bool(false))
+      }
+      _ => Err(WhereClauseError::Parse(
+        "Expected value in filter".to_string(),
+      )),
+    };
+  }
+}
+
+/// Validates the requested `order` and appends the primary key as a tie-breaker, if not already
+/// present, to make the ordering total. The resulting key set is the basis for keyset pagination.
+///
+/// Besides the table's columns, one may order by `computed_columns`, e.g. a distance.
+pub fn build_keyset(
+  table_metadata: &dyn TableOrViewMetadata,
+  order: Vec<(String, Order)>,
+  pk_column: Option<&str>,
+  computed_columns: &[&str],
+) -> Result<Vec<(String, Order)>, WhereClauseError> {
+  let mut keyset: Vec<(String, Order)> = Vec::with_capacity(order.len() + 1);
+  for (column_name, ord) in order {
+    if table_metadata.column_by_name(&column_name).is_none()
+      && !computed_columns.contains(&column_name.as_str())
+    {
+      return Err(WhereClauseError::UnrecognizedParam(format!(
+        "Unrecognized order column: {column_name}"
+      )));
+    }
+    if !keyset.iter().any(|(c, _)| *c == column_name) {
+      keyset.push((column_name, ord));
+    }
+  }
+
+  if let Some(pk_column) = pk_column {
+    if !keyset.iter().any(|(c, _)| c == pk_column) {
+      keyset.push((pk_column.to_string(), Order::Descending));
+    }
+  }
+
+  return Ok(keyset);
+}
+
+/// Builds the ORDER BY clause for the given key set over a table or sub-query aliased `_ROW_`.
+pub fn build_order_clause(keyset: &[(String, Order)]) -> String {
+  if keyset.is_empty() {
+    return "NULL".to_string();
+  }
+
+  return keyset
+    .iter()
+    .map(|(col, ord)| {
+      format!(
+        r#"_ROW_."{col}" {}"#,
+        match ord {
+          Order::Descending => "DESC",
+          Order::Ascending => "ASC",
+        }
+      )
+    })
+    .collect::<Vec<_>>()
+    .join(", ");
+}
+
+/// Opaque keyset pagination cursor, i.e. the values of the key set columns of the last row of the
+/// previous page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cursor(pub Vec<libsql::Value>);
+
+#[derive(Deserialize, Serialize)]
+enum CursorValue {
+  #[serde(rename = "n")]
+  Null,
+  #[serde(rename = "i")]
+  Integer(i64),
+  #[serde(rename = "r")]
+  Real(f64),
+  #[serde(rename = "t")]
+  Text(String),
+  #[serde(rename = "b")]
+  Blob(String),
+}
+
+impl Cursor {
+  /// Extracts the key set values from the given row.
+  pub fn from_row(keyset: &[(String, Order)], row: &libsql::Row) -> Option<Self> {
+    let values = keyset
+      .iter()
+      .map(|(col, _)| {
+        let index = (0..row.column_count()).find(|i| row.column_name(*i) == Some(col.as_str()))?;
+        return row.get_value(index).ok();
+      })
+      .collect::<Option<Vec<_>>>()?;
+
+    return Some(Cursor(values));
+  }
+
+  pub fn encode(&self) -> String {
+    let values: Vec<CursorValue> = self
+      .0
+      .iter()
+      .map(|value| match value {
+        libsql::Value::Null => CursorValue::Null,
+        libsql::Value::Integer(i) => CursorValue::Integer(*i),
+        libsql::Value::Real(r) => CursorValue::Real(*r),
+        libsql::Value::Text(t) => CursorValue::Text(t.clone()),
+        libsql::Value::Blob(b) => CursorValue::Blob(BASE64_URL_SAFE_NO_PAD.encode(b)),
+      })
+      .collect();
+
+    return BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(&values).unwrap_or_default());
+  }
+
+  pub fn parse(cursor: &str) -> Result<Self, WhereClauseError> {
+    let decoded = BASE64_URL_SAFE_NO_PAD
+      .decode(cursor)
+      .ok()
+      .and_then(|bytes| serde_json::from_slice::<Vec<CursorValue>>(&bytes).ok());
+
+    let Some(values) = decoded else {
+      // Fall back to plain base64 encoded UUIDs, i.e. the cursor format prior to keyset
+      // pagination.
+      let id =
+        b64_to_id(cursor).map_err(|_err| WhereClauseError::Parse("Invalid cursor".to_string()))?;
+      return Ok(Cursor(vec![libsql::Value::Blob(id.to_vec())]));
+    };
+
+    return Ok(Cursor(
+      values
+        .into_iter()
+        .map(|value| {
+          return Ok(match value {
+            CursorValue::Null => libsql::Value::Null,
+            CursorValue::Integer(i) => libsql::Value::Integer(i),
+            CursorValue::Real(r) => libsql::Value::Real(r),
+            CursorValue::Text(t) => libsql::Value::Text(t),
+            CursorValue::Blob(b) => libsql::Value::Blob(BASE64_URL_SAFE_NO_PAD.decode(b)?),
+          });
+        })
+        .collect::<Result<Vec<_>, WhereClauseError>>()?,
+    ));
+  }
+}
+
+/// Builds a WHERE clause matching all rows strictly after the cursor with respect to the key set
+/// ordering, i.e. (a, b) > (x, y) expanded to "a > x OR (a = x AND b > y)".
+///
+/// NOTE: SQLite sorts NULLs first in ascending order and last in descending order.
+pub fn build_cursor_where_clause(
+  keyset: &[(String, Order)],
+  cursor: &Cursor,
+) -> Result<WhereClause, WhereClauseError> {
+  if keyset.is_empty() || keyset.len() != cursor.0.len() {
+    return Err(WhereClauseError::Parse("Invalid cursor".to_string()));
+  }
+
+  let mut params: Vec<(String, libsql::Value)> = vec![];
+  let mut disjunctions: Vec<String> = vec![];
+  let mut equalities: Vec<String> = vec![];
+
+  for (index, ((col, ord), value)) in keyset.iter().zip(cursor.0.iter()).enumerate() {
+    let column = format!(r#"_ROW_."{col}""#);
+    let placeholder = format!(":__cursor{index}");
+
+    let after = match (ord, value) {
+      (Order::Ascending, libsql::Value::Null) => Some(format!("{column} IS NOT NULL")),
+      (Order::Ascending, _) => Some(format!("{column} > {placeholder}")),
+      // Nothing sorts after NULL in descending order.
+      (Order::Descending, libsql::Value::Null) => None,
+      (Order::Descending, _) => Some(format!("({column} < {placeholder} OR {column} IS NULL)")),
+    };
+
+    if let Some(after) = after {
+      let mut conjunction = equalities.clone();
+      conjunction.push(after);
+      disjunctions.push(format!("({})", conjunction.join(" AND ")));
+    }
+
+    equalities.push(format!("{column} IS {placeholder}"));
+    params.push((placeholder, value.clone()));
+  }
+
+  let clause = match disjunctions.len() {
+    0 => "FALSE".to_string(),
+    _ => format!("({})", disjunctions.join(" OR ")),
+  };
+
+  return Ok(WhereClause { clause, params });
+}
+
 fn split_key_into_col_and_op(key: &str) -> Option<(&str, Option<&str>)> {
-  let Some(captures) = QUALIFIER_REGEX.captures(key) else {
+  let Some(captures) = FILTER_REGEX
+    .captures(key)
+    .or_else(|| QUALIFIER_REGEX.captures(key))
+  else {
     // Regex didn't match, i.e. key has invalid format.
     return None;
   };
@@ -237,11 +1117,18 @@ lazy_static! {
   /// Regex that splits the key part of "column[op]=value", i.e. column & op.
   static ref QUALIFIER_REGEX: regex::Regex =
     regex::Regex::new(r"^(?<key>\w*)(?:\[(?<qualifier>\w+)\])?$").unwrap();
+
+  /// Regex that splits the key part of the explicit "filter[column][op]=value" form, which also
+  /// works for columns named like reserved keys, e.g. "filter[limit]=5".
+  static ref FILTER_REGEX: regex::Regex =
+    regex::Regex::new(r"^filter\[(?<key>\w+)\](?:\[(?<qualifier>\w+)\])?$").unwrap();
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::schema::Table;
+  use crate::table_metadata::{sqlite3_parse_into_statement, TableMetadata};
   use crate::util::id_to_b64;
 
   #[test]
@@ -259,6 +1146,24 @@ mod tests {
       Some(("_foo", Some("gte")))
     );
     assert_eq!(split_key_into_col_and_op("_foo[$!]"), None);
+
+    // Check explicit filters
+    assert_eq!(
+      split_key_into_col_and_op("filter[limit]"),
+      Some(("limit", None))
+    );
+    assert_eq!(
+      split_key_into_col_and_op("filter[limit][gte]"),
+      Some(("limit", Some("gte")))
+    );
+    assert_eq!(split_key_into_col_and_op("filter[]"), None);
+
+    // Reserved keys shadow columns, whereas explicit filters don't.
+    let result = parse_query(Some("limit=5&filter[limit]=6&filter[q][ne]=x".to_string())).unwrap();
+    assert_eq!(result.limit, Some(5));
+    assert_eq!(result.params["limit"][0].value, "6");
+    assert_eq!(result.params["q"][0].qualifier, Some(Qualifier::NotEqual));
+    assert_eq!(result.search, None);
   }
 
   #[test]
@@ -278,7 +1183,7 @@ mod tests {
       let result = parse_query(query).unwrap();
 
       assert_eq!(result.limit, Some(10));
-      assert_eq!(result.cursor, Some(cursor));
+      assert_eq!(result.cursor, Some(id_to_b64(&cursor)));
       assert_eq!(
         result.order.unwrap(),
         vec![
@@ -373,4 +1278,250 @@ mod tests {
       );
     }
   }
+
+  #[test]
+  fn test_filter_expr_parsing() {
+    assert_eq!(
+      FilterExpr::parse("(a = 'x' OR b IS NULL) AND c IN (1, 2) AND NOT d like 'it''s%'").unwrap(),
+      FilterExpr::And(vec![
+        FilterExpr::Or(vec![
+          FilterExpr::Predicate {
+            column: "a".to_string(),
+            qualifier: Qualifier::Equal,
+            values: vec![serde_json::json!("x")],
+          },
+          FilterExpr::Predicate {
+            column: "b".to_string(),
+            qualifier: Qualifier::Is,
+            values: vec![serde_json::Value::Null],
+          },
+        ]),
+        FilterExpr::Predicate {
+          column: "c".to_string(),
+          qualifier: Qualifier::In,
+          values: vec![serde_json::json!(1), serde_json::json!(2)],
+        },
+        FilterExpr::Not(Box::new(FilterExpr::Predicate {
+          column: "d".to_string(),
+          qualifier: Qualifier::Like,
+          values: vec![serde_json::json!("it's%")],
+        })),
+      ])
+    );
+
+    assert_eq!(
+      FilterExpr::parse("a IS NOT NULL OR b not in ('x') or c >= -1.5").unwrap(),
+      FilterExpr::Or(vec![
+        FilterExpr::Predicate {
+          column: "a".to_string(),
+          qualifier: Qualifier::IsNot,
+          values: vec![serde_json::Value::Null],
+        },
+        FilterExpr::Predicate {
+          column: "b".to_string(),
+          qualifier: Qualifier::NotIn,
+          values: vec![serde_json::json!("x")],
+        },
+        FilterExpr::Predicate {
+          column: "c".to_string(),
+          qualifier: Qualifier::GreaterThanEqual,
+          values: vec![serde_json::json!(-1.5)],
+        },
+      ])
+    );
+
+    assert!(FilterExpr::parse("").is_err());
+    assert!(FilterExpr::parse("a = ").is_err());
+    assert!(FilterExpr::parse("a = 'x").is_err());
+    assert!(FilterExpr::parse("(a = 1").is_err());
+    assert!(FilterExpr::parse("a = 1 b = 2").is_err());
+    assert!(FilterExpr::parse("a IN ()").is_err());
+    assert!(FilterExpr::parse("a IS 5").is_err());
+    assert!(FilterExpr::parse("AND = 5").is_err());
+    assert!(FilterExpr::parse("a = b").is_err());
+    assert!(FilterExpr::parse("a = 1; DROP TABLE x").is_err());
+    assert!(FilterExpr::parse(&format!("{}a = 1{}", "(".repeat(20), ")".repeat(20))).is_err());
+  }
+
+  #[test]
+  fn test_build_filter_where_clause() {
+    let table: Table = sqlite3_parse_into_statement(
+      "CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT, num INTEGER, _hidden TEXT) STRICT",
+    )
+    .unwrap()
+    .unwrap()
+    .try_into()
+    .unwrap();
+    let metadata = TableMetadata::new(table.clone(), &[table]);
+
+    let WhereClause { clause, params } = build_filter_where_clause(
+      &metadata,
+      parse_query(Some("num[in]=1,2".to_string())).map(|q| q.params),
+      Some("name = 'x' OR (num > 5 AND num IS NULL)"),
+      None,
+    )
+    .unwrap();
+    assert_eq!(
+      clause,
+      r#"_ROW_."num" IN (:__filter0, :__filter1) AND (_ROW_."name" = :__filter2 OR (_ROW_."num" > :__filter3 AND _ROW_."num" IS :__filter4))"#
+    );
+    assert_eq!(params.len(), 5);
+    assert_eq!(
+      params[3],
+      (":__filter3".to_string(), libsql::Value::Integer(5))
+    );
+    assert_eq!(params[4], (":__filter4".to_string(), libsql::Value::Null));
+
+    let WhereClause { clause, params } = build_filter_where_clause(
+      &metadata,
+      parse_query(Some("name[isnot]=null".to_string())).map(|q| q.params),
+      None,
+      None,
+    )
+    .unwrap();
+    assert_eq!(clause, r#"_ROW_."name" IS NOT :__filter0"#);
+    assert_eq!(params[0].1, libsql::Value::Null);
+
+    // Unknown and hidden columns are rejected.
+    assert!(build_filter_where_clause(&metadata, None, Some("unknown = 1"), None).is_err());
+    assert!(build_filter_where_clause(&metadata, None, Some("_hidden = 'x'"), None).is_err());
+    // Values must match the column type.
+    assert!(build_filter_where_clause(&metadata, None, Some("num = 'x'"), None).is_err());
+  }
+
+  #[test]
+  fn test_geo_filters() {
+    assert_eq!(
+      GeoNear::parse("52.5, 13.4,1000").unwrap(),
+      GeoNear {
+        lat: 52.5,
+        lng: 13.4,
+        radius: 1000.0
+      }
+    );
+    assert!(GeoNear::parse("52.5,13.4").is_err());
+    assert!(GeoNear::parse("91,13.4,1000").is_err());
+    assert!(GeoNear::parse("52.5,13.4,-1").is_err());
+    assert!(GeoNear::parse("52.5,NaN,1").is_err());
+
+    assert!(GeoBoundingBox::parse("10,20,11,21").is_ok());
+    // Crossing the anti-meridian.
+    assert!(GeoBoundingBox::parse("10,170,11,-170").is_ok());
+    assert!(GeoBoundingBox::parse("11,20,10,21").is_err());
+    assert!(GeoBoundingBox::parse("10,20,11").is_err());
+
+    // ~1 degree of latitude.
+    let bbox = GeoNear::parse("0,0,111195").unwrap().bounding_box();
+    assert!((bbox.max_lat - 1.0).abs() < 1e-3, "{bbox:?}");
+    assert!((bbox.min_lng + 1.0).abs() < 1e-3, "{bbox:?}");
+
+    // Longitude degrees shrink towards the poles.
+    let bbox = GeoNear::parse("60,0,111195").unwrap().bounding_box();
+    assert!(bbox.max_lng > 1.99 && bbox.max_lng < 2.01, "{bbox:?}");
+
+    let bbox = GeoNear::parse("0,179.5,111195").unwrap().bounding_box();
+    assert!(bbox.min_lng > bbox.max_lng, "{bbox:?}");
+
+    let bbox = GeoNear::parse("89.5,0,111195").unwrap().bounding_box();
+    assert_eq!(
+      (bbox.min_lng, bbox.max_lng, bbox.max_lat),
+      (-180.0, 180.0, 90.0)
+    );
+
+    let index = GeoIndex {
+      table: "_geo_api".to_string(),
+      latitude_column: "lat".to_string(),
+      longitude_column: "lng".to_string(),
+    };
+    let mut params = vec![];
+    let clauses = build_geo_clauses(
+      &GeoFilter {
+        index: &index,
+        near: None,
+        bbox: Some(GeoBoundingBox::parse("10,170,11,-170").unwrap()),
+      },
+      &mut params,
+    );
+    assert_eq!(
+      clauses,
+      vec![
+        r#"_ROW_._rowid IN (SELECT id FROM "_geo_api" WHERE "lat_max" >= :__geo0 AND "lat_min" <= :__geo2 AND ("lng_max" >= :__geo1 OR "lng_min" <= :__geo3)) AND _ROW_."lat" BETWEEN :__geo0 AND :__geo2 AND (_ROW_."lng" >= :__geo1 OR _ROW_."lng" <= :__geo3)"#
+      ]
+    );
+    assert_eq!(params.len(), 4);
+  }
+
+  #[test]
+  fn test_cursor() {
+    let cursor = Cursor(vec![
+      libsql::Value::Null,
+      libsql::Value::Integer(-5),
+      libsql::Value::Real(2.5),
+      libsql::Value::Text("text".to_string()),
+      libsql::Value::Blob(vec![0, 1, 2, 255]),
+    ]);
+    assert_eq!(Cursor::parse(&cursor.encode()).unwrap(), cursor);
+
+    // Legacy UUID cursors.
+    let id = [5; 16];
+    assert_eq!(
+      Cursor::parse(&id_to_b64(&id)).unwrap(),
+      Cursor(vec![libsql::Value::Blob(id.to_vec())])
+    );
+
+    assert!(Cursor::parse("invalid").is_err());
+  }
+
+  #[test]
+  fn test_build_cursor_where_clause() {
+    let table: Table = sqlite3_parse_into_statement(
+      "CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT, num INTEGER) STRICT",
+    )
+    .unwrap()
+    .unwrap()
+    .try_into()
+    .unwrap();
+    let metadata = TableMetadata::new(table.clone(), &[table]);
+
+    assert!(build_keyset(
+      &metadata,
+      vec![("unknown".to_string(), Order::Ascending)],
+      None,
+      &[],
+    )
+    .is_err());
+
+    let keyset = build_keyset(
+      &metadata,
+      vec![
+        ("num".to_string(), Order::Ascending),
+        ("name".to_string(), Order::Descending),
+      ],
+      Some("id"),
+      &[],
+    )
+    .unwrap();
+    assert_eq!(
+      build_order_clause(&keyset),
+      r#"_ROW_."num" ASC, _ROW_."name" DESC, _ROW_."id" DESC"#
+    );
+
+    let WhereClause { clause, params } = build_cursor_where_clause(
+      &keyset,
+      &Cursor(vec![
+        libsql::Value::Integer(1),
+        libsql::Value::Null,
+        libsql::Value::Integer(3),
+      ]),
+    )
+    .unwrap();
+    assert_eq!(
+      clause,
+      r#"((_ROW_."num" > :__cursor0) OR (_ROW_."num" IS :__cursor0 AND _ROW_."name" IS :__cursor1 AND (_ROW_."id" < :__cursor2 OR _ROW_."id" IS NULL)))"#
+    );
+    assert_eq!(params.len(), 3);
+
+    // Cursor doesn't match key set.
+    assert!(build_cursor_where_clause(&keyset, &Cursor(vec![libsql::Value::Integer(1)])).is_err());
+  }
 }
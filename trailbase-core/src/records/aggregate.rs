@@ -0,0 +1,518 @@
+use axum::{
+  extract::{Path, RawQuery, State},
+  Json,
+};
+
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::listing::{
+  build_filter_where_clause, parse_query, FilterExpr, QueryParseResult, WhereClause,
+};
+use crate::records::record_api::build_user_sub_select;
+use crate::records::sql_to_json::value_to_json;
+use crate::records::{Permission, RecordApi, RecordError};
+use crate::schema::ColumnDataType;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TimeBucket {
+  Day,
+  Month,
+  Year,
+}
+
+impl TimeBucket {
+  fn format(&self) -> &'static str {
+    return match self {
+      Self::Day => "%Y-%m-%d",
+      Self::Month => "%Y-%m",
+      Self::Year => "%Y",
+    };
+  }
+}
+
+/// Grouping by either plain column values or by time buckets of date-time columns, e.g.
+/// "month(created)".
+#[derive(Clone, Debug, PartialEq)]
+enum GroupBy {
+  Column(String),
+  Bucket(TimeBucket, String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AggregateFunction {
+  Count,
+  Sum,
+  Avg,
+  Min,
+  Max,
+}
+
+/// Aggregate function applied to a column, e.g. "sum(amount)". Only count may omit the column,
+/// i.e. "count" counts records.
+#[derive(Clone, Debug, PartialEq)]
+struct Aggregate {
+  function: AggregateFunction,
+  column: Option<String>,
+}
+
+/// Splits "name(arg)" into ("name", Some("arg")) and "name" into ("name", None).
+fn parse_call(expr: &str) -> Option<(&str, Option<&str>)> {
+  let expr = expr.trim();
+  let Some((name, rest)) = expr.split_once('(') else {
+    return Some((expr, None));
+  };
+  let arg = rest.strip_suffix(')')?.trim();
+  if arg.is_empty() {
+    return None;
+  }
+  return Some((name.trim(), Some(arg)));
+}
+
+fn parse_group_by(group_by: &str) -> Result<Vec<GroupBy>, RecordError> {
+  const ERR: RecordError = RecordError::BadRequest("Invalid group_by");
+
+  return group_by
+    .split(',')
+    .filter(|g| !g.trim().is_empty())
+    .map(|g| {
+      return match parse_call(g).ok_or(ERR)? {
+        (column, None) => Ok(GroupBy::Column(column.to_string())),
+        (bucket, Some(column)) => {
+          let bucket = match bucket {
+            "day" => TimeBucket::Day,
+            "month" => TimeBucket::Month,
+            "year" => TimeBucket::Year,
+            _ => return Err(ERR),
+          };
+          Ok(GroupBy::Bucket(bucket, column.to_string()))
+        }
+      };
+    })
+    .collect();
+}
+
+fn parse_aggregates(aggregate: &str) -> Result<Vec<Aggregate>, RecordError> {
+  const ERR: RecordError = RecordError::BadRequest("Invalid aggregate");
+
+  return aggregate
+    .split(',')
+    .filter(|a| !a.trim().is_empty())
+    .map(|a| {
+      let (function, column) = parse_call(a).ok_or(ERR)?;
+      let function = match function {
+        "count" => AggregateFunction::Count,
+        "sum" => AggregateFunction::Sum,
+        "avg" => AggregateFunction::Avg,
+        "min" => AggregateFunction::Min,
+        "max" => AggregateFunction::Max,
+        _ => return Err(ERR),
+      };
+      if column.is_none() && function != AggregateFunction::Count {
+        return Err(ERR);
+      }
+      return Ok(Aggregate {
+        function,
+        column: column.map(|c| c.to_string()),
+      });
+    })
+    .collect();
+}
+
+fn is_numeric(data_type: ColumnDataType) -> bool {
+  use ColumnDataType as T;
+  return !matches!(
+    data_type,
+    T::Blob
+      | T::Text
+      | T::JSON
+      | T::JSONB
+      | T::Character
+      | T::Varchar
+      | T::VaryingCharacter
+      | T::NChar
+      | T::NativeCharacter
+      | T::NVarChar
+      | T::Clob
+  );
+}
+
+/// Validates the referenced column against the API's metadata and column ACL.
+fn check_column(
+  api: &RecordApi,
+  column: &str,
+  user: Option<&User>,
+) -> Result<ColumnDataType, RecordError> {
+  let Some((col, _)) = api.metadata().column_by_name(column) else {
+    return Err(RecordError::BadRequest("Unknown column"));
+  };
+  if !api.column_readable(column, user) {
+    return Err(RecordError::BadRequest("Unknown column"));
+  }
+  return Ok(col.data_type);
+}
+
+fn group_by_select(
+  api: &RecordApi,
+  group_by: &GroupBy,
+  user: Option<&User>,
+) -> Result<(String, String), RecordError> {
+  return Ok(match group_by {
+    GroupBy::Column(column) => {
+      check_column(api, column, user)?;
+      (format!(r#"_ROW_."{column}""#), column.clone())
+    }
+    GroupBy::Bucket(bucket, column) => {
+      check_column(api, column, user)?;
+      let format = bucket.format();
+      // Numeric values are interpreted as unix timestamps, others as date-time strings.
+      let expr = format!(
+        r#"CASE WHEN typeof(_ROW_."{column}") IN ('integer', 'real') THEN strftime('{format}', _ROW_."{column}", 'unixepoch') ELSE strftime('{format}', _ROW_."{column}") END"#
+      );
+      let name = match bucket {
+        TimeBucket::Day => format!("day({column})"),
+        TimeBucket::Month => format!("month({column})"),
+        TimeBucket::Year => format!("year({column})"),
+      };
+      (expr, name)
+    }
+  });
+}
+
+/// Upper bound on the number of groups returned by an aggregation.
+const MAX_GROUPS: usize = 256;
+
+fn aggregate_select(
+  api: &RecordApi,
+  aggregate: &Aggregate,
+  user: Option<&User>,
+) -> Result<(String, String), RecordError> {
+  let function = match aggregate.function {
+    AggregateFunction::Count => "count",
+    AggregateFunction::Sum => "sum",
+    AggregateFunction::Avg => "avg",
+    AggregateFunction::Min => "min",
+    AggregateFunction::Max => "max",
+  };
+
+  let Some(ref column) = aggregate.column else {
+    return Ok(("COUNT(*)".to_string(), function.to_string()));
+  };
+
+  let data_type = check_column(api, column, user)?;
+  if matches!(
+    aggregate.function,
+    AggregateFunction::Sum | AggregateFunction::Avg
+  ) && !is_numeric(data_type)
+  {
+    return Err(RecordError::BadRequest("Invalid aggregate"));
+  }
+
+  return Ok((
+    format!(r#"{function}(_ROW_."{column}")"#),
+    format!("{function}({column})"),
+  ));
+}
+
+/// Aggregates records matching the given filters.
+///
+/// Accepts `group_by`, a comma-separated list of columns or time buckets thereof, i.e.
+/// `day(col)`, `month(col)` or `year(col)`, and `aggregate`, a comma-separated list of `count`,
+/// `count(col)`, `sum(col)`, `avg(col)`, `min(col)` and `max(col)`, which defaults to `count`.
+/// Filters are the same as for listing records. Only records passing the read and list access
+/// rules are aggregated. Returns one object per group keyed by the group by and aggregate
+/// expressions, e.g. `{"status": "open", "count": 3}`.
+///
+/// Results with more than 256 groups are rejected, unless a `limit` of at most 256 is given
+/// explicitly.
+#[utoipa::path(
+  get,
+  path = "/:name/aggregate",
+  responses(
+    (status = 200, description = "Aggregates, one per group.")
+  )
+)]
+pub async fn aggregate_records_handler(
+  State(state): State<AppState>,
+  Path(api_name): Path<String>,
+  RawQuery(raw_url_query): RawQuery,
+  user: Option<User>,
+) -> Result<Json<Vec<serde_json::Value>>, RecordError> {
+  let Some(api) = state.lookup_record_api(&api_name) else {
+    return Err(RecordError::ApiNotFound);
+  };
+
+  // Like listing, access rules filter the aggregated records rather than blocking access.
+  api.check_table_level_access(Permission::List, user.as_ref())?;
+
+  let QueryParseResult {
+    params: filter_params,
+    filter,
+    limit,
+    group_by,
+    aggregate,
+    ..
+  } = parse_query(raw_url_query).unwrap_or_default();
+
+  let group_by = group_by
+    .as_deref()
+    .map(parse_group_by)
+    .transpose()?
+    .unwrap_or_default();
+  let aggregates = parse_aggregates(aggregate.as_deref().unwrap_or("count"))?;
+  if aggregates.is_empty() {
+    return Err(RecordError::BadRequest("Invalid aggregate"));
+  }
+
+  let group_by_selects = group_by
+    .iter()
+    .map(|g| group_by_select(&api, g, user.as_ref()))
+    .collect::<Result<Vec<_>, _>>()?;
+  let aggregate_selects = aggregates
+    .iter()
+    .map(|a| aggregate_select(&api, a, user.as_ref()))
+    .collect::<Result<Vec<_>, _>>()?;
+
+  // Columns that cannot be read cannot be filtered by either, since that would leak their values.
+  let readable = |col: &str| api.column_readable(col, user.as_ref());
+  let filter_columns_readable = filter_params.keys().all(|col| readable(col))
+    && match filter.as_deref().map(FilterExpr::parse) {
+      Some(Ok(expr)) => expr.columns().into_iter().all(readable),
+      _ => true,
+    };
+  if !filter_columns_readable {
+    return Err(RecordError::BadRequest("Invalid filter params"));
+  }
+
+  let WhereClause {
+    mut clause,
+    mut params,
+  } = build_filter_where_clause(api.metadata(), Some(filter_params), filter.as_deref(), None)
+    .map_err(|_err| RecordError::BadRequest("Invalid filter params"))?;
+
+  let (user_sub_select, mut user_params) = build_user_sub_select(user.as_ref());
+  params.append(&mut user_params);
+
+  // Aggregates must not reveal more than reading or listing the records would, thus both access
+  // rules apply. The list rule falls back to the read rule.
+  let read_rule = api.access_rule(Permission::Read);
+  if let Some(read_rule) = read_rule {
+    clause = format!("({clause}) AND {read_rule}");
+  }
+  if let Some(list_rule) = api.access_rule(Permission::List) {
+    if Some(list_rule) != read_rule.as_ref() {
+      clause = format!("({clause}) AND {list_rule}");
+    }
+  }
+
+  // Soft-deleted records are excluded.
+  if let Some(column) = api.soft_delete_column() {
+    clause = format!(r#"({clause}) AND _ROW_."{column}" IS NULL"#);
+  }
+
+  let selects: Vec<String> = group_by_selects
+    .iter()
+    .chain(aggregate_selects.iter())
+    .map(|(expr, name)| format!(r#"{expr} AS "{name}""#))
+    .collect();
+  let group_by_clause = match group_by_selects.is_empty() {
+    true => String::new(),
+    false => {
+      let exprs: Vec<&str> = group_by_selects.iter().map(|(e, _)| e.as_str()).collect();
+      format!(
+        "GROUP BY {exprs} ORDER BY {exprs}",
+        exprs = exprs.join(", ")
+      )
+    }
+  };
+  // Explicit limits truncate the groups as requested, whereas exceeding the cap is rejected rather
+  // than silently returning partial results.
+  let (limit, truncate) = match limit {
+    Some(limit) if limit <= MAX_GROUPS => (limit, true),
+    _ => (MAX_GROUPS + 1, false),
+  };
+  params.push((":limit".to_string(), libsql::Value::Integer(limit as i64)));
+
+  let mut rows = state
+    .conn()
+    .query(
+      &format!(
+        r#"
+          SELECT {selects}
+          FROM
+            ({user_sub_select}) AS _USER_,
+            (SELECT * FROM '{table_name}') AS _ROW_
+          WHERE
+            {clause}
+          {group_by_clause}
+          LIMIT :limit
+        "#,
+        selects = selects.join(", "),
+        table_name = api.table_name(),
+      ),
+      libsql::params::Params::Named(params),
+    )
+    .await?;
+
+  let mut groups: Vec<serde_json::Value> = vec![];
+  while let Some(row) = rows.next().await? {
+    let mut group = serde_json::Map::new();
+    for (index, (_, name)) in group_by_selects
+      .iter()
+      .chain(aggregate_selects.iter())
+      .enumerate()
+    {
+      let value = row.get_value(index as i32)?;
+      group.insert(
+        name.clone(),
+        value_to_json(value).map_err(|err| RecordError::Internal(err.into()))?,
+      );
+    }
+    groups.push(serde_json::Value::Object(group));
+  }
+
+  if !truncate && groups.len() > MAX_GROUPS {
+    return Err(RecordError::BadRequest("Too many groups"));
+  }
+
+  return Ok(Json(groups));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app_state::*;
+  use crate::config::proto::PermissionFlag;
+  use crate::records::*;
+
+  #[test]
+  fn test_parse_aggregation() {
+    assert_eq!(
+      parse_group_by("status, month(created)").unwrap(),
+      vec![
+        GroupBy::Column("status".to_string()),
+        GroupBy::Bucket(TimeBucket::Month, "created".to_string()),
+      ]
+    );
+    assert!(parse_group_by("week(created)").is_err());
+    assert!(parse_group_by("month(created").is_err());
+
+    assert_eq!(
+      parse_aggregates("count,sum(amount)").unwrap(),
+      vec![
+        Aggregate {
+          function: AggregateFunction::Count,
+          column: None,
+        },
+        Aggregate {
+          function: AggregateFunction::Sum,
+          column: Some("amount".to_string()),
+        },
+      ]
+    );
+    assert!(parse_aggregates("sum").is_err());
+    assert!(parse_aggregates("median(amount)").is_err());
+    assert!(parse_aggregates("count()").is_err());
+  }
+
+  #[tokio::test]
+  async fn test_record_api_aggregate() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    state
+      .conn()
+      .execute_batch(
+        r#"
+          CREATE TABLE invoice (
+            id        INTEGER PRIMARY KEY,
+            status    TEXT NOT NULL,
+            amount    INTEGER NOT NULL,
+            created   INTEGER NOT NULL,
+            public    INTEGER NOT NULL
+          ) STRICT;
+
+          INSERT INTO invoice (status, amount, created, public) VALUES
+            ('open', 10, unixepoch('2024-01-15'), 1),
+            ('open', 20, unixepoch('2024-02-03'), 1),
+            ('paid', 30, unixepoch('2024-02-20'), 1),
+            ('paid', 1000, unixepoch('2024-02-21'), 0);
+        "#,
+      )
+      .await?;
+    state.table_metadata().invalidate_all().await?;
+
+    add_record_api(
+      &state,
+      "invoices_api",
+      "invoice",
+      Acls {
+        world: vec![PermissionFlag::Read, PermissionFlag::List],
+        ..Default::default()
+      },
+      AccessRules {
+        read: Some("_ROW_.public = 1".to_string()),
+        ..Default::default()
+      },
+    )
+    .await?;
+
+    let aggregate = |query: &str| {
+      aggregate_records_handler(
+        State(state.clone()),
+        Path("invoices_api".to_string()),
+        RawQuery(Some(query.to_string())),
+        None,
+      )
+    };
+
+    // Records not passing the read access rule are excluded.
+    let Json(groups) = aggregate("").await?;
+    assert_eq!(groups, vec![serde_json::json!({"count": 3})]);
+
+    let Json(groups) = aggregate("group_by=status&aggregate=count,sum(amount),avg(amount)").await?;
+    assert_eq!(
+      groups,
+      vec![
+        serde_json::json!({"status": "open", "count": 2, "sum(amount)": 30, "avg(amount)": 15.0}),
+        serde_json::json!({"status": "paid", "count": 1, "sum(amount)": 30, "avg(amount)": 30.0}),
+      ]
+    );
+
+    let Json(groups) =
+      aggregate("group_by=month(created)&aggregate=sum(amount)&amount[gt]=10").await?;
+    assert_eq!(
+      groups,
+      vec![serde_json::json!({"month(created)": "2024-02", "sum(amount)": 50})]
+    );
+
+    for query in [
+      "group_by=missing",
+      "aggregate=sum(status)",
+      "aggregate=max(_hidden)",
+      "group_by=status&aggregate=",
+    ] {
+      assert!(
+        matches!(aggregate(query).await, Err(RecordError::BadRequest(_))),
+        "{query}"
+      );
+    }
+
+    // Exceeding the cap on groups is an error unless explicitly limited.
+    state
+      .conn()
+      .execute_batch(
+        r#"
+          WITH RECURSIVE seq(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 300)
+          INSERT INTO invoice (status, amount, created, public)
+            SELECT 'open', n, unixepoch('2024-03-01'), 1 FROM seq;
+        "#,
+      )
+      .await?;
+    assert!(matches!(
+      aggregate("group_by=amount").await,
+      Err(RecordError::BadRequest("Too many groups"))
+    ));
+    let Json(groups) = aggregate("group_by=amount&limit=5").await?;
+    assert_eq!(groups.len(), 5);
+
+    return Ok(());
+  }
+}
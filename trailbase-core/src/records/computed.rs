@@ -0,0 +1,240 @@
+use trailbase_sqlite::query_row;
+
+use crate::auth::user::User;
+use crate::records::record_api::build_user_sub_select;
+use crate::records::sql_to_json::value_to_json;
+use crate::records::{RecordApi, RecordError};
+
+/// Select expressions for the API's computed fields, which need `_ROW_` and `_USER_` in scope.
+pub(crate) fn build_computed_field_selects(api: &RecordApi) -> Vec<String> {
+  return api
+    .computed_fields()
+    .iter()
+    .map(|field| {
+      format!(
+        r#"({expression}) AS "{name}""#,
+        expression = field.expression(),
+        name = field.name()
+      )
+    })
+    .collect();
+}
+
+/// Evaluates the API's computed fields for the given record, if any are configured.
+pub(crate) async fn query_computed_fields(
+  conn: &libsql::Connection,
+  api: &RecordApi,
+  record_id: &libsql::Value,
+  user: Option<&User>,
+) -> Result<Option<libsql::Row>, RecordError> {
+  let selects = build_computed_field_selects(api);
+  if selects.is_empty() {
+    return Ok(None);
+  }
+
+  let (user_sub_select, mut params) = build_user_sub_select(user);
+  params.push((":__record_id".to_string(), record_id.clone()));
+
+  return Ok(
+    query_row(
+      conn,
+      &format!(
+        r#"
+          SELECT {selects}
+          FROM
+            ({user_sub_select}) AS _USER_,
+            (SELECT * FROM '{table_name}' WHERE [{pk_column}] = :__record_id) AS _ROW_
+        "#,
+        selects = selects.join(", "),
+        table_name = api.table_name(),
+        pk_column = api.record_pk_column().name,
+      ),
+      libsql::params::Params::Named(params),
+    )
+    .await?,
+  );
+}
+
+/// Appends computed fields, i.e. the trailing columns of `row`, to `record`.
+///
+/// Like columns, computed fields are subject to the column ACL.
+pub(crate) fn append_computed_fields(
+  api: &RecordApi,
+  row: &libsql::Row,
+  record: &mut serde_json::Value,
+  user: Option<&User>,
+) -> Result<(), RecordError> {
+  let serde_json::Value::Object(map) = record else {
+    return Ok(());
+  };
+
+  let fields = api.computed_fields();
+  let offset = row.column_count() - fields.len() as i32;
+  for (index, field) in fields.iter().enumerate() {
+    if !api.column_readable(field.name(), user) {
+      continue;
+    }
+    let value = row.get_value(offset + index as i32)?;
+    map.insert(
+      field.name().to_string(),
+      value_to_json(value).map_err(|err| RecordError::Internal(err.into()))?,
+    );
+  }
+
+  return Ok(());
+}
+
+#[cfg(test)]
+mod test {
+  use axum::extract::{Json, Path, Query, RawQuery, State};
+  use axum::http::HeaderMap;
+
+  use super::*;
+  use crate::api::JsonSchemaMode;
+  use crate::app_state::*;
+  use crate::config::proto::{ComputedField, PermissionFlag};
+  use crate::records::json_schema::{json_schema_handler, JsonSchemaQuery};
+  use crate::records::list_records::list_records_handler;
+  use crate::records::read_record::{read_record_handler, ReadRecordQuery};
+  use crate::records::*;
+  use crate::test::unpack_json_response;
+
+  #[tokio::test]
+  async fn test_record_api_computed_fields() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    state
+      .conn()
+      .execute_batch(
+        r#"
+          CREATE TABLE person (
+            id        INTEGER PRIMARY KEY,
+            first     TEXT NOT NULL,
+            last      TEXT NOT NULL
+          ) STRICT;
+
+          INSERT INTO person (id, first, last) VALUES (1, 'Ada', 'Lovelace');
+        "#,
+      )
+      .await?;
+    state.table_metadata().invalidate_all().await?;
+
+    add_record_api(
+      &state,
+      "persons_api",
+      "person",
+      Acls {
+        world: vec![
+          PermissionFlag::Read,
+          PermissionFlag::List,
+          PermissionFlag::Schema,
+        ],
+        ..Default::default()
+      },
+      AccessRules::default(),
+    )
+    .await?;
+
+    let computed_field = |name: &str, expression: &str, r#type: Option<&str>| ComputedField {
+      name: Some(name.to_string()),
+      expression: Some(expression.to_string()),
+      r#type: r#type.map(|t| t.to_string()),
+    };
+
+    // Invalid computed fields are rejected.
+    for field in [
+      computed_field("first", "1", None),
+      computed_field("_hidden", "1", None),
+      computed_field("broken", "_ROW_.first ||", None),
+      computed_field("typed", "1", Some("NOT_A_TYPE")),
+    ] {
+      let mut config = state.get_config();
+      for api in &mut config.record_apis {
+        if api.name.as_deref() == Some("persons_api") {
+          api.computed_fields = vec![field.clone()];
+        }
+      }
+      assert!(
+        state
+          .validate_and_update_config(config, None)
+          .await
+          .is_err(),
+        "{field:?}"
+      );
+    }
+
+    let mut config = state.get_config();
+    for api in &mut config.record_apis {
+      if api.name.as_deref() == Some("persons_api") {
+        api.computed_fields = vec![
+          computed_field(
+            "full_name",
+            "_ROW_.first || ' ' || _ROW_.last",
+            Some("TEXT"),
+          ),
+          computed_field("signed_in", "_USER_.id IS NOT NULL", None),
+        ];
+      }
+    }
+    state.validate_and_update_config(config, None).await?;
+
+    let read = |fields: Option<&str>| {
+      let fields = fields.map(|f| f.to_string());
+      async {
+        let response = read_record_handler(
+          State(state.clone()),
+          Path(("persons_api".to_string(), "1".to_string())),
+          Query(ReadRecordQuery {
+            fields,
+            ..Default::default()
+          }),
+          None,
+          HeaderMap::new(),
+        )
+        .await?;
+        return unpack_json_response::<serde_json::Value>(response).await;
+      }
+    };
+
+    assert_eq!(
+      read(None).await?,
+      serde_json::json!({
+        "id": 1,
+        "first": "Ada",
+        "last": "Lovelace",
+        "full_name": "Ada Lovelace",
+        "signed_in": 0,
+      })
+    );
+    assert_eq!(
+      read(Some("id,full_name")).await?,
+      serde_json::json!({"id": 1, "full_name": "Ada Lovelace"})
+    );
+
+    let Json(records) = list_records_handler(
+      State(state.clone()),
+      Path("persons_api".to_string()),
+      RawQuery(None),
+      None,
+    )
+    .await?;
+    assert_eq!(records[0]["full_name"], "Ada Lovelace");
+
+    let Json(schema) = json_schema_handler(
+      State(state.clone()),
+      Path("persons_api".to_string()),
+      Query(JsonSchemaQuery {
+        mode: Some(JsonSchemaMode::Select),
+        fields: None,
+      }),
+      None,
+    )
+    .await?;
+    assert_eq!(
+      schema["properties"]["full_name"],
+      serde_json::json!({"type": "string"})
+    );
+    assert_eq!(schema["properties"]["signed_in"], serde_json::json!({}));
+
+    return Ok(());
+  }
+}
@@ -2,14 +2,21 @@ use axum::extract::{Json, Path, Query, State};
 use axum::response::{IntoResponse, Redirect, Response};
 use base64::prelude::*;
 use serde::{Deserialize, Serialize};
+use trailbase_sqlite::query_one_row;
 use utoipa::{IntoParams, ToSchema};
 
 use crate::app_state::AppState;
 use crate::auth::user::User;
 use crate::extract::Either;
-use crate::records::json_to_sql::{InsertQueryBuilder, LazyParams};
-use crate::records::{Permission, RecordError};
+use crate::js::RecordHookEvent;
+use crate::records::history::{append_history, history_snapshot};
+use crate::records::hooks::{run_after_record_hook, run_before_record_hook};
+use crate::records::json_to_sql::{InsertQueryBuilder, LazyParams, Params, PendingFiles};
+use crate::records::subscribe::RecordAction;
+use crate::records::webhooks::enqueue_webhook_deliveries;
+use crate::records::{Permission, RecordApi, RecordError};
 use crate::schema::ColumnDataType;
+use crate::table_metadata::TableMetadata;
 
 #[derive(Clone, Debug, Default, Deserialize, IntoParams)]
 pub struct CreateRecordQuery {
@@ -51,6 +58,20 @@ pub async fn create_record_handler(
     Either::Form(value) => (value, None),
   };
 
+  // Check table-level access before dispatching to hooks, record-level access is checked below
+  // once the hooks had a chance to amend the request.
+  api.check_table_level_access(Permission::Create, user.as_ref())?;
+  let request = run_before_record_hook(
+    &state,
+    &api,
+    RecordHookEvent::Create,
+    user.as_ref(),
+    None,
+    Some(request),
+  )
+  .await?
+  .unwrap_or_default();
+
   let mut lazy_params = LazyParams::new(table_metadata, request, multipart_files);
 
   api
@@ -65,49 +86,54 @@ pub async fn create_record_handler(
   let Ok(mut params) = lazy_params.consume() else {
     return Err(RecordError::BadRequest("Parameter conversion"));
   };
+  api.check_file_upload_limits(&params)?;
 
   if api.insert_autofill_missing_user_id_columns() {
-    let column_names = params.column_names();
-    let missing_columns = table_metadata
-      .user_id_columns
-      .iter()
-      .filter_map(|index| {
-        let col = &table_metadata.schema.columns[*index];
-        if column_names.iter().any(|c| c == &col.name) {
-          return None;
-        }
-        return Some(col.name.clone());
-      })
-      .collect::<Vec<_>>();
-
-    if !missing_columns.is_empty() {
-      if let Some(user) = user {
-        for col in missing_columns {
-          params.push_param(col, libsql::Value::Blob(user.uuid.into()));
-        }
-      }
-    }
+    autofill_missing_user_id_columns(table_metadata, &mut params, user.as_ref());
   }
 
-  let pk_column = api.record_pk_column();
-  let row = InsertQueryBuilder::run(
+  // We're storing any files to the object store first to make sure the DB entry is valid right
+  // after commit and not racily pointing to soon-to-be-written files.
+  let files = PendingFiles::write(&state, &mut params)
+    .await
+    .map_err(|err| RecordError::Internal(err.into()))?;
+  let (record_id, new) = match insert_record(&state, &api, params, user.as_ref()).await {
+    Ok(result) => result,
+    Err(err) => {
+      files.rollback(&state).await;
+      return Err(err);
+    }
+  };
+  files.commit();
+
+  state
+    .subscription_manager()
+    .broadcast_record(&state, &api, RecordAction::Insert, record_id.clone())
+    .await;
+  run_after_record_hook(
     &state,
-    params,
-    api.insert_conflict_resolution_strategy(),
-    Some(&pk_column.name),
+    &api,
+    RecordHookEvent::Create,
+    user.as_ref(),
+    &record_id,
+    new.as_ref(),
   )
-  .await
-  .map_err(|err| RecordError::Internal(err.into()))?;
+  .await;
 
   if let Some(redirect_to) = create_record_query.redirect_to {
     return Ok(Redirect::to(&redirect_to).into_response());
   }
 
+  let pk_column = api.record_pk_column();
   return Ok(
     Json(CreateRecordResponse {
-      id: match pk_column.data_type {
-        ColumnDataType::Blob => BASE64_URL_SAFE.encode(row.get::<[u8; 16]>(0)?),
-        ColumnDataType::Integer => row.get::<i64>(0)?.to_string(),
+      id: match record_id {
+        libsql::Value::Blob(blob) if pk_column.data_type == ColumnDataType::Blob => {
+          BASE64_URL_SAFE.encode(blob)
+        }
+        libsql::Value::Integer(integer) if pk_column.data_type == ColumnDataType::Integer => {
+          integer.to_string()
+        }
         _ => {
           return Err(RecordError::Internal(
             format!("Unexpected data type: {:?}", pk_column.data_type).into(),
@@ -119,6 +145,87 @@ pub async fn create_record_handler(
   );
 }
 
+/// Inserts the record in a single transaction together with appending its history entry and
+/// enqueuing webhook deliveries.
+///
+/// Returns the new record's id and the record itself.
+async fn insert_record(
+  state: &AppState,
+  api: &RecordApi,
+  params: Params,
+  user: Option<&User>,
+) -> Result<(libsql::Value, Option<libsql::Row>), RecordError> {
+  let (query, named_params, _files) =
+    InsertQueryBuilder::build_insert_query(params, api.insert_conflict_resolution_strategy())
+      .map_err(|err| RecordError::Internal(err.into()))?;
+
+  let tx = state.write_transaction().await?;
+
+  let row = query_one_row(
+    &tx,
+    &format!(
+      "{query} RETURNING [{pk_column}]",
+      pk_column = api.record_pk_column().name
+    ),
+    named_params,
+  )
+  .await?;
+  let record_id = row.get_value(0)?;
+
+  let new = history_snapshot(&tx, api, &record_id).await?;
+  append_history(
+    &tx,
+    api,
+    RecordAction::Insert,
+    &record_id,
+    user,
+    None,
+    new.as_ref(),
+  )
+  .await?;
+  enqueue_webhook_deliveries(
+    state,
+    &tx,
+    api,
+    RecordAction::Insert,
+    &record_id,
+    new.as_ref(),
+  )
+  .await?;
+
+  tx.commit().await?;
+
+  return Ok((record_id, new));
+}
+
+/// Fills user id columns missing from the request with the id of the current user, if any.
+pub(crate) fn autofill_missing_user_id_columns(
+  table_metadata: &TableMetadata,
+  params: &mut Params,
+  user: Option<&User>,
+) {
+  let Some(user) = user else {
+    return;
+  };
+
+  let column_names = params.column_names();
+  let missing_columns = table_metadata
+    .user_id_columns
+    .iter()
+    .filter_map(|index| {
+      let col = &table_metadata.schema.columns[*index];
+      if column_names.iter().any(|c| c == &col.name) {
+        return None;
+      }
+      return Some(col.name.clone());
+    })
+    .collect::<Vec<_>>();
+
+  for col in missing_columns {
+    params.push_param(col, libsql::Value::Blob(user.uuid.into()));
+  }
+}
+
 #[cfg(test)]
 mod test {
   use super::*;
@@ -1,26 +1,40 @@
 use axum::{
   extract::{Path, State},
-  http::StatusCode,
+  http::{HeaderMap, StatusCode},
   response::{IntoResponse, Response},
 };
+use log::*;
 
 use crate::app_state::AppState;
 use crate::auth::user::User;
+use crate::js::RecordHookEvent;
+use crate::records::etag::check_record_if_match;
+use crate::records::files::delete_files_in_row;
+use crate::records::history::append_history;
+use crate::records::hooks::{run_after_record_hook, run_before_record_hook};
 use crate::records::json_to_sql::DeleteQueryBuilder;
-use crate::records::{Permission, RecordError};
+use crate::records::soft_delete::soft_delete_record;
+use crate::records::subscribe::RecordAction;
+use crate::records::webhooks::enqueue_webhook_deliveries;
+use crate::records::{Permission, RecordApi, RecordError};
 
 /// Delete record.
+///
+/// Honors "If-Match", i.e. responds with 412 if the record has been modified concurrently. If
+/// soft-deletes are enabled, the record is merely tombstoned and can be restored until purged.
 #[utoipa::path(
   delete,
   path = "/:name/:record",
   responses(
-    (status = 200, description = "Successful deletion.")
+    (status = 200, description = "Successful deletion."),
+    (status = 412, description = "Record was modified concurrently.")
   )
 )]
 pub async fn delete_record_handler(
   State(state): State<AppState>,
   Path((api_name, record)): Path<(String, String)>,
   user: Option<User>,
+  headers: HeaderMap,
 ) -> Result<Response, RecordError> {
   let Some(api) = state.lookup_record_api(&api_name) else {
     return Err(RecordError::ApiNotFound);
@@ -36,18 +50,85 @@ pub async fn delete_record_handler(
     .check_record_level_access(Permission::Delete, Some(&record_id), None, user.as_ref())
     .await?;
 
-  DeleteQueryBuilder::run(
+  run_before_record_hook(
     &state,
-    table_metadata,
-    &api.record_pk_column().name,
-    record_id,
+    &api,
+    RecordHookEvent::Delete,
+    user.as_ref(),
+    Some(&record_id),
+    None,
   )
-  .await
-  .map_err(|err| RecordError::Internal(err.into()))?;
+  .await?;
+
+  let row = delete_record(&state, &api, &record_id, user.as_ref(), &headers).await?;
+
+  // Files of soft-deleted records are retained until they're purged.
+  if api.soft_delete_column().is_none() {
+    if let Err(err) = delete_files_in_row(&state, table_metadata, &row).await {
+      warn!("Failed to delete files of deleted record: {err}");
+    }
+  }
+
+  state
+    .subscription_manager()
+    .broadcast(&state, api.table_name(), RecordAction::Delete, &row)
+    .await;
+  run_after_record_hook(
+    &state,
+    &api,
+    RecordHookEvent::Delete,
+    user.as_ref(),
+    &record_id,
+    Some(&row),
+  )
+  .await;
 
   return Ok((StatusCode::OK, "deleted").into_response());
 }
 
+/// Deletes the record in a single transaction together with checking the "If-Match" precondition,
+/// appending its history entry and enqueuing webhook deliveries.
+async fn delete_record(
+  state: &AppState,
+  api: &RecordApi,
+  record_id: &libsql::Value,
+  user: Option<&User>,
+  headers: &HeaderMap,
+) -> Result<libsql::Row, RecordError> {
+  let tx = state.write_transaction().await?;
+
+  check_record_if_match(state, &tx, api, record_id, headers).await?;
+
+  let row = match api.soft_delete_column() {
+    Some(_) => soft_delete_record(&tx, api, record_id.clone()).await?,
+    None => {
+      DeleteQueryBuilder::delete(
+        &tx,
+        api.table_name(),
+        &api.record_pk_column().name,
+        record_id.clone(),
+      )
+      .await?
+    }
+  };
+
+  append_history(
+    &tx,
+    api,
+    RecordAction::Delete,
+    record_id,
+    user,
+    Some(&row),
+    None,
+  )
+  .await?;
+  enqueue_webhook_deliveries(state, &tx, api, RecordAction::Delete, record_id, Some(&row)).await?;
+
+  tx.commit().await?;
+
+  return Ok(row);
+}
+
 #[cfg(test)]
 mod test {
   use axum::extract::Query;
@@ -187,6 +268,7 @@ mod test {
       State(state.clone()),
       Path(("messages_api".to_string(), id_to_b64(&id))),
       User::from_auth_token(state, auth_token),
+      HeaderMap::new(),
     )
     .await?;
     return Ok(());
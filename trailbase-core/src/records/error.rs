@@ -19,8 +19,14 @@ pub enum RecordError {
   RecordNotFound,
   #[error("Forbidden")]
   Forbidden,
+  #[error("Precondition Failed")]
+  PreconditionFailed,
   #[error("Bad request: {0}")]
   BadRequest(&'static str),
+  /// Rejection with a specific client error status and optional message, e.g. by a JS before-hook,
+  /// a conflicting upsert or an upload exceeding the file limits.
+  #[error("Rejected: {0}")]
+  Rejected(StatusCode, Option<String>),
   #[error("Internal: {0}")]
   Internal(Box<dyn std::error::Error + Send + Sync>),
 }
@@ -59,7 +65,9 @@ impl IntoResponse for RecordError {
       Self::ApiRequiresTable => (StatusCode::METHOD_NOT_ALLOWED, None),
       Self::RecordNotFound => (StatusCode::NOT_FOUND, None),
       Self::Forbidden => (StatusCode::FORBIDDEN, None),
+      Self::PreconditionFailed => (StatusCode::PRECONDITION_FAILED, None),
       Self::BadRequest(msg) => (StatusCode::BAD_REQUEST, Some(msg.to_string())),
+      Self::Rejected(status, msg) => (status, msg),
       Self::Internal(err) if cfg!(debug_assertions) => {
         (StatusCode::INTERNAL_SERVER_ERROR, Some(err.to_string()))
       }
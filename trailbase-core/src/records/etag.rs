@@ -0,0 +1,210 @@
+use axum::http::{header, HeaderMap};
+use base64::prelude::*;
+use sha2::{Digest, Sha256};
+use trailbase_sqlite::query_row;
+
+use crate::app_state::AppState;
+use crate::records::{RecordApi, RecordError};
+
+/// Computes a strong ETag for the given record `row`.
+///
+/// The ETag is derived from `column`'s value, e.g. a version counter or last-updated timestamp, if
+/// given. Otherwise, it is derived from all of the row's values including hidden ones, which is why
+/// it's keyed with a server secret. An unkeyed hash would let clients brute-force low-entropy
+/// values they cannot read.
+pub(crate) fn record_etag(
+  state: &AppState,
+  row: &libsql::Row,
+  column: Option<&str>,
+) -> Result<String, RecordError> {
+  let mut hasher = Sha256::new();
+  let mut found = false;
+
+  for i in 0..row.column_count() {
+    if let Some(column) = column {
+      if row.column_name(i) != Some(column) {
+        continue;
+      }
+    }
+    found = true;
+
+    match row.get_value(i)? {
+      libsql::Value::Null => hasher.update([0u8]),
+      libsql::Value::Integer(v) => {
+        hasher.update([1u8]);
+        hasher.update(v.to_le_bytes());
+      }
+      libsql::Value::Real(v) => {
+        hasher.update([2u8]);
+        hasher.update(v.to_bits().to_le_bytes());
+      }
+      libsql::Value::Text(v) => {
+        hasher.update([3u8]);
+        hasher.update((v.len() as u64).to_le_bytes());
+        hasher.update(v.as_bytes());
+      }
+      libsql::Value::Blob(v) => {
+        hasher.update([4u8]);
+        hasher.update((v.len() as u64).to_le_bytes());
+        hasher.update(&v);
+      }
+    };
+  }
+
+  if !found {
+    return Err(RecordError::Internal(
+      format!("Missing ETag column: {column:?}").into(),
+    ));
+  }
+
+  return Ok(format!(
+    "\"{}\"",
+    BASE64_URL_SAFE_NO_PAD.encode(state.jwt().sign(&hasher.finalize()))
+  ));
+}
+
+/// Returns true if any entity tag in the "If-None-Match" headers matches `etag` using weak
+/// comparison, i.e. the client's cached copy is still fresh.
+pub(crate) fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+  return entity_tags(headers, header::IF_NONE_MATCH)
+    .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag.trim_start_matches("W/"));
+}
+
+/// Checks the "If-Match" precondition, if present, against the record's current `etag` using
+/// strong comparison. A missing record never matches.
+pub(crate) fn check_if_match(headers: &HeaderMap, etag: Option<&str>) -> Result<(), RecordError> {
+  if !headers.contains_key(header::IF_MATCH) {
+    return Ok(());
+  }
+
+  let Some(etag) = etag else {
+    return Err(RecordError::PreconditionFailed);
+  };
+
+  if entity_tags(headers, header::IF_MATCH).any(|tag| tag == "*" || tag == etag) {
+    return Ok(());
+  }
+  return Err(RecordError::PreconditionFailed);
+}
+
+/// Checks the "If-Match" precondition, if present, against the current state of the record.
+///
+/// To guarantee that the record doesn't change between checking and writing, `conn` is expected
+/// to be in the same transaction as the subsequent write.
+pub(crate) async fn check_record_if_match(
+  state: &AppState,
+  conn: &libsql::Connection,
+  api: &RecordApi,
+  record_id: &libsql::Value,
+  headers: &HeaderMap,
+) -> Result<(), RecordError> {
+  if !headers.contains_key(header::IF_MATCH) {
+    return Ok(());
+  }
+
+  let row = query_row(
+    conn,
+    &format!(
+      "SELECT * FROM '{table_name}' WHERE [{pk_column}] = $1",
+      table_name = api.table_name(),
+      pk_column = api.record_pk_column().name,
+    ),
+    [record_id.clone()],
+  )
+  .await?;
+  let etag = row
+    .map(|row| record_etag(state, &row, api.etag_column()))
+    .transpose()?;
+
+  return check_if_match(headers, etag.as_deref());
+}
+
+fn entity_tags(headers: &HeaderMap, name: header::HeaderName) -> impl Iterator<Item = &str> {
+  return headers
+    .get_all(name)
+    .into_iter()
+    .filter_map(|value| value.to_str().ok())
+    .flat_map(|value| value.split(','))
+    .map(str::trim)
+    .filter(|tag| !tag.is_empty());
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::http::HeaderValue;
+  use trailbase_sqlite::query_one_row;
+
+  use super::*;
+  use crate::app_state::test_state;
+
+  #[tokio::test]
+  async fn test_record_etag() {
+    let state = test_state(None).await.unwrap();
+    let conn = state.conn();
+
+    let query = |sql: &'static str| async move {
+      return query_one_row(conn, sql, ()).await.unwrap();
+    };
+
+    let row0 = query("SELECT 1 AS id, 'a' AS data, 5 AS version").await;
+    let row1 = query("SELECT 1 AS id, 'b' AS data, 5 AS version").await;
+    let row2 = query("SELECT 1 AS id, 'b' AS data, 6 AS version").await;
+
+    let etag0 = record_etag(&state, &row0, None).unwrap();
+    assert!(etag0.starts_with('"') && etag0.ends_with('"'), "{etag0}");
+    assert_eq!(etag0, record_etag(&state, &row0, None).unwrap());
+    assert_ne!(etag0, record_etag(&state, &row1, None).unwrap());
+
+    // Only the version column is considered.
+    assert_eq!(
+      record_etag(&state, &row0, Some("version")).unwrap(),
+      record_etag(&state, &row1, Some("version")).unwrap()
+    );
+    assert_ne!(
+      record_etag(&state, &row1, Some("version")).unwrap(),
+      record_etag(&state, &row2, Some("version")).unwrap()
+    );
+
+    assert!(record_etag(&state, &row0, Some("missing")).is_err());
+
+    // ETags are keyed, i.e. cannot be predicted from a record's values without the server's keys.
+    let other_state = test_state(None).await.unwrap();
+    assert_ne!(etag0, record_etag(&other_state, &row0, None).unwrap());
+  }
+
+  #[test]
+  fn test_preconditions() {
+    let etag = "\"abc\"";
+    let headers = |name: header::HeaderName, value: &'static str| {
+      let mut headers = HeaderMap::new();
+      headers.insert(name, HeaderValue::from_static(value));
+      return headers;
+    };
+
+    assert!(check_if_match(&HeaderMap::new(), Some(etag)).is_ok());
+    assert!(check_if_match(&HeaderMap::new(), None).is_ok());
+
+    assert!(check_if_match(&headers(header::IF_MATCH, "\"abc\""), Some(etag)).is_ok());
+    assert!(check_if_match(&headers(header::IF_MATCH, "\"x\", \"abc\""), Some(etag)).is_ok());
+    assert!(check_if_match(&headers(header::IF_MATCH, "*"), Some(etag)).is_ok());
+    assert!(check_if_match(&headers(header::IF_MATCH, "\"x\""), Some(etag)).is_err());
+    // Strong comparison.
+    assert!(check_if_match(&headers(header::IF_MATCH, "W/\"abc\""), Some(etag)).is_err());
+    assert!(check_if_match(&headers(header::IF_MATCH, "*"), None).is_err());
+
+    assert!(!if_none_match(&HeaderMap::new(), etag));
+    assert!(if_none_match(
+      &headers(header::IF_NONE_MATCH, "\"abc\""),
+      etag
+    ));
+    assert!(if_none_match(
+      &headers(header::IF_NONE_MATCH, "W/\"abc\""),
+      etag
+    ));
+    assert!(if_none_match(&headers(header::IF_NONE_MATCH, "*"), etag));
+    assert!(!if_none_match(
+      &headers(header::IF_NONE_MATCH, "\"x\""),
+      etag
+    ));
+  }
+}
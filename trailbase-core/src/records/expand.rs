@@ -0,0 +1,300 @@
+use futures::future::{BoxFuture, FutureExt};
+use std::collections::BTreeMap;
+
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::records::computed::{append_computed_fields, query_computed_fields};
+use crate::records::json_to_sql::SelectQueryBuilder;
+use crate::records::sql_to_json::row_to_json;
+use crate::records::{Permission, RecordApi, RecordError};
+
+/// Maximum nesting of expansions, e.g. "comments.author" has a depth of 2.
+const MAX_EXPAND_DEPTH: usize = 3;
+
+/// Tree of foreign key columns to expand, e.g. "author,comments.author" is parsed into:
+///
+///   { author: {}, comments: { author: {} } }
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct ExpandTree(BTreeMap<String, ExpandTree>);
+
+impl ExpandTree {
+  pub(crate) fn parse(expand: &str) -> Result<Self, RecordError> {
+    let mut tree = ExpandTree::default();
+
+    for path in expand.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+      let segments: Vec<&str> = path.split('.').collect();
+      if segments.len() > MAX_EXPAND_DEPTH {
+        return Err(RecordError::BadRequest("Expand nested too deeply"));
+      }
+
+      let mut node = &mut tree;
+      for segment in segments {
+        if segment.is_empty() || segment.starts_with("_") {
+          return Err(RecordError::BadRequest("Invalid expand"));
+        }
+        node = node.0.entry(segment.to_string()).or_default();
+      }
+    }
+
+    return Ok(tree);
+  }
+
+  pub(crate) fn is_empty(&self) -> bool {
+    return self.0.is_empty();
+  }
+}
+
+/// Replaces foreign keys in `record` with the referenced records.
+///
+/// Only foreign keys pointing at tables exposed through a RecordApi are expanded and only if
+/// `user` passes the respective API's read access checks. Otherwise the plain foreign key is
+/// retained.
+pub(crate) fn expand_record<'a>(
+  state: &'a AppState,
+  api: &'a RecordApi,
+  record: &'a mut serde_json::Value,
+  tree: &'a ExpandTree,
+  user: Option<&'a User>,
+) -> BoxFuture<'a, Result<(), RecordError>> {
+  return async move {
+    let serde_json::Value::Object(map) = record else {
+      return Ok(());
+    };
+
+    for (column_name, children) in &tree.0 {
+      let Some(table_metadata) = api.table_metadata() else {
+        return Err(RecordError::BadRequest("Expand requires table"));
+      };
+      let Some(foreign_key) = table_metadata.foreign_key_by_column_name(column_name) else {
+        return Err(RecordError::BadRequest("Invalid expand"));
+      };
+
+      let Some(foreign_api) = state.lookup_record_api_for_table(&foreign_key.foreign_table) else {
+        continue;
+      };
+      let references_pk = match foreign_key.referred_columns.as_slice() {
+        // Omitted referred columns imply the primary key.
+        [] => true,
+        [referred_column] => *referred_column == foreign_api.record_pk_column().name,
+        _ => false,
+      };
+      if !references_pk {
+        continue;
+      }
+
+      let Some(value) = map.get_mut(column_name) else {
+        continue;
+      };
+      let foreign_id = match value {
+        serde_json::Value::String(id) => foreign_api.id_to_sql(id)?,
+        serde_json::Value::Number(number) => match number.as_i64() {
+          Some(id) => libsql::Value::Integer(id),
+          None => continue,
+        },
+        _ => continue,
+      };
+
+      if foreign_api
+        .check_record_level_access(Permission::Read, Some(&foreign_id), None, user)
+        .await
+        .is_err()
+      {
+        continue;
+      }
+      if foreign_api
+        .check_not_soft_deleted(&foreign_id)
+        .await
+        .is_err()
+      {
+        continue;
+      }
+
+      let Some(row) = SelectQueryBuilder::run(
+        state,
+        foreign_api.table_name(),
+        &foreign_api.record_pk_column().name,
+        foreign_id.clone(),
+      )
+      .await?
+      else {
+        continue;
+      };
+
+      let mut foreign_record = row_to_json(foreign_api.metadata(), &row, |col_name| {
+        foreign_api.column_readable(col_name, user)
+      })
+      .map_err(|err| RecordError::Internal(err.into()))?;
+
+      if let Some(computed) =
+        query_computed_fields(state.conn(), &foreign_api, &foreign_id, user).await?
+      {
+        append_computed_fields(&foreign_api, &computed, &mut foreign_record, user)?;
+      }
+
+      if !children.is_empty() {
+        expand_record(state, &foreign_api, &mut foreign_record, children, user).await?;
+      }
+
+      *value = foreign_record;
+    }
+
+    return Ok(());
+  }
+  .boxed();
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::extract::{Path, Query, RawQuery, State};
+  use axum::http::HeaderMap;
+  use axum::Json;
+
+  use super::*;
+  use crate::admin::user::*;
+  use crate::app_state::*;
+  use crate::auth::api::login::login_with_password;
+  use crate::config::proto::PermissionFlag;
+  use crate::records::list_records::list_records_handler;
+  use crate::records::read_record::{read_record_handler, ReadRecordQuery};
+  use crate::records::test_utils::*;
+  use crate::records::*;
+  use crate::test::unpack_json_response;
+  use crate::util::id_to_b64;
+
+  #[test]
+  fn test_parse_expand() {
+    assert!(ExpandTree::parse("").unwrap().is_empty());
+
+    let tree = ExpandTree::parse("author, comments.author,comments").unwrap();
+    let mut comments = ExpandTree::default();
+    comments
+      .0
+      .insert("author".to_string(), ExpandTree::default());
+    assert_eq!(
+      tree,
+      ExpandTree(BTreeMap::from([
+        ("author".to_string(), ExpandTree::default()),
+        ("comments".to_string(), comments),
+      ]))
+    );
+
+    assert!(ExpandTree::parse("a.b.c").is_ok());
+    assert!(ExpandTree::parse("a.b.c.d").is_err());
+    assert!(ExpandTree::parse("a..b").is_err());
+    assert!(ExpandTree::parse("_owner").is_err());
+  }
+
+  #[tokio::test]
+  async fn test_expand_foreign_records() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    let conn = state.conn();
+
+    create_chat_message_app_tables(&state).await?;
+    let room0 = add_room(conn, "room0").await?;
+    let room1 = add_room(conn, "room1").await?;
+    let password = "Secret!1!!";
+
+    add_record_api(
+      &state,
+      "messages_api",
+      "message",
+      Acls {
+        authenticated: vec![PermissionFlag::Read, PermissionFlag::List],
+        ..Default::default()
+      },
+      AccessRules::default(),
+    )
+    .await?;
+
+    let user_x_email = "user_x@test.com";
+    let user_x = create_user_for_test(&state, user_x_email, password)
+      .await?
+      .into_bytes();
+    add_user_to_room(conn, user_x, room0).await?;
+    let user_x_token = login_with_password(&state, user_x_email, password).await?;
+    let user = User::from_auth_token(&state, &user_x_token.auth_token);
+
+    let message0 = send_message(conn, user_x, room0, "msg0").await?;
+    let message1 = send_message(conn, user_x, room1, "msg1").await?;
+
+    let read = |id: [u8; 16], expand: Option<&str>| {
+      let state = state.clone();
+      let user = user.clone();
+      let expand = expand.map(|e| e.to_string());
+      async move {
+        return read_record_handler(
+          State(state),
+          Path(("messages_api".to_string(), id_to_b64(&id))),
+          Query(ReadRecordQuery {
+            expand,
+            ..Default::default()
+          }),
+          user,
+          HeaderMap::new(),
+        )
+        .await;
+      }
+    };
+
+    // Rooms aren't exposed via a record API, so there's nothing to expand.
+    let value: serde_json::Value =
+      unpack_json_response(read(message0, Some("room")).await?).await?;
+    assert_eq!(value["room"], id_to_b64(&room0));
+
+    // Unknown and non-foreign-key columns cannot be expanded.
+    assert!(read(message0, Some("data")).await.is_err());
+    assert!(read(message0, Some("unknown")).await.is_err());
+
+    add_record_api(
+      &state,
+      "rooms_api",
+      "room",
+      Acls {
+        authenticated: vec![PermissionFlag::Read],
+        ..Default::default()
+      },
+      AccessRules {
+        read: Some(
+          "EXISTS(SELECT 1 FROM room_members WHERE room = _ROW_.id AND user = _USER_.id)"
+            .to_string(),
+        ),
+        ..Default::default()
+      },
+    )
+    .await?;
+
+    let value: serde_json::Value = unpack_json_response(read(message0, None).await?).await?;
+    assert_eq!(value["room"], id_to_b64(&room0));
+
+    let value: serde_json::Value =
+      unpack_json_response(read(message0, Some("room")).await?).await?;
+    assert_eq!(value["room"]["id"], id_to_b64(&room0));
+    assert_eq!(value["room"]["name"], "room0");
+
+    // User x isn't a member of room1 and thus doesn't pass the read access rule.
+    let value: serde_json::Value =
+      unpack_json_response(read(message1, Some("room")).await?).await?;
+    assert_eq!(value["room"], id_to_b64(&room1));
+
+    let Json(value) = list_records_handler(
+      State(state.clone()),
+      Path("messages_api".to_string()),
+      RawQuery(Some("expand=room".to_string())),
+      user.clone(),
+    )
+    .await?;
+    let serde_json::Value::Array(records) = value else {
+      panic!("expected array");
+    };
+    assert_eq!(records.len(), 2);
+    for record in records {
+      match record["data"].as_str().unwrap() {
+        "msg0" => assert_eq!(record["room"]["name"], "room0"),
+        "msg1" => assert_eq!(record["room"], id_to_b64(&room1)),
+        x => panic!("unexpected: {x}"),
+      }
+    }
+
+    return Ok(());
+  }
+}
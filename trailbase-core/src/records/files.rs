@@ -1,13 +1,28 @@
 use axum::body::Body;
-use axum::http::header;
+use axum::extract::{Path, Request, State};
+use axum::http::{header, HeaderMap, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
 use axum::response::{IntoResponse, Response};
+use axum::RequestPartsExt;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
 use log::*;
-use object_store::ObjectStore;
+use object_store::{GetOptions, GetRange, ObjectMeta, ObjectStore};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 use thiserror::Error;
 use trailbase_sqlite::schema::{FileUpload, FileUploads};
+use utoipa::{IntoParams, ToSchema};
 
 use crate::app_state::AppState;
-use crate::table_metadata::{JsonColumnMetadata, TableOrViewMetadata};
+use crate::auth::user::User;
+use crate::extract::{is_multipart, FileUploadTarget};
+use crate::records::etag::if_none_match;
+use crate::records::thumbnail::delete_thumbnails;
+use crate::records::Permission;
+use crate::server::apply_request_body_limit;
+use crate::table_metadata::{ColumnMetadata, JsonColumnMetadata, TableOrViewMetadata};
 
 #[derive(Debug, Error)]
 pub enum FileError {
@@ -21,42 +36,351 @@ pub enum FileError {
   JsonSerialization(#[from] serde_json::Error),
 }
 
+/// Whether the column stores file metadata, i.e. is a `std.FileUpload(s)` column.
+pub(crate) fn is_file_column(metadata: &ColumnMetadata) -> bool {
+  return matches!(
+    &metadata.json,
+    Some(JsonColumnMetadata::SchemaName(name)) if name == "std.FileUpload" || name == "std.FileUploads"
+  );
+}
+
+/// Middleware letting multipart requests to record APIs stream uploaded files straight to the
+/// object store rather than buffering them, subject to the targeted API's file upload limits.
+///
+/// Uploads are only streamed for callers with table-level write access to an existing API. Other
+/// requests are subject to the regular request body limit, since their files would get buffered.
+pub(crate) async fn attach_file_upload_target(
+  State(state): State<AppState>,
+  Path(params): Path<HashMap<String, String>>,
+  req: Request,
+  next: Next,
+) -> Response {
+  if !is_multipart(req.headers()) {
+    return next.run(req).await;
+  }
+
+  let (mut parts, body) = req.into_parts();
+  let user = parts
+    .extract_with_state::<Option<User>, _>(&state)
+    .await
+    .unwrap_or(None);
+
+  let permissions: &[Permission] = match parts.method {
+    Method::POST => &[Permission::Create],
+    Method::PATCH => &[Permission::Update],
+    Method::PUT => &[Permission::Create, Permission::Update],
+    _ => &[],
+  };
+  let target = params
+    .get("name")
+    .and_then(|name| state.lookup_record_api(name))
+    .filter(|api| {
+      // Record-level access is checked by the handlers once the request has been parsed.
+      permissions
+        .iter()
+        .any(|p| api.check_table_level_access(*p, user.as_ref()).is_ok())
+    })
+    .map(|api| FileUploadTarget::new(state.clone(), api.file_upload_limits()));
+
+  let mut req = Request::from_parts(parts, body);
+  let Some(target) = target else {
+    return match apply_request_body_limit(req) {
+      Ok(req) => next.run(req).await,
+      Err(response) => response,
+    };
+  };
+
+  req.extensions_mut().insert(target);
+  return next.run(req).await;
+}
+
+/// How browsers should present downloaded files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FileDisposition {
+  /// Prompt to save the file.
+  #[default]
+  Attachment,
+  /// Display the file in place, e.g. to embed images or play videos. Only honored for images,
+  /// videos, audio and PDFs, other files are served as attachments.
+  Inline,
+}
+
+impl FileDisposition {
+  pub(crate) fn name(&self) -> &'static str {
+    return match self {
+      Self::Attachment => "attachment",
+      Self::Inline => "inline",
+    };
+  }
+}
+
+/// Query parameters for downloading uploaded files.
+#[derive(Clone, Debug, Default, Deserialize, IntoParams)]
+pub struct FileQuery {
+  /// Defaults to "attachment".
+  pub disposition: Option<FileDisposition>,
+  /// Unix timestamp in seconds when a signed URL expires.
+  pub expires: Option<i64>,
+  /// Signature of signed URLs, which grant access in place of user auth.
+  pub signature: Option<String>,
+}
+
+/// Responds with the file's contents, streamed from the object store.
+///
+/// Supports single byte "Range" requests, e.g. to let video and audio players seek, as well as
+/// conditional requests via "If-None-Match" and "If-Modified-Since". Since files are immutable, their
+/// unique id doubles as a strong ETag.
 pub(crate) async fn read_file_into_response(
   state: &AppState,
   file_upload: FileUpload,
+  request_headers: &HeaderMap,
+  disposition: FileDisposition,
 ) -> Result<Response, FileError> {
   let store = state.objectstore();
   let path = object_store::path::Path::from(file_upload.path());
-  let result = store.get(&path).await?;
-
-  let headers = || {
-    return [
-      (
-        header::CONTENT_TYPE,
-        file_upload.content_type().map_or_else(
-          || "text/plain; charset=utf-8".to_string(),
-          |c| c.to_string(),
-        ),
-      ),
-      (header::CONTENT_DISPOSITION, "attachment".to_string()),
-    ];
+  let meta = store.head(&path).await?;
+
+  let etag = format!("\"{}\"", file_upload.path());
+  let last_modified = http_date(&meta.last_modified);
+
+  let mut headers = HeaderMap::new();
+  headers.insert(header::ETAG, header_value(&etag));
+  headers.insert(header::LAST_MODIFIED, header_value(&last_modified));
+  // Access may be revoked, thus have clients revalidate, which is cheap thanks to the ETag.
+  headers.insert(
+    header::CACHE_CONTROL,
+    HeaderValue::from_static("private, no-cache"),
+  );
+  headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+
+  if not_modified(request_headers, &etag, &meta.last_modified) {
+    return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+  }
+
+  headers.insert(
+    header::CONTENT_TYPE,
+    header_value(&content_type(&file_upload, disposition)),
+  );
+  headers.insert(
+    header::X_CONTENT_TYPE_OPTIONS,
+    HeaderValue::from_static("nosniff"),
+  );
+  // Keep scripts from running in the API's origin, should a browser still render the file.
+  headers.insert(
+    header::CONTENT_SECURITY_POLICY,
+    HeaderValue::from_static("sandbox"),
+  );
+  headers.insert(
+    header::CONTENT_DISPOSITION,
+    header_value(&content_disposition(&file_upload, disposition)),
+  );
+
+  let size = meta.size;
+  let range = match parse_range(request_headers, &etag, &last_modified, size) {
+    Ok(range) => range,
+    Err(RangeNotSatisfiable) => {
+      headers.insert(
+        header::CONTENT_RANGE,
+        header_value(&format!("bytes */{size}")),
+      );
+      return Ok((StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response());
+    }
+  };
+
+  let (status, range) = match range {
+    Some(range) => {
+      headers.insert(
+        header::CONTENT_RANGE,
+        header_value(&format!("bytes {}-{}/{size}", range.start, range.end - 1)),
+      );
+      (StatusCode::PARTIAL_CONTENT, range)
+    }
+    None => (StatusCode::OK, 0..size),
+  };
+  headers.insert(header::CONTENT_LENGTH, HeaderValue::from(range.len()));
+
+  // NOTE: `into_stream` reads local files in chunks rather than all at once.
+  let result = store
+    .get_opts(
+      &path,
+      GetOptions {
+        range: (status == StatusCode::PARTIAL_CONTENT).then_some(GetRange::Bounded(range)),
+        ..Default::default()
+      },
+    )
+    .await?;
+
+  return Ok((status, headers, Body::from_stream(result.into_stream())).into_response());
+}
+
+fn header_value(value: &str) -> HeaderValue {
+  // All values are constructed from ASCII.
+  return HeaderValue::from_str(value).unwrap_or_else(|_| HeaderValue::from_static(""));
+}
+
+/// Formats the timestamp as IMF-fixdate, e.g. "Sun, 06 Nov 1994 08:49:37 GMT".
+fn http_date(date: &DateTime<Utc>) -> String {
+  return date.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+}
+
+/// Whether the client's cached copy is still fresh. "If-None-Match" takes precedence over
+/// "If-Modified-Since".
+fn not_modified(headers: &HeaderMap, etag: &str, last_modified: &DateTime<Utc>) -> bool {
+  if headers.contains_key(header::IF_NONE_MATCH) {
+    return if_none_match(headers, etag);
+  }
+
+  let Some(since) = headers
+    .get(header::IF_MODIFIED_SINCE)
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+  else {
+    return false;
+  };
+  // HTTP dates only have second precision.
+  return last_modified.timestamp() <= since.timestamp();
+}
+
+/// Whether browsers may display files of the given type in place. Other types, e.g. HTML or SVG,
+/// could run scripts and are thus always served as attachments.
+fn inline_safe(mime_type: &str) -> bool {
+  return match mime_type.split_once('/') {
+    Some(("image", subtype)) => !subtype.starts_with("svg"),
+    Some(("video" | "audio", _)) => true,
+    _ => mime_type == "application/pdf",
+  };
+}
+
+/// Only displays files inline, if requested and their inferred type is safe to render.
+fn effective_disposition(
+  file_upload: &FileUpload,
+  disposition: FileDisposition,
+) -> FileDisposition {
+  return match disposition {
+    FileDisposition::Inline if file_upload.mime_type().is_some_and(inline_safe) => {
+      FileDisposition::Inline
+    }
+    _ => FileDisposition::Attachment,
+  };
+}
+
+fn content_type(file_upload: &FileUpload, disposition: FileDisposition) -> String {
+  return match (disposition, effective_disposition(file_upload, disposition)) {
+    // Only rely on the inferred type for content rendered by browsers, e.g. to not have user
+    // uploads masquerade as HTML.
+    (_, FileDisposition::Inline) => file_upload
+      .mime_type()
+      .unwrap_or("application/octet-stream")
+      .to_string(),
+    (FileDisposition::Inline, FileDisposition::Attachment) => {
+      "application/octet-stream".to_string()
+    }
+    (FileDisposition::Attachment, FileDisposition::Attachment) => {
+      file_upload.content_type().map_or_else(
+        || "text/plain; charset=utf-8".to_string(),
+        |c| c.to_string(),
+      )
+    }
+  };
+}
+
+fn content_disposition(file_upload: &FileUpload, disposition: FileDisposition) -> String {
+  let disposition = effective_disposition(file_upload, disposition).name();
+
+  let Some(filename) = file_upload.original_filename().filter(|f| !f.is_empty()) else {
+    return disposition.to_string();
+  };
+
+  // Plain ASCII fallback for legacy clients and the RFC 6266 encoded original.
+  let fallback: String = filename
+    .chars()
+    .map(|c| match c {
+      ' '..='~' if c != '"' && c != '\\' => c,
+      _ => '_',
+    })
+    .collect();
+  let encoded = form_urlencoded::byte_serialize(filename.as_bytes())
+    .collect::<String>()
+    .replace('+', "%20");
+
+  return format!("{disposition}; filename=\"{fallback}\"; filename*=UTF-8''{encoded}");
+}
+
+#[derive(Debug, PartialEq)]
+struct RangeNotSatisfiable;
+
+/// Parses a single byte range from the "Range" header.
+///
+/// Returns None if the whole file should be served, e.g. because there's no or an unsupported
+/// range, multiple ranges, or a stale "If-Range".
+fn parse_range(
+  headers: &HeaderMap,
+  etag: &str,
+  last_modified: &str,
+  size: usize,
+) -> Result<Option<Range<usize>>, RangeNotSatisfiable> {
+  let Some(range) = headers
+    .get(header::RANGE)
+    .and_then(|value| value.to_str().ok())
+  else {
+    return Ok(None);
+  };
+
+  if let Some(if_range) = headers
+    .get(header::IF_RANGE)
+    .and_then(|value| value.to_str().ok())
+  {
+    if if_range != etag && if_range != last_modified {
+      return Ok(None);
+    }
+  }
+
+  let Some(spec) = range.trim().strip_prefix("bytes=") else {
+    return Ok(None);
+  };
+  if spec.contains(',') {
+    return Ok(None);
+  }
+  let Some((start, end)) = spec.trim().split_once('-') else {
+    return Ok(None);
   };
 
-  return match result.payload {
-    object_store::GetResultPayload::File(_file, path) => {
-      let contents = tokio::fs::read(path).await?;
-      Ok((headers(), Body::from(contents)).into_response())
+  let range = match (start.trim(), end.trim()) {
+    ("", suffix) => {
+      let Ok(suffix) = suffix.parse::<usize>() else {
+        return Ok(None);
+      };
+      if suffix == 0 {
+        return Err(RangeNotSatisfiable);
+      }
+      size.saturating_sub(suffix)..size
     }
-    object_store::GetResultPayload::Stream(stream) => {
-      Ok((headers(), Body::from_stream(stream)).into_response())
+    (start, end) => {
+      let Ok(start) = start.parse::<usize>() else {
+        return Ok(None);
+      };
+      let end = match end {
+        "" => size,
+        end => match end.parse::<usize>() {
+          Ok(end) if end >= start => end.saturating_add(1).min(size),
+          _ => return Ok(None),
+        },
+      };
+      start..end
     }
   };
+
+  if range.start >= size {
+    return Err(RangeNotSatisfiable);
+  }
+  return Ok(Some(range));
 }
 
 pub(crate) async fn delete_files_in_row(
   state: &AppState,
   metadata: &(dyn TableOrViewMetadata + Send + Sync),
-  row: libsql::Row,
+  row: &libsql::Row,
 ) -> Result<(), FileError> {
   for i in 0..row.column_count() {
     let Some(col_name) = row.column_name(i) else {
@@ -100,8 +424,208 @@ pub(crate) async fn delete_files_in_row(
 //   return Ok(());
 // }
 
+/// Lists uploaded files in the object store, which aren't referenced by any file column and are
+/// older than `grace_period`, e.g. left behind by raw SQL deletions.
+///
+/// The grace period protects uploads that have been stored but not yet committed. Objects not
+/// named after a file id, i.e. not managed by TrailBase, are never considered.
+pub(crate) async fn find_orphaned_files(
+  state: &AppState,
+  grace_period: chrono::Duration,
+) -> Result<Vec<ObjectMeta>, FileError> {
+  let cutoff = Utc::now() - grace_period;
+
+  // List objects first, thus files uploaded in the meantime cannot be missing from the references.
+  let objects: Vec<ObjectMeta> = state.objectstore().list(None).try_collect().await?;
+  let referenced = referenced_file_ids(state).await?;
+
+  return Ok(
+    objects
+      .into_iter()
+      .filter(|object| object.last_modified < cutoff)
+      .filter(|object| {
+        // Resized variants are stored in a "<id>.variants" directory next to the original.
+        let Some(first) = object.location.as_ref().split('/').next() else {
+          return false;
+        };
+        let id = first.trim_end_matches(".variants");
+        return uuid::Uuid::parse_str(id).is_ok() && !referenced.contains(id);
+      })
+      .collect(),
+  );
+}
+
+/// Deletes the files found by [find_orphaned_files] and returns them.
+pub(crate) async fn delete_orphaned_files(
+  state: &AppState,
+  grace_period: chrono::Duration,
+) -> Result<Vec<ObjectMeta>, FileError> {
+  let orphans = find_orphaned_files(state, grace_period).await?;
+
+  let store = state.objectstore();
+  for orphan in &orphans {
+    match store.delete(&orphan.location).await {
+      Ok(_) | Err(object_store::Error::NotFound { .. }) => {}
+      Err(err) => return Err(err.into()),
+    };
+  }
+
+  return Ok(orphans);
+}
+
+/// Ids of all files referenced from file columns across all tables.
+///
+/// NOTE: Record history snapshots aren't considered. Files are deleted as soon as they're replaced
+/// or cleared, thus snapshots of prior versions may reference files, which no longer exist.
+async fn referenced_file_ids(state: &AppState) -> Result<HashSet<String>, FileError> {
+  let mut ids = HashSet::<String>::new();
+
+  for table in state.table_metadata().tables() {
+    let columns = table
+      .file_upload_columns
+      .iter()
+      .map(|index| (*index, false))
+      .chain(
+        table
+          .file_uploads_columns
+          .iter()
+          .map(|index| (*index, true)),
+      );
+
+    for (index, multiple) in columns {
+      let column = &table.schema.columns[index].name;
+      let mut rows = state
+        .conn()
+        .query(
+          &format!(
+            "SELECT [{column}] FROM '{table_name}' WHERE [{column}] IS NOT NULL",
+            table_name = table.name()
+          ),
+          (),
+        )
+        .await?;
+
+      while let Some(row) = rows.next().await? {
+        let json = row.get_str(0)?;
+        if multiple {
+          let file_uploads: FileUploads = serde_json::from_str(json)?;
+          ids.extend(file_uploads.0.iter().map(|f| f.path().to_string()));
+        } else {
+          let file_upload: FileUpload = serde_json::from_str(json)?;
+          ids.insert(file_upload.path().to_string());
+        }
+      }
+    }
+  }
+
+  return Ok(ids);
+}
+
 async fn delete_file(store: &dyn ObjectStore, file: FileUpload) -> Result<(), object_store::Error> {
+  delete_thumbnails(store, &file).await?;
   return store
     .delete(&object_store::path::Path::from(file.path()))
     .await;
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn range(value: &str, size: usize) -> Result<Option<Range<usize>>, RangeNotSatisfiable> {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::RANGE, value.parse().unwrap());
+    return parse_range(&headers, "\"etag\"", "date", size);
+  }
+
+  #[test]
+  fn test_parse_range() {
+    assert_eq!(parse_range(&HeaderMap::new(), "", "", 10), Ok(None));
+
+    assert_eq!(range("bytes=0-4", 10), Ok(Some(0..5)));
+    assert_eq!(range("bytes=5-", 10), Ok(Some(5..10)));
+    assert_eq!(range("bytes=5-100", 10), Ok(Some(5..10)));
+    assert_eq!(range("bytes=-3", 10), Ok(Some(7..10)));
+    assert_eq!(range("bytes=-30", 10), Ok(Some(0..10)));
+
+    assert_eq!(range("bytes=10-", 10), Err(RangeNotSatisfiable));
+    assert_eq!(range("bytes=-0", 10), Err(RangeNotSatisfiable));
+
+    // Unsupported or invalid ranges are ignored.
+    assert_eq!(range("bytes=0-1,3-4", 10), Ok(None));
+    assert_eq!(range("bytes=4-1", 10), Ok(None));
+    assert_eq!(range("items=0-1", 10), Ok(None));
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::RANGE, "bytes=0-4".parse().unwrap());
+    headers.insert(header::IF_RANGE, "\"etag\"".parse().unwrap());
+    assert_eq!(
+      parse_range(&headers, "\"etag\"", "date", 10),
+      Ok(Some(0..5))
+    );
+    headers.insert(header::IF_RANGE, "\"stale\"".parse().unwrap());
+    assert_eq!(parse_range(&headers, "\"etag\"", "date", 10), Ok(None));
+  }
+
+  #[test]
+  fn test_content_disposition() {
+    let file_upload = FileUpload::new(
+      uuid::Uuid::new_v4(),
+      Some("my \"vacation\" ü.mp4".to_string()),
+      None,
+      Some("video/mp4".to_string()),
+    );
+
+    assert_eq!(
+      content_disposition(&file_upload, FileDisposition::Inline),
+      "inline; filename=\"my _vacation_ _.mp4\"; filename*=UTF-8''my%20%22vacation%22%20%C3%BC.mp4"
+    );
+    assert_eq!(
+      content_type(&file_upload, FileDisposition::Inline),
+      "video/mp4"
+    );
+
+    // Types which could run scripts are never displayed inline.
+    for mime_type in ["text/html", "image/svg+xml", "application/xhtml+xml"] {
+      let file_upload = FileUpload::new(
+        uuid::Uuid::new_v4(),
+        None,
+        Some("image/png".to_string()),
+        Some(mime_type.to_string()),
+      );
+      assert_eq!(
+        content_disposition(&file_upload, FileDisposition::Inline),
+        "attachment"
+      );
+      assert_eq!(
+        content_type(&file_upload, FileDisposition::Inline),
+        "application/octet-stream"
+      );
+    }
+    let pdf = FileUpload::new(
+      uuid::Uuid::new_v4(),
+      None,
+      None,
+      Some("application/pdf".to_string()),
+    );
+    assert_eq!(content_disposition(&pdf, FileDisposition::Inline), "inline");
+    assert_eq!(
+      content_type(&pdf, FileDisposition::Inline),
+      "application/pdf"
+    );
+
+    let unnamed = FileUpload::new(uuid::Uuid::new_v4(), None, None, None);
+    assert_eq!(
+      content_disposition(&unnamed, FileDisposition::Attachment),
+      "attachment"
+    );
+    assert_eq!(
+      content_disposition(&unnamed, FileDisposition::Inline),
+      "attachment"
+    );
+    assert_eq!(
+      content_type(&unnamed, FileDisposition::Inline),
+      "application/octet-stream"
+    );
+  }
+}
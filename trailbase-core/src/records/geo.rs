@@ -0,0 +1,141 @@
+use libsql::{params, Connection};
+use log::*;
+use std::collections::HashMap;
+
+use crate::config::proto::{Config, RecordApiConfig};
+use crate::constants::SQLITE_SCHEMA_TABLE;
+
+const GEO_INDEX_PREFIX: &str = "_geo_";
+
+/// R*-tree index over a pair of latitude/longitude columns, keyed by the content table's rowid.
+///
+/// The R*-tree's dimensions are named after the indexed columns, e.g. "lat_min" and "lat_max",
+/// which keeps the index's schema self-describing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeoIndex {
+  pub table: String,
+  pub latitude_column: String,
+  pub longitude_column: String,
+}
+
+impl GeoIndex {
+  /// Geo index for the given record API, if both latitude and longitude columns are configured.
+  pub(crate) fn from_config(api_name: &str, config: &RecordApiConfig) -> Option<Self> {
+    let (Some(latitude_column), Some(longitude_column)) =
+      (&config.geo_latitude_column, &config.geo_longitude_column)
+    else {
+      return None;
+    };
+
+    return Some(GeoIndex {
+      table: format!("{GEO_INDEX_PREFIX}{api_name}"),
+      latitude_column: latitude_column.clone(),
+      longitude_column: longitude_column.clone(),
+    });
+  }
+
+  fn build_create_table(&self) -> String {
+    let GeoIndex {
+      table,
+      latitude_column: lat,
+      longitude_column: lng,
+    } = self;
+
+    return format!(
+      r#"CREATE VIRTUAL TABLE "{table}" USING rtree(id, "{lat}_min", "{lat}_max", "{lng}_min", "{lng}_max")"#
+    );
+  }
+
+  fn build_triggers(&self, table_name: &str) -> String {
+    let GeoIndex {
+      table,
+      latitude_column: lat,
+      longitude_column: lng,
+    } = self;
+
+    return indoc::formatdoc!(
+      r#"
+        CREATE TRIGGER "{table}_insert" AFTER INSERT ON "{table_name}" BEGIN
+          INSERT INTO "{table}" SELECT new.rowid, new."{lat}", new."{lat}", new."{lng}", new."{lng}"
+            WHERE new."{lat}" IS NOT NULL AND new."{lng}" IS NOT NULL;
+        END;
+        CREATE TRIGGER "{table}_delete" AFTER DELETE ON "{table_name}" BEGIN
+          DELETE FROM "{table}" WHERE id = old.rowid;
+        END;
+        CREATE TRIGGER "{table}_update" AFTER UPDATE ON "{table_name}" BEGIN
+          DELETE FROM "{table}" WHERE id = old.rowid;
+          INSERT INTO "{table}" SELECT new.rowid, new."{lat}", new."{lat}", new."{lng}", new."{lng}"
+            WHERE new."{lat}" IS NOT NULL AND new."{lng}" IS NOT NULL;
+        END;
+        INSERT INTO "{table}" SELECT rowid, "{lat}", "{lat}", "{lng}", "{lng}" FROM "{table_name}"
+          WHERE "{lat}" IS NOT NULL AND "{lng}" IS NOT NULL;
+      "#
+    );
+  }
+}
+
+/// Creates, updates, or drops R*-tree tables and the triggers keeping them in sync with their
+/// content tables according to the geo columns declared by record APIs in `config`.
+///
+/// Unchanged indexes are left untouched, otherwise they're rebuilt from their content table.
+pub(crate) async fn sync_geo_indexes(
+  conn: &Connection,
+  config: &Config,
+) -> Result<(), libsql::Error> {
+  let mut existing: HashMap<String, String> = HashMap::new();
+  let mut rows = conn
+    .query(
+      &format!(
+        r#"SELECT name, sql FROM {SQLITE_SCHEMA_TABLE} WHERE type = 'table' AND sql LIKE 'CREATE VIRTUAL TABLE%' AND substr(name, 1, $1) = $2"#
+      ),
+      params!(GEO_INDEX_PREFIX.len() as i64, GEO_INDEX_PREFIX),
+    )
+    .await?;
+  while let Some(row) = rows.next().await? {
+    existing.insert(row.get::<String>(0)?, row.get::<String>(1)?);
+  }
+
+  for api_config in &config.record_apis {
+    let (Some(api_name), Some(table_name)) = (&api_config.name, &api_config.table_name) else {
+      continue;
+    };
+    let Some(index) = GeoIndex::from_config(api_name, api_config) else {
+      continue;
+    };
+
+    let create_table = index.build_create_table();
+    if existing.remove(&index.table).as_ref() == Some(&create_table) {
+      continue;
+    }
+
+    info!("Rebuilding geo index: {}", index.table);
+    let tx = conn.transaction().await?;
+    tx.execute_batch(&build_drop_geo_index(&index.table))
+      .await?;
+    tx.execute_batch(&format!(
+      "{create_table};\n{}",
+      index.build_triggers(table_name)
+    ))
+    .await?;
+    tx.commit().await?;
+  }
+
+  // Drop indexes no longer backing any record API.
+  for table in existing.keys() {
+    info!("Dropping stale geo index: {table}");
+    conn.execute_batch(&build_drop_geo_index(table)).await?;
+  }
+
+  return Ok(());
+}
+
+fn build_drop_geo_index(table: &str) -> String {
+  return indoc::formatdoc!(
+    r#"
+      DROP TRIGGER IF EXISTS "{table}_insert";
+      DROP TRIGGER IF EXISTS "{table}_delete";
+      DROP TRIGGER IF EXISTS "{table}_update";
+      DROP TABLE IF EXISTS "{table}";
+    "#
+  );
+}
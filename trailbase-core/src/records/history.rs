@@ -0,0 +1,383 @@
+use axum::extract::{Json, Path, Query, State};
+use serde::{Deserialize, Serialize};
+use trailbase_sqlite::query_row;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::constants::RECORD_HISTORY_TABLE;
+use crate::listing::limit_or_default;
+use crate::records::sql_to_json::row_to_json;
+use crate::records::subscribe::RecordAction;
+use crate::records::{Permission, RecordApi, RecordError};
+use crate::util::id_to_b64;
+
+#[derive(Clone, Debug, Default, Deserialize, IntoParams)]
+pub struct RecordHistoryQuery {
+  /// Max number of entries to return.
+  pub limit: Option<usize>,
+
+  /// Only return entries older than the entry with the given id, i.e. the id of the last entry of
+  /// the previous page.
+  pub before: Option<i64>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct RecordHistoryEntry {
+  pub id: i64,
+  /// One of "insert", "update" or "delete".
+  pub action: String,
+  /// Safe-url base64 encoded id of the acting user, if any.
+  pub user: Option<String>,
+  /// Record before the change. Absent for insertions.
+  pub old_value: Option<serde_json::Value>,
+  /// Record after the change. Absent for deletions.
+  pub new_value: Option<serde_json::Value>,
+  /// Unix timestamp in seconds.
+  pub created: i64,
+}
+
+/// Snapshot of the given record prior to a change, if history is enabled for the API.
+pub(crate) async fn history_snapshot(
+  conn: &libsql::Connection,
+  api: &RecordApi,
+  record_id: &libsql::Value,
+) -> Result<Option<libsql::Row>, RecordError> {
+  if !api.history_enabled() {
+    return Ok(None);
+  }
+
+  return Ok(
+    query_row(
+      conn,
+      &format!(
+        "SELECT * FROM '{table_name}' WHERE [{pk_column}] = $1",
+        table_name = api.table_name(),
+        pk_column = api.record_pk_column().name,
+      ),
+      [record_id.clone()],
+    )
+    .await?,
+  );
+}
+
+/// Appends an entry to the record history, if history is enabled for the API.
+///
+/// Hidden columns, i.e. ones prefixed with "_", are omitted from the recorded values.
+pub(crate) async fn append_history(
+  conn: &libsql::Connection,
+  api: &RecordApi,
+  action: RecordAction,
+  record_id: &libsql::Value,
+  user: Option<&User>,
+  old: Option<&libsql::Row>,
+  new: Option<&libsql::Row>,
+) -> Result<(), RecordError> {
+  if !api.history_enabled() {
+    return Ok(());
+  }
+
+  let to_json = |row: Option<&libsql::Row>| -> Result<libsql::Value, RecordError> {
+    let Some(row) = row else {
+      return Ok(libsql::Value::Null);
+    };
+    let value = row_to_json(api.metadata(), row, |c| !c.starts_with("_"))
+      .map_err(|err| RecordError::Internal(err.into()))?;
+    return Ok(libsql::Value::Text(value.to_string()));
+  };
+
+  conn
+    .execute(
+      &format!(
+        "INSERT INTO {RECORD_HISTORY_TABLE} (table_name, record, action, user, old_value, new_value) VALUES ($1, $2, $3, $4, $5, $6)"
+      ),
+      libsql::params::Params::Positional(vec![
+        libsql::Value::Text(api.table_name().to_string()),
+        record_id.clone(),
+        libsql::Value::Text(action_to_str(action).to_string()),
+        user.map_or(libsql::Value::Null, |user| {
+          libsql::Value::Blob(user.uuid.into())
+        }),
+        to_json(old)?,
+        to_json(new)?,
+      ]),
+    )
+    .await?;
+
+  return Ok(());
+}
+
+fn action_to_str(action: RecordAction) -> &'static str {
+  return match action {
+    RecordAction::Insert => "insert",
+    RecordAction::Update => "update",
+    RecordAction::Delete => "delete",
+  };
+}
+
+/// List change history of a record.
+///
+/// Requires read access to the record and returns entries newest first. Note that if a read access
+/// rule is configured, the history of deleted records is inaccessible, since the rule cannot be
+/// evaluated against a record that no longer exists.
+#[utoipa::path(
+  get,
+  path = "/:name/:record/history",
+  params(RecordHistoryQuery),
+  responses(
+    (status = 200, description = "History entries, newest first.", body = Vec<RecordHistoryEntry>),
+  )
+)]
+pub async fn record_history_handler(
+  State(state): State<AppState>,
+  Path((api_name, record)): Path<(String, String)>,
+  Query(query): Query<RecordHistoryQuery>,
+  user: Option<User>,
+) -> Result<Json<Vec<RecordHistoryEntry>>, RecordError> {
+  let Some(api) = state.lookup_record_api(&api_name) else {
+    return Err(RecordError::ApiNotFound);
+  };
+  if !api.history_enabled() {
+    return Err(RecordError::BadRequest("History not enabled"));
+  }
+
+  let record_id = api.id_to_sql(&record)?;
+
+  api
+    .check_record_level_access(Permission::Read, Some(&record_id), None, user.as_ref())
+    .await?;
+
+  let mut rows = state
+    .conn()
+    .query(
+      &format!(
+        "SELECT id, action, user, old_value, new_value, created FROM {RECORD_HISTORY_TABLE} WHERE table_name = $1 AND record = $2 AND id < $3 ORDER BY id DESC LIMIT $4"
+      ),
+      libsql::params::Params::Positional(vec![
+        libsql::Value::Text(api.table_name().to_string()),
+        record_id,
+        libsql::Value::Integer(query.before.unwrap_or(i64::MAX)),
+        libsql::Value::Integer(limit_or_default(query.limit) as i64),
+      ]),
+    )
+    .await?;
+
+  // Recorded values are restricted to the columns the user can currently read.
+  let parse_json = |value: Option<String>| -> Result<Option<serde_json::Value>, RecordError> {
+    let Some(value) = value else {
+      return Ok(None);
+    };
+    let mut json: serde_json::Value =
+      serde_json::from_str(&value).map_err(|err| RecordError::Internal(err.into()))?;
+    if let serde_json::Value::Object(ref mut map) = json {
+      map.retain(|col, _| api.column_readable(col, user.as_ref()));
+    }
+    return Ok(Some(json));
+  };
+
+  let mut entries: Vec<RecordHistoryEntry> = vec![];
+  while let Some(row) = rows.next().await? {
+    let acting_user = match row.get::<Option<Vec<u8>>>(2)? {
+      Some(bytes) => {
+        let id: [u8; 16] = bytes
+          .try_into()
+          .map_err(|_| RecordError::Internal("Invalid user id".into()))?;
+        Some(id_to_b64(&id))
+      }
+      None => None,
+    };
+
+    entries.push(RecordHistoryEntry {
+      id: row.get::<i64>(0)?,
+      action: row.get::<String>(1)?,
+      user: acting_user,
+      old_value: parse_json(row.get::<Option<String>>(3)?)?,
+      new_value: parse_json(row.get::<Option<String>>(4)?)?,
+      created: row.get::<i64>(5)?,
+    });
+  }
+
+  return Ok(Json(entries));
+}
+
+#[cfg(test)]
+mod test {
+  use axum::http::HeaderMap;
+
+  use super::*;
+  use crate::admin::user::*;
+  use crate::app_state::*;
+  use crate::auth::api::login::login_with_password;
+  use crate::config::proto::PermissionFlag;
+  use crate::extract::Either;
+  use crate::records::create_record::{create_record_handler, CreateRecordQuery};
+  use crate::records::delete_record::delete_record_handler;
+  use crate::records::update_record::update_record_handler;
+  use crate::records::*;
+
+  #[tokio::test]
+  async fn test_record_api_history() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    state
+      .conn()
+      .execute_batch(
+        r#"
+          CREATE TABLE note (
+            id        INTEGER PRIMARY KEY,
+            body      TEXT NOT NULL,
+            _secret   TEXT
+          ) STRICT;
+        "#,
+      )
+      .await?;
+    state.table_metadata().invalidate_all().await?;
+
+    add_record_api(
+      &state,
+      "notes_api",
+      "note",
+      Acls {
+        authenticated: vec![
+          PermissionFlag::Create,
+          PermissionFlag::Read,
+          PermissionFlag::Update,
+          PermissionFlag::Delete,
+        ],
+        ..Default::default()
+      },
+      AccessRules::default(),
+    )
+    .await?;
+
+    let password = "Secret!1!!";
+    let email = "user@test.com";
+    let user_id = create_user_for_test(&state, email, password).await?;
+    let user = User::from_auth_token(
+      &state,
+      &login_with_password(&state, email, password)
+        .await?
+        .auth_token,
+    );
+
+    let path = |id: &str| Path(("notes_api".to_string(), id.to_string()));
+    let history = |id: &'static str, query: RecordHistoryQuery| {
+      record_history_handler(State(state.clone()), path(id), Query(query), user.clone())
+    };
+    let create = || {
+      create_record_handler(
+        State(state.clone()),
+        Path("notes_api".to_string()),
+        Query(CreateRecordQuery::default()),
+        user.clone(),
+        Either::Json(serde_json::json!({"id": 1, "body": "first"})),
+      )
+    };
+
+    // History is opt-in.
+    create().await?;
+    assert!(matches!(
+      history("1", RecordHistoryQuery::default()).await,
+      Err(RecordError::BadRequest(_))
+    ));
+
+    let mut config = state.get_config();
+    for api in &mut config.record_apis {
+      if api.name.as_deref() == Some("notes_api") {
+        api.enable_history = Some(true);
+      }
+    }
+    state.validate_and_update_config(config, None).await?;
+
+    update_record_handler(
+      State(state.clone()),
+      path("1"),
+      user.clone(),
+      HeaderMap::new(),
+      Either::Json(serde_json::json!({"body": "updated"})),
+    )
+    .await?;
+    delete_record_handler(
+      State(state.clone()),
+      path("1"),
+      user.clone(),
+      HeaderMap::new(),
+    )
+    .await?;
+
+    let Json(entries) = history("1", RecordHistoryQuery::default()).await?;
+    assert_eq!(
+      entries
+        .iter()
+        .map(|e| e.action.as_str())
+        .collect::<Vec<_>>(),
+      vec!["delete", "update"]
+    );
+
+    let (delete, update) = (&entries[0], &entries[1]);
+    assert_eq!(
+      update.old_value,
+      Some(serde_json::json!({"id": 1, "body": "first"}))
+    );
+    assert_eq!(
+      update.new_value,
+      Some(serde_json::json!({"id": 1, "body": "updated"}))
+    );
+    assert_eq!(update.user, Some(id_to_b64(&user_id.into_bytes())));
+    assert_eq!(delete.old_value, update.new_value);
+    assert_eq!(delete.new_value, None);
+
+    // Pagination.
+    let Json(page) = history(
+      "1",
+      RecordHistoryQuery {
+        limit: Some(1),
+        before: Some(delete.id),
+      },
+    )
+    .await?;
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].id, update.id);
+
+    // Re-created records carry on with their history.
+    create().await?;
+    let Json(entries) = history("1", RecordHistoryQuery::default()).await?;
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].action, "insert");
+    assert_eq!(entries[0].old_value, None);
+
+    // Records and their history are written atomically, i.e. failing to append to the history
+    // rolls back the change.
+    state
+      .conn()
+      .execute_batch(&format!(
+        r#"
+          CREATE TRIGGER fail_history BEFORE INSERT ON {RECORD_HISTORY_TABLE}
+            WHEN NEW.new_value LIKE '%fail%'
+            BEGIN SELECT RAISE(ABORT, 'fail'); END;
+        "#
+      ))
+      .await?;
+    assert!(update_record_handler(
+      State(state.clone()),
+      path("1"),
+      user.clone(),
+      HeaderMap::new(),
+      Either::Json(serde_json::json!({"body": "fail"})),
+    )
+    .await
+    .is_err());
+    let Json(entries) = history("1", RecordHistoryQuery::default()).await?;
+    assert_eq!(entries.len(), 3);
+    assert_eq!(
+      entries[0].new_value,
+      Some(serde_json::json!({"id": 1, "body": "first"}))
+    );
+    let body: String = query_row(state.conn(), "SELECT body FROM note WHERE id = 1", ())
+      .await?
+      .unwrap()
+      .get(0)?;
+    assert_eq!(body, "first");
+
+    return Ok(());
+  }
+}
@@ -0,0 +1,260 @@
+use axum::http::StatusCode;
+use log::*;
+use trailbase_sqlite::query_row;
+
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::js::{RecordHookContext, RecordHookEvent, RecordHookPhase};
+use crate::records::sql_to_json::{row_to_json, value_to_json};
+use crate::records::{RecordApi, RecordError};
+
+fn record_id_to_json(record_id: &libsql::Value) -> Result<serde_json::Value, RecordError> {
+  return value_to_json(record_id.clone()).map_err(|err| RecordError::Internal(err.into()));
+}
+
+/// Runs the JS before-hook registered for the API and event, if any.
+///
+/// Returns the incoming record values, which the hook may have replaced, or an error if the hook
+/// rejected the change.
+pub(crate) async fn run_before_record_hook(
+  state: &AppState,
+  api: &RecordApi,
+  event: RecordHookEvent,
+  user: Option<&User>,
+  record_id: Option<&libsql::Value>,
+  params: Option<serde_json::Value>,
+) -> Result<Option<serde_json::Value>, RecordError> {
+  let runtime = state.script_runtime();
+  if !runtime.has_record_hook(RecordHookPhase::Before, event, api.api_name()) {
+    return Ok(params);
+  }
+
+  let result = runtime
+    .dispatch_record_hook(
+      RecordHookPhase::Before,
+      RecordHookContext {
+        api: api.api_name().to_string(),
+        event,
+        user: user.map(|u| u.into()),
+        id: record_id.map(record_id_to_json).transpose()?,
+        params: params.clone(),
+        record: None,
+      },
+    )
+    .await
+    .map_err(RecordError::Internal)?;
+
+  if let Some(rejection) = result.error {
+    return match StatusCode::from_u16(rejection.status) {
+      Ok(status) if status.is_client_error() => {
+        Err(RecordError::Rejected(status, rejection.message))
+      }
+      _ => Err(RecordError::Internal(
+        format!("Hook rejected with status {}", rejection.status).into(),
+      )),
+    };
+  }
+
+  // Hooks cannot add values to deletions.
+  return Ok(params.and(result.params));
+}
+
+/// Runs the JS after-hook registered for the API and event, if any, with the committed record.
+///
+/// `row` is looked up by `record_id` if absent, i.e. deleted records have to be provided. Since the
+/// change has already been committed, failures are merely logged.
+pub(crate) async fn run_after_record_hook(
+  state: &AppState,
+  api: &RecordApi,
+  event: RecordHookEvent,
+  user: Option<&User>,
+  record_id: &libsql::Value,
+  row: Option<&libsql::Row>,
+) {
+  let runtime = state.script_runtime();
+  if !runtime.has_record_hook(RecordHookPhase::After, event, api.api_name()) {
+    return;
+  }
+
+  let record = async {
+    let looked_up: Option<libsql::Row>;
+    let row = match row {
+      Some(row) => row,
+      None => {
+        looked_up = query_row(
+          state.conn(),
+          &format!(
+            "SELECT * FROM '{table_name}' WHERE [{pk_column}] = $1",
+            table_name = api.table_name(),
+            pk_column = api.record_pk_column().name,
+          ),
+          [record_id.clone()],
+        )
+        .await?;
+        let Some(ref row) = looked_up else {
+          return Err(RecordError::RecordNotFound);
+        };
+        row
+      }
+    };
+
+    return row_to_json(api.metadata(), row, |c| !c.starts_with("_"))
+      .map_err(|err| RecordError::Internal(err.into()));
+  };
+
+  let context = match (record.await, record_id_to_json(record_id)) {
+    (Ok(record), Ok(id)) => RecordHookContext {
+      api: api.api_name().to_string(),
+      event,
+      user: user.map(|u| u.into()),
+      id: Some(id),
+      params: None,
+      record: Some(record),
+    },
+    (Err(err), _) | (_, Err(err)) => {
+      warn!("Failed to build after-hook context: {err}");
+      return;
+    }
+  };
+
+  if let Err(err) = runtime
+    .dispatch_record_hook(RecordHookPhase::After, context)
+    .await
+  {
+    warn!("After-hook failed for {}: {err}", api.api_name());
+  }
+}
+
+#[cfg(all(test, feature = "v8"))]
+mod test {
+  use axum::extract::{Path, Query, State};
+  use axum::http::HeaderMap;
+  use trailbase_sqlite::query_one_row;
+
+  use super::*;
+  use crate::app_state::*;
+  use crate::config::proto::PermissionFlag;
+  use crate::extract::Either;
+  use crate::js::load_routes_from_js_modules;
+  use crate::records::create_record::{create_record_handler, CreateRecordQuery};
+  use crate::records::delete_record::delete_record_handler;
+  use crate::records::*;
+
+  #[tokio::test]
+  async fn test_record_hooks() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    state
+      .conn()
+      .execute_batch(
+        r#"
+          CREATE TABLE hooked_note (
+            id        INTEGER PRIMARY KEY,
+            body      TEXT NOT NULL
+          ) STRICT;
+        "#,
+      )
+      .await?;
+    state.table_metadata().invalidate_all().await?;
+
+    add_record_api(
+      &state,
+      "hooked_notes_api",
+      "hooked_note",
+      Acls {
+        world: vec![PermissionFlag::Create, PermissionFlag::Delete],
+        ..Default::default()
+      },
+      AccessRules::default(),
+    )
+    .await?;
+
+    let scripts_dir = state.data_dir().root().join("scripts");
+    tokio::fs::create_dir_all(&scripts_dir).await?;
+    tokio::fs::write(
+      scripts_dir.join("hooks.ts"),
+      r#"
+        import { HttpError, StatusCodes, onRecordCreate, onRecordDelete } from "trailbase:main";
+
+        onRecordCreate("hooked_notes_api", {
+          before: (ctx) => {
+            const body = ctx.params?.body as string;
+            if (body === "forbidden") {
+              throw new HttpError(StatusCodes.FORBIDDEN, "Forbidden body");
+            }
+            return { ...ctx.params, body: body.toUpperCase() };
+          },
+          after: (ctx) => {
+            console.debug("created", ctx.record);
+          },
+        });
+
+        onRecordDelete("hooked_notes_api", {
+          before: (ctx) => {
+            if (ctx.id === 1) {
+              throw new HttpError(StatusCodes.CONFLICT);
+            }
+          },
+        });
+      "#,
+    )
+    .await?;
+    load_routes_from_js_modules(&state).await.unwrap();
+
+    let runtime = state.script_runtime();
+    let api_name = "hooked_notes_api";
+    assert!(runtime.has_record_hook(RecordHookPhase::Before, RecordHookEvent::Create, api_name));
+    assert!(runtime.has_record_hook(RecordHookPhase::After, RecordHookEvent::Create, api_name));
+    assert!(!runtime.has_record_hook(RecordHookPhase::After, RecordHookEvent::Delete, api_name));
+
+    let create = |body: &str| {
+      create_record_handler(
+        State(state.clone()),
+        Path(api_name.to_string()),
+        Query(CreateRecordQuery::default()),
+        None,
+        Either::Json(serde_json::json!({"body": body})),
+      )
+    };
+    let delete = |id: &str| {
+      delete_record_handler(
+        State(state.clone()),
+        Path((api_name.to_string(), id.to_string())),
+        None,
+        HeaderMap::new(),
+      )
+    };
+
+    // Before-hooks may alter the incoming values.
+    create("hello").await?;
+    create("world").await?;
+    let body = query_one_row(
+      state.conn(),
+      "SELECT body FROM hooked_note WHERE id = 1",
+      (),
+    )
+    .await?
+    .get::<String>(0)?;
+    assert_eq!(body, "HELLO");
+
+    // Or reject changes altogether.
+    match create("forbidden").await {
+      Err(RecordError::Rejected(status, message)) => {
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(message.as_deref(), Some("Forbidden body"));
+      }
+      _ => panic!("Expected rejection"),
+    };
+    assert!(matches!(
+      delete("1").await,
+      Err(RecordError::Rejected(StatusCode::CONFLICT, None))
+    ));
+    delete("2").await?;
+
+    let count = query_one_row(state.conn(), "SELECT COUNT(*) FROM hooked_note", ())
+      .await?
+      .get::<i64>(0)?;
+    assert_eq!(count, 1);
+
+    return Ok(());
+  }
+}
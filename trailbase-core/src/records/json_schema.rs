@@ -3,12 +3,16 @@ use serde::Deserialize;
 
 use crate::auth::user::User;
 use crate::records::{Permission, RecordError};
-use crate::table_metadata::build_json_schema;
+use crate::schema::ColumnDataType;
+use crate::table_metadata::{build_json_schema, column_data_type_to_json_type};
 use crate::{api::JsonSchemaMode, app_state::AppState};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct JsonSchemaQuery {
   pub mode: Option<JsonSchemaMode>,
+  /// Comma-separated list of columns to restrict the schema to, e.g. to describe the shape of
+  /// records read or listed with `?fields=`.
+  pub fields: Option<String>,
 }
 
 /// Retrieve json schema associated with given record api.
@@ -33,8 +37,90 @@ pub async fn json_schema_handler(
     .check_record_level_access(Permission::Schema, None, None, user.as_ref())
     .await?;
 
+  let fields = request
+    .fields
+    .as_deref()
+    .map(|f| api.parse_fields(f, user.as_ref()))
+    .transpose()?;
+
   let mode = request.mode.unwrap_or(JsonSchemaMode::Insert);
-  let (_schema, json) = build_json_schema(api.table_name(), api.metadata(), mode)
+  let (_schema, mut json) = build_json_schema(api.table_name(), api.metadata(), mode)
     .map_err(|err| RecordError::Internal(err.into()))?;
+
+  // Computed fields are part of read records.
+  if let (JsonSchemaMode::Select, Some(serde_json::Value::Object(properties))) =
+    (mode, json.get_mut("properties"))
+  {
+    for field in api.computed_fields() {
+      let property = match ColumnDataType::from_type_name(field.r#type()) {
+        Some(data_type) => serde_json::json!({
+          "type": column_data_type_to_json_type(data_type),
+        }),
+        None => serde_json::json!({}),
+      };
+      properties.insert(field.name().to_string(), property);
+    }
+  }
+
+  // Only describe columns the user can read or write, respectively, and were requested.
+  project_json_schema(&mut json, |col| {
+    let accessible = match mode {
+      JsonSchemaMode::Select => api.column_readable(col, user.as_ref()),
+      JsonSchemaMode::Insert | JsonSchemaMode::Update => api.column_writable(col, user.as_ref()),
+    };
+    return accessible && fields.as_ref().map_or(true, |f| f.iter().any(|f| f == col));
+  });
+
   return Ok(Json(json));
 }
+
+/// Restricts the given schema's properties to the columns matching the given filter.
+fn project_json_schema(schema: &mut serde_json::Value, contains: impl Fn(&str) -> bool) {
+  let Some(schema) = schema.as_object_mut() else {
+    return;
+  };
+  if let Some(serde_json::Value::Object(properties)) = schema.get_mut("properties") {
+    properties.retain(|col, _| contains(col));
+  }
+  if let Some(serde_json::Value::Array(required)) = schema.get_mut("required") {
+    required.retain(|col| col.as_str().is_some_and(&contains));
+  }
+  // Definitions are keyed by column name.
+  if let Some(serde_json::Value::Object(defs)) = schema.get_mut("$defs") {
+    defs.retain(|col, _| contains(col));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::schema::Table;
+  use crate::table_metadata::{sqlite3_parse_into_statement, TableMetadata};
+
+  #[test]
+  fn test_project_json_schema() {
+    let table: Table = sqlite3_parse_into_statement(
+      r#"CREATE TABLE t (
+        id       INTEGER PRIMARY KEY,
+        name     TEXT NOT NULL,
+        file     TEXT CHECK(jsonschema('std.FileUpload', file))
+      ) STRICT"#,
+    )
+    .unwrap()
+    .unwrap()
+    .try_into()
+    .unwrap();
+    let metadata = TableMetadata::new(table.clone(), &[table]);
+
+    let (_schema, mut json) = build_json_schema("t", &metadata, JsonSchemaMode::Select).unwrap();
+    assert!(json["$defs"].get("file").is_some());
+
+    project_json_schema(&mut json, |col| col == "name");
+    assert_eq!(
+      json["properties"],
+      serde_json::json!({"name": {"type": "string"}})
+    );
+    assert_eq!(json["required"], serde_json::json!(["name"]));
+    assert_eq!(json["$defs"], serde_json::json!({}));
+  }
+}
@@ -8,7 +8,8 @@ use trailbase_sqlite::schema::{FileUpload, FileUploadInput, FileUploads};
 use trailbase_sqlite::{query_one_row, query_row};
 
 use crate::config::proto::ConflictResolutionStrategy;
-use crate::records::files::delete_files_in_row;
+use crate::extract::{FileUploadLimit, FileUploadLimitError, MultipartFile, StoredFileUpload};
+use crate::records::files::{delete_files_in_row, is_file_column};
 use crate::schema::{Column, ColumnDataType};
 use crate::table_metadata::{self, ColumnMetadata, JsonColumnMetadata, TableMetadata};
 use crate::AppState;
@@ -105,7 +106,8 @@ impl From<crate::records::files::FileError> for QueryError {
   }
 }
 
-type FileMetadataContents = Vec<(FileUpload, Vec<u8>)>;
+/// List of (column name, file metadata, contents) tuples.
+type FileMetadataContents = Vec<(String, FileUpload, Vec<u8>)>;
 
 #[derive(Default)]
 pub struct Params {
@@ -123,6 +125,9 @@ pub struct Params {
 
   /// List of files and contents to be written to an object store.
   files: FileMetadataContents,
+  /// List of files, which have already been streamed to the object store. They're deleted again
+  /// unless persisted after successfully writing the record.
+  stored_files: Vec<StoredFileUpload>,
   /// Subset of `col_names` containing only file columns. Useful for building Update/Delete queries
   /// to remove the files from the object store afterwards.
   file_col_names: Vec<String>,
@@ -150,7 +155,7 @@ impl Params {
   pub fn from(
     metadata: &TableMetadata,
     json: serde_json::Value,
-    multipart_files: Option<Vec<FileUploadInput>>,
+    multipart_files: Option<Vec<MultipartFile>>,
   ) -> Result<Self, ParamsError> {
     let serde_json::Value::Object(map) = json else {
       return Err(ParamsError::NotAnObject);
@@ -168,11 +173,20 @@ impl Params {
         continue;
       };
 
-      let (param, mut json_files) = extract_params_and_files_from_json(col, col_meta, value)?;
-      if let Some(json_files) = json_files.as_mut() {
+      let is_null = value.is_null();
+      let (param, json_files) = extract_params_and_files_from_json(col, col_meta, value)?;
+      if let Some(json_files) = json_files {
         // Note: files provided as a multipart form upload are handled below. They need more
         // special handling to establish the field.name to column mapping.
-        params.files.append(json_files);
+        params.files.extend(
+          json_files
+            .into_iter()
+            .map(|(metadata, content)| (key.clone(), metadata, content)),
+        );
+        params.file_col_names.push(key.to_string());
+      } else if is_null && is_file_column(col_meta) {
+        // Explicitly cleared file columns, whose previous files need deleting as well. Other values,
+        // e.g. metadata of already stored files, may well reference the current files.
         params.file_col_names.push(key.to_string());
       }
 
@@ -222,6 +236,22 @@ impl Params {
     &self.params
   }
 
+  pub(crate) fn has_files(&self) -> bool {
+    return !self.files.is_empty() || !self.stored_files.is_empty();
+  }
+
+  /// Checks files against the given per-column limits. Files that have been streamed to the object
+  /// store were already checked along the way.
+  pub(crate) fn check_file_upload_limits(
+    &self,
+    limit: impl Fn(&str) -> FileUploadLimit,
+  ) -> Result<(), FileUploadLimitError> {
+    for (col_name, metadata, content) in &self.files {
+      limit(col_name).check(content.len(), metadata.mime_type())?;
+    }
+    return Ok(());
+  }
+
   pub(crate) fn placeholders(&self) -> String {
     return self.params.iter().map(|(k, _v)| k.clone()).join(", ");
   }
@@ -229,19 +259,25 @@ impl Params {
   fn append_multipart_files(
     &mut self,
     metadata: &TableMetadata,
-    multipart_files: Vec<FileUploadInput>,
+    multipart_files: Vec<MultipartFile>,
   ) -> Result<(), ParamsError> {
-    let mut files: Vec<(String, FileUpload, Vec<u8>)> = vec![];
+    let missing_name = || ParamsError::Column("Multipart form upload missing name property");
+
+    let mut files: Vec<(String, FileUpload)> = vec![];
     for file in multipart_files {
-      let (col_name, metadata, content) = file.consume()?;
-      match col_name {
-        None => {
-          return Err(ParamsError::Column(
-            "Multipart form upload missing name property",
-          ));
+      match file {
+        MultipartFile::Buffered(file) => {
+          let (col_name, metadata, content) = file.consume()?;
+          let col_name = col_name.ok_or_else(missing_name)?;
+
+          files.push((col_name.clone(), metadata.clone()));
+          self.files.push((col_name, metadata, content));
         }
-        Some(col_name) => {
-          files.push((col_name, metadata, content));
+        MultipartFile::Stored(file) => {
+          let col_name = file.name.clone().ok_or_else(missing_name)?;
+
+          files.push((col_name, file.file_upload.clone()));
+          self.stored_files.push(file);
         }
       }
     }
@@ -250,7 +286,7 @@ impl Params {
     let mut file_uploads_map = HashMap::<String, Vec<FileUpload>>::new();
 
     // Validate and organize by type;
-    for (field_name, file_metadata, _content) in &files {
+    for (field_name, file_metadata) in &files {
       // We simply skip unknown columns, this could simply be malformed input or version skew. This
       // is similar in spirit to protobuf's unknown fields behavior.
       let Some((col, col_meta)) = Self::column_by_name(metadata, field_name) else {
@@ -303,13 +339,6 @@ impl Params {
       self.file_col_names.push(col_name);
     }
 
-    self.files.append(
-      &mut files
-        .into_iter()
-        .map(|(_, metadata, content)| (metadata, content))
-        .collect(),
-    );
-
     return Ok(());
   }
 }
@@ -330,6 +359,28 @@ impl SelectQueryBuilder {
     )
     .await;
   }
+
+  /// Same as `run` but only selects the given, already validated, columns.
+  pub(crate) async fn run_projected(
+    state: &AppState,
+    table_name: &str,
+    columns: &[String],
+    pk_column: &str,
+    pk_value: libsql::Value,
+  ) -> Result<Option<libsql::Row>, libsql::Error> {
+    let columns = columns
+      .iter()
+      .map(|c| format!("[{c}]"))
+      .collect::<Vec<_>>()
+      .join(", ");
+
+    return query_row(
+      state.conn(),
+      &format!("SELECT {columns} FROM '{table_name}' WHERE {pk_column} = $1"),
+      [pk_value],
+    )
+    .await;
+  }
 }
 
 pub(crate) struct GetFileQueryBuilder;
@@ -403,47 +454,34 @@ pub(crate) struct InsertQueryBuilder;
 impl InsertQueryBuilder {
   pub(crate) async fn run(
     state: &AppState,
-    params: Params,
+    mut params: Params,
     conflict_resolution: Option<ConflictResolutionStrategy>,
     return_column_name: Option<&str>,
   ) -> Result<libsql::Row, QueryError> {
-    let (query_fragment, named_params, mut files) =
+    // We're storing any files to the object store first to make sure the DB entry is valid right
+    // after commit and not racily pointing to soon-to-be-written files.
+    let files = PendingFiles::write(state, &mut params).await?;
+
+    let (query_fragment, named_params, _files) =
       Self::build_insert_query(params, conflict_resolution)?;
     let query = match return_column_name {
       Some(return_column_name) => format!("{query_fragment} RETURNING {return_column_name}"),
       None => format!("{query_fragment} RETURNING NULL"),
     };
 
-    // We're storing any files to the object store first to make sure the DB entry is valid right
-    // after commit and not racily pointing to soon-to-be-written files.
-    if !files.is_empty() {
-      let objectstore = state.objectstore();
-      for (metadata, content) in &mut files {
-        write_file(objectstore, metadata, content).await?;
-      }
-    }
-
     let row = match query_one_row(state.conn(), &query, named_params).await {
       Ok(row) => row,
       Err(err) => {
-        if !files.is_empty() {
-          let objectstore = state.objectstore();
-
-          for (metadata, _files) in &files {
-            let path = object_store::path::Path::from(metadata.path());
-            if let Err(err) = objectstore.delete(&path).await {
-              warn!("Failed to cleanup file after failed insertion (leak): {err}");
-            }
-          }
-        }
+        files.rollback(state).await;
         return Err(err.into());
       }
     };
+    files.commit();
 
     return Ok(row);
   }
 
-  fn build_insert_query(
+  pub(crate) fn build_insert_query(
     params: Params,
     conflict_resolution: Option<ConflictResolutionStrategy>,
   ) -> Result<(String, libsql::params::Params, FileMetadataContents), QueryError> {
@@ -499,16 +537,8 @@ impl UpdateQueryBuilder {
       return Ok(());
     }
 
-    params.push_param(pk_column.to_string(), pk_value.clone());
-
     // We're storing to object store before writing the entry to the DB.
-    let mut files = std::mem::take(&mut params.files);
-    if !files.is_empty() {
-      let objectstore = state.objectstore();
-      for (metadata, content) in &mut files {
-        write_file(objectstore, metadata, content).await?;
-      }
-    }
+    let files = PendingFiles::write(state, &mut params).await?;
 
     async fn row_update(
       conn: &libsql::Connection,
@@ -517,40 +547,9 @@ impl UpdateQueryBuilder {
       pk_column: &str,
       pk_value: libsql::Value,
     ) -> Result<Option<libsql::Row>, QueryError> {
-      let build_setters = || -> String {
-        assert_eq!(params.col_names.len(), params.params.len());
-        return std::iter::zip(&params.col_names, &params.params)
-          .map(|(col_name, p)| format!("{col_name} = {placeholder}", placeholder = p.0))
-          .join(", ");
-      };
-
-      let setters = build_setters();
-      let named_params = libsql::params::Params::Named(params.params);
-
       let tx = conn.transaction().await?;
-
-      // First, fetch updated file column contents so we can delete the files after updating the
-      // column.
-      let files_row = if params.file_col_names.is_empty() {
-        None
-      } else {
-        let file_columns = params.file_col_names.join(", ");
-        query_row(
-          &tx,
-          &format!("SELECT {file_columns} FROM '{table_name}' WHERE {pk_column} = ${pk_column}"),
-          libsql::params::Params::Named(vec![(":pk_column".to_string(), pk_value)]),
-        )
-        .await?
-      };
-
-      // Update the column.
-      let _ = tx
-        .execute(
-          &format!("UPDATE '{table_name}' SET {setters} WHERE {pk_column} = :{pk_column}"),
-          named_params,
-        )
-        .await?;
-
+      let files_row =
+        UpdateQueryBuilder::update(&tx, table_name, params, pk_column, pk_value).await?;
       tx.commit().await?;
 
       return Ok(files_row);
@@ -559,28 +558,113 @@ impl UpdateQueryBuilder {
     let files_row = match row_update(state.conn(), table_name, params, pk_column, pk_value).await {
       Ok(files_row) => files_row,
       Err(err) => {
-        if !files.is_empty() {
-          let store = state.objectstore();
-          for (metadata, _content) in &files {
-            let path = object_store::path::Path::from(metadata.path());
-            if let Err(err) = store.delete(&path).await {
-              warn!("Failed to cleanup file after failed insertion (leak): {err}");
-            }
-          }
-        }
-
+        files.rollback(state).await;
         return Err(err);
       }
     };
+    files.commit();
 
     // Finally, if everything else went well delete files from columns that were updated and are no
     // longer referenced.
     if let Some(files_row) = files_row {
-      delete_files_in_row(state, metadata, files_row).await?;
+      delete_files_in_row(state, metadata, &files_row).await?;
     }
 
     return Ok(());
   }
+
+  /// Updates the record on the given connection, which is expected to be in a transaction, after
+  /// its files have been written.
+  ///
+  /// Returns the prior contents of the updated file columns, whose files should be deleted once
+  /// the transaction has been committed, or `QueryError::NotFound` if the record doesn't exist.
+  pub(crate) async fn update(
+    conn: &libsql::Connection,
+    table_name: &str,
+    params: Params,
+    pk_column: &str,
+    pk_value: libsql::Value,
+  ) -> Result<Option<libsql::Row>, QueryError> {
+    assert!(params.files.is_empty(), "files must be written first");
+    assert_eq!(params.col_names.len(), params.params.len());
+
+    let setters = std::iter::zip(&params.col_names, &params.params)
+      .map(|(col_name, p)| format!("{col_name} = {placeholder}", placeholder = p.0))
+      .join(", ");
+
+    // First, fetch updated file column contents so we can delete the files after updating the
+    // column.
+    let files_row = if params.file_col_names.is_empty() {
+      None
+    } else {
+      let file_columns = params.file_col_names.join(", ");
+      query_row(
+        conn,
+        &format!("SELECT {file_columns} FROM '{table_name}' WHERE {pk_column} = $1"),
+        [pk_value.clone()],
+      )
+      .await?
+    };
+
+    let mut named_params = params.params;
+    named_params.push((":__record_id".to_string(), pk_value));
+
+    // Update the column.
+    let rows_affected = conn
+      .execute(
+        &format!("UPDATE '{table_name}' SET {setters} WHERE {pk_column} = :__record_id"),
+        libsql::params::Params::Named(named_params),
+      )
+      .await?;
+    if rows_affected == 0 {
+      return Err(QueryError::NotFound);
+    }
+
+    return Ok(files_row);
+  }
+}
+
+/// Files written to the object store ahead of the DB write referencing them, which makes sure
+/// records never point to missing files.
+///
+/// Once the record has been written, the files must either be committed or rolled back, i.e.
+/// deleted, if writing the record failed.
+pub(crate) struct PendingFiles {
+  files: FileMetadataContents,
+  stored_files: Vec<StoredFileUpload>,
+}
+
+impl PendingFiles {
+  pub(crate) async fn write(state: &AppState, params: &mut Params) -> Result<Self, QueryError> {
+    let stored_files = std::mem::take(&mut params.stored_files);
+    let mut files = std::mem::take(&mut params.files);
+    if !files.is_empty() {
+      let objectstore = state.objectstore();
+      for (_col_name, metadata, content) in &mut files {
+        write_file(objectstore, metadata, content).await?;
+      }
+    }
+
+    return Ok(PendingFiles {
+      files,
+      stored_files,
+    });
+  }
+
+  pub(crate) fn commit(self) {
+    persist_files(self.stored_files);
+  }
+
+  pub(crate) async fn rollback(self, state: &AppState) {
+    // Streamed files are deleted when dropped.
+    let store = state.objectstore();
+    for (_col_name, metadata, _content) in &self.files {
+      let path = object_store::path::Path::from(metadata.path());
+      if let Err(err) = store.delete(&path).await {
+        warn!("Failed to cleanup file after failed write (leak): {err}");
+      }
+    }
+  }
 }
 
 pub(crate) struct DeleteQueryBuilder;
@@ -591,20 +675,36 @@ impl DeleteQueryBuilder {
     metadata: &TableMetadata,
     pk_column: &str,
     pk_value: libsql::Value,
-  ) -> Result<(), QueryError> {
-    let table_name = metadata.name();
+  ) -> Result<libsql::Row, QueryError> {
+    let row = Self::delete(state.conn(), metadata.name(), pk_column, pk_value).await?;
 
-    let row = query_one_row(
-      state.conn(),
+    // Finally, delete files.
+    delete_files_in_row(state, metadata, &row).await?;
+
+    return Ok(row);
+  }
+
+  /// Deletes the record on the given connection and returns it. Deleting its files is left to the
+  /// caller, e.g. once the surrounding transaction has been committed.
+  pub(crate) async fn delete(
+    conn: &libsql::Connection,
+    table_name: &str,
+    pk_column: &str,
+    pk_value: libsql::Value,
+  ) -> Result<libsql::Row, libsql::Error> {
+    return query_one_row(
+      conn,
       &format!("DELETE FROM '{table_name}' WHERE {pk_column} = $1 RETURNING *"),
       [pk_value],
     )
-    .await?;
-
-    // Finally, delete files.
-    delete_files_in_row(state, metadata, row).await?;
+    .await;
+  }
+}
 
-    return Ok(());
+/// Keeps files, which have been streamed to the object store, once they're referenced by a record.
+fn persist_files(stored_files: Vec<StoredFileUpload>) {
+  for file in stored_files {
+    file.persist();
   }
 }
 
@@ -838,7 +938,7 @@ pub struct LazyParams<'a> {
   // Input
   request: serde_json::Value,
   metadata: &'a TableMetadata,
-  multipart_files: Option<Vec<FileUploadInput>>,
+  multipart_files: Option<Vec<MultipartFile>>,
 
   // Output
   params: Option<Result<Params, ParamsError>>,
@@ -848,7 +948,7 @@ impl<'a> LazyParams<'a> {
   pub fn new(
     metadata: &'a TableMetadata,
     request: serde_json::Value,
-    multipart_files: Option<Vec<FileUploadInput>>,
+    multipart_files: Option<Vec<MultipartFile>>,
   ) -> Self {
     LazyParams {
       request,
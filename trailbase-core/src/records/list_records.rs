@@ -2,22 +2,55 @@ use axum::{
   extract::{Path, RawQuery, State},
   Json,
 };
+use serde::{Deserialize, Serialize};
+use trailbase_sqlite::query_one_row;
+use utoipa::ToSchema;
 
 use crate::app_state::AppState;
 use crate::auth::user::User;
 use crate::listing::{
-  build_filter_where_clause, limit_or_default, parse_query, Order, WhereClause,
+  build_cursor_where_clause, build_filter_where_clause, build_keyset, build_order_clause,
+  limit_or_default, parse_query, Cursor, FilterExpr, GeoBoundingBox, GeoFilter, GeoNear, Order,
+  QueryParseResult, WhereClause,
 };
+use crate::records::computed::{append_computed_fields, build_computed_field_selects};
+use crate::records::expand::{expand_record, ExpandTree};
 use crate::records::record_api::build_user_sub_select;
-use crate::records::sql_to_json::rows_to_json;
-use crate::records::{Permission, RecordError};
+use crate::records::search::build_search_query;
+use crate::records::sql_to_json::row_to_json;
+use crate::records::{Permission, RecordApi, RecordError};
+
+/// Hidden column holding the distance in meters for `near` queries, which one can order by.
+const DISTANCE_COLUMN: &str = "_distance";
+
+/// Maps `geo_distance` failures on non-numeric coordinates to client errors rather than internal
+/// ones.
+fn map_geo_error(err: libsql::Error) -> RecordError {
+  if let libsql::Error::SqliteFailure(_code, ref msg) = err {
+    if msg.contains(trailbase_extension::geo::NON_NUMERIC_COORDINATES) {
+      return RecordError::BadRequest("Non-numeric geo coordinates");
+    }
+  }
+  return err.into();
+}
+
+/// Envelope for listed records, returned when requested via `?envelope=true` or `?count=true`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, ToSchema)]
+pub struct ListResponse {
+  /// Cursor to fetch the next page. Only present if the page is full and there may be more records.
+  pub cursor: Option<String>,
+  /// Total number of records matching the filter and list access rule, if requested via
+  /// `?count=true`.
+  pub total_count: Option<i64>,
+  pub records: Vec<serde_json::Value>,
+}
 
 /// Lists records matching the given filters
 #[utoipa::path(
   get,
   path = "/:name",
   responses(
-    (status = 200, description = "Matching records.")
+    (status = 200, description = "Matching records. Either a list or a ListResponse envelope. All matching records are streamed as CSV or NDJSON if requested via `?format=` or the Accept header.")
   )
 )]
 pub async fn list_records_handler(
@@ -32,99 +65,409 @@ pub async fn list_records_handler(
 
   // WARN: We do different access checking here because the access rule is used as a filter query
   // on the table, i.e. no access -> empty results.
-  api.check_table_level_access(Permission::Read, user.as_ref())?;
+  api.check_table_level_access(Permission::List, user.as_ref())?;
+
+  let query = parse_query(raw_url_query).unwrap_or_default();
+  let limit = limit_or_default(query.limit);
+  let (envelope, count) = (query.envelope, query.count);
+  let cursor = query
+    .cursor
+    .as_deref()
+    .map(Cursor::parse)
+    .transpose()
+    .map_err(|_err| RecordError::BadRequest("Invalid cursor"))?;
 
-  let (filter_params, cursor, limit, order) = match parse_query(raw_url_query) {
-    Some(q) => (Some(q.params), q.cursor, q.limit, q.order),
-    None => (None, None, None, None),
+  let list_query = ListQuery::new(api, query, user)?;
+  let total_count = match count {
+    true => Some(list_query.count(&state).await?),
+    false => None,
   };
 
-  // Where clause contains column filters and cursor depending on what's present.
-  let metadata = api.metadata();
-  let WhereClause {
-    mut clause,
-    mut params,
-  } = build_filter_where_clause(metadata, filter_params)
-    .map_err(|_err| RecordError::BadRequest("Invalid filter params"))?;
-  if let Some(cursor) = cursor {
-    params.push((":cursor".to_string(), libsql::Value::Blob(cursor.to_vec())));
-    clause = format!("{clause} AND _ROW_.id < :cursor");
-  }
-  params.push((
-    ":limit".to_string(),
-    libsql::Value::Integer(limit_or_default(limit) as i64),
-  ));
+  let (records, last_row) = list_query.fetch(&state, cursor.as_ref(), limit).await?;
 
-  // User properties
-  let (user_sub_select, mut user_params) = build_user_sub_select(user.as_ref());
-  params.append(&mut user_params);
-
-  // NOTE: We're using the read access rule to filter the rows as opposed to yes/no early access
-  // blocking as for read-record.
-  //
-  // TODO: Should this be a separate access rule? Maybe one wants users to access a specific
-  // record but not list all the records.
-  if let Some(read_access) = api.access_rule(Permission::Read) {
-    clause = format!("({clause}) AND {read_access}");
+  if !envelope && !count {
+    return Ok(Json(serde_json::Value::Array(records)));
   }
 
-  let default_ordering = || {
-    return vec![(api.record_pk_column().name.clone(), Order::Descending)];
+  let cursor = match last_row {
+    Some(row) if records.len() >= limit => {
+      Cursor::from_row(&list_query.keyset, &row).map(|c| c.encode())
+    }
+    _ => None,
   };
 
-  let order_clause = order
-    .unwrap_or_else(default_ordering)
-    .iter()
-    .map(|(col, ord)| {
-      format!(
-        "_ROW_.{col} {}",
-        match ord {
-          Order::Descending => "DESC",
-          Order::Ascending => "ASC",
-        }
-      )
+  return Ok(Json(
+    serde_json::to_value(ListResponse {
+      cursor,
+      total_count,
+      records,
     })
-    .collect::<Vec<_>>()
-    .join(", ");
-
-  let query = format!(
-    r#"
-      SELECT _ROW_.*
-      FROM
-        ({user_sub_select}) AS _USER_,
-        (SELECT * FROM '{table_name}') as _ROW_
-      WHERE
-        {clause}
-      ORDER BY
-        {order_clause}
-      LIMIT :limit
-    "#,
-    table_name = api.table_name()
-  );
-
-  let rows = state
-    .conn()
-    .query(&query, libsql::params::Params::Named(params))
-    .await?;
+    .map_err(|err| RecordError::Internal(err.into()))?,
+  ));
+}
+
+/// Query for the records of an API matching the given filters and list access rule, which can be
+/// fetched page by page.
+pub(crate) struct ListQuery {
+  api: RecordApi,
+  user: Option<User>,
+  fields: Option<Vec<String>>,
+  expand: Option<ExpandTree>,
+  /// Columns determining the order of records, from which pagination cursors are derived.
+  pub(crate) keyset: Vec<(String, Order)>,
+  select_columns: String,
+  user_sub_select: String,
+  row_source: String,
+  clause: String,
+  params: Vec<(String, libsql::Value)>,
+}
+
+impl ListQuery {
+  /// Builds the query for the given list query params. Pagination params, i.e. cursor and limit,
+  /// are passed when fetching.
+  pub(crate) fn new(
+    api: RecordApi,
+    query: QueryParseResult,
+    user: Option<User>,
+  ) -> Result<Self, RecordError> {
+    let QueryParseResult {
+      params: filter_params,
+      filter,
+      order,
+      expand,
+      fields,
+      search,
+      near,
+      bbox,
+      ..
+    } = query;
+    let expand = expand.map(|e| ExpandTree::parse(&e)).transpose()?;
+    let fields = fields
+      .map(|f| api.parse_fields(&f, user.as_ref()))
+      .transpose()?;
+
+    // Columns that cannot be read cannot be filtered by either, since that would leak their
+    // values.
+    let readable = |col: &str| api.column_readable(col, user.as_ref());
+    let filter_columns_readable = filter_params.keys().all(|col| readable(col))
+      && match filter.as_deref().map(FilterExpr::parse) {
+        Some(Ok(expr)) => expr.columns().into_iter().all(readable),
+        _ => true,
+      };
+    if !filter_columns_readable {
+      return Err(RecordError::BadRequest("Invalid filter params"));
+    }
+
+    let geo = match (near, bbox) {
+      (None, None) => None,
+      (near, bbox) => {
+        let Some(index) = api
+          .geo_index()
+          .filter(|index| readable(&index.latitude_column) && readable(&index.longitude_column))
+        else {
+          return Err(RecordError::BadRequest("Geo filters not supported"));
+        };
+        Some(GeoFilter {
+          index,
+          near: near
+            .map(|near| GeoNear::parse(&near))
+            .transpose()
+            .map_err(|_err| RecordError::BadRequest("Invalid near"))?,
+          bbox: bbox
+            .map(|bbox| GeoBoundingBox::parse(&bbox))
+            .transpose()
+            .map_err(|_err| RecordError::BadRequest("Invalid bbox"))?,
+        })
+      }
+    };
+
+    // Where clause contains column filters and cursor depending on what's present.
+    let metadata = api.metadata();
+    let WhereClause {
+      mut clause,
+      mut params,
+    } = build_filter_where_clause(
+      metadata,
+      Some(filter_params),
+      filter.as_deref(),
+      geo.as_ref(),
+    )
+    .map_err(|_err| RecordError::BadRequest("Invalid filter params"))?;
+
+    // User properties
+    let (user_sub_select, mut user_params) = build_user_sub_select(user.as_ref());
+    params.append(&mut user_params);
+
+    // NOTE: We're using the list access rule, which falls back to the read access rule, to filter
+    // the rows as opposed to yes/no early access blocking as for read-record.
+    if let Some(list_access) = api.access_rule(Permission::List) {
+      clause = format!("({clause}) AND {list_access}");
+    }
+
+    // Soft-deleted records are hidden.
+    if let Some(column) = api.soft_delete_column() {
+      clause = format!(r#"({clause}) AND _ROW_."{column}" IS NULL"#);
+    }
+
+    // Rows may carry additional hidden columns, i.e. ones starting with "_", which aren't part of
+    // the returned records.
+    let table_name = api.table_name();
+    let mut row_columns = vec!["_T_.*".to_string()];
+    let mut row_join = String::new();
+    let mut row_filter = "TRUE".to_string();
+
+    // Full-text search joins the FTS5 table and exposes the match's rank as hidden `_rank` column.
+    let search_query = search.as_deref().and_then(build_search_query);
+    if let Some(ref search_query) = search_query {
+      let Some(search_table) = api
+        .search_table()
+        .filter(|_| api.search_columns().iter().all(|col| readable(col)))
+      else {
+        return Err(RecordError::BadRequest("Search not supported"));
+      };
+      params.push((
+        ":__search".to_string(),
+        libsql::Value::Text(search_query.clone()),
+      ));
+
+      row_columns.push(format!(r#""{search_table}".rank AS _rank"#));
+      row_join = format!(r#"JOIN "{search_table}" ON "{search_table}".rowid = _T_.rowid"#);
+      row_filter = format!(r#""{search_table}" MATCH :__search"#);
+    }
+
+    // Geo filters look up rows by rowid in the R*-tree index. Near queries additionally expose the
+    // distance in meters as hidden `_distance` column.
+    if let Some(ref geo) = geo {
+      row_columns.push("_T_.rowid AS _rowid".to_string());
 
-  return Ok(Json(serde_json::Value::Array(
-    rows_to_json(metadata, rows, |col_name| !col_name.starts_with("_"))
+      if let Some(ref near) = geo.near {
+        params.push((":__geo_lat".to_string(), libsql::Value::Real(near.lat)));
+        params.push((":__geo_lng".to_string(), libsql::Value::Real(near.lng)));
+
+        let lat = &geo.index.latitude_column;
+        let lng = &geo.index.longitude_column;
+        row_columns.push(format!(
+          r#"geo_distance(_T_."{lat}", _T_."{lng}", :__geo_lat, :__geo_lng) AS {DISTANCE_COLUMN}"#
+        ));
+      }
+    }
+    let near = geo.as_ref().is_some_and(|geo| geo.near.is_some());
+
+    let row_source = format!(
+      "SELECT {columns} FROM '{table_name}' AS _T_ {row_join} WHERE {row_filter}",
+      columns = row_columns.join(", ")
+    );
+
+    let pk_column = &api.record_pk_column().name;
+    let keyset = match order {
+      Some(order) => {
+        let computed_columns: &[&str] = match near {
+          true => &[DISTANCE_COLUMN],
+          false => &[],
+        };
+        if order
+          .iter()
+          .any(|(col, _)| !computed_columns.contains(&col.as_str()) && !readable(col))
+        {
+          return Err(RecordError::BadRequest("Invalid order"));
+        }
+        build_keyset(metadata, order, Some(pk_column), computed_columns)
+          .map_err(|_err| RecordError::BadRequest("Invalid order"))?
+      }
+      // Search results are ordered by relevance, i.e. best matches first, by default.
+      None if search_query.is_some() => vec![
+        ("_rank".to_string(), Order::Ascending),
+        (pk_column.clone(), Order::Descending),
+      ],
+      // Near results are ordered by distance, i.e. closest first, by default.
+      None if near => vec![
+        (DISTANCE_COLUMN.to_string(), Order::Ascending),
+        (pk_column.clone(), Order::Descending),
+      ],
+      None => vec![(pk_column.clone(), Order::Descending)],
+    };
+
+    // NOTE: The key set columns are always selected to construct the next cursor.
+    let mut select_columns = match fields {
+      Some(ref fields) => {
+        let mut columns: Vec<String> = fields
+          .iter()
+          .filter(|col| metadata.column_by_name(col).is_some())
+          .cloned()
+          .collect();
+        for (col, _) in &keyset {
+          if !columns.contains(col) {
+            columns.push(col.clone());
+          }
+        }
+        columns
+          .iter()
+          .map(|col| format!(r#"_ROW_."{col}""#))
+          .collect::<Vec<_>>()
+          .join(", ")
+      }
+      None => "_ROW_.*".to_string(),
+    };
+
+    // Computed fields are selected last, i.e. they're the trailing columns of every row.
+    let computed_field_selects = build_computed_field_selects(&api);
+    if !computed_field_selects.is_empty() {
+      select_columns = format!("{select_columns}, {}", computed_field_selects.join(", "));
+    }
+
+    return Ok(ListQuery {
+      api,
+      user,
+      fields,
+      expand,
+      keyset,
+      select_columns,
+      user_sub_select,
+      row_source,
+      clause,
+      params,
+    });
+  }
+
+  /// Names of the fields of the returned records in order, i.e. the readable columns followed by
+  /// the readable computed fields, restricted to the requested fields if any.
+  pub(crate) fn field_names(&self) -> Vec<String> {
+    let requested = |name: &str| {
+      self
+        .fields
+        .as_ref()
+        .map_or(true, |fields| fields.iter().any(|f| f == name))
+    };
+
+    let columns = self.api.metadata().columns().unwrap_or_default();
+    return columns
+      .iter()
+      .map(|col| col.name.as_str())
+      .chain(self.api.computed_fields().iter().map(|f| f.name()))
+      .filter(|name| self.api.column_readable(name, self.user.as_ref()) && requested(name))
+      .map(|name| name.to_string())
+      .collect();
+  }
+
+  /// Total number of matching records.
+  pub(crate) async fn count(&self, state: &AppState) -> Result<i64, RecordError> {
+    let row = query_one_row(
+      state.conn(),
+      &format!(
+        r#"
+          SELECT COUNT(*)
+          FROM
+            ({user_sub_select}) AS _USER_,
+            ({row_source}) as _ROW_
+          WHERE
+            {clause}
+        "#,
+        user_sub_select = self.user_sub_select,
+        row_source = self.row_source,
+        clause = self.clause,
+      ),
+      libsql::params::Params::Named(self.params.clone()),
+    )
+    .await
+    .map_err(map_geo_error)?;
+    return Ok(row.get::<i64>(0)?);
+  }
+
+  /// Fetches up to `limit` records following the given cursor, if any. Also returns the last row,
+  /// from which the cursor for the next page can be derived.
+  pub(crate) async fn fetch(
+    &self,
+    state: &AppState,
+    cursor: Option<&Cursor>,
+    limit: usize,
+  ) -> Result<(Vec<serde_json::Value>, Option<libsql::Row>), RecordError> {
+    let mut clause = self.clause.clone();
+    let mut params = self.params.clone();
+
+    if let Some(cursor) = cursor {
+      let WhereClause {
+        clause: cursor_clause,
+        params: mut cursor_params,
+      } = build_cursor_where_clause(&self.keyset, cursor)
+        .map_err(|_err| RecordError::BadRequest("Invalid cursor"))?;
+
+      params.append(&mut cursor_params);
+      clause = format!("{clause} AND {cursor_clause}");
+    }
+    params.push((":limit".to_string(), libsql::Value::Integer(limit as i64)));
+
+    let query = format!(
+      r#"
+        SELECT {select_columns}
+        FROM
+          ({user_sub_select}) AS _USER_,
+          ({row_source}) as _ROW_
+        WHERE
+          {clause}
+        ORDER BY
+          {order_clause}
+        LIMIT :limit
+      "#,
+      select_columns = self.select_columns,
+      user_sub_select = self.user_sub_select,
+      row_source = self.row_source,
+      order_clause = build_order_clause(&self.keyset),
+    );
+
+    let mut rows = state
+      .conn()
+      .query(&query, libsql::params::Params::Named(params))
       .await
-      .map_err(|err| RecordError::Internal(err.into()))?,
-  )));
+      .map_err(map_geo_error)?;
+
+    let api = &self.api;
+    let user = self.user.as_ref();
+    let readable = |col: &str| api.column_readable(col, user);
+    let is_computed = |col: &str| api.computed_fields().iter().any(|f| f.name() == col);
+
+    let mut records: Vec<serde_json::Value> = vec![];
+    let mut last_row: Option<libsql::Row> = None;
+    while let Some(row) = rows.next().await.map_err(map_geo_error)? {
+      let mut record = row_to_json(api.metadata(), &row, |col| {
+        readable(col) && !is_computed(col)
+      })
+      .map_err(|err| RecordError::Internal(err.into()))?;
+      append_computed_fields(api, &row, &mut record, user)?;
+      if let (Some(fields), serde_json::Value::Object(map)) = (&self.fields, &mut record) {
+        map.retain(|col, _| fields.contains(col));
+      }
+
+      records.push(record);
+      last_row = Some(row);
+    }
+
+    if let Some(ref tree) = self.expand {
+      for record in &mut records {
+        expand_record(state, api, record, tree, user).await?;
+      }
+    }
+
+    return Ok((records, last_row));
+  }
 }
 
 #[cfg(test)]
 mod tests {
+  use axum::extract::Query;
+  use axum::http::HeaderMap;
+
   use super::*;
   use crate::admin::user::*;
   use crate::app_state::*;
   use crate::auth::api::login::login_with_password;
   use crate::auth::user::User;
   use crate::config::proto::PermissionFlag;
+  use crate::records::read_record::{read_record_handler, ReadRecordQuery};
+  use crate::records::search::search_table_name;
   use crate::records::test_utils::*;
   use crate::records::Acls;
   use crate::records::{add_record_api, AccessRules, RecordError};
+  use crate::test::unpack_json_response;
   use crate::util::id_to_b64;
 
   fn is_auth_err(error: &RecordError) -> bool {
@@ -149,7 +492,11 @@ mod tests {
       "messages_api",
       "message",
       Acls {
-        authenticated: vec![PermissionFlag::Create, PermissionFlag::Read],
+        authenticated: vec![
+          PermissionFlag::Create,
+          PermissionFlag::Read,
+          PermissionFlag::List,
+        ],
         ..Default::default()
       },
       AccessRules {
@@ -231,6 +578,48 @@ mod tests {
       assert_eq!(arr_asc, arr_desc.into_iter().rev().collect::<Vec<_>>());
     }
 
+    {
+      // Opt-in envelope with total count and cursor.
+      let response = list_records_envelope(
+        &state,
+        Some(&user_y_token.auth_token),
+        Some("count=true&limit=2".to_string()),
+      )
+      .await?;
+      assert_eq!(response.total_count, Some(3));
+      assert_eq!(response.records.len(), 2);
+      let cursor = response.cursor.unwrap();
+
+      let response = list_records_envelope(
+        &state,
+        Some(&user_y_token.auth_token),
+        Some(format!("count=true&limit=2&cursor={cursor}")),
+      )
+      .await?;
+      // Total count is independent of the cursor.
+      assert_eq!(response.total_count, Some(3));
+      assert_eq!(response.records.len(), 1);
+      assert_eq!(response.cursor, None);
+
+      // The count respects the read access rule.
+      let response = list_records_envelope(
+        &state,
+        Some(&user_x_token.auth_token),
+        Some("envelope=true&count=true".to_string()),
+      )
+      .await?;
+      assert_eq!(response.total_count, Some(2));
+
+      let response = list_records_envelope(
+        &state,
+        Some(&user_x_token.auth_token),
+        Some("envelope=true".to_string()),
+      )
+      .await?;
+      assert_eq!(response.total_count, None);
+      assert_eq!(response.records.len(), 2);
+    }
+
     {
       // Filter by room
       let arr0 = list_records(
@@ -250,6 +639,525 @@ mod tests {
       assert_eq!(arr1.len(), 1);
     }
 
+    {
+      // Projection.
+      let arr = list_records(
+        &state,
+        Some(&user_y_token.auth_token),
+        Some("fields=data&order=-data".to_string()),
+      )
+      .await?;
+      assert_eq!(
+        arr,
+        vec![
+          serde_json::json!({"data": "user_y to room1"}),
+          serde_json::json!({"data": "user_y to room0"}),
+          serde_json::json!({"data": "user_x to room0"}),
+        ]
+      );
+
+      // Paging works independent of the projection.
+      let response = list_records_envelope(
+        &state,
+        Some(&user_y_token.auth_token),
+        Some("fields=data&limit=2".to_string()),
+      )
+      .await?;
+      assert!(response.cursor.is_some());
+
+      assert!(list_records(
+        &state,
+        Some(&user_y_token.auth_token),
+        Some("fields=_owner".to_string()),
+      )
+      .await
+      .is_err());
+    }
+
+    {
+      // Composite filter.
+      let filter = "data = 'user_y to room1' OR (data LIKE 'user_x%' AND data IS NOT NULL)";
+      let arr = list_records(
+        &state,
+        Some(&user_y_token.auth_token),
+        Some(format!(
+          "filter={}",
+          form_urlencoded::byte_serialize(filter.as_bytes()).collect::<String>()
+        )),
+      )
+      .await?;
+      assert_eq!(arr.len(), 2);
+
+      let arr = list_records(
+        &state,
+        Some(&user_y_token.auth_token),
+        Some(format!(
+          "room[in]={},{}",
+          id_to_b64(&room0),
+          id_to_b64(&room1)
+        )),
+      )
+      .await?;
+      assert_eq!(arr.len(), 3);
+
+      // Invalid filters are rejected rather than ignored.
+      assert!(list_records(
+        &state,
+        Some(&user_y_token.auth_token),
+        Some("filter=_owner%20IS%20NULL".to_string()),
+      )
+      .await
+      .is_err());
+    }
+
+    return Ok(());
+  }
+
+  #[tokio::test]
+  async fn test_record_api_list_keyset_pagination() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    state
+      .conn()
+      .execute_batch(
+        r#"
+          CREATE TABLE item (
+            pk        INTEGER PRIMARY KEY,
+            rank      INTEGER,
+            name      TEXT NOT NULL
+          ) STRICT;
+
+          INSERT INTO item (rank, name) VALUES
+            (1, 'a'), (NULL, 'b'), (2, 'c'), (1, 'd'), (NULL, 'e'), (2, 'f'), (3, 'g');
+        "#,
+      )
+      .await?;
+    state.table_metadata().invalidate_all().await?;
+
+    add_record_api(
+      &state,
+      "items_api",
+      "item",
+      Acls {
+        world: vec![PermissionFlag::Read, PermissionFlag::List],
+        ..Default::default()
+      },
+      AccessRules::default(),
+    )
+    .await?;
+
+    let list = |query: String| {
+      let state = state.clone();
+      async move {
+        let Json(value) = list_records_handler(
+          State(state),
+          Path("items_api".to_string()),
+          RawQuery(Some(query)),
+          None,
+        )
+        .await?;
+        return Ok::<ListResponse, anyhow::Error>(serde_json::from_value(value)?);
+      }
+    };
+    let names = |records: Vec<serde_json::Value>| -> Vec<String> {
+      return records
+        .iter()
+        .map(|r| r["name"].as_str().unwrap().to_string())
+        .collect();
+    };
+
+    for order in ["-pk", "pk", "rank,-name", "-rank,pk", "-rank", "name"] {
+      let all = names(
+        list(format!("envelope=true&limit=100&order={order}"))
+          .await?
+          .records,
+      );
+      assert_eq!(all.len(), 7);
+
+      let mut paged: Vec<String> = vec![];
+      let mut cursor: Option<String> = None;
+      loop {
+        let mut query = format!("envelope=true&limit=2&order={order}");
+        if let Some(cursor) = cursor {
+          query = format!("{query}&cursor={cursor}");
+        }
+
+        let response = list(query).await?;
+        paged.extend(names(response.records));
+
+        cursor = response.cursor;
+        if cursor.is_none() {
+          break;
+        }
+      }
+
+      assert_eq!(all, paged, "order={order}");
+    }
+
+    assert!(list("cursor=invalid".to_string()).await.is_err());
+    assert!(list("order=unknown".to_string()).await.is_err());
+
+    return Ok(());
+  }
+
+  #[tokio::test]
+  async fn test_record_api_list_search() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    state
+      .conn()
+      .execute_batch(
+        r#"
+          CREATE TABLE article (
+            id        INTEGER PRIMARY KEY,
+            title     TEXT NOT NULL,
+            body      TEXT NOT NULL,
+            public    INTEGER NOT NULL
+          ) STRICT;
+
+          INSERT INTO article (title, body, public) VALUES
+            ('Rust', 'Rust is a systems programming language', 1),
+            ('SQLite', 'SQLite is an embedded database written in C', 1),
+            ('Secret', 'A private note about rust', 0);
+        "#,
+      )
+      .await?;
+    state.table_metadata().invalidate_all().await?;
+
+    add_record_api(
+      &state,
+      "articles_api",
+      "article",
+      Acls {
+        world: vec![PermissionFlag::Read, PermissionFlag::List],
+        ..Default::default()
+      },
+      AccessRules {
+        read: Some("_ROW_.public = 1".to_string()),
+        ..Default::default()
+      },
+    )
+    .await?;
+
+    let list = |query: &str| {
+      let state = state.clone();
+      let query = query.to_string();
+      async move {
+        let Json(value) = list_records_handler(
+          State(state),
+          Path("articles_api".to_string()),
+          RawQuery(Some(query)),
+          None,
+        )
+        .await?;
+        return Ok::<Vec<String>, anyhow::Error>(
+          value
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["title"].as_str().unwrap().to_string())
+            .collect(),
+        );
+      }
+    };
+
+    // Search needs to be enabled first.
+    assert!(list("q=rust").await.is_err());
+
+    let mut config = state.get_config();
+    for api in &mut config.record_apis {
+      if api.name.as_deref() == Some("articles_api") {
+        api.search_columns = vec!["title".to_string(), "body".to_string()];
+      }
+    }
+
+    // Rejected config updates don't create search tables.
+    assert!(state
+      .validate_and_update_config(config.clone(), Some(config.hash().wrapping_add(1)))
+      .await
+      .is_err());
+    let search_tables = query_one_row(
+      state.conn(),
+      "SELECT COUNT(*) FROM sqlite_schema WHERE name = $1",
+      [search_table_name("articles_api")],
+    )
+    .await?
+    .get::<i64>(0)?;
+    assert_eq!(search_tables, 0);
+
+    state.validate_and_update_config(config, None).await?;
+
+    // Pre-existing records are indexed and results are filtered by the read access rule.
+    assert_eq!(list("q=rust").await?, vec!["Rust"]);
+    assert_eq!(list("q=database").await?, vec!["SQLite"]);
+    assert_eq!(list("q=embedded%20database").await?, vec!["SQLite"]);
+    assert!(list("q=embedded%20rust").await?.is_empty());
+
+    // Triggers keep the index in sync.
+    state
+      .conn()
+      .execute_batch(
+        r#"
+          INSERT INTO article (title, body, public) VALUES ('Rusty', 'Rust Rust Rust', 1);
+          UPDATE article SET body = 'An embedded database' WHERE title = 'Rust';
+          DELETE FROM article WHERE title = 'SQLite';
+        "#,
+      )
+      .await?;
+
+    assert_eq!(list("q=rust").await?, vec!["Rusty", "Rust"]);
+    assert_eq!(list("q=database").await?, vec!["Rust"]);
+    assert_eq!(list("q=rust&order=id").await?, vec!["Rust", "Rusty"]);
+    assert_eq!(list("q=rust&limit=1").await?, vec!["Rusty"]);
+
+    // FTS5 query syntax is matched literally rather than being interpreted.
+    assert!(list("q=rust%20OR%20sqlite").await?.is_empty());
+    assert!(list("q=%22unbalanced").await?.is_empty());
+
+    return Ok(());
+  }
+
+  #[tokio::test]
+  async fn test_record_api_list_geo() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    state
+      .conn()
+      .execute_batch(
+        r#"
+          CREATE TABLE store (
+            id        INTEGER PRIMARY KEY,
+            name      TEXT NOT NULL,
+            lat       REAL,
+            lng       REAL
+          ) STRICT;
+
+          INSERT INTO store (name, lat, lng) VALUES
+            ('Alexanderplatz', 52.5219, 13.4132),
+            ('Brandenburger Tor', 52.5163, 13.3777),
+            ('Potsdam', 52.3906, 13.0645),
+            ('Paris', 48.8566, 2.3522),
+            ('Nowhere', NULL, NULL);
+        "#,
+      )
+      .await?;
+    state.table_metadata().invalidate_all().await?;
+
+    add_record_api(
+      &state,
+      "stores_api",
+      "store",
+      Acls {
+        world: vec![PermissionFlag::Read, PermissionFlag::List],
+        ..Default::default()
+      },
+      AccessRules::default(),
+    )
+    .await?;
+
+    let list = |query: String| {
+      let state = state.clone();
+      async move {
+        let Json(value) = list_records_handler(
+          State(state),
+          Path("stores_api".to_string()),
+          RawQuery(Some(query)),
+          None,
+        )
+        .await?;
+        return Ok::<ListResponse, anyhow::Error>(serde_json::from_value(value)?);
+      }
+    };
+    let names = |query: &str| {
+      let query = format!("envelope=true&{query}");
+      async move {
+        return Ok::<Vec<String>, anyhow::Error>(
+          list(query)
+            .await?
+            .records
+            .iter()
+            .map(|r| r["name"].as_str().unwrap().to_string())
+            .collect(),
+        );
+      }
+    };
+
+    // Geo columns need to be configured first.
+    assert!(names("near=52.52,13.405,5000").await.is_err());
+
+    let mut config = state.get_config();
+    for api in &mut config.record_apis {
+      if api.name.as_deref() == Some("stores_api") {
+        api.geo_latitude_column = Some("lat".to_string());
+        api.geo_longitude_column = Some("lng".to_string());
+      }
+    }
+    state.validate_and_update_config(config, None).await?;
+
+    // Triggers keep the index in sync.
+    state
+      .conn()
+      .execute_batch(
+        r#"
+          INSERT INTO store (name, lat, lng) VALUES ('Fernsehturm', 52.5208, 13.4094);
+          UPDATE store SET lat = 52.5219, lng = 13.4132 WHERE name = 'Nowhere';
+          DELETE FROM store WHERE name = 'Alexanderplatz';
+        "#,
+      )
+      .await?;
+
+    // Sorted by distance by default.
+    assert_eq!(
+      names("near=52.52,13.405,5000").await?,
+      vec!["Fernsehturm", "Nowhere", "Brandenburger Tor"]
+    );
+    assert_eq!(
+      names("near=52.52,13.405,30000&order=-_distance").await?,
+      vec!["Potsdam", "Brandenburger Tor", "Nowhere", "Fernsehturm"]
+    );
+    assert_eq!(
+      names("near=52.52,13.405,30000&name[ne]=Potsdam&order=name").await?,
+      vec!["Brandenburger Tor", "Fernsehturm", "Nowhere"]
+    );
+    assert_eq!(
+      names("bbox=52,13,53,14").await?,
+      vec!["Fernsehturm", "Nowhere", "Potsdam", "Brandenburger Tor"]
+    );
+    assert!(names("bbox=52,170,53,-170").await?.is_empty());
+
+    // Distances aren't exposed but one can page through results ordered by distance.
+    let mut paged: Vec<String> = vec![];
+    let mut cursor: Option<String> = None;
+    loop {
+      let mut query = "envelope=true&limit=1&near=52.52,13.405,1000000".to_string();
+      if let Some(cursor) = cursor {
+        query = format!("{query}&cursor={cursor}");
+      }
+
+      let response = list(query).await?;
+      for record in &response.records {
+        assert!(record.get("_distance").is_none(), "{record}");
+        paged.push(record["name"].as_str().unwrap().to_string());
+      }
+
+      cursor = response.cursor;
+      if cursor.is_none() {
+        break;
+      }
+    }
+    assert_eq!(
+      paged,
+      vec![
+        "Fernsehturm",
+        "Nowhere",
+        "Brandenburger Tor",
+        "Potsdam",
+        "Paris"
+      ]
+    );
+
+    assert!(names("near=52.52,13.405").await.is_err());
+    assert!(names("bbox=53,13,52,14").await.is_err());
+    assert!(names("order=_distance").await.is_err());
+
+    // Non-numeric coordinates are client errors.
+    let err = query_one_row(state.conn(), "SELECT geo_distance('a', 13, 48, 2)", ())
+      .await
+      .unwrap_err();
+    assert!(matches!(
+      map_geo_error(err),
+      RecordError::BadRequest("Non-numeric geo coordinates")
+    ));
+
+    return Ok(());
+  }
+
+  #[tokio::test]
+  async fn test_record_api_list_access_rule() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    let conn = state.conn();
+
+    create_chat_message_app_tables(&state).await?;
+    let room = add_room(conn, "room0").await?;
+    let password = "Secret!1!!";
+
+    // Members can read all messages in their rooms but only list their own.
+    add_record_api(
+      &state,
+      "messages_api",
+      "message",
+      Acls {
+        authenticated: vec![PermissionFlag::Read, PermissionFlag::List],
+        ..Default::default()
+      },
+      AccessRules {
+        read: Some(
+          "EXISTS(SELECT 1 FROM room_members WHERE room = _ROW_.room AND user = _USER_.id)"
+            .to_string(),
+        ),
+        list: Some("_ROW_._owner = _USER_.id".to_string()),
+        ..Default::default()
+      },
+    )
+    .await?;
+
+    // Without the LIST permission records can be read but not listed.
+    add_record_api(
+      &state,
+      "messages_read_only_api",
+      "message",
+      Acls {
+        authenticated: vec![PermissionFlag::Read],
+        ..Default::default()
+      },
+      AccessRules::default(),
+    )
+    .await?;
+
+    let user_x_email = "user_x@test.com";
+    let user_x = create_user_for_test(&state, user_x_email, password)
+      .await?
+      .into_bytes();
+    add_user_to_room(conn, user_x, room).await?;
+    let user_x_token = login_with_password(&state, user_x_email, password).await?;
+
+    let user_y_email = "user_y@test.com";
+    let user_y = create_user_for_test(&state, user_y_email, password)
+      .await?
+      .into_bytes();
+    add_user_to_room(conn, user_y, room).await?;
+
+    send_message(conn, user_x, room, "user_x to room0").await?;
+    let message_y = send_message(conn, user_y, room, "user_y to room0").await?;
+
+    let user = User::from_auth_token(&state, &user_x_token.auth_token);
+
+    let value: serde_json::Value = unpack_json_response(
+      read_record_handler(
+        State(state.clone()),
+        Path(("messages_api".to_string(), id_to_b64(&message_y))),
+        Query(ReadRecordQuery::default()),
+        user.clone(),
+        HeaderMap::new(),
+      )
+      .await?,
+    )
+    .await?;
+    assert_eq!(value["data"], "user_y to room0");
+
+    let arr = list_records(&state, Some(&user_x_token.auth_token), None).await?;
+    assert_eq!(arr.len(), 1);
+    assert_eq!(arr[0]["data"], "user_x to room0");
+
+    let response = list_records_handler(
+      State(state.clone()),
+      Path("messages_read_only_api".to_string()),
+      RawQuery(None),
+      user,
+    )
+    .await;
+    assert!(
+      is_auth_err(response.as_ref().err().unwrap()),
+      "{response:?}"
+    );
+
     return Ok(());
   }
 
@@ -272,4 +1180,21 @@ mod tests {
     }
     return Err(RecordError::BadRequest("Not a json array"));
   }
+
+  async fn list_records_envelope(
+    state: &AppState,
+    auth_token: Option<&str>,
+    query: Option<String>,
+  ) -> Result<ListResponse, RecordError> {
+    let response = list_records_handler(
+      State(state.clone()),
+      Path("messages_api".to_string()),
+      RawQuery(query),
+      auth_token.and_then(|token| User::from_auth_token(&state, token)),
+    )
+    .await?;
+
+    return serde_json::from_value(response.0)
+      .map_err(|_err| RecordError::BadRequest("Not a list response"));
+  }
 }
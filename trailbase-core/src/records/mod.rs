@@ -1,22 +1,38 @@
 use axum::{
-  routing::{delete, get, patch, post},
+  routing::{delete, get, patch, post, put},
   Router,
 };
 use utoipa::OpenApi;
 
+mod aggregate;
+mod computed;
 pub(crate) mod create_record;
 pub(crate) mod delete_record;
 mod error;
+mod etag;
+mod expand;
+mod export;
 pub(crate) mod files;
+pub(crate) mod geo;
+mod history;
+mod hooks;
 mod json_schema;
 pub mod json_to_sql;
 mod list_records;
 pub(crate) mod read_record;
 mod record_api;
+pub(crate) mod search;
+mod signed_files;
+pub(crate) mod soft_delete;
 pub mod sql_to_json;
+pub(crate) mod subscribe;
 pub mod test_utils;
+pub(crate) mod thumbnail;
+mod transaction;
 mod update_record;
+mod upsert_record;
 mod validate;
+pub(crate) mod webhooks;
 
 pub(crate) use error::RecordError;
 pub use record_api::RecordApi;
@@ -32,16 +48,38 @@ use crate::AppState;
     read_record::read_record_handler,
     read_record::get_uploaded_file_from_record_handler,
     read_record::get_uploaded_files_from_record_handler,
+    signed_files::sign_uploaded_file_url_handler,
+    signed_files::sign_uploaded_files_url_handler,
     list_records::list_records_handler,
+    aggregate::aggregate_records_handler,
     create_record::create_record_handler,
     update_record::update_record_handler,
+    upsert_record::upsert_record_handler,
     delete_record::delete_record_handler,
+    soft_delete::restore_record_handler,
+    history::record_history_handler,
     json_schema::json_schema_handler,
+    subscribe::add_subscription_sse_handler,
   ),
-  components(schemas(create_record::CreateRecordResponse))
+  components(schemas(
+    create_record::CreateRecordResponse,
+    history::RecordHistoryEntry,
+    signed_files::SignedFileUrlResponse
+  ))
 )]
 pub(super) struct RecordOpenApi;
 
+#[derive(OpenApi)]
+#[openapi(
+  paths(transaction::record_transactions_handler),
+  components(schemas(
+    transaction::Operation,
+    transaction::TransactionRequest,
+    transaction::TransactionResponse
+  ))
+)]
+pub(super) struct TransactionOpenApi;
+
 pub(crate) fn router() -> Router<AppState> {
   return Router::new()
     .route("/:name/:record", get(read_record::read_record_handler))
@@ -54,7 +92,16 @@ pub(crate) fn router() -> Router<AppState> {
       "/:name/:record",
       delete(delete_record::delete_record_handler),
     )
-    .route("/:name", get(list_records::list_records_handler))
+    .route("/:name/:record", put(upsert_record::upsert_record_handler))
+    .route(
+      "/:name/:record/restore",
+      post(soft_delete::restore_record_handler),
+    )
+    .route(
+      "/:name/:record/history",
+      get(history::record_history_handler),
+    )
+    .route("/:name", get(export::list_or_export_records_handler))
     .route(
       "/:name/:record/file/:column_name",
       get(read_record::get_uploaded_file_from_record_handler),
@@ -63,7 +110,27 @@ pub(crate) fn router() -> Router<AppState> {
       "/:name/:record/files/:column_name/:file_index",
       get(read_record::get_uploaded_files_from_record_handler),
     )
-    .route("/:name/schema", get(json_schema::json_schema_handler));
+    .route(
+      "/:name/:record/file/:column_name/sign",
+      post(signed_files::sign_uploaded_file_url_handler),
+    )
+    .route(
+      "/:name/:record/files/:column_name/:file_index/sign",
+      post(signed_files::sign_uploaded_files_url_handler),
+    )
+    .route("/:name/schema", get(json_schema::json_schema_handler))
+    .route(
+      "/:name/aggregate",
+      get(aggregate::aggregate_records_handler),
+    )
+    .route(
+      "/:name/subscribe/:record",
+      get(subscribe::add_subscription_sse_handler),
+    );
+}
+
+pub(crate) fn transaction_router() -> Router<AppState> {
+  return Router::new().route("/execute", post(transaction::record_transactions_handler));
 }
 
 // Since this is for APIs access control, we'll use the API- space CRUD terminology instead of
@@ -71,12 +138,12 @@ pub(crate) fn router() -> Router<AppState> {
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Permission {
-  // TODO: Should there be a separate "list records" permission or is "read" enough?
   Create = 1,  // ~ DB insert
   Read = 2,    // ~ DB select
   Update = 4,  // ~ DB update
   Delete = 8,  // ~ DB delete
   Schema = 16, // Lookup json schema for the given record api .
+  List = 32,   // ~ DB select of multiple records.
 }
 
 #[derive(Default)]
@@ -92,6 +159,7 @@ pub struct AccessRules {
   pub update: Option<String>,
   pub delete: Option<String>,
   pub schema: Option<String>,
+  pub list: Option<String>,
 }
 
 // NOTE: used in integration test.
@@ -117,6 +185,18 @@ pub async fn add_record_api(
     update_access_rule: access_rules.update,
     delete_access_rule: access_rules.delete,
     schema_access_rule: access_rules.schema,
+    list_access_rule: access_rules.list,
+    etag_column: None,
+    search_columns: vec![],
+    geo_latitude_column: None,
+    geo_longitude_column: None,
+    soft_delete_column: None,
+    soft_delete_retention_sec: None,
+    enable_history: None,
+    column_acl_world: None,
+    column_acl_authenticated: None,
+    computed_fields: vec![],
+    file_upload_limits: vec![],
   });
 
   return state.validate_and_update_config(config, None).await;
@@ -1,54 +1,135 @@
 use axum::{
-  extract::{Path, State},
-  response::Response,
+  extract::{Path, Query, State},
+  http::{header, HeaderMap, StatusCode},
+  response::{IntoResponse, Response},
   Json,
 };
+use serde::Deserialize;
+use utoipa::IntoParams;
 
 use crate::app_state::AppState;
 use crate::auth::user::User;
-use crate::records::files::read_file_into_response;
+use crate::records::computed::{append_computed_fields, query_computed_fields};
+use crate::records::etag::{if_none_match, record_etag};
+use crate::records::expand::{expand_record, ExpandTree};
+use crate::records::files::{read_file_into_response, FileQuery};
 use crate::records::json_to_sql::{GetFileQueryBuilder, GetFilesQueryBuilder, SelectQueryBuilder};
+use crate::records::signed_files::{is_signed, verify_file_signature, FileRoute};
 use crate::records::sql_to_json::row_to_json;
+use crate::records::thumbnail::{read_thumbnail_into_response, ThumbnailQuery};
 use crate::records::{Permission, RecordError};
 
+#[derive(Clone, Debug, Default, Deserialize, IntoParams)]
+pub struct ReadRecordQuery {
+  /// Comma-separated list of foreign key columns to expand into the referenced records, e.g.
+  /// "author,comments.author".
+  pub expand: Option<String>,
+
+  /// Comma-separated list of columns to return, e.g. "id,title". Defaults to all columns.
+  pub fields: Option<String>,
+}
+
 /// Read record.
+///
+/// Responds with the record's ETag and honors "If-None-Match", i.e. responds with 304 if the
+/// client's copy is still fresh.
 #[utoipa::path(
   get,
   path = "/:name/:record",
+  params(ReadRecordQuery),
   responses(
-    (status = 200, description = "Record contents.", body = serde_json::Value)
+    (status = 200, description = "Record contents.", body = serde_json::Value),
+    (status = 304, description = "Record not modified.")
   )
 )]
 pub async fn read_record_handler(
   State(state): State<AppState>,
   Path((api_name, record)): Path<(String, String)>,
+  Query(query): Query<ReadRecordQuery>,
   user: Option<User>,
-) -> Result<Json<serde_json::Value>, RecordError> {
+  headers: HeaderMap,
+) -> Result<Response, RecordError> {
   let Some(api) = state.lookup_record_api(&api_name) else {
     return Err(RecordError::ApiNotFound);
   };
 
   let record_id = api.id_to_sql(&record)?;
+  let fields = query
+    .fields
+    .as_deref()
+    .map(|f| api.parse_fields(f, user.as_ref()))
+    .transpose()?;
 
   api
     .check_record_level_access(Permission::Read, Some(&record_id), None, user.as_ref())
     .await?;
+  api.check_not_soft_deleted(&record_id).await?;
+
+  let pk_column = &api.record_pk_column().name;
+  let etag_column = api.etag_column();
+  let row = match fields {
+    Some(ref fields) => {
+      // Only fetch projected columns, the primary key in case only computed fields were
+      // requested, and the ETag column, if any.
+      let mut columns: Vec<String> = fields
+        .iter()
+        .filter(|f| api.metadata().column_by_name(f).is_some())
+        .cloned()
+        .collect();
+      for column in std::iter::once(pk_column.as_str()).chain(etag_column) {
+        if !columns.iter().any(|c| c == column) {
+          columns.push(column.to_string());
+        }
+      }
 
-  let Some(row) = SelectQueryBuilder::run(
-    &state,
-    api.table_name(),
-    &api.record_pk_column().name,
-    record_id,
-  )
-  .await?
-  else {
+      SelectQueryBuilder::run_projected(
+        &state,
+        api.table_name(),
+        &columns,
+        pk_column,
+        record_id.clone(),
+      )
+      .await?
+    }
+    None => SelectQueryBuilder::run(&state, api.table_name(), pk_column, record_id.clone()).await?,
+  };
+  let Some(row) = row else {
     return Err(RecordError::RecordNotFound);
   };
 
-  return Ok(Json(
-    row_to_json(api.metadata(), row, |col_name| !col_name.starts_with("_"))
-      .map_err(|err| RecordError::Internal(err.into()))?,
-  ));
+  // NOTE: The ETag only reflects the record itself and neither computed fields nor expanded
+  // foreign records, which may have changed independently. Without a dedicated ETag column,
+  // projections yield a weak ETag over the projected columns, which is good for caching but won't
+  // satisfy "If-Match" preconditions.
+  let etag = match (&fields, etag_column) {
+    (Some(_), None) => format!("W/{}", record_etag(&state, &row, None)?),
+    _ => record_etag(&state, &row, etag_column)?,
+  };
+  if query.expand.is_none() && api.computed_fields().is_empty() && if_none_match(&headers, &etag) {
+    return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+  }
+
+  let mut record = row_to_json(api.metadata(), &row, |col_name| {
+    api.column_readable(col_name, user.as_ref())
+  })
+  .map_err(|err| RecordError::Internal(err.into()))?;
+
+  if let Some(computed) =
+    query_computed_fields(state.conn(), &api, &record_id, user.as_ref()).await?
+  {
+    append_computed_fields(&api, &computed, &mut record, user.as_ref())?;
+  }
+
+  if let (Some(fields), serde_json::Value::Object(map)) = (fields, &mut record) {
+    map.retain(|col, _| fields.contains(col));
+  }
+
+  if let Some(ref expand) = query.expand {
+    let tree = ExpandTree::parse(expand)?;
+    expand_record(&state, &api, &mut record, &tree, user.as_ref()).await?;
+  }
+
+  return Ok(([(header::ETAG, etag)], Json(record)).into_response());
 }
 
 type GetUploadedFileFromRecordPath = Path<(
@@ -58,17 +139,27 @@ type GetUploadedFileFromRecordPath = Path<(
 )>;
 
 /// Read file associated with record.
+///
+/// Resized variants of images can be requested via `w`, `h`, `fit` and `format`. Supports byte
+/// "Range" requests as well as "If-None-Match" and "If-Modified-Since" conditional requests. Signed
+/// URLs, i.e. `expires` and `signature`, grant access in place of user auth.
 #[utoipa::path(
   get,
   path = "/:name/:record/file/:column_name",
+  params(ThumbnailQuery, FileQuery),
   responses(
-    (status = 200, description = "File contents.")
+    (status = 200, description = "File contents."),
+    (status = 206, description = "Requested range of the file contents."),
+    (status = 304, description = "File not modified."),
   )
 )]
 pub async fn get_uploaded_file_from_record_handler(
   state: State<AppState>,
   Path((api_name, record, column_name)): GetUploadedFileFromRecordPath,
+  Query(thumbnail_query): Query<ThumbnailQuery>,
+  Query(file_query): Query<FileQuery>,
   user: Option<User>,
+  headers: HeaderMap,
 ) -> Result<Response, RecordError> {
   let Some(api) = state.lookup_record_api(&api_name) else {
     return Err(RecordError::ApiNotFound);
@@ -76,12 +167,22 @@ pub async fn get_uploaded_file_from_record_handler(
 
   let record_id = api.id_to_sql(&record)?;
 
-  let Ok(()) = api
-    .check_record_level_access(Permission::Read, Some(&record_id), None, user.as_ref())
-    .await
-  else {
+  // Signed URLs grant access in place of user auth.
+  let signed = is_signed(&file_query)?;
+
+  if !signed {
+    let Ok(()) = api
+      .check_record_level_access(Permission::Read, Some(&record_id), None, user.as_ref())
+      .await
+    else {
+      return Err(RecordError::Forbidden);
+    };
+  }
+  api.check_not_soft_deleted(&record_id).await?;
+
+  if !signed && !api.column_readable(&column_name, user.as_ref()) {
     return Err(RecordError::Forbidden);
-  };
+  }
 
   let metadata = api.metadata();
   let Some(column) = metadata.column_by_name(&column_name) else {
@@ -98,9 +199,32 @@ pub async fn get_uploaded_file_from_record_handler(
   .await
   .map_err(|err| RecordError::Internal(err.into()))?;
 
-  return read_file_into_response(&state, file_upload)
-    .await
-    .map_err(|err| RecordError::Internal(err.into()));
+  if signed {
+    let route = FileRoute {
+      api_name: &api_name,
+      record: &record,
+      column_name: &column_name,
+      file_index: None,
+      thumbnail: &thumbnail_query,
+      disposition: file_query.disposition.unwrap_or_default(),
+    };
+    verify_file_signature(&state, &route, &file_upload, &file_query)?;
+  }
+
+  if let Some(response) =
+    read_thumbnail_into_response(&state, &file_upload, &thumbnail_query).await?
+  {
+    return Ok(response);
+  }
+
+  return read_file_into_response(
+    &state,
+    file_upload,
+    &headers,
+    file_query.disposition.unwrap_or_default(),
+  )
+  .await
+  .map_err(|err| RecordError::Internal(err.into()));
 }
 
 type GetUploadedFilesFromRecordPath = Path<(
@@ -111,17 +235,27 @@ type GetUploadedFilesFromRecordPath = Path<(
 )>;
 
 /// Read single file from list associated with record.
+///
+/// Resized variants of images can be requested via `w`, `h`, `fit` and `format`. Supports byte
+/// "Range" requests as well as "If-None-Match" and "If-Modified-Since" conditional requests. Signed
+/// URLs, i.e. `expires` and `signature`, grant access in place of user auth.
 #[utoipa::path(
   get,
   path = "/:name/:record/files/:column_name/:file_index",
+  params(ThumbnailQuery, FileQuery),
   responses(
-    (status = 200, description = "File contents.")
+    (status = 200, description = "File contents."),
+    (status = 206, description = "Requested range of the file contents."),
+    (status = 304, description = "File not modified."),
   )
 )]
 pub async fn get_uploaded_files_from_record_handler(
   State(state): State<AppState>,
   Path((api_name, record, column_name, file_index)): GetUploadedFilesFromRecordPath,
+  Query(thumbnail_query): Query<ThumbnailQuery>,
+  Query(file_query): Query<FileQuery>,
   user: Option<User>,
+  headers: HeaderMap,
 ) -> Result<Response, RecordError> {
   let Some(api) = state.lookup_record_api(&api_name) else {
     return Err(RecordError::ApiNotFound);
@@ -129,12 +263,22 @@ pub async fn get_uploaded_files_from_record_handler(
 
   let record_id = api.id_to_sql(&record)?;
 
-  let Ok(()) = api
-    .check_record_level_access(Permission::Read, Some(&record_id), None, user.as_ref())
-    .await
-  else {
+  // Signed URLs grant access in place of user auth.
+  let signed = is_signed(&file_query)?;
+
+  if !signed {
+    let Ok(()) = api
+      .check_record_level_access(Permission::Read, Some(&record_id), None, user.as_ref())
+      .await
+    else {
+      return Err(RecordError::Forbidden);
+    };
+  }
+  api.check_not_soft_deleted(&record_id).await?;
+
+  if !signed && !api.column_readable(&column_name, user.as_ref()) {
     return Err(RecordError::Forbidden);
-  };
+  }
 
   let Some(column) = api.metadata().column_by_name(&column_name) else {
     return Err(RecordError::RecordNotFound);
@@ -153,16 +297,39 @@ pub async fn get_uploaded_files_from_record_handler(
   if file_index >= file_uploads.0.len() {
     return Err(RecordError::RecordNotFound);
   }
+  let file_upload = file_uploads.0.remove(file_index);
+
+  if signed {
+    let route = FileRoute {
+      api_name: &api_name,
+      record: &record,
+      column_name: &column_name,
+      file_index: Some(file_index),
+      thumbnail: &thumbnail_query,
+      disposition: file_query.disposition.unwrap_or_default(),
+    };
+    verify_file_signature(&state, &route, &file_upload, &file_query)?;
+  }
 
-  return read_file_into_response(&state, file_uploads.0.remove(file_index))
-    .await
-    .map_err(|err| RecordError::Internal(err.into()));
+  if let Some(response) =
+    read_thumbnail_into_response(&state, &file_upload, &thumbnail_query).await?
+  {
+    return Ok(response);
+  }
+
+  return read_file_into_response(
+    &state,
+    file_upload,
+    &headers,
+    file_query.disposition.unwrap_or_default(),
+  )
+  .await
+  .map_err(|err| RecordError::Internal(err.into()));
 }
 
 #[cfg(test)]
 mod test {
   use axum::extract::{Path, Query, State};
-  use axum::Json;
   use trailbase_sqlite::{query_one_row, schema::FileUpload, schema::FileUploadInput};
 
   use super::*;
@@ -177,6 +344,7 @@ mod test {
     create_record_handler, CreateRecordQuery, CreateRecordResponse,
   };
   use crate::records::delete_record::delete_record_handler;
+  use crate::records::files::FileDisposition;
   use crate::records::test_utils::*;
   use crate::records::*;
   use crate::test::unpack_json_response;
@@ -267,7 +435,9 @@ mod test {
       assert!(read_record_handler(
         State(state.clone()),
         Path(("messages_api".to_string(), id_to_b64(&message_id),)),
-        None
+        Query(ReadRecordQuery::default()),
+        None,
+        HeaderMap::new()
       )
       .await
       .is_err());
@@ -277,7 +447,9 @@ mod test {
         let response = read_record_handler(
           State(state.clone()),
           Path(("messages_api".to_string(), id_to_b64(&message_id))),
+          Query(ReadRecordQuery::default()),
           User::from_auth_token(&state, &user_x_token.auth_token),
+          HeaderMap::new(),
         )
         .await;
         assert!(response.is_ok(), "{response:?}");
@@ -288,11 +460,52 @@ mod test {
         let response = read_record_handler(
           State(state.clone()),
           Path(("messages_api".to_string(), id_to_b64(&message_id))),
+          Query(ReadRecordQuery::default()),
           User::from_auth_token(&state, &user_y_token.auth_token),
+          HeaderMap::new(),
         )
         .await;
         assert!(response.is_ok(), "{response:?}");
       }
+
+      {
+        // Projection onto a subset of columns.
+        let read_fields = |fields: &str| {
+          read_record_handler(
+            State(state.clone()),
+            Path(("messages_api".to_string(), id_to_b64(&message_id))),
+            Query(ReadRecordQuery {
+              fields: Some(fields.to_string()),
+              ..Default::default()
+            }),
+            User::from_auth_token(&state, &user_x_token.auth_token),
+            HeaderMap::new(),
+          )
+        };
+
+        let value: serde_json::Value = unpack_json_response(read_fields("data").await?).await?;
+        assert_eq!(value, serde_json::json!({"data": "from user_x to room0"}));
+
+        let value: serde_json::Value = unpack_json_response(read_fields("id, data").await?).await?;
+        assert_eq!(
+          value,
+          serde_json::json!({
+            "id": id_to_b64(&message_id),
+            "data": "from user_x to room0",
+          })
+        );
+
+        // Hidden and unknown columns cannot be requested.
+        for fields in ["_owner", "unknown", "data,unknown", ""] {
+          assert!(read_fields(fields).await.is_err(), "{fields}");
+        }
+
+        // Projections only fetch the projected columns and thus carry a weak ETag.
+        let etag = |response: Response| response.headers().get(header::ETAG).cloned().unwrap();
+        let projected_etag = etag(read_fields("data").await?);
+        assert!(projected_etag.to_str()?.starts_with("W/\""));
+        assert_ne!(projected_etag, etag(read_fields("id, data").await?));
+      }
     }
 
     {
@@ -303,7 +516,9 @@ mod test {
       let response = read_record_handler(
         State(state.clone()),
         Path(("messages_api".to_string(), id_to_b64(&message_id))),
+        Query(ReadRecordQuery::default()),
         User::from_auth_token(&state, &user_y_token.auth_token),
+        HeaderMap::new(),
       )
       .await;
       assert!(response.is_err(), "{response:?}");
@@ -398,8 +613,17 @@ mod test {
 
     let record_path = Path((API_NAME.to_string(), create_response.id.clone()));
 
-    let Json(value) =
-      read_record_handler(State(state.clone()), Path(record_path.clone()), None).await?;
+    let value: serde_json::Value = unpack_json_response(
+      read_record_handler(
+        State(state.clone()),
+        Path(record_path.clone()),
+        Query(ReadRecordQuery::default()),
+        None,
+        HeaderMap::new(),
+      )
+      .await?,
+    )
+    .await?;
 
     let serde_json::Value::Object(map) = value else {
       panic!("Not a map");
@@ -418,16 +642,69 @@ mod test {
     let read_response = get_uploaded_file_from_record_handler(
       State(state.clone()),
       Path(record_file_path.clone()),
+      Query(ThumbnailQuery::default()),
+      Query(FileQuery::default()),
       None,
+      HeaderMap::new(),
     )
     .await?;
 
     let body = axum::body::to_bytes(read_response.into_body(), usize::MAX).await?;
     assert_eq!(body.to_vec(), bytes);
 
-    let _ = delete_record_handler(State(state.clone()), Path(record_path.clone()), None)
-      .await
-      .unwrap();
+    let read_file = |headers: HeaderMap, disposition: Option<FileDisposition>| {
+      get_uploaded_file_from_record_handler(
+        State(state.clone()),
+        Path(record_file_path.clone()),
+        Query(ThumbnailQuery::default()),
+        Query(FileQuery {
+          disposition,
+          ..Default::default()
+        }),
+        None,
+        headers,
+      )
+    };
+
+    let etag = read_file(HeaderMap::new(), None).await?.headers()[header::ETAG].clone();
+    let mut headers = HeaderMap::new();
+    headers.insert(header::IF_NONE_MATCH, etag);
+    assert_eq!(
+      read_file(headers, None).await?.status(),
+      StatusCode::NOT_MODIFIED
+    );
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::RANGE, "bytes=1-2".parse()?);
+    let response = read_file(headers, Some(FileDisposition::Inline)).await?;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+      response.headers()[header::CONTENT_RANGE],
+      format!("bytes 1-2/{}", bytes.len())
+    );
+    // Files of unknown type are never displayed inline.
+    assert!(response.headers()[header::CONTENT_DISPOSITION]
+      .to_str()?
+      .starts_with("attachment"));
+    assert_eq!(
+      response.headers()[header::CONTENT_TYPE],
+      "application/octet-stream"
+    );
+    assert_eq!(
+      response.headers()[header::CONTENT_SECURITY_POLICY],
+      "sandbox"
+    );
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+    assert_eq!(body.to_vec(), bytes[1..3]);
+
+    let _ = delete_record_handler(
+      State(state.clone()),
+      Path(record_path.clone()),
+      None,
+      HeaderMap::new(),
+    )
+    .await
+    .unwrap();
 
     let mut dir_cnt = 0;
     let mut read_dir = tokio::fs::read_dir(state.data_dir().uploads_path()).await?;
@@ -440,7 +717,10 @@ mod test {
     assert!(get_uploaded_file_from_record_handler(
       State(state.clone()),
       Path(record_file_path.clone()),
+      Query(ThumbnailQuery::default()),
+      Query(FileQuery::default()),
       None,
+      HeaderMap::new(),
     )
     .await
     .is_err());
@@ -487,7 +767,17 @@ mod test {
 
     let record_path = Path((API_NAME.to_string(), resp.id.clone()));
 
-    let Json(value) = read_record_handler(State(state.clone()), record_path, None).await?;
+    let value: serde_json::Value = unpack_json_response(
+      read_record_handler(
+        State(state.clone()),
+        record_path,
+        Query(ReadRecordQuery::default()),
+        None,
+        HeaderMap::new(),
+      )
+      .await?,
+    )
+    .await?;
 
     let serde_json::Value::Object(map) = value else {
       panic!("Not a map");
@@ -507,9 +797,15 @@ mod test {
         index,
       ));
 
-      let response =
-        get_uploaded_files_from_record_handler(State(state.clone()), record_file_path, None)
-          .await?;
+      let response = get_uploaded_files_from_record_handler(
+        State(state.clone()),
+        record_file_path,
+        Query(ThumbnailQuery::default()),
+        Query(FileQuery::default()),
+        None,
+        HeaderMap::new(),
+      )
+      .await?;
 
       let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
       assert_eq!(body.to_vec(), bytes);
@@ -518,6 +814,104 @@ mod test {
     return Ok(());
   }
 
+  #[tokio::test]
+  async fn test_file_upload_limits() -> Result<(), anyhow::Error> {
+    use axum::extract::FromRequest;
+    use axum::http::StatusCode;
+
+    use crate::config::proto::FileUploadLimits;
+    use crate::extract::FileUploadTarget;
+
+    let state = test_state(None).await?;
+    const API_NAME: &str = "test_api";
+    create_test_record_api(&state, API_NAME).await?;
+
+    let mut config = state.get_config();
+    let api_config = config
+      .record_apis
+      .iter_mut()
+      .find(|api| api.name.as_deref() == Some(API_NAME))
+      .unwrap();
+    api_config.file_upload_limits = vec![
+      FileUploadLimits {
+        max_size: Some(8),
+        ..Default::default()
+      },
+      FileUploadLimits {
+        column: Some("file".to_string()),
+        allowed_mime_types: vec!["image/*".to_string()],
+        ..Default::default()
+      },
+    ];
+    state.validate_and_update_config(config, None).await?;
+
+    let api = state.lookup_record_api(API_NAME).unwrap();
+    assert_eq!(api.file_upload_limit("file").max_size, 8);
+    assert_eq!(
+      api.file_upload_limit("file").allowed_mime_types,
+      ["image/*"]
+    );
+    assert!(api.file_upload_limit("files").allowed_mime_types.is_empty());
+
+    let create = |request: Either<serde_json::Value>| {
+      create_record_handler(
+        State(state.clone()),
+        Path(API_NAME.to_string()),
+        Query(CreateRecordQuery::default()),
+        None,
+        request,
+      )
+    };
+    let upload = |data: &[u8]| FileUploadInput {
+      name: None,
+      filename: None,
+      content_type: None,
+      data: data.to_vec(),
+    };
+
+    const PNG_HEADER: [u8; 8] = [0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a];
+    create(Either::Json(serde_json::json!({
+      "file": upload(&PNG_HEADER),
+      "files": [upload(&[0; 8])],
+    })))
+    .await?;
+
+    assert!(matches!(
+      create(Either::Json(
+        serde_json::json!({"files": [upload(&[0; 9])]})
+      ))
+      .await,
+      Err(RecordError::Rejected(StatusCode::PAYLOAD_TOO_LARGE, _))
+    ));
+    assert!(matches!(
+      create(Either::Json(serde_json::json!({"file": upload(&[0; 8])}))).await,
+      Err(RecordError::Rejected(StatusCode::UNSUPPORTED_MEDIA_TYPE, _))
+    ));
+
+    // Streamed multipart uploads are checked while being streamed.
+    let body = "--fieldB\r\n\
+      Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+      Content-Type: text/plain\r\n\r\n\
+      text\r\n\
+      --fieldB--\r\n";
+    let mut request = axum::http::Request::builder()
+      .header("content-type", "multipart/form-data; boundary=fieldB")
+      .body(axum::body::Body::from(body))?;
+    request.extensions_mut().insert(FileUploadTarget::new(
+      state.clone(),
+      api.file_upload_limits(),
+    ));
+    let rejection = Either::<serde_json::Value>::from_request(request, &())
+      .await
+      .unwrap_err();
+    assert_eq!(
+      rejection.into_response().status(),
+      StatusCode::UNSUPPORTED_MEDIA_TYPE
+    );
+
+    return Ok(());
+  }
+
   #[tokio::test]
   async fn test_read_record_from_view() -> Result<(), anyhow::Error> {
     let state = test_state(None).await?;
@@ -574,7 +968,9 @@ mod test {
     let response = read_record_handler(
       State(state.clone()),
       Path(("messages_api".to_string(), id_to_b64(&message_id))),
+      Query(ReadRecordQuery::default()),
       User::from_auth_token(&state, &user_x_token.auth_token),
+      HeaderMap::new(),
     )
     .await;
     assert!(response.is_ok(), "{response:?}");
@@ -1,11 +1,20 @@
+use chrono::Duration;
 use itertools::Itertools;
 use log::*;
+use std::collections::HashMap;
 use std::sync::Arc;
 use trailbase_sqlite::query_one_row;
 
 use crate::auth::user::User;
-use crate::config::proto::{ConflictResolutionStrategy, RecordApiConfig};
+use crate::config::proto::{
+  ColumnAcl, ComputedField, ConflictResolutionStrategy, FileUploadLimits, RecordApiConfig,
+};
+use crate::constants::{FILE_UPLOAD_MAX_SIZE_DEFAULT, SOFT_DELETE_RETENTION_DEFAULT};
+use crate::extract::FileUploadLimit;
+use crate::records::files::is_file_column;
+use crate::records::geo::GeoIndex;
 use crate::records::json_to_sql::{LazyParams, Params};
+use crate::records::search::search_table_name;
 use crate::records::{Permission, RecordError};
 use crate::schema::{Column, ColumnDataType};
 use crate::table_metadata::{TableMetadata, TableOrViewMetadata, ViewMetadata};
@@ -13,11 +22,9 @@ use crate::util::{assert_uuidv7, b64_to_id};
 
 /// FILTER CONTROL.
 ///
-/// Open question: right now we use the read_access rule also for listing. It could be nice to
-/// allow different access rules. On the other hand, this could also lead to setups where you can
-/// list records you cannot read (the other way round might be more sensible).
-/// On the other hand, different permissions could also be modeled as multiple apis on the same
-/// table.
+/// Listed records are filtered by the list access rule, which falls back to the read access rule
+/// if unset. Note that a more permissive list rule can lead to setups where you can list records
+/// you cannot read (the other way round might be more sensible).
 ///
 /// Independently, listing a user's own items might be a common task. Should we support a magic
 /// filter "mine" or is "owner_col=<my_user_id>" good enough?
@@ -39,6 +46,8 @@ struct RecordApiState {
   // Below properties are filled from `proto::RecordApiConfig`.
   api_name: String,
   acl: [u8; 2],
+  column_acl: [Option<ColumnAcl>; 2],
+  computed_fields: Vec<ComputedField>,
   insert_conflict_resolution_strategy: Option<ConflictResolutionStrategy>,
   insert_autofill_missing_user_id_columns: bool,
 
@@ -47,6 +56,19 @@ struct RecordApiState {
   update_access_rule: Option<String>,
   delete_access_rule: Option<String>,
   schema_access_rule: Option<String>,
+  list_access_rule: Option<String>,
+
+  etag_column: Option<String>,
+  search_table: Option<String>,
+  search_columns: Vec<String>,
+  geo_index: Option<GeoIndex>,
+
+  soft_delete_column: Option<String>,
+  soft_delete_retention: Duration,
+
+  enable_history: bool,
+
+  file_upload_limits: Vec<FileUploadLimits>,
 }
 
 impl RecordApi {
@@ -128,6 +150,12 @@ impl RecordApi {
       return Err(format!("RecordApi misses name: {config:?}"));
     };
 
+    let search_table = match config.search_columns.is_empty() {
+      true => None,
+      false => Some(search_table_name(&api_name)),
+    };
+    let geo_index = GeoIndex::from_config(&api_name, &config);
+
     return Ok(RecordApi {
       state: Arc::new(RecordApiState {
         conn,
@@ -149,12 +177,32 @@ impl RecordApi {
           convert_acl(&config.acl_world),
           convert_acl(&config.acl_authenticated),
         ],
+        column_acl: [config.column_acl_world, config.column_acl_authenticated],
+        computed_fields: config.computed_fields,
         // Access rules.
         create_access_rule: config.create_access_rule,
+        // Listing falls back to the read access rule, which also filters listed records.
+        list_access_rule: config
+          .list_access_rule
+          .or_else(|| config.read_access_rule.clone()),
         read_access_rule: config.read_access_rule,
         update_access_rule: config.update_access_rule,
         delete_access_rule: config.delete_access_rule,
         schema_access_rule: config.schema_access_rule,
+
+        etag_column: config.etag_column,
+        search_table,
+        search_columns: config.search_columns,
+        geo_index,
+
+        soft_delete_column: config.soft_delete_column,
+        soft_delete_retention: config
+          .soft_delete_retention_sec
+          .map_or(SOFT_DELETE_RETENTION_DEFAULT, Duration::seconds),
+
+        enable_history: config.enable_history.unwrap_or(false),
+
+        file_upload_limits: config.file_upload_limits,
       }),
     });
   }
@@ -202,11 +250,205 @@ impl RecordApi {
     };
   }
 
+  /// Parses a comma-separated list of columns to project records onto, e.g. "id,title".
+  ///
+  /// Columns or computed fields must exist and be readable by the given user, i.e. hidden columns
+  /// starting with "_" cannot be requested.
+  pub fn parse_fields(
+    &self,
+    fields: &str,
+    user: Option<&User>,
+  ) -> Result<Vec<String>, RecordError> {
+    let mut columns: Vec<String> = vec![];
+    for field in fields.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+      let exists = self.metadata().column_by_name(field).is_some()
+        || self.state.computed_fields.iter().any(|f| f.name() == field);
+      if !exists || !self.column_readable(field, user) {
+        return Err(RecordError::BadRequest("Invalid fields"));
+      }
+      if !columns.iter().any(|c| c == field) {
+        columns.push(field.to_string());
+      }
+    }
+
+    if columns.is_empty() {
+      return Err(RecordError::BadRequest("Invalid fields"));
+    }
+    return Ok(columns);
+  }
+
+  /// Whether the given user (if any) can read the given column.
+  ///
+  /// Hidden columns, i.e. ones starting with "_", are never readable, whereas the primary key
+  /// column always is.
+  pub(crate) fn column_readable(&self, column: &str, user: Option<&User>) -> bool {
+    if column.starts_with("_") {
+      return false;
+    }
+    if column == self.state.record_pk_column.name {
+      return true;
+    }
+    return self.column_acl(user).map_or(true, |acl| {
+      column_allowed(&acl.read_allow, &acl.read_deny, column)
+    });
+  }
+
+  /// Whether the given user (if any) can write the given column.
+  pub(crate) fn column_writable(&self, column: &str, user: Option<&User>) -> bool {
+    if column == self.state.record_pk_column.name {
+      return true;
+    }
+    // Tombstones are only set by deletions, otherwise records could be purged bypassing the
+    // delete access checks.
+    if self.state.soft_delete_column.as_deref() == Some(column) {
+      return false;
+    }
+    return self.column_acl(user).map_or(true, |acl| {
+      column_allowed(&acl.write_allow, &acl.write_deny, column)
+    });
+  }
+
+  /// Column ACL the given user (if any) is subject to. Authenticated users fall back to the
+  /// world's column ACL.
+  #[inline]
+  fn column_acl(&self, user: Option<&User>) -> Option<&ColumnAcl> {
+    let [world, authenticated] = &self.state.column_acl;
+    if user.is_some() && authenticated.is_some() {
+      return authenticated.as_ref();
+    }
+    return world.as_ref();
+  }
+
   #[inline]
   pub fn record_pk_column(&self) -> &Column {
     return &self.state.record_pk_column;
   }
 
+  /// Column from which record ETags are derived. Defaults to the entire row if unset.
+  #[inline]
+  pub fn etag_column(&self) -> Option<&str> {
+    return self.state.etag_column.as_deref();
+  }
+
+  /// FTS5 table backing full-text search, if any search columns are configured.
+  #[inline]
+  pub fn search_table(&self) -> Option<&str> {
+    return self.state.search_table.as_deref();
+  }
+
+  /// Named SQL expressions appended to read and listed records.
+  #[inline]
+  pub fn computed_fields(&self) -> &[ComputedField] {
+    return &self.state.computed_fields;
+  }
+
+  /// Columns indexed for full-text search.
+  #[inline]
+  pub fn search_columns(&self) -> &[String] {
+    return &self.state.search_columns;
+  }
+
+  /// Size and type constraints for files uploaded to the given column. Unset per-column limits fall
+  /// back to the API-wide ones.
+  pub(crate) fn file_upload_limit(&self, column: &str) -> FileUploadLimit {
+    let limits = &self.state.file_upload_limits;
+    let api_limits = limits.iter().find(|l| l.column.is_none());
+    let column_limits = limits.iter().find(|l| l.column.as_deref() == Some(column));
+
+    let max_size = column_limits
+      .and_then(|l| l.max_size)
+      .or_else(|| api_limits.and_then(|l| l.max_size))
+      .map_or(FILE_UPLOAD_MAX_SIZE_DEFAULT, |s| s as usize);
+    let allowed_mime_types = [column_limits, api_limits]
+      .into_iter()
+      .flatten()
+      .map(|l| &l.allowed_mime_types)
+      .find(|m| !m.is_empty())
+      .cloned()
+      .unwrap_or_default();
+
+    return FileUploadLimit {
+      max_size,
+      allowed_mime_types,
+    };
+  }
+
+  /// File upload limits by file column.
+  pub(crate) fn file_upload_limits(&self) -> HashMap<String, FileUploadLimit> {
+    let Some(table_metadata) = self.table_metadata() else {
+      return HashMap::new();
+    };
+
+    return table_metadata
+      .schema
+      .columns
+      .iter()
+      .filter(|col| {
+        table_metadata
+          .column_by_name(&col.name)
+          .is_some_and(|(_, meta)| is_file_column(meta))
+      })
+      .map(|col| (col.name.clone(), self.file_upload_limit(&col.name)))
+      .collect();
+  }
+
+  /// Checks files uploaded as part of a request against the API's limits.
+  pub(crate) fn check_file_upload_limits(&self, params: &Params) -> Result<(), RecordError> {
+    return params
+      .check_file_upload_limits(|column| self.file_upload_limit(column))
+      .map_err(|err| RecordError::Rejected(err.status(), Some(err.to_string())));
+  }
+
+  /// R*-tree index backing geospatial filters, if latitude and longitude columns are configured.
+  #[inline]
+  pub fn geo_index(&self) -> Option<&GeoIndex> {
+    return self.state.geo_index.as_ref();
+  }
+
+  /// Tombstone column, if soft-deletes are enabled.
+  #[inline]
+  pub fn soft_delete_column(&self) -> Option<&str> {
+    return self.state.soft_delete_column.as_deref();
+  }
+
+  /// Max age of soft-deleted records before they get purged.
+  #[inline]
+  pub fn soft_delete_retention(&self) -> Duration {
+    return self.state.soft_delete_retention;
+  }
+
+  /// Whether changes made through this API are recorded in the record history.
+  #[inline]
+  pub fn history_enabled(&self) -> bool {
+    return self.state.enable_history;
+  }
+
+  /// Fails with `RecordNotFound` if the given record has been soft-deleted.
+  pub(crate) async fn check_not_soft_deleted(
+    &self,
+    record_id: &libsql::Value,
+  ) -> Result<(), RecordError> {
+    let Some(ref column) = self.state.soft_delete_column else {
+      return Ok(());
+    };
+
+    let row = query_one_row(
+      &self.state.conn,
+      &format!(
+        "SELECT EXISTS(SELECT 1 FROM '{table_name}' WHERE [{pk_column}] = $1 AND [{column}] IS NOT NULL)",
+        table_name = self.table_name(),
+        pk_column = self.state.record_pk_column.name,
+      ),
+      [record_id.clone()],
+    )
+    .await?;
+
+    if row.get::<bool>(0)? {
+      return Err(RecordError::RecordNotFound);
+    }
+    return Ok(());
+  }
+
   #[inline]
   pub fn access_rule(&self, p: Permission) -> &Option<String> {
     return match p {
@@ -215,6 +457,7 @@ impl RecordApi {
       Permission::Update => &self.state.update_access_rule,
       Permission::Delete => &self.state.delete_access_rule,
       Permission::Schema => &self.state.schema_access_rule,
+      Permission::List => &self.state.list_access_rule,
     };
   }
 
@@ -233,12 +476,27 @@ impl RecordApi {
     &self,
     p: Permission,
     record_id: Option<&libsql::Value>,
-    request_params: Option<&mut LazyParams<'_>>,
+    mut request_params: Option<&mut LazyParams<'_>>,
     user: Option<&User>,
   ) -> Result<(), RecordError> {
     // First check table level access and if present check row-level access based on access rule.
     self.check_table_level_access(p, user)?;
 
+    // Requests must only touch columns the user can write.
+    let restricted = self.column_acl(user).is_some() || self.state.soft_delete_column.is_some();
+    if let (true, Some(params)) = (restricted, request_params.as_mut()) {
+      let params = params
+        .params()
+        .map_err(|_err| RecordError::BadRequest("Parameter conversion"))?;
+      if !params
+        .column_names()
+        .iter()
+        .all(|col| self.column_writable(col, user))
+      {
+        return Err(RecordError::Forbidden);
+      }
+    }
+
     'acl: {
       let Some(ref access_rule) = self.access_rule(p) else {
         return Ok(());
@@ -283,6 +541,77 @@ impl RecordApi {
     return Err(RecordError::Forbidden);
   }
 
+  /// Check if the given user (if any) can access the given, already materialized row.
+  ///
+  /// Unlike `check_record_level_access` the access rule is evaluated against the provided values
+  /// rather than the table's current contents, e.g. to filter change events for records that may
+  /// no longer exist.
+  pub(crate) async fn check_row_level_access(
+    &self,
+    p: Permission,
+    row: &[(String, libsql::Value)],
+    user: Option<&User>,
+  ) -> Result<(), RecordError> {
+    self.check_table_level_access(p, user)?;
+
+    let Some(ref access_rule) = self.access_rule(p) else {
+      return Ok(());
+    };
+
+    if row.is_empty() {
+      return Err(RecordError::Forbidden);
+    }
+
+    let (user_sub_select, mut params) = build_user_sub_select(user);
+    let row_sub_select = format!(
+      "SELECT {placeholders}",
+      placeholders = row
+        .iter()
+        .enumerate()
+        .map(|(index, (col_name, _value))| format!(":__row{index} AS '{col_name}'"))
+        .join(", ")
+    );
+    params.extend(
+      row
+        .iter()
+        .enumerate()
+        .map(|(index, (_col_name, value))| (format!(":__row{index}"), value.clone())),
+    );
+
+    let access_query = indoc::formatdoc!(
+      r#"
+        SELECT
+          ({access_rule})
+        FROM
+          ({user_sub_select}) AS _USER_,
+          ({row_sub_select}) AS _ROW_
+      "#
+    );
+
+    let row = match query_one_row(
+      &self.state.conn,
+      &access_query,
+      libsql::params::Params::Named(params),
+    )
+    .await
+    {
+      Ok(row) => row,
+      Err(err) => {
+        error!("RLA query '{access_query}' failed: {err}");
+        return Err(RecordError::Forbidden);
+      }
+    };
+
+    return match row.get::<bool>(0) {
+      Ok(true) => Ok(()),
+      Ok(false) => Err(RecordError::Forbidden),
+      Err(err) => {
+        warn!("RLA query returned NULL. Failing closed: '{access_query}'\n{err}");
+        Err(RecordError::Forbidden)
+      }
+    };
+  }
+
   #[inline]
   pub fn check_table_level_access(
     &self,
@@ -380,15 +709,17 @@ impl RecordApi {
         "#,
         )
       }
-      Permission::Read | Permission::Delete | Permission::Schema => indoc::formatdoc!(
-        r#"
+      Permission::Read | Permission::Delete | Permission::Schema | Permission::List => {
+        indoc::formatdoc!(
+          r#"
           SELECT
             ({access_rule})
           FROM
             ({user_sub_select}) AS _USER_,
             (SELECT * FROM [{table_name}] WHERE [{pk_column_name}] = :__record_id) AS _ROW_
         "#
-      ),
+        )
+      }
     };
 
     return Ok((query, libsql::params::Params::Named(params)));
@@ -454,6 +785,12 @@ fn build_request_sub_select(
   );
 }
 
+#[inline]
+fn column_allowed(allow: &[String], deny: &[String], column: &str) -> bool {
+  return (allow.is_empty() || allow.iter().any(|c| c == column))
+    && !deny.iter().any(|c| c == column);
+}
+
 fn convert_acl(acl: &Vec<i32>) -> u8 {
   let mut value: u8 = 0;
   for flag in acl {
@@ -473,8 +810,23 @@ enum Entity {
 
 #[cfg(test)]
 mod tests {
-  use super::convert_acl;
-  use crate::{config::proto::PermissionFlag, records::Permission};
+  use axum::extract::{Json, Path, Query, RawQuery, State};
+  use axum::http::HeaderMap;
+
+  use super::*;
+  use crate::admin::user::*;
+  use crate::api::JsonSchemaMode;
+  use crate::app_state::*;
+  use crate::auth::api::login::login_with_password;
+  use crate::config::proto::PermissionFlag;
+  use crate::extract::Either;
+  use crate::records::create_record::{create_record_handler, CreateRecordQuery};
+  use crate::records::json_schema::{json_schema_handler, JsonSchemaQuery};
+  use crate::records::list_records::list_records_handler;
+  use crate::records::read_record::{read_record_handler, ReadRecordQuery};
+  use crate::records::update_record::update_record_handler;
+  use crate::records::*;
+  use crate::test::unpack_json_response;
 
   fn has_access(flags: u8, p: Permission) -> bool {
     return (flags & (p as u8)) > 0;
@@ -505,4 +857,185 @@ mod tests {
       assert!(has_access(acl, Permission::Update), "ACL: {acl}");
     }
   }
+
+  #[test]
+  fn test_column_allowed() {
+    let cols = |cols: &[&str]| cols.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+
+    assert!(column_allowed(&[], &[], "a"));
+    assert!(column_allowed(&cols(&["a"]), &[], "a"));
+    assert!(!column_allowed(&cols(&["a"]), &[], "b"));
+    assert!(!column_allowed(&[], &cols(&["a"]), "a"));
+    assert!(!column_allowed(&cols(&["a"]), &cols(&["a"]), "a"));
+  }
+
+  #[tokio::test]
+  async fn test_record_api_column_acl() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    state
+      .conn()
+      .execute_batch(
+        r#"
+          CREATE TABLE employee (
+            id        INTEGER PRIMARY KEY,
+            name      TEXT NOT NULL,
+            salary    INTEGER,
+            verified  INTEGER DEFAULT 0 NOT NULL
+          ) STRICT;
+
+          INSERT INTO employee (id, name, salary, verified) VALUES (1, 'alice', 100, 1);
+        "#,
+      )
+      .await?;
+    state.table_metadata().invalidate_all().await?;
+
+    add_record_api(
+      &state,
+      "employees_api",
+      "employee",
+      Acls {
+        world: vec![
+          PermissionFlag::Create,
+          PermissionFlag::Read,
+          PermissionFlag::Update,
+          PermissionFlag::List,
+          PermissionFlag::Schema,
+        ],
+        ..Default::default()
+      },
+      AccessRules::default(),
+    )
+    .await?;
+
+    let column_acl = |read_deny: &[&str], write_deny: &[&str]| ColumnAcl {
+      read_deny: read_deny.iter().map(|c| c.to_string()).collect(),
+      write_deny: write_deny.iter().map(|c| c.to_string()).collect(),
+      ..Default::default()
+    };
+
+    // The primary key cannot be hidden.
+    let mut config = state.get_config();
+    for api in &mut config.record_apis {
+      if api.name.as_deref() == Some("employees_api") {
+        api.column_acl_world = Some(column_acl(&["id"], &[]));
+      }
+    }
+    assert!(state
+      .validate_and_update_config(config, None)
+      .await
+      .is_err());
+
+    let mut config = state.get_config();
+    for api in &mut config.record_apis {
+      if api.name.as_deref() == Some("employees_api") {
+        api.column_acl_world = Some(column_acl(&["salary"], &["verified"]));
+        api.column_acl_authenticated = Some(column_acl(&[], &["verified"]));
+      }
+    }
+    state.validate_and_update_config(config, None).await?;
+
+    let password = "Secret!1!!";
+    let email = "user@test.com";
+    create_user_for_test(&state, email, password).await?;
+    let user = User::from_auth_token(
+      &state,
+      &login_with_password(&state, email, password)
+        .await?
+        .auth_token,
+    );
+
+    let read = |user: Option<User>| async {
+      let response = read_record_handler(
+        State(state.clone()),
+        Path(("employees_api".to_string(), "1".to_string())),
+        Query(ReadRecordQuery::default()),
+        user,
+        HeaderMap::new(),
+      )
+      .await?;
+      return unpack_json_response::<serde_json::Value>(response).await;
+    };
+    let list = |query: &str, user: Option<User>| {
+      list_records_handler(
+        State(state.clone()),
+        Path("employees_api".to_string()),
+        RawQuery(Some(query.to_string())),
+        user,
+      )
+    };
+
+    // Reads.
+    let record = read(None).await?;
+    assert_eq!(record["name"], "alice");
+    assert!(record.get("salary").is_none());
+    assert_eq!(read(user.clone()).await?["salary"], 100);
+
+    let Json(records) = list("", None).await?;
+    assert!(records[0].get("salary").is_none());
+    let Json(records) = list("", user.clone()).await?;
+    assert_eq!(records[0]["salary"], 100);
+
+    // Hidden columns cannot be filtered, ordered by, or projected onto.
+    for query in [
+      "salary[gte]=100",
+      "filter=salary%20>%2050",
+      "order=salary",
+      "fields=salary",
+    ] {
+      assert!(
+        matches!(list(query, None).await, Err(RecordError::BadRequest(_))),
+        "{query}"
+      );
+      list(query, user.clone()).await?;
+    }
+
+    // Writes.
+    let create = |value: serde_json::Value| {
+      create_record_handler(
+        State(state.clone()),
+        Path("employees_api".to_string()),
+        Query(CreateRecordQuery::default()),
+        None,
+        Either::Json(value),
+      )
+    };
+    assert!(matches!(
+      create(serde_json::json!({"name": "bob", "verified": 1})).await,
+      Err(RecordError::Forbidden)
+    ));
+    create(serde_json::json!({"name": "bob", "salary": 50})).await?;
+
+    assert!(matches!(
+      update_record_handler(
+        State(state.clone()),
+        Path(("employees_api".to_string(), "1".to_string())),
+        user.clone(),
+        HeaderMap::new(),
+        Either::Json(serde_json::json!({"verified": 0})),
+      )
+      .await,
+      Err(RecordError::Forbidden)
+    ));
+
+    // Schemas.
+    let schema = |mode: JsonSchemaMode| {
+      json_schema_handler(
+        State(state.clone()),
+        Path("employees_api".to_string()),
+        Query(JsonSchemaQuery {
+          mode: Some(mode),
+          fields: None,
+        }),
+        None,
+      )
+    };
+    let Json(select) = schema(JsonSchemaMode::Select).await?;
+    assert!(select["properties"].get("salary").is_none());
+    assert!(select["properties"].get("verified").is_some());
+    let Json(insert) = schema(JsonSchemaMode::Insert).await?;
+    assert!(insert["properties"].get("salary").is_some());
+    assert!(insert["properties"].get("verified").is_none());
+
+    return Ok(());
+  }
 }
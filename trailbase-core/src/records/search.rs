@@ -0,0 +1,160 @@
+use libsql::{params, Connection};
+use log::*;
+use std::collections::HashMap;
+
+use crate::config::proto::Config;
+use crate::constants::SQLITE_SCHEMA_TABLE;
+
+const SEARCH_TABLE_PREFIX: &str = "_search_";
+
+/// Name of the FTS5 table backing full-text search for the given record API.
+pub(crate) fn search_table_name(api_name: &str) -> String {
+  return format!("{SEARCH_TABLE_PREFIX}{api_name}");
+}
+
+/// Builds an FTS5 query from free-form user input.
+///
+/// Every whitespace-separated term is quoted, i.e. matched literally rather than interpreted as
+/// FTS5 query syntax, and all terms must match.
+pub(crate) fn build_search_query(q: &str) -> Option<String> {
+  let terms: Vec<String> = q
+    .split_whitespace()
+    .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+    .collect();
+
+  if terms.is_empty() {
+    return None;
+  }
+  return Some(terms.join(" "));
+}
+
+/// Creates, updates, or drops FTS5 tables and the triggers keeping them in sync with their
+/// content tables according to the search columns declared by record APIs in `config`.
+///
+/// Unchanged search tables are left untouched, otherwise they're rebuilt from their content table.
+pub(crate) async fn sync_search_tables(
+  conn: &Connection,
+  config: &Config,
+) -> Result<(), libsql::Error> {
+  let mut existing: HashMap<String, String> = HashMap::new();
+  let mut rows = conn
+    .query(
+      &format!(
+        r#"SELECT name, sql FROM {SQLITE_SCHEMA_TABLE} WHERE type = 'table' AND sql LIKE 'CREATE VIRTUAL TABLE%' AND substr(name, 1, $1) = $2"#
+      ),
+      params!(SEARCH_TABLE_PREFIX.len() as i64, SEARCH_TABLE_PREFIX),
+    )
+    .await?;
+  while let Some(row) = rows.next().await? {
+    existing.insert(row.get::<String>(0)?, row.get::<String>(1)?);
+  }
+
+  for api_config in &config.record_apis {
+    let (Some(api_name), Some(table_name)) = (&api_config.name, &api_config.table_name) else {
+      continue;
+    };
+    if api_config.search_columns.is_empty() {
+      continue;
+    }
+
+    let search_table = search_table_name(api_name);
+    let create_table =
+      build_create_search_table(&search_table, table_name, &api_config.search_columns);
+
+    if existing.remove(&search_table).as_ref() == Some(&create_table) {
+      continue;
+    }
+
+    info!("Rebuilding search table: {search_table}");
+    let tx = conn.transaction().await?;
+    tx.execute_batch(&build_drop_search_table(&search_table))
+      .await?;
+    tx.execute_batch(&format!(
+      "{create_table};\n{triggers}\nINSERT INTO \"{search_table}\"(\"{search_table}\") VALUES('rebuild');",
+      triggers = build_search_triggers(&search_table, table_name, &api_config.search_columns),
+    ))
+    .await?;
+    tx.commit().await?;
+  }
+
+  // Drop search tables no longer backing any record API.
+  for search_table in existing.keys() {
+    info!("Dropping stale search table: {search_table}");
+    conn
+      .execute_batch(&build_drop_search_table(search_table))
+      .await?;
+  }
+
+  return Ok(());
+}
+
+fn build_create_search_table(search_table: &str, table_name: &str, columns: &[String]) -> String {
+  let columns = columns
+    .iter()
+    .map(|c| format!("\"{c}\""))
+    .collect::<Vec<_>>()
+    .join(", ");
+
+  return format!(
+    "CREATE VIRTUAL TABLE \"{search_table}\" USING fts5({columns}, content='{table_name}', content_rowid='rowid')"
+  );
+}
+
+fn build_search_triggers(search_table: &str, table_name: &str, columns: &[String]) -> String {
+  let column_list = columns
+    .iter()
+    .map(|c| format!("\"{c}\""))
+    .collect::<Vec<_>>()
+    .join(", ");
+  let values = |prefix: &str| {
+    return columns
+      .iter()
+      .map(|c| format!("{prefix}.\"{c}\""))
+      .collect::<Vec<_>>()
+      .join(", ");
+  };
+  let new_values = values("new");
+  let old_values = values("old");
+
+  return indoc::formatdoc!(
+    r#"
+      CREATE TRIGGER "{search_table}_insert" AFTER INSERT ON "{table_name}" BEGIN
+        INSERT INTO "{search_table}"(rowid, {column_list}) VALUES (new.rowid, {new_values});
+      END;
+      CREATE TRIGGER "{search_table}_delete" AFTER DELETE ON "{table_name}" BEGIN
+        INSERT INTO "{search_table}"("{search_table}", rowid, {column_list}) VALUES ('delete', old.rowid, {old_values});
+      END;
+      CREATE TRIGGER "{search_table}_update" AFTER UPDATE ON "{table_name}" BEGIN
+        INSERT INTO "{search_table}"("{search_table}", rowid, {column_list}) VALUES ('delete', old.rowid, {old_values});
+        INSERT INTO "{search_table}"(rowid, {column_list}) VALUES (new.rowid, {new_values});
+      END;
+    "#
+  );
+}
+
+fn build_drop_search_table(search_table: &str) -> String {
+  return indoc::formatdoc!(
+    r#"
+      DROP TRIGGER IF EXISTS "{search_table}_insert";
+      DROP TRIGGER IF EXISTS "{search_table}_delete";
+      DROP TRIGGER IF EXISTS "{search_table}_update";
+      DROP TABLE IF EXISTS "{search_table}";
+    "#
+  );
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_build_search_query() {
+    assert_eq!(build_search_query(""), None);
+    assert_eq!(build_search_query("   "), None);
+    assert_eq!(build_search_query("foo"), Some(r#""foo""#.to_string()));
+    assert_eq!(
+      build_search_query(" foo  OR bar\"* "),
+      Some(r#""foo" "OR" "bar""*""#.to_string())
+    );
+  }
+}
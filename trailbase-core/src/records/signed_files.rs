@@ -0,0 +1,457 @@
+use axum::extract::{Json, Path, Query, State};
+use serde::{Deserialize, Serialize};
+use trailbase_sqlite::schema::FileUpload;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::constants::{RECORD_API_PATH, SIGNED_FILE_URL_DEFAULT_TTL, SIGNED_FILE_URL_MAX_TTL};
+use crate::records::files::{FileDisposition, FileQuery};
+use crate::records::json_to_sql::{GetFileQueryBuilder, GetFilesQueryBuilder, QueryError};
+use crate::records::thumbnail::ThumbnailQuery;
+use crate::records::{Permission, RecordApi, RecordError};
+use crate::util::{hex_encode, urlencode};
+
+#[derive(Clone, Debug, Default, Deserialize, IntoParams)]
+pub struct SignFileQuery {
+  /// Validity of the URL in seconds. Defaults to 15min and is capped at 7 days.
+  pub expires_in: Option<i64>,
+  /// Disposition the URL is signed for. Defaults to "attachment".
+  pub disposition: Option<FileDisposition>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct SignedFileUrlResponse {
+  /// Absolute URL granting read access to the file without further auth until it expires.
+  pub url: String,
+  /// Unix timestamp in seconds.
+  pub expires: i64,
+}
+
+/// Identifies a file download, i.e. the route as well as the requested variant and disposition.
+/// Signatures are bound to it and thus cannot be re-used for other records, columns, files or
+/// responses.
+pub(crate) struct FileRoute<'a> {
+  pub api_name: &'a str,
+  pub record: &'a str,
+  pub column_name: &'a str,
+  /// Index into `std.FileUploads` columns.
+  pub file_index: Option<usize>,
+  pub thumbnail: &'a ThumbnailQuery,
+  pub disposition: FileDisposition,
+}
+
+impl FileRoute<'_> {
+  fn path(&self) -> String {
+    let (api_name, record, column_name) = (
+      urlencode(self.api_name),
+      urlencode(self.record),
+      urlencode(self.column_name),
+    );
+    return match self.file_index {
+      Some(index) => format!("{api_name}/{record}/files/{column_name}/{index}"),
+      None => format!("{api_name}/{record}/file/{column_name}"),
+    };
+  }
+
+  fn query(&self) -> String {
+    let thumbnail = self.thumbnail.to_query_string();
+    return match thumbnail.is_empty() {
+      true => format!("disposition={}", self.disposition.name()),
+      false => format!("disposition={}&{thumbnail}", self.disposition.name()),
+    };
+  }
+
+  /// Signs the route together with the file's unique id, i.e. signatures don't carry over to files
+  /// replacing the signed one.
+  fn signature(&self, state: &AppState, file_upload: &FileUpload, expires: i64) -> String {
+    let message = format!(
+      "{path}?{query}:{file}:{expires}",
+      path = self.path(),
+      query = self.query(),
+      file = file_upload.path(),
+    );
+    return hex_encode(&state.jwt().sign(message.as_bytes()));
+  }
+}
+
+/// Returns true if the request carries a signature, in which case it's authorized by the signature
+/// rather than the user. Expired signatures are rejected right away.
+pub(crate) fn is_signed(query: &FileQuery) -> Result<bool, RecordError> {
+  if query.signature.is_none() {
+    return Ok(false);
+  }
+  let Some(expires) = query.expires else {
+    return Err(RecordError::BadRequest("Missing expiry"));
+  };
+
+  if expires < chrono::Utc::now().timestamp() {
+    return Err(RecordError::Forbidden);
+  }
+  return Ok(true);
+}
+
+/// Checks the signature of signed file URLs against the requested file.
+pub(crate) fn verify_file_signature(
+  state: &AppState,
+  route: &FileRoute<'_>,
+  file_upload: &FileUpload,
+  query: &FileQuery,
+) -> Result<(), RecordError> {
+  let (Some(signature), Some(expires)) = (&query.signature, query.expires) else {
+    return Err(RecordError::Forbidden);
+  };
+
+  if expires < chrono::Utc::now().timestamp() {
+    return Err(RecordError::Forbidden);
+  }
+  if !constant_time_eq(
+    route.signature(state, file_upload, expires).as_bytes(),
+    signature.as_bytes(),
+  ) {
+    return Err(RecordError::Forbidden);
+  }
+  return Ok(());
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  if a.len() != b.len() {
+    return false;
+  }
+  return a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0;
+}
+
+async fn sign_file_url(
+  state: &AppState,
+  api: &RecordApi,
+  route: FileRoute<'_>,
+  expires_in: Option<i64>,
+  user: Option<&User>,
+) -> Result<SignedFileUrlResponse, RecordError> {
+  let record_id = api.id_to_sql(route.record)?;
+
+  let Ok(()) = api
+    .check_record_level_access(Permission::Read, Some(&record_id), None, user)
+    .await
+  else {
+    return Err(RecordError::Forbidden);
+  };
+  api.check_not_soft_deleted(&record_id).await?;
+
+  if !api.column_readable(route.column_name, user) {
+    return Err(RecordError::Forbidden);
+  }
+  let Some(column) = api.metadata().column_by_name(route.column_name) else {
+    return Err(RecordError::BadRequest("Invalid field/column name"));
+  };
+
+  let pk_column = &api.record_pk_column().name;
+  let file_upload = match route.file_index {
+    None => GetFileQueryBuilder::run(state, api.table_name(), column, pk_column, record_id).await,
+    Some(index) => GetFilesQueryBuilder::run(state, api.table_name(), column, pk_column, record_id)
+      .await
+      .and_then(|mut file_uploads| {
+        if index >= file_uploads.0.len() {
+          return Err(QueryError::NotFound);
+        }
+        return Ok(file_uploads.0.remove(index));
+      }),
+  }
+  .map_err(|err| match err {
+    QueryError::NotFound => RecordError::RecordNotFound,
+    QueryError::Precondition(_) => RecordError::BadRequest("Not a file column"),
+    err => RecordError::Internal(err.into()),
+  })?;
+
+  let ttl = expires_in.map_or(SIGNED_FILE_URL_DEFAULT_TTL, chrono::Duration::seconds);
+  if ttl <= chrono::Duration::zero() {
+    return Err(RecordError::BadRequest("Invalid expiry"));
+  }
+  let expires = (chrono::Utc::now() + ttl.min(SIGNED_FILE_URL_MAX_TTL)).timestamp();
+
+  return Ok(SignedFileUrlResponse {
+    url: format!(
+      "{site}/{RECORD_API_PATH}/{path}?{query}&expires={expires}&signature={signature}",
+      site = state.site_url(),
+      path = route.path(),
+      query = route.query(),
+      signature = route.signature(state, &file_upload, expires),
+    ),
+    expires,
+  });
+}
+
+/// Mint a signed, expiring URL for the file associated with record.
+///
+/// The URL grants read access without auth, e.g. to embed protected files in `<img>` tags, and
+/// requires read access to the record and column. It's bound to the current file as well as the
+/// requested disposition and thumbnail variant, i.e. `w`, `h`, `fit` and `format`.
+#[utoipa::path(
+  post,
+  path = "/:name/:record/file/:column_name/sign",
+  params(SignFileQuery, ThumbnailQuery),
+  responses(
+    (status = 200, description = "Signed URL.", body = SignedFileUrlResponse)
+  )
+)]
+pub async fn sign_uploaded_file_url_handler(
+  State(state): State<AppState>,
+  Path((api_name, record, column_name)): Path<(String, String, String)>,
+  Query(query): Query<SignFileQuery>,
+  Query(thumbnail_query): Query<ThumbnailQuery>,
+  user: Option<User>,
+) -> Result<Json<SignedFileUrlResponse>, RecordError> {
+  let Some(api) = state.lookup_record_api(&api_name) else {
+    return Err(RecordError::ApiNotFound);
+  };
+
+  let route = FileRoute {
+    api_name: &api_name,
+    record: &record,
+    column_name: &column_name,
+    file_index: None,
+    thumbnail: &thumbnail_query,
+    disposition: query.disposition.unwrap_or_default(),
+  };
+  return Ok(Json(
+    sign_file_url(&state, &api, route, query.expires_in, user.as_ref()).await?,
+  ));
+}
+
+/// Mint a signed, expiring URL for a single file from list associated with record.
+#[utoipa::path(
+  post,
+  path = "/:name/:record/files/:column_name/:file_index/sign",
+  params(SignFileQuery, ThumbnailQuery),
+  responses(
+    (status = 200, description = "Signed URL.", body = SignedFileUrlResponse)
+  )
+)]
+pub async fn sign_uploaded_files_url_handler(
+  State(state): State<AppState>,
+  Path((api_name, record, column_name, file_index)): Path<(String, String, String, usize)>,
+  Query(query): Query<SignFileQuery>,
+  Query(thumbnail_query): Query<ThumbnailQuery>,
+  user: Option<User>,
+) -> Result<Json<SignedFileUrlResponse>, RecordError> {
+  let Some(api) = state.lookup_record_api(&api_name) else {
+    return Err(RecordError::ApiNotFound);
+  };
+
+  let route = FileRoute {
+    api_name: &api_name,
+    record: &record,
+    column_name: &column_name,
+    file_index: Some(file_index),
+    thumbnail: &thumbnail_query,
+    disposition: query.disposition.unwrap_or_default(),
+  };
+  return Ok(Json(
+    sign_file_url(&state, &api, route, query.expires_in, user.as_ref()).await?,
+  ));
+}
+
+#[cfg(test)]
+mod test {
+  use axum::http::HeaderMap;
+  use trailbase_sqlite::schema::FileUploadInput;
+
+  use super::*;
+  use crate::admin::user::*;
+  use crate::app_state::*;
+  use crate::auth::api::login::login_with_password;
+  use crate::config::proto::PermissionFlag;
+  use crate::extract::Either;
+  use crate::records::create_record::{
+    create_record_handler, CreateRecordQuery, CreateRecordResponse,
+  };
+  use crate::records::read_record::get_uploaded_file_from_record_handler;
+  use crate::records::*;
+  use crate::test::unpack_json_response;
+
+  #[tokio::test]
+  async fn test_signed_file_urls() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    state
+      .conn()
+      .execute_batch(
+        r#"
+          CREATE TABLE private_file (
+            id           INTEGER PRIMARY KEY,
+            file         TEXT CHECK(jsonschema('std.FileUpload', file))
+          ) STRICT;
+        "#,
+      )
+      .await?;
+    state.table_metadata().invalidate_all().await?;
+
+    const API_NAME: &str = "private_files_api";
+    add_record_api(
+      &state,
+      API_NAME,
+      "private_file",
+      Acls {
+        world: vec![PermissionFlag::Create],
+        authenticated: vec![PermissionFlag::Read],
+      },
+      AccessRules::default(),
+    )
+    .await?;
+
+    let password = "Secret!1!!";
+    let user_email = "user@test.com";
+    create_user_for_test(&state, user_email, password).await?;
+    let user_token = login_with_password(&state, user_email, password).await?;
+
+    let bytes = vec![42, 5, 42, 5];
+    let response: CreateRecordResponse = unpack_json_response(
+      create_record_handler(
+        State(state.clone()),
+        Path(API_NAME.to_string()),
+        Query(CreateRecordQuery::default()),
+        None,
+        Either::Json(serde_json::json!({
+          "file": FileUploadInput {
+            name: None,
+            filename: Some("secret.bin".to_string()),
+            content_type: None,
+            data: bytes.clone(),
+          },
+        })),
+      )
+      .await?,
+    )
+    .await?;
+    let record = response.id;
+
+    let sign = |query: SignFileQuery, user: Option<User>| {
+      sign_uploaded_file_url_handler(
+        State(state.clone()),
+        Path((API_NAME.to_string(), record.clone(), "file".to_string())),
+        Query(query),
+        Query(ThumbnailQuery::default()),
+        user,
+      )
+    };
+
+    // Signing requires read access.
+    assert!(matches!(
+      sign(SignFileQuery::default(), None).await,
+      Err(RecordError::Forbidden)
+    ));
+
+    let read_file = |query: FileQuery| {
+      get_uploaded_file_from_record_handler(
+        State(state.clone()),
+        Path((API_NAME.to_string(), record.clone(), "file".to_string())),
+        Query(ThumbnailQuery::default()),
+        Query(query),
+        None,
+        HeaderMap::new(),
+      )
+    };
+
+    assert!(matches!(
+      read_file(FileQuery::default()).await,
+      Err(RecordError::Forbidden)
+    ));
+
+    let Json(signed) = sign(
+      SignFileQuery {
+        expires_in: Some(60),
+        disposition: Some(FileDisposition::Inline),
+      },
+      User::from_auth_token(&state, &user_token.auth_token),
+    )
+    .await?;
+    let url = url::Url::parse(&signed.url)?;
+    assert_eq!(
+      url.path(),
+      format!("/{RECORD_API_PATH}/{API_NAME}/{record}/file/file")
+    );
+    let signed_query: FileQuery = serde_urlencoded::from_str(url.query().unwrap())?;
+    assert_eq!(signed_query.disposition, Some(FileDisposition::Inline));
+    assert_eq!(signed_query.expires, Some(signed.expires));
+
+    let response = read_file(signed_query.clone()).await?;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+    assert_eq!(body.to_vec(), bytes);
+
+    // Signatures are bound to the expiry, disposition and variant.
+    assert!(matches!(
+      read_file(FileQuery {
+        expires: signed_query.expires.map(|e| e + 1),
+        ..signed_query.clone()
+      })
+      .await,
+      Err(RecordError::Forbidden)
+    ));
+    assert!(matches!(
+      read_file(FileQuery {
+        disposition: None,
+        ..signed_query.clone()
+      })
+      .await,
+      Err(RecordError::Forbidden)
+    ));
+    assert!(matches!(
+      get_uploaded_file_from_record_handler(
+        State(state.clone()),
+        Path((API_NAME.to_string(), record.clone(), "file".to_string())),
+        Query(ThumbnailQuery {
+          w: Some(32),
+          ..Default::default()
+        }),
+        Query(signed_query.clone()),
+        None,
+        HeaderMap::new(),
+      )
+      .await,
+      Err(RecordError::Forbidden)
+    ));
+
+    // As well as the route and the file.
+    let file_upload = FileUpload::new(uuid::Uuid::new_v4(), None, None, None);
+    let route = FileRoute {
+      api_name: API_NAME,
+      record: &record,
+      column_name: "file",
+      file_index: None,
+      thumbnail: &ThumbnailQuery::default(),
+      disposition: FileDisposition::Inline,
+    };
+    let signature = route.signature(&state, &file_upload, signed.expires);
+    assert_ne!(Some(&signature), signed_query.signature.as_ref());
+    let other_route = FileRoute {
+      file_index: Some(0),
+      ..route
+    };
+    assert_ne!(
+      other_route.signature(&state, &file_upload, signed.expires),
+      signature
+    );
+
+    // Expired URLs are rejected.
+    assert!(matches!(
+      sign(
+        SignFileQuery {
+          expires_in: Some(-1),
+          ..Default::default()
+        },
+        User::from_auth_token(&state, &user_token.auth_token),
+      )
+      .await,
+      Err(RecordError::BadRequest(_))
+    ));
+    assert!(matches!(
+      read_file(FileQuery {
+        expires: Some(chrono::Utc::now().timestamp() - 1),
+        ..signed_query
+      })
+      .await,
+      Err(RecordError::Forbidden)
+    ));
+
+    return Ok(());
+  }
+}
@@ -0,0 +1,353 @@
+use axum::{
+  extract::{Path, State},
+  http::StatusCode,
+  response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use log::*;
+use trailbase_sqlite::query_row;
+
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::records::files::delete_files_in_row;
+use crate::records::history::append_history;
+use crate::records::subscribe::RecordAction;
+use crate::records::webhooks::enqueue_webhook_deliveries;
+use crate::records::{Permission, RecordApi, RecordError};
+
+/// Soft-deletes the given record, i.e. sets its tombstone, and returns the tombstoned row.
+///
+/// Files are retained until the record gets purged.
+pub(crate) async fn soft_delete_record(
+  conn: &libsql::Connection,
+  api: &RecordApi,
+  record_id: libsql::Value,
+) -> Result<libsql::Row, RecordError> {
+  let Some(column) = api.soft_delete_column() else {
+    return Err(RecordError::Internal("Soft-deletes not enabled".into()));
+  };
+
+  let Some(row) = query_row(
+    conn,
+    &format!(
+      "UPDATE '{table_name}' SET [{column}] = unixepoch() WHERE [{pk_column}] = $1 AND [{column}] IS NULL RETURNING *",
+      table_name = api.table_name(),
+      pk_column = api.record_pk_column().name,
+    ),
+    [record_id],
+  )
+  .await?
+  else {
+    return Err(RecordError::RecordNotFound);
+  };
+
+  return Ok(row);
+}
+
+/// Restore soft-deleted record.
+///
+/// Requires delete access to the record.
+#[utoipa::path(
+  post,
+  path = "/:name/:record/restore",
+  responses(
+    (status = 200, description = "Successful restoration."),
+    (status = 404, description = "Record doesn't exist or isn't soft-deleted.")
+  )
+)]
+pub async fn restore_record_handler(
+  State(state): State<AppState>,
+  Path((api_name, record)): Path<(String, String)>,
+  user: Option<User>,
+) -> Result<Response, RecordError> {
+  let Some(api) = state.lookup_record_api(&api_name) else {
+    return Err(RecordError::ApiNotFound);
+  };
+  let Some(column) = api.soft_delete_column() else {
+    return Err(RecordError::BadRequest("Soft-deletes not enabled"));
+  };
+
+  let record_id = api.id_to_sql(&record)?;
+
+  api
+    .check_record_level_access(Permission::Delete, Some(&record_id), None, user.as_ref())
+    .await?;
+
+  let row = restore_record(&state, &api, column, &record_id, user.as_ref()).await?;
+
+  // From the perspective of subscribers and webhooks, the record reappears.
+  state
+    .subscription_manager()
+    .broadcast(&state, api.table_name(), RecordAction::Insert, &row)
+    .await;
+
+  return Ok((StatusCode::OK, "restored").into_response());
+}
+
+/// Clears the record's tombstone in a single transaction together with appending its history
+/// entry and enqueuing webhook deliveries.
+async fn restore_record(
+  state: &AppState,
+  api: &RecordApi,
+  column: &str,
+  record_id: &libsql::Value,
+  user: Option<&User>,
+) -> Result<libsql::Row, RecordError> {
+  let tx = state.write_transaction().await?;
+
+  let Some(row) = query_row(
+    &tx,
+    &format!(
+      "UPDATE '{table_name}' SET [{column}] = NULL WHERE [{pk_column}] = $1 AND [{column}] IS NOT NULL RETURNING *",
+      table_name = api.table_name(),
+      pk_column = api.record_pk_column().name,
+    ),
+    [record_id.clone()],
+  )
+  .await?
+  else {
+    return Err(RecordError::RecordNotFound);
+  };
+
+  append_history(
+    &tx,
+    api,
+    RecordAction::Insert,
+    record_id,
+    user,
+    None,
+    Some(&row),
+  )
+  .await?;
+  enqueue_webhook_deliveries(state, &tx, api, RecordAction::Insert, record_id, Some(&row)).await?;
+
+  tx.commit().await?;
+
+  return Ok(row);
+}
+
+/// Hard-deletes soft-deleted records past their API's retention and only then deletes their
+/// files.
+pub(crate) async fn purge_soft_deleted_records(state: &AppState) -> Result<usize, RecordError> {
+  let mut purged: usize = 0;
+
+  for api in state.record_apis() {
+    let (Some(column), Some(table_metadata)) = (api.soft_delete_column(), api.table_metadata())
+    else {
+      continue;
+    };
+
+    let cutoff = (Utc::now() - api.soft_delete_retention()).timestamp();
+    let mut rows = state
+      .conn()
+      .query(
+        &format!(
+          "DELETE FROM '{table_name}' WHERE [{column}] IS NOT NULL AND [{column}] < $1 RETURNING *",
+          table_name = api.table_name(),
+        ),
+        [cutoff],
+      )
+      .await?;
+
+    while let Some(row) = rows.next().await? {
+      if let Err(err) = delete_files_in_row(state, table_metadata, &row).await {
+        warn!("Failed to delete files of purged record: {err}");
+      }
+      purged += 1;
+    }
+  }
+
+  return Ok(purged);
+}
+
+#[cfg(test)]
+mod test {
+  use axum::extract::{Query, RawQuery};
+  use axum::http::HeaderMap;
+  use axum::Json;
+  use trailbase_sqlite::query_one_row;
+
+  use super::*;
+  use crate::app_state::*;
+  use crate::config::proto::PermissionFlag;
+  use crate::constants::RECORD_HISTORY_TABLE;
+  use crate::extract::Either;
+  use crate::records::delete_record::delete_record_handler;
+  use crate::records::list_records::list_records_handler;
+  use crate::records::read_record::{read_record_handler, ReadRecordQuery};
+  use crate::records::update_record::update_record_handler;
+  use crate::records::*;
+
+  #[tokio::test]
+  async fn test_record_api_soft_delete() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    let conn = state.conn();
+    conn
+      .execute_batch(
+        r#"
+          CREATE TABLE note (
+            id        INTEGER PRIMARY KEY,
+            body      TEXT NOT NULL,
+            deleted   INTEGER
+          ) STRICT;
+
+          INSERT INTO note (id, body) VALUES (1, 'first'), (2, 'second');
+        "#,
+      )
+      .await?;
+    state.table_metadata().invalidate_all().await?;
+
+    add_record_api(
+      &state,
+      "notes_api",
+      "note",
+      Acls {
+        world: vec![
+          PermissionFlag::Read,
+          PermissionFlag::List,
+          PermissionFlag::Update,
+          PermissionFlag::Delete,
+        ],
+        ..Default::default()
+      },
+      AccessRules::default(),
+    )
+    .await?;
+
+    let mut config = state.get_config();
+    for api in &mut config.record_apis {
+      if api.name.as_deref() == Some("notes_api") {
+        api.soft_delete_column = Some("deleted".to_string());
+        api.enable_history = Some(true);
+      }
+    }
+
+    // Retention has to be positive, i.e. soft-deleted records cannot be purged right away.
+    let mut invalid_config = config.clone();
+    for api in &mut invalid_config.record_apis {
+      api.soft_delete_retention_sec = Some(0);
+    }
+    assert!(state
+      .validate_and_update_config(invalid_config, None)
+      .await
+      .is_err());
+
+    state.validate_and_update_config(config, None).await?;
+
+    let path = |id: &str| Path(("notes_api".to_string(), id.to_string()));
+    let read = |id: &'static str| {
+      read_record_handler(
+        State(state.clone()),
+        path(id),
+        Query(ReadRecordQuery::default()),
+        None,
+        HeaderMap::new(),
+      )
+    };
+    let delete = |id: &'static str| {
+      delete_record_handler(State(state.clone()), path(id), None, HeaderMap::new())
+    };
+    let restore = |id: &'static str| restore_record_handler(State(state.clone()), path(id), None);
+    let list = || async {
+      let Json(value) = list_records_handler(
+        State(state.clone()),
+        Path("notes_api".to_string()),
+        RawQuery(None),
+        None,
+      )
+      .await?;
+      return Ok::<Vec<String>, anyhow::Error>(
+        value
+          .as_array()
+          .unwrap()
+          .iter()
+          .map(|r| r["body"].as_str().unwrap().to_string())
+          .collect(),
+      );
+    };
+    let count = || async {
+      return query_one_row(conn, "SELECT COUNT(*) FROM note", ())
+        .await?
+        .get::<i64>(0);
+    };
+
+    // Soft-deleted records are retained but hidden.
+    delete("1").await?;
+    assert_eq!(count().await?, 2);
+    assert!(matches!(read("1").await, Err(RecordError::RecordNotFound)));
+    assert_eq!(list().await?, vec!["second"]);
+    assert!(delete("1").await.is_err());
+    assert!(update_record_handler(
+      State(state.clone()),
+      path("1"),
+      None,
+      HeaderMap::new(),
+      Either::Json(serde_json::json!({"body": "updated"})),
+    )
+    .await
+    .is_err());
+
+    // Restored records reappear.
+    restore("1").await?;
+    read("1").await?;
+    assert_eq!(list().await?, vec!["second", "first"]);
+    let mut rows = conn
+      .query(
+        &format!(
+          "SELECT action, new_value FROM {RECORD_HISTORY_TABLE} WHERE record = 1 ORDER BY id"
+        ),
+        (),
+      )
+      .await?;
+    let mut history: Vec<(String, Option<String>)> = vec![];
+    while let Some(row) = rows.next().await? {
+      history.push((row.get(0)?, row.get(1)?));
+    }
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0], ("delete".to_string(), None));
+    assert_eq!(history[1].0, "insert");
+    let restored: serde_json::Value = serde_json::from_str(history[1].1.as_ref().unwrap())?;
+    assert_eq!(restored["body"], "first");
+    assert_eq!(restored["deleted"], serde_json::Value::Null);
+    assert!(matches!(
+      restore("1").await,
+      Err(RecordError::RecordNotFound)
+    ));
+
+    // Clients cannot tombstone records themselves, which would bypass delete access checks once
+    // purged.
+    assert!(matches!(
+      update_record_handler(
+        State(state.clone()),
+        path("1"),
+        None,
+        HeaderMap::new(),
+        Either::Json(serde_json::json!({"deleted": 0})),
+      )
+      .await,
+      Err(RecordError::Forbidden)
+    ));
+    read("1").await?;
+
+    // Soft-deleted records are only purged after the retention period.
+    delete("1").await?;
+    delete("2").await?;
+    assert_eq!(purge_soft_deleted_records(&state).await?, 0);
+
+    conn
+      .execute(
+        "UPDATE note SET deleted = deleted - 31 * 86400 WHERE id = 1",
+        (),
+      )
+      .await?;
+    assert_eq!(purge_soft_deleted_records(&state).await?, 1);
+    assert_eq!(count().await?, 1);
+    assert!(matches!(
+      restore("1").await,
+      Err(RecordError::RecordNotFound)
+    ));
+    restore("2").await?;
+
+    return Ok(());
+  }
+}
@@ -19,7 +19,7 @@ pub enum JsonError {
   ValueNotFound,
 }
 
-fn value_to_json(value: libsql::Value) -> Result<serde_json::Value, JsonError> {
+pub(crate) fn value_to_json(value: libsql::Value) -> Result<serde_json::Value, JsonError> {
   return Ok(match value {
     libsql::Value::Null => serde_json::Value::Null,
     libsql::Value::Real(real) => {
@@ -37,8 +37,8 @@ fn value_to_json(value: libsql::Value) -> Result<serde_json::Value, JsonError> {
 // Serialize libsql row to json.
 pub fn row_to_json(
   metadata: &(dyn TableOrViewMetadata + Send + Sync),
-  row: libsql::Row,
-  column_filter: fn(&str) -> bool,
+  row: &libsql::Row,
+  column_filter: impl Fn(&str) -> bool,
 ) -> Result<serde_json::Value, JsonError> {
   let mut map = serde_json::Map::<String, serde_json::Value>::default();
 
@@ -73,12 +73,12 @@ pub fn row_to_json(
 pub async fn rows_to_json(
   metadata: &(dyn TableOrViewMetadata + Send + Sync),
   mut rows: libsql::Rows,
-  column_filter: fn(&str) -> bool,
+  column_filter: impl Fn(&str) -> bool,
 ) -> Result<Vec<serde_json::Value>, JsonError> {
   let mut objects: Vec<serde_json::Value> = vec![];
 
   while let Some(row) = rows.next().await.map_err(|_err| JsonError::RowNotFound)? {
-    objects.push(row_to_json(metadata, row, column_filter)?);
+    objects.push(row_to_json(metadata, &row, &column_filter)?);
   }
 
   return Ok(objects);
@@ -0,0 +1,508 @@
+use axum::{
+  extract::{Path, State},
+  response::sse::{Event, KeepAlive, Sse},
+};
+use futures::stream::{Stream, StreamExt};
+use log::*;
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::records::json_to_sql::SelectQueryBuilder;
+use crate::records::sql_to_json::row_to_json;
+use crate::records::{Permission, RecordApi, RecordError};
+
+/// Maximum number of events buffered per subscriber. Subscribers that fall further behind are
+/// dropped and need to re-subscribe.
+const EVENT_BUFFER_SIZE: usize = 256;
+
+/// Change event pushed to subscribers.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum DbEvent {
+  Insert(serde_json::Value),
+  Update(serde_json::Value),
+  Delete(serde_json::Value),
+}
+
+impl DbEvent {
+  /// Copy of the event only carrying the record's columns matching the given filter.
+  fn filter_columns(&self, column_filter: impl Fn(&str) -> bool) -> DbEvent {
+    let mut event = self.clone();
+    let (DbEvent::Insert(record) | DbEvent::Update(record) | DbEvent::Delete(record)) = &mut event;
+    if let serde_json::Value::Object(map) = record {
+      map.retain(|col, _| column_filter(col));
+    }
+    return event;
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RecordAction {
+  Insert,
+  Update,
+  Delete,
+}
+
+#[derive(Clone)]
+struct Subscription {
+  id: i64,
+  /// Name of the RecordApi the subscription was established through. Access is evaluated against
+  /// the API's current configuration for every event.
+  api_name: String,
+  /// Only notify about changes to this specific record. Subscribe to all records otherwise.
+  record_id: Option<libsql::Value>,
+  user: Option<User>,
+  sender: async_channel::Sender<DbEvent>,
+}
+
+#[derive(Default)]
+struct ManagerState {
+  next_id: AtomicI64,
+  /// Subscriptions keyed by table name, since multiple APIs can expose the same table.
+  subscriptions: RwLock<HashMap<String, Vec<Subscription>>>,
+}
+
+/// Keeps track of realtime subscriptions and fans out record changes to subscribers.
+#[derive(Clone, Default)]
+pub(crate) struct SubscriptionManager {
+  state: Arc<ManagerState>,
+}
+
+impl SubscriptionManager {
+  pub(crate) fn add_subscription(
+    &self,
+    api: &RecordApi,
+    record_id: Option<libsql::Value>,
+    user: Option<User>,
+  ) -> async_channel::Receiver<DbEvent> {
+    let (sender, receiver) = async_channel::bounded::<DbEvent>(EVENT_BUFFER_SIZE);
+
+    let subscription = Subscription {
+      id: self.state.next_id.fetch_add(1, Ordering::SeqCst),
+      api_name: api.api_name().to_string(),
+      record_id,
+      user,
+      sender,
+    };
+
+    self
+      .state
+      .subscriptions
+      .write()
+      .entry(api.table_name().to_string())
+      .or_default()
+      .push(subscription);
+
+    return receiver;
+  }
+
+  /// Cheap check to avoid materializing changed records when nobody is listening.
+  pub(crate) fn has_subscriptions(&self, table_name: &str) -> bool {
+    return self
+      .state
+      .subscriptions
+      .read()
+      .get(table_name)
+      .is_some_and(|s| !s.is_empty());
+  }
+
+  /// Fans out the change of `record` in `table_name` to all subscribers with read access.
+  pub(crate) async fn broadcast(
+    &self,
+    state: &AppState,
+    table_name: &str,
+    action: RecordAction,
+    record: &libsql::Row,
+  ) {
+    let subscriptions: Vec<Subscription> = match self.state.subscriptions.read().get(table_name) {
+      Some(subscriptions) if !subscriptions.is_empty() => subscriptions.clone(),
+      _ => return,
+    };
+
+    let values: Vec<(String, libsql::Value)> = (0..record.column_count())
+      .filter_map(|i| {
+        let name = record.column_name(i)?;
+        let value = record.get_value(i).ok()?;
+        return Some((name.to_string(), value));
+      })
+      .collect();
+
+    let mut dead: Vec<i64> = vec![];
+    let mut apis: HashMap<String, Option<(RecordApi, DbEvent)>> = HashMap::new();
+
+    for subscription in subscriptions {
+      if subscription.sender.is_closed() {
+        dead.push(subscription.id);
+        continue;
+      }
+
+      let entry = apis
+        .entry(subscription.api_name.clone())
+        .or_insert_with(|| {
+          let api = state.lookup_record_api(&subscription.api_name)?;
+          let json = match row_to_json(api.metadata(), record, |col_name| {
+            !col_name.starts_with("_")
+          }) {
+            Ok(json) => json,
+            Err(err) => {
+              warn!("Failed to serialize change event: {err}");
+              return None;
+            }
+          };
+
+          return Some((
+            api,
+            match action {
+              RecordAction::Insert => DbEvent::Insert(json),
+              RecordAction::Update => DbEvent::Update(json),
+              RecordAction::Delete => DbEvent::Delete(json),
+            },
+          ));
+        });
+
+      let Some((api, event)) = entry else {
+        // The API has been removed or reconfigured, drop the subscription.
+        dead.push(subscription.id);
+        continue;
+      };
+
+      if let Some(ref record_id) = subscription.record_id {
+        let pk_column = &api.record_pk_column().name;
+        let matches = values
+          .iter()
+          .any(|(name, value)| name == pk_column && value == record_id);
+        if !matches {
+          continue;
+        }
+      }
+
+      if api
+        .check_row_level_access(Permission::Read, &values, subscription.user.as_ref())
+        .await
+        .is_err()
+      {
+        continue;
+      }
+
+      // Subscribers only receive the columns they can read.
+      let user = subscription.user.as_ref();
+      let event = event.filter_columns(|col| api.column_readable(col, user));
+      if let Err(err) = subscription.sender.try_send(event) {
+        debug!("Dropping subscription {}: {err}", subscription.id);
+        dead.push(subscription.id);
+      }
+    }
+
+    if !dead.is_empty() {
+      let mut lock = self.state.subscriptions.write();
+      if let Some(subscriptions) = lock.get_mut(table_name) {
+        subscriptions.retain(|s| !dead.contains(&s.id));
+        if subscriptions.is_empty() {
+          lock.remove(table_name);
+        }
+      }
+    }
+  }
+
+  /// Looks up the current state of the given record and broadcasts it, if anyone's listening.
+  pub(crate) async fn broadcast_record(
+    &self,
+    state: &AppState,
+    api: &RecordApi,
+    action: RecordAction,
+    record_id: libsql::Value,
+  ) {
+    let table_name = api.table_name();
+    if !self.has_subscriptions(table_name) {
+      return;
+    }
+
+    match SelectQueryBuilder::run(state, table_name, &api.record_pk_column().name, record_id).await
+    {
+      Ok(Some(row)) => self.broadcast(state, table_name, action, &row).await,
+      Ok(None) => {}
+      Err(err) => warn!("Failed to look up changed record: {err}"),
+    };
+  }
+}
+
+/// Subscribe to changes of a record or, if `*` is given as record id, all records of an API.
+///
+/// Changes are delivered as server-sent events. Only changes the subscriber has read access to are
+/// delivered, which is re-evaluated for every change.
+#[utoipa::path(
+  get,
+  path = "/:name/subscribe/:record",
+  responses(
+    (status = 200, description = "SSE stream of record changes.")
+  )
+)]
+pub async fn add_subscription_sse_handler(
+  State(state): State<AppState>,
+  Path((api_name, record)): Path<(String, String)>,
+  user: Option<User>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, RecordError> {
+  let Some(api) = state.lookup_record_api(&api_name) else {
+    return Err(RecordError::ApiNotFound);
+  };
+
+  let record_id = match record.as_str() {
+    "*" => {
+      api.check_table_level_access(Permission::Read, user.as_ref())?;
+      None
+    }
+    _ => {
+      let record_id = api.id_to_sql(&record)?;
+      api
+        .check_record_level_access(Permission::Read, Some(&record_id), None, user.as_ref())
+        .await?;
+      Some(record_id)
+    }
+  };
+
+  let receiver = state
+    .subscription_manager()
+    .add_subscription(&api, record_id, user);
+
+  return Ok(
+    Sse::new(receiver.map(|event| Event::default().json_data(event)))
+      .keep_alive(KeepAlive::default()),
+  );
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::extract::{Path, Query, State};
+  use axum::http::HeaderMap;
+  use trailbase_sqlite::query_one_row;
+
+  use super::*;
+  use crate::admin::user::*;
+  use crate::app_state::*;
+  use crate::auth::api::login::login_with_password;
+  use crate::config::proto::PermissionFlag;
+  use crate::extract::Either;
+  use crate::records::create_record::{
+    create_record_handler, CreateRecordQuery, CreateRecordResponse,
+  };
+  use crate::records::delete_record::delete_record_handler;
+  use crate::records::test_utils::*;
+  use crate::records::*;
+  use crate::test::unpack_json_response;
+  use crate::util::id_to_b64;
+
+  #[tokio::test]
+  async fn test_subscriptions_respect_read_access() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    let conn = state.conn();
+
+    create_chat_message_app_tables(&state).await?;
+    let room0 = add_room(conn, "room0").await?;
+    let room1 = add_room(conn, "room1").await?;
+    let password = "Secret!1!!";
+
+    add_record_api(
+      &state,
+      "messages_api",
+      "message",
+      Acls {
+        authenticated: vec![
+          PermissionFlag::Create,
+          PermissionFlag::Read,
+          PermissionFlag::Delete,
+        ],
+        ..Default::default()
+      },
+      AccessRules {
+        read: Some(
+          "EXISTS(SELECT 1 FROM room_members WHERE room = _ROW_.room AND user = _USER_.id)"
+            .to_string(),
+        ),
+        delete: Some("_ROW_._owner = _USER_.id".to_string()),
+        ..Default::default()
+      },
+    )
+    .await?;
+
+    let user_x_email = "user_x@test.com";
+    let user_x = create_user_for_test(&state, user_x_email, password)
+      .await?
+      .into_bytes();
+    add_user_to_room(conn, user_x, room0).await?;
+    add_user_to_room(conn, user_x, room1).await?;
+    let user_x_token = login_with_password(&state, user_x_email, password).await?;
+
+    let user_y_email = "user_y@test.com";
+    let user_y = create_user_for_test(&state, user_y_email, password)
+      .await?
+      .into_bytes();
+    add_user_to_room(conn, user_y, room0).await?;
+    let user_y_token = login_with_password(&state, user_y_email, password).await?;
+
+    let api = state.lookup_record_api("messages_api").unwrap();
+    let manager = state.subscription_manager();
+    assert!(!manager.has_subscriptions("message"));
+
+    let user_x_receiver = manager.add_subscription(
+      &api,
+      None,
+      User::from_auth_token(&state, &user_x_token.auth_token),
+    );
+    let user_y_receiver = manager.add_subscription(
+      &api,
+      None,
+      User::from_auth_token(&state, &user_y_token.auth_token),
+    );
+    assert!(manager.has_subscriptions("message"));
+
+    let create = |room: [u8; 16]| {
+      let state = state.clone();
+      let token = user_x_token.auth_token.clone();
+      async move {
+        let response: CreateRecordResponse = unpack_json_response(
+          create_record_handler(
+            State(state.clone()),
+            Path("messages_api".to_string()),
+            Query(CreateRecordQuery::default()),
+            User::from_auth_token(&state, &token),
+            Either::Json(serde_json::json!({
+              "_owner": id_to_b64(&user_x),
+              "room": id_to_b64(&room),
+              "data": "message",
+            })),
+          )
+          .await
+          .unwrap(),
+        )
+        .await
+        .unwrap();
+        response.id
+      }
+    };
+
+    // Both users are members of room0.
+    let id0 = create(room0).await;
+    for receiver in [&user_x_receiver, &user_y_receiver] {
+      let DbEvent::Insert(value) = receiver.try_recv()? else {
+        panic!("expected insert");
+      };
+      assert_eq!(value["id"], serde_json::Value::String(id0.clone()));
+      assert!(value.get("_owner").is_none());
+    }
+
+    // Only user x is a member of room1.
+    let id1 = create(room1).await;
+    let DbEvent::Insert(value) = user_x_receiver.try_recv()? else {
+      panic!("expected insert");
+    };
+    assert_eq!(value["id"], serde_json::Value::String(id1.clone()));
+    assert!(user_y_receiver.try_recv().is_err());
+
+    // Deletions are delivered even though the record no longer exists.
+    delete_record_handler(
+      State(state.clone()),
+      Path(("messages_api".to_string(), id0.clone())),
+      User::from_auth_token(&state, &user_x_token.auth_token),
+      HeaderMap::new(),
+    )
+    .await?;
+    for receiver in [&user_x_receiver, &user_y_receiver] {
+      let DbEvent::Delete(value) = receiver.try_recv()? else {
+        panic!("expected delete");
+      };
+      assert_eq!(value["id"], serde_json::Value::String(id0.clone()));
+    }
+
+    // Dropped subscribers get cleaned up on the next change.
+    drop(user_y_receiver);
+    let _id2 = create(room0).await;
+    assert!(matches!(user_x_receiver.try_recv()?, DbEvent::Insert(_)));
+    assert_eq!(
+      state
+        .subscription_manager()
+        .state
+        .subscriptions
+        .read()
+        .get("message")
+        .unwrap()
+        .len(),
+      1
+    );
+
+    let count: i64 = query_one_row(conn, "SELECT COUNT(*) FROM message", ())
+      .await?
+      .get(0)?;
+    assert_eq!(count, 2);
+
+    return Ok(());
+  }
+
+  #[tokio::test]
+  async fn test_record_subscription() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    let conn = state.conn();
+
+    create_chat_message_app_tables(&state).await?;
+    let room = add_room(conn, "room0").await?;
+    let password = "Secret!1!!";
+
+    add_record_api(
+      &state,
+      "messages_api",
+      "message",
+      Acls {
+        authenticated: vec![PermissionFlag::Read],
+        ..Default::default()
+      },
+      AccessRules::default(),
+    )
+    .await?;
+
+    let user_x_email = "user_x@test.com";
+    let user_x = create_user_for_test(&state, user_x_email, password)
+      .await?
+      .into_bytes();
+    let user_x_token = login_with_password(&state, user_x_email, password).await?;
+
+    let message0 = send_message(conn, user_x, room, "msg0").await?;
+    let message1 = send_message(conn, user_x, room, "msg1").await?;
+
+    // Unauthenticated users cannot subscribe.
+    assert!(add_subscription_sse_handler(
+      State(state.clone()),
+      Path(("messages_api".to_string(), id_to_b64(&message0))),
+      None,
+    )
+    .await
+    .is_err());
+
+    let api = state.lookup_record_api("messages_api").unwrap();
+    let receiver = state.subscription_manager().add_subscription(
+      &api,
+      Some(libsql::Value::Blob(message0.to_vec())),
+      User::from_auth_token(&state, &user_x_token.auth_token),
+    );
+
+    for (id, action) in [
+      (message1, RecordAction::Update),
+      (message0, RecordAction::Update),
+    ] {
+      state
+        .subscription_manager()
+        .broadcast_record(&state, &api, action, libsql::Value::Blob(id.to_vec()))
+        .await;
+    }
+
+    let DbEvent::Update(value) = receiver.try_recv()? else {
+      panic!("expected update");
+    };
+    assert_eq!(value["data"], "msg0");
+    assert!(receiver.try_recv().is_err());
+
+    return Ok(());
+  }
+}
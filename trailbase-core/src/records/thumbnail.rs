@@ -0,0 +1,509 @@
+use axum::body::Body;
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use futures::TryStreamExt;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageReader, Limits};
+use object_store::{path::Path, ObjectStore, PutPayload};
+use serde::Deserialize;
+use trailbase_sqlite::schema::FileUpload;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::app_state::AppState;
+use crate::records::RecordError;
+
+/// Supported widths and heights of generated variants. Restricting variants to a small set of sizes
+/// bounds the number of variants, and thus the work and storage, per image.
+const SIZES: [u32; 7] = [32, 64, 128, 256, 512, 1024, 2048];
+const MAX_SIZE: u32 = SIZES[SIZES.len() - 1];
+
+/// Upper bounds for decoding originals to not exhaust memory on decompression bombs.
+const MAX_SOURCE_DIMENSION: u32 = 16384;
+const MAX_SOURCE_ALLOC: u64 = 256 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailFit {
+  /// Scale to fit within the given dimensions, preserving the aspect ratio.
+  #[default]
+  Contain,
+  /// Scale and crop to fill the given dimensions, preserving the aspect ratio.
+  Cover,
+  /// Scale to exactly the given dimensions, ignoring the aspect ratio.
+  Fill,
+}
+
+impl ThumbnailFit {
+  fn name(&self) -> &'static str {
+    return match self {
+      Self::Contain => "contain",
+      Self::Cover => "cover",
+      Self::Fill => "fill",
+    };
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailFormat {
+  Jpeg,
+  Png,
+  Webp,
+}
+
+impl ThumbnailFormat {
+  fn from_mime_type(mime_type: &str) -> Option<Self> {
+    return match mime_type {
+      "image/jpeg" => Some(Self::Jpeg),
+      "image/png" => Some(Self::Png),
+      "image/webp" => Some(Self::Webp),
+      _ => None,
+    };
+  }
+
+  fn image_format(&self) -> ImageFormat {
+    return match self {
+      Self::Jpeg => ImageFormat::Jpeg,
+      Self::Png => ImageFormat::Png,
+      Self::Webp => ImageFormat::WebP,
+    };
+  }
+
+  fn mime_type(&self) -> &'static str {
+    return match self {
+      Self::Jpeg => "image/jpeg",
+      Self::Png => "image/png",
+      Self::Webp => "image/webp",
+    };
+  }
+
+  fn name(&self) -> &'static str {
+    return match self {
+      Self::Jpeg => "jpeg",
+      Self::Png => "png",
+      Self::Webp => "webp",
+    };
+  }
+
+  fn extension(&self) -> &'static str {
+    return match self {
+      Self::Jpeg => "jpg",
+      Self::Png => "png",
+      Self::Webp => "webp",
+    };
+  }
+}
+
+/// Query parameters for requesting resized variants of uploaded images.
+///
+/// Variants are never larger than the original in either dimension.
+#[derive(Clone, Debug, Default, Deserialize, IntoParams)]
+pub struct ThumbnailQuery {
+  /// Width in pixels, one of 32, 64, 128, 256, 512, 1024 or 2048. Derived from the height and
+  /// aspect ratio if absent.
+  pub w: Option<u32>,
+  /// Height in pixels, one of 32, 64, 128, 256, 512, 1024 or 2048. Derived from the width and
+  /// aspect ratio if absent.
+  pub h: Option<u32>,
+  pub fit: Option<ThumbnailFit>,
+  /// Output format. Defaults to the original's format, or PNG for other image types.
+  pub format: Option<ThumbnailFormat>,
+}
+
+impl ThumbnailQuery {
+  fn is_empty(&self) -> bool {
+    return self.w.is_none() && self.h.is_none() && self.fit.is_none() && self.format.is_none();
+  }
+
+  /// Query string of the requested variant, e.g. "w=128&fit=cover", or an empty string.
+  pub(crate) fn to_query_string(&self) -> String {
+    let params = [
+      self.w.map(|w| format!("w={w}")),
+      self.h.map(|h| format!("h={h}")),
+      self.fit.map(|fit| format!("fit={}", fit.name())),
+      self
+        .format
+        .map(|format| format!("format={}", format.name())),
+    ];
+    return params.into_iter().flatten().collect::<Vec<_>>().join("&");
+  }
+
+  fn validate(&self) -> Result<(), RecordError> {
+    for size in [self.w, self.h].into_iter().flatten() {
+      if !SIZES.contains(&size) {
+        return Err(RecordError::BadRequest("Invalid thumbnail dimensions"));
+      }
+    }
+    return Ok(());
+  }
+}
+
+/// Variants are stored in a directory next to the original, which is named after the original's
+/// unique id. Replaced files get new ids and thus never serve stale variants.
+fn variants_dir(file_upload: &FileUpload) -> Path {
+  return Path::from(format!("{}.variants", file_upload.path()));
+}
+
+fn resize(
+  image: DynamicImage,
+  w: Option<u32>,
+  h: Option<u32>,
+  fit: ThumbnailFit,
+) -> Result<DynamicImage, RecordError> {
+  let (width, height) = (image.width().max(1), image.height().max(1));
+  // Derived dimensions are bounded, since extreme aspect ratios would otherwise yield huge variants.
+  let derive = |a: u32, b: u32, c: u32| -> u32 {
+    return ((a as u64 * b as u64) / c as u64).clamp(1, MAX_SIZE as u64) as u32;
+  };
+  let (w, h) = match (w, h) {
+    (Some(w), Some(h)) => (w, h),
+    (Some(w), None) => {
+      let w = w.min(width);
+      (w, derive(w, height, width))
+    }
+    (None, Some(h)) => {
+      let h = h.min(height);
+      (derive(h, width, height), h)
+    }
+    (None, None) => return Ok(image),
+  };
+  // Never upscale beyond the original.
+  let (w, h) = (w.min(width), h.min(height));
+
+  return Ok(match fit {
+    ThumbnailFit::Contain => image.resize(w, h, FilterType::Lanczos3),
+    ThumbnailFit::Cover => {
+      // Crop to the target aspect ratio before scaling rather than scaling to cover first, which
+      // would allocate an intermediate image of up to the original's size.
+      let (crop_w, crop_h) = if width as u64 * h as u64 > height as u64 * w as u64 {
+        (
+          ((height as u64 * w as u64) / h as u64).max(1) as u32,
+          height,
+        )
+      } else {
+        (width, ((width as u64 * h as u64) / w as u64).max(1) as u32)
+      };
+      image
+        .crop_imm((width - crop_w) / 2, (height - crop_h) / 2, crop_w, crop_h)
+        .resize_exact(w, h, FilterType::Lanczos3)
+    }
+    ThumbnailFit::Fill => image.resize_exact(w, h, FilterType::Lanczos3),
+  });
+}
+
+fn decode(data: &[u8]) -> Result<DynamicImage, RecordError> {
+  let mut limits = Limits::default();
+  limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+  limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+  limits.max_alloc = Some(MAX_SOURCE_ALLOC);
+
+  let mut reader = ImageReader::new(std::io::Cursor::new(data))
+    .with_guessed_format()
+    .map_err(|err| RecordError::Internal(err.into()))?;
+  reader.limits(limits);
+
+  return reader
+    .decode()
+    .map_err(|_err| RecordError::BadRequest("Unsupported image"));
+}
+
+fn encode(image: DynamicImage, format: ThumbnailFormat) -> Result<Vec<u8>, RecordError> {
+  // JPEG doesn't support transparency.
+  let image = match format {
+    ThumbnailFormat::Jpeg => DynamicImage::ImageRgb8(image.into_rgb8()),
+    _ => image,
+  };
+
+  let mut buffer = std::io::Cursor::new(Vec::<u8>::new());
+  image
+    .write_to(&mut buffer, format.image_format())
+    .map_err(|err| RecordError::Internal(err.into()))?;
+  return Ok(buffer.into_inner());
+}
+
+/// Responds with a resized variant of the given image, generating and caching it in the object
+/// store on first request.
+///
+/// Returns None if no variant was requested, i.e. the original should be served.
+pub(crate) async fn read_thumbnail_into_response(
+  state: &AppState,
+  file_upload: &FileUpload,
+  query: &ThumbnailQuery,
+) -> Result<Option<Response>, RecordError> {
+  if query.is_empty() {
+    return Ok(None);
+  }
+  query.validate()?;
+
+  // Only rely on the inferred rather than the user-provided type.
+  let Some(mime_type) = file_upload.mime_type().filter(|m| m.starts_with("image/")) else {
+    return Err(RecordError::BadRequest("Not an image"));
+  };
+  let format = query
+    .format
+    .or_else(|| ThumbnailFormat::from_mime_type(mime_type))
+    .unwrap_or(ThumbnailFormat::Png);
+  let fit = query.fit.unwrap_or_default();
+
+  let variant_path = variants_dir(file_upload).child(format!(
+    "{w}x{h}-{fit}.{extension}",
+    w = query
+      .w
+      .map_or_else(|| "auto".to_string(), |w| w.to_string()),
+    h = query
+      .h
+      .map_or_else(|| "auto".to_string(), |h| h.to_string()),
+    fit = fit.name(),
+    extension = format.extension(),
+  ));
+
+  let store = state.objectstore();
+  let contents = match store.get(&variant_path).await {
+    Ok(result) => result
+      .bytes()
+      .await
+      .map_err(|err| RecordError::Internal(err.into()))?
+      .to_vec(),
+    Err(object_store::Error::NotFound { .. }) => {
+      let original = store
+        .get(&Path::from(file_upload.path()))
+        .await
+        .map_err(|err| RecordError::Internal(err.into()))?
+        .bytes()
+        .await
+        .map_err(|err| RecordError::Internal(err.into()))?;
+
+      let (w, h) = (query.w, query.h);
+      let contents = tokio::task::spawn_blocking(move || {
+        return encode(resize(decode(&original)?, w, h, fit)?, format);
+      })
+      .await
+      .map_err(|err| RecordError::Internal(err.into()))??;
+
+      store
+        .put(&variant_path, PutPayload::from(contents.clone()))
+        .await
+        .map_err(|err| RecordError::Internal(err.into()))?;
+
+      contents
+    }
+    Err(err) => return Err(RecordError::Internal(err.into())),
+  };
+
+  return Ok(Some(
+    (
+      [
+        (header::CONTENT_TYPE, format.mime_type()),
+        (header::CONTENT_DISPOSITION, "attachment"),
+      ],
+      Body::from(contents),
+    )
+      .into_response(),
+  ));
+}
+
+/// Deletes all cached variants of the given file.
+pub(crate) async fn delete_thumbnails(
+  store: &dyn ObjectStore,
+  file_upload: &FileUpload,
+) -> Result<(), object_store::Error> {
+  let variants: Vec<_> = store
+    .list(Some(&variants_dir(file_upload)))
+    .try_collect()
+    .await?;
+
+  for variant in variants {
+    store.delete(&variant.location).await?;
+  }
+  return Ok(());
+}
+
+#[cfg(test)]
+mod test {
+  use image::{ImageBuffer, Rgba};
+
+  use super::*;
+  use crate::app_state::test_state;
+
+  fn png(width: u32, height: u32) -> Vec<u8> {
+    let image = ImageBuffer::from_fn(width, height, |x, _y| Rgba([(x % 256) as u8, 0, 0, 255]));
+    let mut buffer = std::io::Cursor::new(Vec::<u8>::new());
+    DynamicImage::ImageRgba8(image)
+      .write_to(&mut buffer, ImageFormat::Png)
+      .unwrap();
+    return buffer.into_inner();
+  }
+
+  #[tokio::test]
+  async fn test_thumbnails() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    let store = state.objectstore();
+
+    let file_upload = FileUpload::new(
+      uuid::Uuid::new_v4(),
+      Some("photo.png".to_string()),
+      Some("image/png".to_string()),
+      Some("image/png".to_string()),
+    );
+    store
+      .put(
+        &Path::from(file_upload.path()),
+        PutPayload::from(png(400, 200)),
+      )
+      .await?;
+
+    let thumbnail = |query: ThumbnailQuery| {
+      let (state, file_upload) = (state.clone(), file_upload.clone());
+      async move {
+        let response = read_thumbnail_into_response(&state, &file_upload, &query)
+          .await?
+          .unwrap();
+        let content_type = response.headers()[header::CONTENT_TYPE]
+          .to_str()?
+          .to_string();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        let image = image::load_from_memory(&bytes)?;
+        return Ok::<_, anyhow::Error>((content_type, image.width(), image.height()));
+      }
+    };
+
+    // Without parameters, the original is served.
+    assert!(
+      read_thumbnail_into_response(&state, &file_upload, &ThumbnailQuery::default())
+        .await?
+        .is_none()
+    );
+
+    assert_eq!(
+      thumbnail(ThumbnailQuery {
+        w: Some(128),
+        ..Default::default()
+      })
+      .await?,
+      ("image/png".to_string(), 128, 64)
+    );
+    assert_eq!(
+      thumbnail(ThumbnailQuery {
+        w: Some(128),
+        h: Some(128),
+        ..Default::default()
+      })
+      .await?,
+      ("image/png".to_string(), 128, 64)
+    );
+    assert_eq!(
+      thumbnail(ThumbnailQuery {
+        w: Some(128),
+        h: Some(128),
+        fit: Some(ThumbnailFit::Cover),
+        format: Some(ThumbnailFormat::Webp),
+      })
+      .await?,
+      ("image/webp".to_string(), 128, 128)
+    );
+    assert_eq!(
+      thumbnail(ThumbnailQuery {
+        w: Some(32),
+        h: Some(64),
+        fit: Some(ThumbnailFit::Fill),
+        format: Some(ThumbnailFormat::Jpeg),
+      })
+      .await?,
+      ("image/jpeg".to_string(), 32, 64)
+    );
+
+    // Variants are cached.
+    let variants: Vec<_> = store
+      .list(Some(&variants_dir(&file_upload)))
+      .try_collect()
+      .await?;
+    assert_eq!(variants.len(), 4);
+    assert_eq!(
+      thumbnail(ThumbnailQuery {
+        w: Some(128),
+        ..Default::default()
+      })
+      .await?,
+      ("image/png".to_string(), 128, 64)
+    );
+
+    // Variants are never upscaled.
+    assert_eq!(
+      thumbnail(ThumbnailQuery {
+        w: Some(2048),
+        ..Default::default()
+      })
+      .await?,
+      ("image/png".to_string(), 400, 200)
+    );
+
+    // Only a fixed set of sizes is supported.
+    for (w, h) in [(Some(100), None), (Some(128), Some(4096)), (Some(0), None)] {
+      assert!(matches!(
+        thumbnail(ThumbnailQuery {
+          w,
+          h,
+          ..Default::default()
+        })
+        .await
+        .unwrap_err()
+        .downcast::<RecordError>()?,
+        RecordError::BadRequest(_)
+      ));
+    }
+
+    let text_upload = FileUpload::new(
+      uuid::Uuid::new_v4(),
+      Some("photo.png".to_string()),
+      Some("image/png".to_string()),
+      Some("text/plain".to_string()),
+    );
+    assert!(matches!(
+      read_thumbnail_into_response(
+        &state,
+        &text_upload,
+        &ThumbnailQuery {
+          w: Some(128),
+          ..Default::default()
+        }
+      )
+      .await,
+      Err(RecordError::BadRequest(_))
+    ));
+
+    // Deleting variants retains the original.
+    delete_thumbnails(store, &file_upload).await?;
+    let variants: Vec<_> = store
+      .list(Some(&variants_dir(&file_upload)))
+      .try_collect()
+      .await?;
+    assert!(variants.is_empty());
+    store.head(&Path::from(file_upload.path())).await?;
+
+    return Ok(());
+  }
+
+  #[test]
+  fn test_resize_extreme_aspect_ratio() {
+    let image = image::load_from_memory(&png(16384, 1)).unwrap();
+
+    let contain = resize(image.clone(), None, Some(128), ThumbnailFit::Contain).unwrap();
+    assert_eq!((contain.width(), contain.height()), (MAX_SIZE, 1));
+
+    let contain = resize(image.clone(), Some(32), None, ThumbnailFit::Contain).unwrap();
+    assert_eq!((contain.width(), contain.height()), (32, 1));
+
+    let cover = resize(image.clone(), Some(128), Some(128), ThumbnailFit::Cover).unwrap();
+    assert_eq!((cover.width(), cover.height()), (128, 1));
+
+    let fill = resize(image, Some(2048), Some(2048), ThumbnailFit::Fill).unwrap();
+    assert_eq!((fill.width(), fill.height()), (2048, 1));
+
+    let image = image::load_from_memory(&png(1, 16384)).unwrap();
+    let contain = resize(image.clone(), Some(128), None, ThumbnailFit::Contain).unwrap();
+    assert_eq!((contain.width(), contain.height()), (1, MAX_SIZE));
+
+    let cover = resize(image, Some(64), Some(32), ThumbnailFit::Cover).unwrap();
+    assert_eq!((cover.width(), cover.height()), (1, 32));
+  }
+}
@@ -0,0 +1,780 @@
+use axum::extract::{Json, State};
+use axum::response::{IntoResponse, Response};
+use base64::prelude::*;
+use log::*;
+use serde::{Deserialize, Serialize};
+use trailbase_sqlite::query_one_row;
+use utoipa::ToSchema;
+
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::js::RecordHookEvent;
+use crate::records::create_record::autofill_missing_user_id_columns;
+use crate::records::files::delete_files_in_row;
+use crate::records::history::{append_history, history_snapshot};
+use crate::records::hooks::{run_after_record_hook, run_before_record_hook};
+use crate::records::json_to_sql::{InsertQueryBuilder, LazyParams, Params};
+use crate::records::soft_delete::soft_delete_record;
+use crate::records::subscribe::RecordAction;
+use crate::records::webhooks::enqueue_webhook_deliveries;
+use crate::records::{Permission, RecordApi, RecordError};
+
+/// Upper bound on the number of operations in a single transaction.
+const MAX_OPERATIONS: usize = 256;
+
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub enum Operation {
+  Create {
+    api_name: String,
+    value: serde_json::Value,
+  },
+  Update {
+    api_name: String,
+    record_id: String,
+    value: serde_json::Value,
+  },
+  Delete {
+    api_name: String,
+    record_id: String,
+  },
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct TransactionRequest {
+  pub operations: Vec<Operation>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct TransactionResponse {
+  /// Safe-url base64 encoded or integer ids of the affected records in the order of operations.
+  pub ids: Vec<String>,
+}
+
+/// Error of a transaction, which has been rolled back in its entirety.
+#[derive(Debug)]
+pub struct TransactionError {
+  /// Index of the failed operation, if the failure can be attributed to a specific operation.
+  operation: Option<usize>,
+  error: RecordError,
+}
+
+impl From<RecordError> for TransactionError {
+  fn from(error: RecordError) -> Self {
+    return TransactionError {
+      operation: None,
+      error,
+    };
+  }
+}
+
+impl From<libsql::Error> for TransactionError {
+  fn from(err: libsql::Error) -> Self {
+    return RecordError::from(err).into();
+  }
+}
+
+impl IntoResponse for TransactionError {
+  fn into_response(self) -> Response {
+    let message = match self.error {
+      RecordError::Internal(_) if !cfg!(debug_assertions) => None,
+      ref err => Some(err.to_string()),
+    };
+    let status = self.error.into_response().status();
+
+    return (
+      status,
+      Json(serde_json::json!({
+        "operation": self.operation,
+        "error": message,
+      })),
+    )
+      .into_response();
+  }
+}
+
+/// Operation, which passed hooks and access checks and is ready to be applied.
+enum Prepared {
+  Create(RecordApi, Params),
+  Update(RecordApi, String, libsql::Value, Params),
+  Delete(RecordApi, String, libsql::Value),
+}
+
+/// Side-effects that must only be applied after the transaction has been committed successfully.
+enum Committed {
+  Upserted(RecordApi, RecordAction, libsql::Value),
+  Deleted(RecordApi, libsql::Value, libsql::Row),
+}
+
+/// Atomically execute create, update and delete operations across record APIs.
+///
+/// Operations are applied in order within a single transaction, each one subject to the respective
+/// API's ACLs and access rules. On the first failure, all operations are rolled back. Note that
+/// hooks and access checks run ahead of the transaction, i.e. access rules are evaluated against
+/// the state prior to any of the operations.
+#[utoipa::path(
+  post,
+  path = "/execute",
+  request_body = TransactionRequest,
+  responses(
+    (status = 200, description = "Ids of affected records.", body = TransactionResponse),
+  )
+)]
+pub async fn record_transactions_handler(
+  State(state): State<AppState>,
+  user: Option<User>,
+  Json(request): Json<TransactionRequest>,
+) -> Result<Json<TransactionResponse>, TransactionError> {
+  if request.operations.len() > MAX_OPERATIONS {
+    return Err(RecordError::BadRequest("Too many operations").into());
+  }
+
+  // Run hooks and access checks up-front to keep the transaction short, since write transactions
+  // are serialized.
+  let mut prepared: Vec<Prepared> = Vec::with_capacity(request.operations.len());
+  for (index, operation) in request.operations.into_iter().enumerate() {
+    let result = match operation {
+      Operation::Create { api_name, value } => {
+        prepare_create(&state, &api_name, value, user.as_ref()).await
+      }
+      Operation::Update {
+        api_name,
+        record_id,
+        value,
+      } => prepare_update(&state, &api_name, record_id, value, user.as_ref()).await,
+      Operation::Delete {
+        api_name,
+        record_id,
+      } => prepare_delete(&state, &api_name, record_id, user.as_ref()).await,
+    };
+
+    match result {
+      Ok(p) => prepared.push(p),
+      Err(err) => {
+        return Err(TransactionError {
+          operation: Some(index),
+          error: err,
+        });
+      }
+    };
+  }
+
+  let tx = state.write_transaction().await?;
+
+  let mut ids: Vec<String> = Vec::with_capacity(prepared.len());
+  let mut committed: Vec<Committed> = Vec::with_capacity(prepared.len());
+
+  for (index, p) in prepared.into_iter().enumerate() {
+    let result = match p {
+      Prepared::Create(api, params) => create(&tx, &state, api, params, user.as_ref()).await,
+      Prepared::Update(api, record, record_id, params) => {
+        update(&tx, &state, api, record, record_id, params, user.as_ref()).await
+      }
+      Prepared::Delete(api, record, record_id) => {
+        delete(&tx, &state, api, record, record_id, user.as_ref()).await
+      }
+    };
+
+    match result {
+      Ok((id, c)) => {
+        ids.push(id);
+        committed.push(c);
+      }
+      Err(err) => {
+        if let Err(err) = tx.rollback().await {
+          warn!("Failed to roll back transaction: {err}");
+        }
+        return Err(TransactionError {
+          operation: Some(index),
+          error: err,
+        });
+      }
+    };
+  }
+
+  tx.commit().await?;
+
+  let manager = state.subscription_manager();
+  for c in committed {
+    match c {
+      Committed::Upserted(api, action, record_id) => {
+        manager
+          .broadcast_record(&state, &api, action, record_id.clone())
+          .await;
+
+        let event = match action {
+          RecordAction::Insert => RecordHookEvent::Create,
+          _ => RecordHookEvent::Update,
+        };
+        run_after_record_hook(&state, &api, event, user.as_ref(), &record_id, None).await;
+      }
+      Committed::Deleted(api, record_id, row) => {
+        // Files of soft-deleted records are retained until they're purged.
+        if let (None, Some(table_metadata)) = (api.soft_delete_column(), api.table_metadata()) {
+          if let Err(err) = delete_files_in_row(&state, table_metadata, &row).await {
+            warn!("Failed to delete files of deleted record: {err}");
+          }
+        }
+        manager
+          .broadcast(&state, api.table_name(), RecordAction::Delete, &row)
+          .await;
+
+        run_after_record_hook(
+          &state,
+          &api,
+          RecordHookEvent::Delete,
+          user.as_ref(),
+          &record_id,
+          Some(&row),
+        )
+        .await;
+      }
+    }
+  }
+
+  return Ok(Json(TransactionResponse { ids }));
+}
+
+async fn prepare_create(
+  state: &AppState,
+  api_name: &str,
+  value: serde_json::Value,
+  user: Option<&User>,
+) -> Result<Prepared, RecordError> {
+  let Some(api) = state.lookup_record_api(api_name) else {
+    return Err(RecordError::ApiNotFound);
+  };
+  let table_metadata = api
+    .table_metadata()
+    .ok_or_else(|| RecordError::ApiRequiresTable)?;
+
+  // Record-level access can only be checked once hooks had a chance to amend the value.
+  api.check_table_level_access(Permission::Create, user)?;
+  let value = run_before_record_hook(
+    state,
+    &api,
+    RecordHookEvent::Create,
+    user,
+    None,
+    Some(value),
+  )
+  .await?
+  .unwrap_or_default();
+
+  let mut lazy_params = LazyParams::new(table_metadata, value, None);
+  api
+    .check_record_level_access(Permission::Create, None, Some(&mut lazy_params), user)
+    .await?;
+
+  let Ok(mut params) = lazy_params.consume() else {
+    return Err(RecordError::BadRequest("Parameter conversion"));
+  };
+  reject_files(&params)?;
+
+  if api.insert_autofill_missing_user_id_columns() {
+    autofill_missing_user_id_columns(table_metadata, &mut params, user);
+  }
+
+  return Ok(Prepared::Create(api, params));
+}
+
+async fn create(
+  tx: &libsql::Connection,
+  state: &AppState,
+  api: RecordApi,
+  params: Params,
+  user: Option<&User>,
+) -> Result<(String, Committed), RecordError> {
+  let (query, named_params, _files) =
+    InsertQueryBuilder::build_insert_query(params, api.insert_conflict_resolution_strategy())
+      .map_err(|err| RecordError::Internal(err.into()))?;
+
+  let pk_column = &api.record_pk_column().name;
+  let row = query_one_row(
+    tx,
+    &format!("{query} RETURNING [{pk_column}]"),
+    named_params,
+  )
+  .await?;
+  let record_id = row.get_value(0)?;
+
+  let new = history_snapshot(tx, &api, &record_id).await?;
+  append_history(
+    tx,
+    &api,
+    RecordAction::Insert,
+    &record_id,
+    user,
+    None,
+    new.as_ref(),
+  )
+  .await?;
+  enqueue_webhook_deliveries(
+    state,
+    tx,
+    &api,
+    RecordAction::Insert,
+    &record_id,
+    new.as_ref(),
+  )
+  .await?;
+
+  return Ok((
+    record_id_to_string(&record_id)?,
+    Committed::Upserted(api, RecordAction::Insert, record_id),
+  ));
+}
+
+async fn prepare_update(
+  state: &AppState,
+  api_name: &str,
+  record: String,
+  value: serde_json::Value,
+  user: Option<&User>,
+) -> Result<Prepared, RecordError> {
+  let Some(api) = state.lookup_record_api(api_name) else {
+    return Err(RecordError::ApiNotFound);
+  };
+  let table_metadata = api
+    .table_metadata()
+    .ok_or_else(|| RecordError::ApiRequiresTable)?;
+
+  let record_id = api.id_to_sql(&record)?;
+
+  api.check_table_level_access(Permission::Update, user)?;
+  let value = run_before_record_hook(
+    state,
+    &api,
+    RecordHookEvent::Update,
+    user,
+    Some(&record_id),
+    Some(value),
+  )
+  .await?
+  .unwrap_or_default();
+
+  let mut lazy_params = LazyParams::new(table_metadata, value, None);
+  api
+    .check_record_level_access(
+      Permission::Update,
+      Some(&record_id),
+      Some(&mut lazy_params),
+      user,
+    )
+    .await?;
+  api.check_not_soft_deleted(&record_id).await?;
+
+  let Ok(params) = lazy_params.consume() else {
+    return Err(RecordError::BadRequest("Parameter conversion"));
+  };
+  reject_files(&params)?;
+
+  return Ok(Prepared::Update(api, record, record_id, params));
+}
+
+async fn update(
+  tx: &libsql::Connection,
+  state: &AppState,
+  api: RecordApi,
+  record: String,
+  record_id: libsql::Value,
+  params: Params,
+  user: Option<&User>,
+) -> Result<(String, Committed), RecordError> {
+  if !params.column_names().is_empty() {
+    let setters = std::iter::zip(params.column_names(), params.named_params())
+      .map(|(col_name, (placeholder, _value))| format!("[{col_name}] = {placeholder}"))
+      .collect::<Vec<_>>()
+      .join(", ");
+
+    let old = history_snapshot(tx, &api, &record_id).await?;
+
+    let mut named_params = params.named_params().clone();
+    named_params.push((":__record_id".to_string(), record_id.clone()));
+
+    let rows_affected = tx
+      .execute(
+        &format!(
+          "UPDATE '{table_name}' SET {setters} WHERE [{pk_column}] = :__record_id",
+          table_name = api.table_name(),
+          pk_column = api.record_pk_column().name,
+        ),
+        libsql::params::Params::Named(named_params),
+      )
+      .await?;
+
+    if rows_affected == 0 {
+      return Err(RecordError::RecordNotFound);
+    }
+
+    let new = history_snapshot(tx, &api, &record_id).await?;
+    append_history(
+      tx,
+      &api,
+      RecordAction::Update,
+      &record_id,
+      user,
+      old.as_ref(),
+      new.as_ref(),
+    )
+    .await?;
+    enqueue_webhook_deliveries(
+      state,
+      tx,
+      &api,
+      RecordAction::Update,
+      &record_id,
+      new.as_ref(),
+    )
+    .await?;
+  }
+
+  return Ok((
+    record,
+    Committed::Upserted(api, RecordAction::Update, record_id),
+  ));
+}
+
+async fn prepare_delete(
+  state: &AppState,
+  api_name: &str,
+  record: String,
+  user: Option<&User>,
+) -> Result<Prepared, RecordError> {
+  let Some(api) = state.lookup_record_api(api_name) else {
+    return Err(RecordError::ApiNotFound);
+  };
+  if api.table_metadata().is_none() {
+    return Err(RecordError::ApiRequiresTable);
+  }
+
+  let record_id = api.id_to_sql(&record)?;
+
+  api
+    .check_record_level_access(Permission::Delete, Some(&record_id), None, user)
+    .await?;
+  run_before_record_hook(
+    state,
+    &api,
+    RecordHookEvent::Delete,
+    user,
+    Some(&record_id),
+    None,
+  )
+  .await?;
+
+  return Ok(Prepared::Delete(api, record, record_id));
+}
+
+async fn delete(
+  tx: &libsql::Connection,
+  state: &AppState,
+  api: RecordApi,
+  record: String,
+  record_id: libsql::Value,
+  user: Option<&User>,
+) -> Result<(String, Committed), RecordError> {
+  let row = match api.soft_delete_column() {
+    Some(_) => soft_delete_record(tx, &api, record_id.clone()).await?,
+    None => {
+      query_one_row(
+        tx,
+        &format!(
+          "DELETE FROM '{table_name}' WHERE [{pk_column}] = $1 RETURNING *",
+          table_name = api.table_name(),
+          pk_column = api.record_pk_column().name,
+        ),
+        [record_id.clone()],
+      )
+      .await?
+    }
+  };
+
+  append_history(
+    tx,
+    &api,
+    RecordAction::Delete,
+    &record_id,
+    user,
+    Some(&row),
+    None,
+  )
+  .await?;
+  enqueue_webhook_deliveries(
+    state,
+    tx,
+    &api,
+    RecordAction::Delete,
+    &record_id,
+    Some(&row),
+  )
+  .await?;
+
+  return Ok((record, Committed::Deleted(api, record_id, row)));
+}
+
+/// Files are written to the object store outside the transaction, which would leave them dangling
+/// on rollback. Thus, uploads have to go through the regular record APIs.
+#[inline]
+fn reject_files(params: &Params) -> Result<(), RecordError> {
+  if params.has_files() {
+    return Err(RecordError::BadRequest("File uploads not supported"));
+  }
+  return Ok(());
+}
+
+fn record_id_to_string(record_id: &libsql::Value) -> Result<String, RecordError> {
+  return match record_id {
+    libsql::Value::Blob(blob) => Ok(BASE64_URL_SAFE.encode(blob)),
+    libsql::Value::Integer(integer) => Ok(integer.to_string()),
+    _ => Err(RecordError::Internal(
+      format!("Unexpected record id: {record_id:?}").into(),
+    )),
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::extract::{Json, State};
+  use axum::response::IntoResponse;
+  use trailbase_sqlite::query_one_row;
+
+  use super::*;
+  use crate::admin::user::*;
+  use crate::app_state::*;
+  use crate::auth::api::login::login_with_password;
+  use crate::config::proto::PermissionFlag;
+  use crate::records::test_utils::*;
+  use crate::records::*;
+  use crate::util::id_to_b64;
+
+  async fn count_messages(state: &AppState) -> i64 {
+    return query_one_row(state.conn(), "SELECT COUNT(*) FROM message", ())
+      .await
+      .unwrap()
+      .get(0)
+      .unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_transaction() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    let conn = state.conn();
+
+    create_chat_message_app_tables(&state).await?;
+    let room0 = add_room(conn, "room0").await?;
+    let room1 = add_room(conn, "room1").await?;
+    let password = "Secret!1!!";
+
+    add_record_api(
+      &state,
+      "messages_api",
+      "message",
+      Acls {
+        authenticated: vec![
+          PermissionFlag::Create,
+          PermissionFlag::Read,
+          PermissionFlag::Update,
+          PermissionFlag::Delete,
+        ],
+        ..Default::default()
+      },
+      AccessRules {
+        create: Some(
+          "EXISTS(SELECT 1 FROM room_members AS m WHERE _USER_.id = _REQ_._owner AND m.user = _USER_.id AND m.room = _REQ_.room)".to_string(),
+        ),
+        update: Some("_ROW_._owner = _USER_.id".to_string()),
+        delete: Some("_ROW_._owner = _USER_.id".to_string()),
+        ..Default::default()
+      },
+    )
+    .await?;
+
+    let user_x_email = "user_x@test.com";
+    let user_x = create_user_for_test(&state, user_x_email, password)
+      .await?
+      .into_bytes();
+    add_user_to_room(conn, user_x, room0).await?;
+    let user_x_token = login_with_password(&state, user_x_email, password).await?;
+
+    let existing = send_message(conn, user_x, room0, "existing").await?;
+    assert_eq!(count_messages(&state).await, 1);
+
+    let create = |room: [u8; 16]| Operation::Create {
+      api_name: "messages_api".to_string(),
+      value: serde_json::json!({
+        "_owner": id_to_b64(&user_x),
+        "room": id_to_b64(&room),
+        "data": "new",
+      }),
+    };
+
+    {
+      // All operations succeed.
+      let Json(response) = record_transactions_handler(
+        State(state.clone()),
+        User::from_auth_token(&state, &user_x_token.auth_token),
+        Json(TransactionRequest {
+          operations: vec![
+            create(room0),
+            Operation::Update {
+              api_name: "messages_api".to_string(),
+              record_id: id_to_b64(&existing),
+              value: serde_json::json!({"data": "updated"}),
+            },
+            create(room0),
+          ],
+        }),
+      )
+      .await?;
+
+      assert_eq!(response.ids.len(), 3);
+      assert_eq!(response.ids[1], id_to_b64(&existing));
+      assert_eq!(count_messages(&state).await, 3);
+
+      let data: String = query_one_row(
+        conn,
+        "SELECT data FROM message WHERE id = $1",
+        [existing.to_vec()],
+      )
+      .await?
+      .get(0)?;
+      assert_eq!(data, "updated");
+    }
+
+    {
+      // User x isn't a member of room1, thus the second operation fails and the deletion gets
+      // rolled back.
+      let err = record_transactions_handler(
+        State(state.clone()),
+        User::from_auth_token(&state, &user_x_token.auth_token),
+        Json(TransactionRequest {
+          operations: vec![
+            Operation::Delete {
+              api_name: "messages_api".to_string(),
+              record_id: id_to_b64(&existing),
+            },
+            create(room1),
+          ],
+        }),
+      )
+      .await
+      .unwrap_err();
+
+      assert_eq!(err.operation, Some(1));
+      assert!(matches!(err.error, RecordError::Forbidden));
+      assert_eq!(
+        err.into_response().status(),
+        axum::http::StatusCode::FORBIDDEN
+      );
+      assert_eq!(count_messages(&state).await, 3);
+    }
+
+    {
+      // Unauthenticated users have no access.
+      let err = record_transactions_handler(
+        State(state.clone()),
+        None,
+        Json(TransactionRequest {
+          operations: vec![Operation::Delete {
+            api_name: "messages_api".to_string(),
+            record_id: id_to_b64(&existing),
+          }],
+        }),
+      )
+      .await
+      .unwrap_err();
+      assert_eq!(err.operation, Some(0));
+      assert_eq!(count_messages(&state).await, 3);
+    }
+
+    {
+      // Deletion succeeds.
+      let Json(response) = record_transactions_handler(
+        State(state.clone()),
+        User::from_auth_token(&state, &user_x_token.auth_token),
+        Json(TransactionRequest {
+          operations: vec![Operation::Delete {
+            api_name: "messages_api".to_string(),
+            record_id: id_to_b64(&existing),
+          }],
+        }),
+      )
+      .await?;
+      assert_eq!(response.ids, vec![id_to_b64(&existing)]);
+      assert_eq!(count_messages(&state).await, 2);
+    }
+
+    return Ok(());
+  }
+
+  #[tokio::test]
+  async fn test_concurrent_writes() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    let conn = state.conn();
+
+    create_chat_message_app_tables(&state).await?;
+    let room = add_room(conn, "room0").await?;
+    let password = "Secret!1!!";
+
+    add_record_api(
+      &state,
+      "messages_api",
+      "message",
+      Acls {
+        authenticated: vec![PermissionFlag::Create, PermissionFlag::Read],
+        ..Default::default()
+      },
+      AccessRules::default(),
+    )
+    .await?;
+
+    let user_x_email = "user_x@test.com";
+    let user_x = create_user_for_test(&state, user_x_email, password)
+      .await?
+      .into_bytes();
+    add_user_to_room(conn, user_x, room).await?;
+    let user_x_token = login_with_password(&state, user_x_email, password).await?;
+
+    let tx = state.write_transaction().await?;
+    send_message(&tx, user_x, room, "pending").await?;
+
+    // Queries on the shared connection neither get captured by nor see the pending transaction.
+    assert_eq!(count_messages(&state).await, 0);
+
+    // An overlapping single-record write waits for the pending transaction.
+    let handle = {
+      let state = state.clone();
+      let user = User::from_auth_token(&state, &user_x_token.auth_token);
+      let json = serde_json::json!({
+        "_owner": id_to_b64(&user_x),
+        "room": id_to_b64(&room),
+        "data": "concurrent",
+      });
+
+      tokio::spawn(async move {
+        return crate::records::create_record::create_record_handler(
+          State(state),
+          axum::extract::Path("messages_api".to_string()),
+          axum::extract::Query(Default::default()),
+          user,
+          crate::extract::Either::Json(json),
+        )
+        .await
+        .map(|_| ());
+      })
+    };
+
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert!(!handle.is_finished());
+
+    tx.rollback().await?;
+    handle.await?.unwrap();
+
+    let data: String = query_one_row(conn, "SELECT data FROM message", ())
+      .await?
+      .get(0)?;
+    assert_eq!(data, "concurrent");
+
+    return Ok(());
+  }
+}
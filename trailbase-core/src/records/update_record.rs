@@ -1,24 +1,39 @@
 use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use log::*;
 
 use crate::app_state::AppState;
 use crate::auth::user::User;
 use crate::extract::Either;
-use crate::records::json_to_sql::{LazyParams, UpdateQueryBuilder};
-use crate::records::{Permission, RecordError};
+use crate::js::RecordHookEvent;
+use crate::records::etag::check_record_if_match;
+use crate::records::files::delete_files_in_row;
+use crate::records::history::{append_history, history_snapshot};
+use crate::records::hooks::{run_after_record_hook, run_before_record_hook};
+use crate::records::json_to_sql::{
+  LazyParams, Params, PendingFiles, QueryError, UpdateQueryBuilder,
+};
+use crate::records::subscribe::RecordAction;
+use crate::records::webhooks::enqueue_webhook_deliveries;
+use crate::records::{Permission, RecordApi, RecordError};
 
 /// Update existing record.
+///
+/// Honors "If-Match", i.e. responds with 412 if the record has been modified concurrently.
 #[utoipa::path(
   patch,
   path = "/:name/:record",
   request_body = serde_json::Value,
   responses(
-    (status = 200, description = "Successful update.")
+    (status = 200, description = "Successful update."),
+    (status = 412, description = "Record was modified concurrently.")
   )
 )]
 pub async fn update_record_handler(
   State(state): State<AppState>,
   Path((api_name, record)): Path<(String, String)>,
   user: Option<User>,
+  headers: HeaderMap,
   either_request: Either<serde_json::Value>,
 ) -> Result<(), RecordError> {
   let Some(api) = state.lookup_record_api(&api_name) else {
@@ -37,6 +52,18 @@ pub async fn update_record_handler(
     Either::Form(value) => (value, None),
   };
 
+  api.check_table_level_access(Permission::Update, user.as_ref())?;
+  let request = run_before_record_hook(
+    &state,
+    &api,
+    RecordHookEvent::Update,
+    user.as_ref(),
+    Some(&record_id),
+    Some(request),
+  )
+  .await?
+  .unwrap_or_default();
+
   let mut lazy_params = LazyParams::new(table_metadata, request, multipart_files);
   api
     .check_record_level_access(
@@ -46,25 +73,115 @@ pub async fn update_record_handler(
       user.as_ref(),
     )
     .await?;
+  api.check_not_soft_deleted(&record_id).await?;
+
+  let mut params = lazy_params
+    .consume()
+    .map_err(|err| RecordError::Internal(err.into()))?;
+  api.check_file_upload_limits(&params)?;
+
+  // We're storing to object store before writing the record to the DB.
+  let files = PendingFiles::write(&state, &mut params)
+    .await
+    .map_err(|err| RecordError::Internal(err.into()))?;
+  let (new, files_row) =
+    match update_record(&state, &api, params, &record_id, user.as_ref(), &headers).await {
+      Ok(result) => result,
+      Err(err) => {
+        files.rollback(&state).await;
+        return Err(err);
+      }
+    };
+  files.commit();
 
-  UpdateQueryBuilder::run(
+  // Delete files from columns that were updated and are no longer referenced.
+  if let Some(files_row) = files_row {
+    if let Err(err) = delete_files_in_row(&state, table_metadata, &files_row).await {
+      warn!("Failed to delete replaced files: {err}");
+    }
+  }
+
+  state
+    .subscription_manager()
+    .broadcast_record(&state, &api, RecordAction::Update, record_id.clone())
+    .await;
+  run_after_record_hook(
     &state,
-    table_metadata,
-    lazy_params
-      .consume()
-      .map_err(|err| RecordError::Internal(err.into()))?,
-    &api.record_pk_column().name,
-    record_id,
+    &api,
+    RecordHookEvent::Update,
+    user.as_ref(),
+    &record_id,
+    new.as_ref(),
   )
-  .await
-  .map_err(|err| RecordError::Internal(err.into()))?;
+  .await;
 
   return Ok(());
 }
 
+/// Updates the record in a single transaction together with checking the "If-Match" precondition,
+/// appending its history entry and enqueuing webhook deliveries.
+///
+/// Returns the updated record and prior contents of updated file columns.
+async fn update_record(
+  state: &AppState,
+  api: &RecordApi,
+  params: Params,
+  record_id: &libsql::Value,
+  user: Option<&User>,
+  headers: &HeaderMap,
+) -> Result<(Option<libsql::Row>, Option<libsql::Row>), RecordError> {
+  let tx = state.write_transaction().await?;
+
+  check_record_if_match(state, &tx, api, record_id, headers).await?;
+
+  let old = history_snapshot(&tx, api, record_id).await?;
+
+  let files_row = match params.column_names().is_empty() {
+    true => None,
+    false => UpdateQueryBuilder::update(
+      &tx,
+      api.table_name(),
+      params,
+      &api.record_pk_column().name,
+      record_id.clone(),
+    )
+    .await
+    .map_err(|err| match err {
+      QueryError::NotFound => RecordError::RecordNotFound,
+      err => RecordError::Internal(err.into()),
+    })?,
+  };
+
+  let new = history_snapshot(&tx, api, record_id).await?;
+  append_history(
+    &tx,
+    api,
+    RecordAction::Update,
+    record_id,
+    user,
+    old.as_ref(),
+    new.as_ref(),
+  )
+  .await?;
+  enqueue_webhook_deliveries(
+    state,
+    &tx,
+    api,
+    RecordAction::Update,
+    record_id,
+    new.as_ref(),
+  )
+  .await?;
+
+  tx.commit().await?;
+
+  return Ok((new, files_row));
+}
+
 #[cfg(test)]
 mod test {
   use axum::extract::Query;
+  use axum::http::{header, HeaderValue, StatusCode};
   use libsql::params;
   use trailbase_sqlite::query_one_row;
 
@@ -78,6 +195,7 @@ mod test {
   use crate::records::create_record::{
     create_record_handler, CreateRecordQuery, CreateRecordResponse,
   };
+  use crate::records::read_record::{read_record_handler, ReadRecordQuery};
   use crate::records::test_utils::*;
   use crate::records::*;
   use crate::test::unpack_json_response;
@@ -164,6 +282,7 @@ mod test {
         State(state.clone()),
         Path(("messages_api".to_string(), b64_id.clone())),
         User::from_auth_token(&state, &user_x_token.auth_token),
+        HeaderMap::new(),
         Either::Json(update_json),
       )
       .await;
@@ -189,6 +308,7 @@ mod test {
         State(state.clone()),
         Path(("messages_api".to_string(), b64_id.clone())),
         User::from_auth_token(&state, &user_y_token.auth_token),
+        HeaderMap::new(),
         Either::Json(update_json),
       )
       .await;
@@ -196,6 +316,150 @@ mod test {
       assert!(update_response.is_err(), "{b64_id} {update_response:?}");
     }
 
+    {
+      // Optimistic concurrency using ETags.
+      let read = |headers: HeaderMap| {
+        read_record_handler(
+          State(state.clone()),
+          Path(("messages_api".to_string(), b64_id.clone())),
+          Query(ReadRecordQuery::default()),
+          User::from_auth_token(&state, &user_x_token.auth_token),
+          headers,
+        )
+      };
+      let update = |headers: HeaderMap, data: &str| {
+        update_record_handler(
+          State(state.clone()),
+          Path(("messages_api".to_string(), b64_id.clone())),
+          User::from_auth_token(&state, &user_x_token.auth_token),
+          headers,
+          Either::Json(serde_json::json!({"data": data})),
+        )
+      };
+      let with_header = |name: header::HeaderName, etag: &HeaderValue| {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, etag.clone());
+        return headers;
+      };
+
+      let response = read(HeaderMap::new()).await?;
+      assert_eq!(response.status(), StatusCode::OK);
+      let etag = response.headers().get(header::ETAG).unwrap().clone();
+
+      let response = read(with_header(header::IF_NONE_MATCH, &etag)).await?;
+      assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+      let stale = HeaderValue::from_static("\"stale\"");
+      assert!(matches!(
+        update(with_header(header::IF_MATCH, &stale), "stale update").await,
+        Err(RecordError::PreconditionFailed)
+      ));
+
+      update(with_header(header::IF_MATCH, &etag), "first update").await?;
+
+      // The record changed, thus the previous ETag no longer matches.
+      assert!(matches!(
+        update(with_header(header::IF_MATCH, &etag), "second update").await,
+        Err(RecordError::PreconditionFailed)
+      ));
+
+      let response = read(with_header(header::IF_NONE_MATCH, &etag)).await?;
+      assert_eq!(response.status(), StatusCode::OK);
+      assert_ne!(response.headers().get(header::ETAG), Some(&etag));
+
+      let value: serde_json::Value = unpack_json_response(response).await?;
+      assert_eq!(value["data"], "first update");
+    }
+
+    return Ok(());
+  }
+
+  #[tokio::test]
+  async fn test_update_deletes_replaced_files() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    state
+      .conn()
+      .execute_batch(
+        r#"
+          CREATE TABLE doc (
+            id           INTEGER PRIMARY KEY,
+            file         TEXT CHECK(jsonschema('std.FileUpload', file))
+          ) STRICT;
+        "#,
+      )
+      .await?;
+    state.table_metadata().invalidate_all().await?;
+
+    const API_NAME: &str = "docs_api";
+    add_record_api(
+      &state,
+      API_NAME,
+      "doc",
+      Acls {
+        world: vec![PermissionFlag::Create, PermissionFlag::Update],
+        ..Default::default()
+      },
+      AccessRules::default(),
+    )
+    .await?;
+
+    let upload = |data: &[u8]| {
+      serde_json::json!({
+        "file": trailbase_sqlite::schema::FileUploadInput {
+          name: None,
+          filename: None,
+          content_type: None,
+          data: data.to_vec(),
+        },
+      })
+    };
+    let count_files = || async {
+      let mut count = 0;
+      let mut read_dir = tokio::fs::read_dir(state.data_dir().uploads_path()).await?;
+      while read_dir.next_entry().await?.is_some() {
+        count += 1;
+      }
+      return Ok::<_, std::io::Error>(count);
+    };
+
+    let response: CreateRecordResponse = unpack_json_response(
+      create_record_handler(
+        State(state.clone()),
+        Path(API_NAME.to_string()),
+        Query(CreateRecordQuery::default()),
+        None,
+        Either::Json(upload(&[0, 1])),
+      )
+      .await?,
+    )
+    .await?;
+    assert_eq!(count_files().await?, 1);
+
+    let update = |request: serde_json::Value| {
+      update_record_handler(
+        State(state.clone()),
+        Path((API_NAME.to_string(), response.id.clone())),
+        None,
+        HeaderMap::new(),
+        Either::Json(request),
+      )
+    };
+
+    // Replaced files are deleted.
+    update(upload(&[2, 3])).await?;
+    assert_eq!(count_files().await?, 1);
+
+    // Files are retained when re-submitting the current metadata.
+    let current: String = query_one_row(state.conn(), "SELECT file FROM doc", ())
+      .await?
+      .get(0)?;
+    update(serde_json::json!({"file": current})).await?;
+    assert_eq!(count_files().await?, 1);
+
+    // Cleared ones are deleted.
+    update(serde_json::json!({"file": null})).await?;
+    assert_eq!(count_files().await?, 0);
+
     return Ok(());
   }
 }
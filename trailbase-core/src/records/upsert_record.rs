@@ -0,0 +1,413 @@
+use axum::extract::{Json, Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use log::*;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::auth::user::User;
+use crate::extract::Either;
+use crate::js::RecordHookEvent;
+use crate::records::create_record::{autofill_missing_user_id_columns, CreateRecordResponse};
+use crate::records::files::delete_files_in_row;
+use crate::records::history::{append_history, history_snapshot};
+use crate::records::hooks::{run_after_record_hook, run_before_record_hook};
+use crate::records::json_to_sql::{
+  InsertQueryBuilder, LazyParams, Params, PendingFiles, QueryError, SelectQueryBuilder,
+  UpdateQueryBuilder,
+};
+use crate::records::subscribe::RecordAction;
+use crate::records::webhooks::enqueue_webhook_deliveries;
+use crate::records::{Permission, RecordApi, RecordError};
+use crate::schema::ColumnDataType;
+use crate::util::b64_to_id;
+
+/// Create or replace record with the given id.
+///
+/// Requires create access if the record doesn't exist yet and update access otherwise.
+#[utoipa::path(
+  put,
+  path = "/:name/:record",
+  request_body = serde_json::Value,
+  responses(
+    (status = 200, description = "Existing record was updated.", body = CreateRecordResponse),
+    (status = 201, description = "New record was created.", body = CreateRecordResponse),
+  )
+)]
+pub async fn upsert_record_handler(
+  State(state): State<AppState>,
+  Path((api_name, record)): Path<(String, String)>,
+  user: Option<User>,
+  either_request: Either<serde_json::Value>,
+) -> Result<Response, RecordError> {
+  let Some(api) = state.lookup_record_api(&api_name) else {
+    return Err(RecordError::ApiNotFound);
+  };
+
+  let table_metadata = api
+    .table_metadata()
+    .ok_or_else(|| RecordError::ApiRequiresTable)?;
+
+  let pk_column = api.record_pk_column();
+  // Unlike for other operations, ids are provided by the client for records that may not exist
+  // yet and thus need to be validated.
+  if pk_column.data_type == ColumnDataType::Blob {
+    let id = b64_to_id(&record).map_err(|_err| RecordError::BadRequest("Invalid id"))?;
+    if Uuid::from_bytes(id).get_version_num() != 7 {
+      return Err(RecordError::BadRequest("Invalid id"));
+    }
+  }
+  let record_id = api.id_to_sql(&record)?;
+
+  let (mut request, multipart_files) = match either_request {
+    Either::Json(value) => (value, None),
+    Either::Multipart(value, files) => (value, Some(files)),
+    Either::Form(value) => (value, None),
+  };
+
+  let serde_json::Value::Object(ref mut map) = request else {
+    return Err(RecordError::BadRequest("Expected object"));
+  };
+  match map.get(&pk_column.name) {
+    None => {}
+    Some(serde_json::Value::String(id)) if *id == record => {}
+    Some(serde_json::Value::Number(id)) if id.to_string() == record => {}
+    Some(_) => return Err(RecordError::BadRequest("Mismatching record id")),
+  };
+  map.insert(
+    pk_column.name.clone(),
+    serde_json::Value::String(record.clone()),
+  );
+
+  let exists =
+    SelectQueryBuilder::run(&state, api.table_name(), &pk_column.name, record_id.clone())
+      .await?
+      .is_some();
+
+  let (event, permission) = match exists {
+    true => (RecordHookEvent::Update, Permission::Update),
+    false => (RecordHookEvent::Create, Permission::Create),
+  };
+
+  api.check_table_level_access(permission, user.as_ref())?;
+  let request = run_before_record_hook(
+    &state,
+    &api,
+    event,
+    user.as_ref(),
+    Some(&record_id),
+    Some(request),
+  )
+  .await?
+  .unwrap_or_default();
+
+  let mut lazy_params = LazyParams::new(table_metadata, request, multipart_files);
+  if exists {
+    api
+      .check_record_level_access(
+        Permission::Update,
+        Some(&record_id),
+        Some(&mut lazy_params),
+        user.as_ref(),
+      )
+      .await?;
+    // Soft-deleted records need to be restored explicitly.
+    api.check_not_soft_deleted(&record_id).await?;
+  } else {
+    api
+      .check_record_level_access(
+        Permission::Create,
+        None,
+        Some(&mut lazy_params),
+        user.as_ref(),
+      )
+      .await?;
+  }
+
+  let Ok(mut params) = lazy_params.consume() else {
+    return Err(RecordError::BadRequest("Parameter conversion"));
+  };
+  api.check_file_upload_limits(&params)?;
+
+  if !exists && api.insert_autofill_missing_user_id_columns() {
+    autofill_missing_user_id_columns(table_metadata, &mut params, user.as_ref());
+  }
+
+  // The record may have been created or deleted concurrently since checking for its existence
+  // above. Rather than blindly upserting, which would bypass the access rules checked against, we
+  // thus only either insert or update and reject otherwise.
+  let files = PendingFiles::write(&state, &mut params)
+    .await
+    .map_err(|err| RecordError::Internal(err.into()))?;
+  let (new, files_row) =
+    match write_record(&state, &api, params, exists, &record_id, user.as_ref()).await {
+      Ok(result) => result,
+      Err(err) => {
+        files.rollback(&state).await;
+        return Err(err);
+      }
+    };
+  files.commit();
+
+  // Delete files from columns that were replaced and are no longer referenced.
+  if let Some(files_row) = files_row {
+    if let Err(err) = delete_files_in_row(&state, table_metadata, &files_row).await {
+      warn!("Failed to delete replaced files: {err}");
+    }
+  }
+
+  let (status, action) = match exists {
+    true => (StatusCode::OK, RecordAction::Update),
+    false => (StatusCode::CREATED, RecordAction::Insert),
+  };
+
+  state
+    .subscription_manager()
+    .broadcast_record(&state, &api, action, record_id.clone())
+    .await;
+  run_after_record_hook(&state, &api, event, user.as_ref(), &record_id, new.as_ref()).await;
+
+  return Ok((status, Json(CreateRecordResponse { id: record })).into_response());
+}
+
+/// Inserts the record or updates it if it `exists`, in a single transaction together with its
+/// history entry and webhook deliveries.
+///
+/// Returns the new record and prior contents of updated file columns.
+async fn write_record(
+  state: &AppState,
+  api: &RecordApi,
+  params: Params,
+  exists: bool,
+  record_id: &libsql::Value,
+  user: Option<&User>,
+) -> Result<(Option<libsql::Row>, Option<libsql::Row>), RecordError> {
+  let tx = state.write_transaction().await?;
+
+  let (action, old, files_row) = match exists {
+    true => {
+      let old = history_snapshot(&tx, api, record_id).await?;
+      let files_row = UpdateQueryBuilder::update(
+        &tx,
+        api.table_name(),
+        params,
+        &api.record_pk_column().name,
+        record_id.clone(),
+      )
+      .await
+      .map_err(|err| match err {
+        QueryError::NotFound => RecordError::RecordNotFound,
+        err => RecordError::Internal(err.into()),
+      })?;
+
+      (RecordAction::Update, old, files_row)
+    }
+    false => {
+      let (query, named_params, _files) = InsertQueryBuilder::build_insert_query(params, None)
+        .map_err(|err| RecordError::Internal(err.into()))?;
+
+      match tx.execute(&query, named_params).await {
+        Ok(_) => {}
+        Err(libsql::Error::SqliteFailure(1555 | 2579, _msg)) => {
+          return Err(RecordError::Rejected(
+            StatusCode::CONFLICT,
+            Some("Record was created concurrently".to_string()),
+          ));
+        }
+        Err(err) => return Err(err.into()),
+      };
+
+      (RecordAction::Insert, None, None)
+    }
+  };
+
+  let new = history_snapshot(&tx, api, record_id).await?;
+  append_history(
+    &tx,
+    api,
+    action,
+    record_id,
+    user,
+    old.as_ref(),
+    new.as_ref(),
+  )
+  .await?;
+  enqueue_webhook_deliveries(state, &tx, api, action, record_id, new.as_ref()).await?;
+
+  tx.commit().await?;
+
+  return Ok((new, files_row));
+}
+
+#[cfg(test)]
+mod test {
+  use libsql::params;
+  use trailbase_sqlite::query_one_row;
+
+  use super::*;
+  use crate::admin::user::*;
+  use crate::app_state::*;
+  use crate::auth::api::login::login_with_password;
+  use crate::config::proto::PermissionFlag;
+  use crate::records::test_utils::*;
+  use crate::records::*;
+  use crate::util::id_to_b64;
+
+  #[tokio::test]
+  async fn test_record_api_upsert() -> Result<(), anyhow::Error> {
+    let state = test_state(None).await?;
+    let conn = state.conn();
+
+    create_chat_message_app_tables(&state).await?;
+    let room = add_room(conn, "room0").await?;
+    let password = "Secret!1!!";
+
+    add_record_api(
+      &state,
+      "messages_api",
+      "message",
+      Acls {
+        authenticated: vec![PermissionFlag::Create, PermissionFlag::Update],
+        ..Default::default()
+      },
+      AccessRules {
+        create: Some(
+          "EXISTS(SELECT 1 FROM room_members WHERE room = _REQ_.room AND user = _USER_.id)"
+            .to_string(),
+        ),
+        update: Some("_ROW_._owner = _USER_.id".to_string()),
+        ..Default::default()
+      },
+    )
+    .await?;
+
+    let user_x_email = "user_x@test.com";
+    let user_x = create_user_for_test(&state, user_x_email, password)
+      .await?
+      .into_bytes();
+    add_user_to_room(conn, user_x, room).await?;
+    let user_x_token = login_with_password(&state, user_x_email, password).await?;
+
+    let user_y_email = "user_y@test.com";
+    let user_y = create_user_for_test(&state, user_y_email, password)
+      .await?
+      .into_bytes();
+    add_user_to_room(conn, user_y, room).await?;
+    let user_y_token = login_with_password(&state, user_y_email, password).await?;
+
+    let id: [u8; 16] = query_one_row(conn, "SELECT uuid_v7()", ()).await?.get(0)?;
+    let b64_id = id_to_b64(&id);
+
+    let upsert = |token: &str, id: &str, data: &str, owner: [u8; 16]| {
+      upsert_record_handler(
+        State(state.clone()),
+        Path(("messages_api".to_string(), id.to_string())),
+        User::from_auth_token(&state, token),
+        Either::Json(serde_json::json!({
+          "_owner": id_to_b64(&owner),
+          "room": id_to_b64(&room),
+          "data": data,
+        })),
+      )
+    };
+    let message_data = || async {
+      return query_one_row(conn, "SELECT data FROM message WHERE id = $1", params!(id))
+        .await?
+        .get::<String>(0);
+    };
+
+    // Creates the record.
+    let response = upsert(&user_x_token.auth_token, &b64_id, "created", user_x).await?;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(message_data().await?, "created");
+
+    // Replaces the record.
+    let response = upsert(&user_x_token.auth_token, &b64_id, "replaced", user_x).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(message_data().await?, "replaced");
+
+    // User Y can create messages but not update User X's message.
+    assert!(
+      upsert(&user_y_token.auth_token, &b64_id, "by user_y", user_y)
+        .await
+        .is_err()
+    );
+    assert_eq!(message_data().await?, "replaced");
+
+    // Non-UUIDv7 ids and mismatching ids in the body are rejected.
+    let v4_id = id_to_b64(Uuid::new_v4().as_bytes());
+    assert!(upsert(&user_x_token.auth_token, &v4_id, "v4", user_x)
+      .await
+      .is_err());
+
+    let response = upsert_record_handler(
+      State(state.clone()),
+      Path(("messages_api".to_string(), b64_id.clone())),
+      User::from_auth_token(&state, &user_x_token.auth_token),
+      Either::Json(serde_json::json!({
+        "id": v4_id,
+        "_owner": id_to_b64(&user_x),
+        "room": id_to_b64(&room),
+      })),
+    )
+    .await;
+    assert!(response.is_err(), "{response:?}");
+
+    // Records created or deleted concurrently, i.e. after having checked for their existence, are
+    // neither overwritten nor re-created.
+    let api = state.lookup_record_api("messages_api").unwrap();
+    let params = |id: &str, data: &str| {
+      Params::from(
+        api.table_metadata().unwrap(),
+        serde_json::json!({
+          "id": id,
+          "_owner": id_to_b64(&user_y),
+          "room": id_to_b64(&room),
+          "data": data,
+        }),
+        None,
+      )
+    };
+
+    let result = write_record(
+      &state,
+      &api,
+      params(&b64_id, "racy insert")?,
+      false,
+      &api.id_to_sql(&b64_id)?,
+      None,
+    )
+    .await;
+    assert!(
+      matches!(result, Err(RecordError::Rejected(StatusCode::CONFLICT, _))),
+      "{result:?}"
+    );
+    assert_eq!(message_data().await?, "replaced");
+
+    let other_id: [u8; 16] = query_one_row(conn, "SELECT uuid_v7()", ()).await?.get(0)?;
+    let other_b64_id = id_to_b64(&other_id);
+    let result = write_record(
+      &state,
+      &api,
+      params(&other_b64_id, "racy update")?,
+      true,
+      &api.id_to_sql(&other_b64_id)?,
+      None,
+    )
+    .await;
+    assert!(
+      matches!(result, Err(RecordError::RecordNotFound)),
+      "{result:?}"
+    );
+    let count: i64 = query_one_row(
+      conn,
+      "SELECT COUNT(*) FROM message WHERE id = $1",
+      params!(other_id),
+    )
+    .await?
+    .get(0)?;
+    assert_eq!(count, 0);
+
+    return Ok(());
+  }
+}
@@ -1,4 +1,6 @@
 use crate::config::{proto, ConfigError};
+use crate::records::files::is_file_column;
+use crate::schema::ColumnDataType;
 use crate::table_metadata::{
   sqlite3_parse_into_statements, TableMetadataCache, TableOrViewMetadata,
 };
@@ -19,6 +21,226 @@ fn validate_record_api_name(name: &str) -> Result<(), ConfigError> {
   Ok(())
 }
 
+fn validate_geo_columns(
+  name: &str,
+  metadata: &dyn TableOrViewMetadata,
+  api_config: &proto::RecordApiConfig,
+) -> Result<(), ConfigError> {
+  let columns = match (
+    &api_config.geo_latitude_column,
+    &api_config.geo_longitude_column,
+  ) {
+    (None, None) => return Ok(()),
+    (Some(lat), Some(lng)) => [lat, lng],
+    _ => {
+      return Err(ConfigError::Invalid(format!(
+        "Geo index for api '{name}' requires both latitude and longitude columns"
+      )));
+    }
+  };
+
+  for column in columns {
+    let Some((col, _)) = metadata.column_by_name(column) else {
+      return Err(ConfigError::Invalid(format!(
+        "Geo column '{column}' for api '{name}' does not exist"
+      )));
+    };
+
+    if !matches!(
+      col.data_type,
+      ColumnDataType::Real | ColumnDataType::Integer
+    ) {
+      return Err(ConfigError::Invalid(format!(
+        "Geo column '{column}' for api '{name}' must be of type REAL or INTEGER"
+      )));
+    }
+  }
+
+  return Ok(());
+}
+
+fn validate_soft_delete_column(
+  name: &str,
+  metadata: &dyn TableOrViewMetadata,
+  api_config: &proto::RecordApiConfig,
+) -> Result<(), ConfigError> {
+  if let Some(retention) = api_config.soft_delete_retention_sec {
+    if retention <= 0 {
+      return Err(ConfigError::Invalid(format!(
+        "Soft-delete retention for api '{name}' must be positive, got: {retention}"
+      )));
+    }
+  }
+
+  let Some(ref column) = api_config.soft_delete_column else {
+    return Ok(());
+  };
+
+  let Some((col, _)) = metadata.column_by_name(column) else {
+    return Err(ConfigError::Invalid(format!(
+      "Soft-delete column '{column}' for api '{name}' does not exist"
+    )));
+  };
+
+  if col.data_type != ColumnDataType::Integer {
+    return Err(ConfigError::Invalid(format!(
+      "Soft-delete column '{column}' for api '{name}' must be of type INTEGER"
+    )));
+  }
+
+  return Ok(());
+}
+
+fn validate_column_acls(
+  name: &str,
+  metadata: &dyn TableOrViewMetadata,
+  api_config: &proto::RecordApiConfig,
+) -> Result<(), ConfigError> {
+  let pk_column = metadata
+    .record_pk_column()
+    .map(|(_, col)| col.name.as_str());
+
+  let acls = [
+    &api_config.column_acl_world,
+    &api_config.column_acl_authenticated,
+  ];
+  for acl in acls.into_iter().flatten() {
+    let allow_lists = [&acl.read_allow, &acl.write_allow];
+    let deny_lists = [&acl.read_deny, &acl.write_deny];
+
+    for column in allow_lists.into_iter().chain(deny_lists).flatten() {
+      let computed = api_config
+        .computed_fields
+        .iter()
+        .any(|f| f.name() == column);
+      if metadata.column_by_name(column).is_none() && !computed {
+        return Err(ConfigError::Invalid(format!(
+          "Column ACL for api '{name}' references missing column '{column}'"
+        )));
+      }
+    }
+
+    for column in deny_lists.into_iter().flatten() {
+      if Some(column.as_str()) == pk_column {
+        return Err(ConfigError::Invalid(format!(
+          "Column ACL for api '{name}' cannot deny access to primary key column '{column}'"
+        )));
+      }
+    }
+  }
+
+  return Ok(());
+}
+
+fn validate_computed_fields(
+  name: &str,
+  metadata: &dyn TableOrViewMetadata,
+  api_config: &proto::RecordApiConfig,
+) -> Result<(), ConfigError> {
+  for (index, field) in api_config.computed_fields.iter().enumerate() {
+    let field_name = field.name();
+    if field_name.is_empty()
+      || field_name.starts_with("_")
+      || !field_name
+        .chars()
+        .all(|x| x.is_ascii_alphanumeric() || x == '_')
+    {
+      return Err(ConfigError::Invalid(format!(
+        "Invalid computed field name for api '{name}': '{field_name}'. Must only contain alphanumeric characters or '_' and not start with '_'."
+      )));
+    }
+
+    if metadata.column_by_name(field_name).is_some()
+      || api_config.computed_fields[..index]
+        .iter()
+        .any(|f| f.name() == field_name)
+    {
+      return Err(ConfigError::Invalid(format!(
+        "Computed field '{field_name}' for api '{name}' collides with another column or field"
+      )));
+    }
+
+    let expression = field.expression();
+    if expression.is_empty() {
+      return Err(ConfigError::Invalid(format!(
+        "Computed field '{field_name}' for api '{name}' misses expression"
+      )));
+    }
+    sqlite3_parse_into_statements(&format!("SELECT ({expression})")).map_err(|err| {
+      ConfigError::Invalid(format!(
+        "Computed field '{field_name}' for api '{name}': '{expression}' not a valid SQL expression: {err}"
+      ))
+    })?;
+
+    if field.r#type.is_some() && ColumnDataType::from_type_name(field.r#type()).is_none() {
+      return Err(ConfigError::Invalid(format!(
+        "Computed field '{field_name}' for api '{name}' has invalid type: {}",
+        field.r#type()
+      )));
+    }
+  }
+
+  return Ok(());
+}
+
+fn validate_file_upload_limits(
+  name: &str,
+  metadata: &dyn TableOrViewMetadata,
+  api_config: &proto::RecordApiConfig,
+) -> Result<(), ConfigError> {
+  for (index, limits) in api_config.file_upload_limits.iter().enumerate() {
+    if let Some(ref column) = limits.column {
+      if !metadata
+        .column_by_name(column)
+        .is_some_and(|(_, meta)| is_file_column(meta))
+      {
+        return Err(ConfigError::Invalid(format!(
+          "File upload limits for api '{name}' reference missing or non-file column '{column}'"
+        )));
+      }
+    }
+
+    if api_config.file_upload_limits[..index]
+      .iter()
+      .any(|l| l.column == limits.column)
+    {
+      return Err(ConfigError::Invalid(format!(
+        "Duplicate file upload limits for api '{name}' and column '{}'",
+        limits.column()
+      )));
+    }
+
+    for mime_type in &limits.allowed_mime_types {
+      let valid = mime_type
+        .split_once('/')
+        .is_some_and(|(t, s)| !t.is_empty() && !s.is_empty() && !t.contains('*'));
+      if !valid {
+        return Err(ConfigError::Invalid(format!(
+          "Invalid MIME type for api '{name}': '{mime_type}'. Expected e.g. 'image/png' or 'image/*'."
+        )));
+      }
+    }
+  }
+
+  return Ok(());
+}
+
+fn validate_etag_column(
+  name: &str,
+  metadata: &dyn TableOrViewMetadata,
+  etag_column: &Option<String>,
+) -> Result<(), ConfigError> {
+  if let Some(column) = etag_column {
+    if metadata.column_by_name(column).is_none() {
+      return Err(ConfigError::Invalid(format!(
+        "ETag column '{column}' for api '{name}' does not exist"
+      )));
+    }
+  }
+
+  return Ok(());
+}
+
 pub(crate) fn validate_record_api_config(
   tables: &TableMetadataCache,
   api_config: &proto::RecordApiConfig,
@@ -47,6 +269,22 @@ pub(crate) fn validate_record_api_config(
         metadata.schema
       )));
     }
+
+    validate_etag_column(name, &*metadata, &api_config.etag_column)?;
+    validate_computed_fields(name, &*metadata, api_config)?;
+    validate_column_acls(name, &*metadata, api_config)?;
+
+    for column in &api_config.search_columns {
+      if metadata.column_by_name(column).is_none() {
+        return Err(ConfigError::Invalid(format!(
+          "Search column '{column}' for api '{name}' does not exist"
+        )));
+      }
+    }
+
+    validate_geo_columns(name, &*metadata, api_config)?;
+    validate_soft_delete_column(name, &*metadata, api_config)?;
+    validate_file_upload_limits(name, &*metadata, api_config)?;
   } else if let Some(metadata) = tables.get_view(table_name) {
     if metadata.schema.temporary {
       return Err(ConfigError::Invalid(format!(
@@ -66,6 +304,34 @@ pub(crate) fn validate_record_api_config(
         "View for api '{name}' is not a \"simple\" view, i.e. the column types couldn't be inferred and thus type-safety cannot be guaranteed."
       )));
     };
+
+    validate_etag_column(name, &*metadata, &api_config.etag_column)?;
+    validate_computed_fields(name, &*metadata, api_config)?;
+    validate_column_acls(name, &*metadata, api_config)?;
+
+    if !api_config.search_columns.is_empty() {
+      return Err(ConfigError::Invalid(format!(
+        "Search columns for api '{name}' require a table, got view: {table_name}"
+      )));
+    }
+
+    if api_config.geo_latitude_column.is_some() || api_config.geo_longitude_column.is_some() {
+      return Err(ConfigError::Invalid(format!(
+        "Geo columns for api '{name}' require a table, got view: {table_name}"
+      )));
+    }
+
+    if api_config.soft_delete_column.is_some() {
+      return Err(ConfigError::Invalid(format!(
+        "Soft-deletes for api '{name}' require a table, got view: {table_name}"
+      )));
+    }
+
+    if !api_config.file_upload_limits.is_empty() {
+      return Err(ConfigError::Invalid(format!(
+        "File upload limits for api '{name}' require a table, got view: {table_name}"
+      )));
+    }
   } else {
     return Err(ConfigError::Invalid(format!(
       "Missing table or view for API: {name}"
@@ -78,6 +344,7 @@ pub(crate) fn validate_record_api_config(
     &api_config.update_access_rule,
     &api_config.delete_access_rule,
     &api_config.schema_access_rule,
+    &api_config.list_access_rule,
   ];
   for rule in rules.into_iter().flatten() {
     let map = |err| ConfigError::Invalid(format!("'{rule}' not a valid SQL expression: {err}"));
@@ -0,0 +1,517 @@
+use chrono::Utc;
+use reqwest::header::CONTENT_TYPE;
+use std::time::Duration;
+use trailbase_sqlite::query_row;
+
+use crate::app_state::AppState;
+use crate::config::proto::{WebhookConfig, WebhookEvent};
+use crate::constants::WEBHOOK_DELIVERY_TABLE;
+use crate::records::sql_to_json::row_to_json;
+use crate::records::subscribe::RecordAction;
+use crate::records::{RecordApi, RecordError};
+use crate::util::{hex_encode, hmac_sha256};
+
+/// Max number of delivery attempts before a delivery is considered failed.
+const MAX_ATTEMPTS: i64 = 10;
+/// Delay before the first retry, which doubles with every subsequent attempt.
+const INITIAL_BACKOFF_SEC: i64 = 30;
+const MAX_BACKOFF_SEC: i64 = 6 * 3600;
+/// Deliveries are claimed by a delivery run for longer than the request timeout, so that
+/// concurrent runs don't deliver the same event twice.
+const CLAIM_SEC: i64 = 60;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const BATCH_SIZE: i64 = 100;
+
+pub(crate) const SIGNATURE_HEADER: &str = "X-TrailBase-Signature";
+pub(crate) const DELIVERY_HEADER: &str = "X-TrailBase-Delivery";
+
+fn webhook_event(action: RecordAction) -> (WebhookEvent, &'static str) {
+  return match action {
+    RecordAction::Insert => (WebhookEvent::RecordCreated, "insert"),
+    RecordAction::Update => (WebhookEvent::RecordUpdated, "update"),
+    RecordAction::Delete => (WebhookEvent::RecordDeleted, "delete"),
+  };
+}
+
+/// Enqueues deliveries for all webhooks subscribed to the given change.
+///
+/// `row` is the changed record and is looked up by `record_id` if absent, i.e. deleted records
+/// have to be provided. Hidden columns, i.e. ones prefixed with "_", are omitted from payloads.
+pub(crate) async fn enqueue_webhook_deliveries(
+  state: &AppState,
+  conn: &libsql::Connection,
+  api: &RecordApi,
+  action: RecordAction,
+  record_id: &libsql::Value,
+  row: Option<&libsql::Row>,
+) -> Result<(), RecordError> {
+  let (event, event_name) = webhook_event(action);
+  let webhooks: Vec<String> = state.access_config(|c| {
+    c.webhooks
+      .iter()
+      .filter(|(_name, webhook)| {
+        webhook.api_name() == api.api_name()
+          && (webhook.events.is_empty() || webhook.events().any(|e| e == event))
+      })
+      .map(|(name, _webhook)| name.clone())
+      .collect()
+  });
+  if webhooks.is_empty() {
+    return Ok(());
+  }
+
+  let looked_up: Option<libsql::Row>;
+  let row = match row {
+    Some(row) => row,
+    None => {
+      looked_up = query_row(
+        conn,
+        &format!(
+          "SELECT * FROM '{table_name}' WHERE [{pk_column}] = $1",
+          table_name = api.table_name(),
+          pk_column = api.record_pk_column().name,
+        ),
+        [record_id.clone()],
+      )
+      .await?;
+      let Some(ref row) = looked_up else {
+        return Ok(());
+      };
+      row
+    }
+  };
+
+  let record = row_to_json(api.metadata(), row, |c| !c.starts_with("_"))
+    .map_err(|err| RecordError::Internal(err.into()))?;
+  let created = Utc::now().timestamp();
+
+  for webhook in webhooks {
+    let payload = serde_json::json!({
+      "webhook": webhook,
+      "event": event_name,
+      "api": api.api_name(),
+      "record": record,
+      "created": created,
+    });
+
+    conn
+      .execute(
+        &format!("INSERT INTO {WEBHOOK_DELIVERY_TABLE} (webhook, payload) VALUES ($1, $2)"),
+        [webhook, payload.to_string()],
+      )
+      .await?;
+  }
+
+  return Ok(());
+}
+
+/// Hex-encoded HMAC-SHA256 signature of the payload.
+pub(crate) fn sign_payload(secret: &str, payload: &str) -> String {
+  return format!(
+    "sha256={}",
+    hex_encode(&hmac_sha256(secret.as_bytes(), payload.as_bytes()))
+  );
+}
+
+fn backoff_sec(attempts: i64) -> i64 {
+  let exponent = (attempts - 1).clamp(0, 20) as u32;
+  return INITIAL_BACKOFF_SEC
+    .saturating_mul(2_i64.pow(exponent))
+    .min(MAX_BACKOFF_SEC);
+}
+
+enum Outcome {
+  Delivered(u16),
+  Failed {
+    status_code: Option<u16>,
+    error: String,
+    retry: bool,
+  },
+}
+
+async fn deliver(
+  client: &reqwest::Client,
+  id: i64,
+  webhook: Option<&WebhookConfig>,
+  payload: String,
+) -> Outcome {
+  let Some(webhook) = webhook else {
+    return Outcome::Failed {
+      status_code: None,
+      error: "Webhook not found".to_string(),
+      retry: false,
+    };
+  };
+
+  let mut request = client
+    .post(webhook.url())
+    .header(CONTENT_TYPE, "application/json")
+    .header(DELIVERY_HEADER, id.to_string());
+  if let Some(secret) = webhook.secret.as_deref().filter(|s| !s.is_empty()) {
+    request = request.header(SIGNATURE_HEADER, sign_payload(secret, &payload));
+  }
+
+  return match request.body(payload).send().await {
+    Ok(response) if response.status().is_success() => {
+      Outcome::Delivered(response.status().as_u16())
+    }
+    Ok(response) => Outcome::Failed {
+      status_code: Some(response.status().as_u16()),
+      error: format!("Unexpected status: {}", response.status()),
+      retry: true,
+    },
+    Err(err) => Outcome::Failed {
+      status_code: None,
+      error: err.to_string(),
+      retry: true,
+    },
+  };
+}
+
+/// Attempts all due webhook deliveries and returns the number of successful ones.
+///
+/// Failed deliveries are retried with exponential backoff until they run out of attempts.
+pub(crate) async fn deliver_pending_webhooks(state: &AppState) -> Result<usize, RecordError> {
+  let conn = state.conn();
+  let now = Utc::now().timestamp();
+
+  // Claim due deliveries by pushing back their next attempt.
+  let mut rows = conn
+    .query(
+      &format!(
+        r#"
+          UPDATE {WEBHOOK_DELIVERY_TABLE} SET next_attempt = $1
+          WHERE id IN (
+            SELECT id FROM {WEBHOOK_DELIVERY_TABLE}
+            WHERE status = 'pending' AND next_attempt <= $2
+            ORDER BY id LIMIT $3
+          )
+          RETURNING id, webhook, payload, attempts
+        "#
+      ),
+      [now + CLAIM_SEC, now, BATCH_SIZE],
+    )
+    .await?;
+
+  let mut claimed: Vec<(i64, String, String, i64)> = vec![];
+  while let Some(row) = rows.next().await? {
+    claimed.push((
+      row.get::<i64>(0)?,
+      row.get::<String>(1)?,
+      row.get::<String>(2)?,
+      row.get::<i64>(3)?,
+    ));
+  }
+  if claimed.is_empty() {
+    return Ok(0);
+  }
+
+  let client = reqwest::Client::builder()
+    .timeout(REQUEST_TIMEOUT)
+    .build()
+    .map_err(|err| RecordError::Internal(err.into()))?;
+  let webhooks = state.access_config(|c| c.webhooks.clone());
+
+  let outcomes =
+    futures::future::join_all(claimed.into_iter().map(|(id, webhook, payload, attempts)| {
+      let (client, webhook) = (&client, webhooks.get(&webhook));
+      async move {
+        (
+          id,
+          attempts + 1,
+          deliver(client, id, webhook, payload).await,
+        )
+      }
+    }))
+    .await;
+
+  let mut delivered: usize = 0;
+  for (id, attempts, outcome) in outcomes {
+    let (status, status_code, error, next_attempt) = match outcome {
+      Outcome::Delivered(status_code) => {
+        delivered += 1;
+        ("delivered", Some(status_code), None, now)
+      }
+      Outcome::Failed {
+        status_code,
+        error,
+        retry,
+      } => {
+        let status = match retry && attempts < MAX_ATTEMPTS {
+          true => "pending",
+          false => "failed",
+        };
+        (
+          status,
+          status_code,
+          Some(error),
+          now + backoff_sec(attempts),
+        )
+      }
+    };
+
+    conn
+      .execute(
+        &format!(
+          r#"
+            UPDATE {WEBHOOK_DELIVERY_TABLE}
+            SET status = $1, attempts = $2, last_status_code = $3, last_error = $4,
+                next_attempt = $5, updated = UNIXEPOCH()
+            WHERE id = $6
+          "#
+        ),
+        libsql::params::Params::Positional(vec![
+          libsql::Value::Text(status.to_string()),
+          libsql::Value::Integer(attempts),
+          status_code.map_or(libsql::Value::Null, |c| libsql::Value::Integer(c as i64)),
+          error.map_or(libsql::Value::Null, libsql::Value::Text),
+          libsql::Value::Integer(next_attempt),
+          libsql::Value::Integer(id),
+        ]),
+      )
+      .await?;
+  }
+
+  return Ok(delivered);
+}
+
+/// Purges delivered and failed deliveries older than `retention` and returns their number.
+///
+/// Pending deliveries are kept regardless of their age.
+pub(crate) async fn purge_webhook_deliveries(
+  state: &AppState,
+  retention: chrono::Duration,
+) -> Result<u64, RecordError> {
+  let timestamp = (Utc::now() - retention).timestamp();
+  return Ok(
+    state
+      .conn()
+      .execute(
+        &format!(
+          "DELETE FROM {WEBHOOK_DELIVERY_TABLE} WHERE status IN ('delivered', 'failed') AND updated < $1"
+        ),
+        [timestamp],
+      )
+      .await?,
+  );
+}
+
+#[cfg(test)]
+mod test {
+  use axum::extract::{Path, Query, State};
+  use axum::http::{HeaderMap, StatusCode};
+  use axum::routing::post;
+  use axum::Router;
+  use std::sync::{Arc, Mutex};
+  use trailbase_sqlite::query_one_row;
+
+  use super::*;
+  use crate::app_state::*;
+  use crate::config::proto::PermissionFlag;
+  use crate::extract::Either;
+  use crate::records::create_record::{create_record_handler, CreateRecordQuery};
+  use crate::records::delete_record::delete_record_handler;
+  use crate::records::*;
+
+  type Received = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+  /// Local stand-in for a webhook receiver, which records requests to "/ok" and fails requests
+  /// to "/fail".
+  async fn serve_receiver() -> Result<(String, Received), anyhow::Error> {
+    let received: Received = Arc::new(Mutex::new(vec![]));
+    let router = Router::new()
+      .route(
+        "/ok",
+        post(
+          |State(received): State<Received>, headers: HeaderMap, body: String| async move {
+            received.lock().unwrap().push((headers, body));
+            StatusCode::OK
+          },
+        ),
+      )
+      .route("/fail", post(|| async { StatusCode::SERVICE_UNAVAILABLE }))
+      .with_state(received.clone());
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let address = format!("http://{}", listener.local_addr()?);
+    tokio::spawn(async move { axum::serve(listener, router).await });
+
+    return Ok((address, received));
+  }
+
+  #[test]
+  fn test_backoff() {
+    assert_eq!(backoff_sec(1), INITIAL_BACKOFF_SEC);
+    assert_eq!(backoff_sec(2), 2 * INITIAL_BACKOFF_SEC);
+    assert_eq!(backoff_sec(4), 8 * INITIAL_BACKOFF_SEC);
+    assert_eq!(backoff_sec(MAX_ATTEMPTS), MAX_BACKOFF_SEC);
+  }
+
+  #[tokio::test]
+  async fn test_webhook_deliveries() -> Result<(), anyhow::Error> {
+    let (address, received) = serve_receiver().await?;
+
+    let state = test_state(None).await?;
+    let conn = state.conn();
+    conn
+      .execute_batch(
+        r#"
+          CREATE TABLE note (
+            id        INTEGER PRIMARY KEY,
+            body      TEXT NOT NULL,
+            _secret   TEXT
+          ) STRICT;
+        "#,
+      )
+      .await?;
+    state.table_metadata().invalidate_all().await?;
+
+    add_record_api(
+      &state,
+      "notes_api",
+      "note",
+      Acls {
+        world: vec![PermissionFlag::Create, PermissionFlag::Delete],
+        ..Default::default()
+      },
+      AccessRules::default(),
+    )
+    .await?;
+
+    let mut config = state.get_config();
+    config.webhooks.insert(
+      "receiver".to_string(),
+      WebhookConfig {
+        api_name: Some("notes_api".to_string()),
+        events: vec![],
+        url: Some(format!("{address}/ok")),
+        secret: Some("secret".to_string()),
+      },
+    );
+    config.webhooks.insert(
+      "failing".to_string(),
+      WebhookConfig {
+        api_name: Some("notes_api".to_string()),
+        events: vec![WebhookEvent::RecordDeleted as i32],
+        url: Some(format!("{address}/fail")),
+        secret: None,
+      },
+    );
+    state.validate_and_update_config(config, None).await?;
+
+    create_record_handler(
+      State(state.clone()),
+      Path("notes_api".to_string()),
+      Query(CreateRecordQuery::default()),
+      None,
+      Either::Json(serde_json::json!({"id": 1, "body": "first", "_secret": "hidden"})),
+    )
+    .await?;
+    delete_record_handler(
+      State(state.clone()),
+      Path(("notes_api".to_string(), "1".to_string())),
+      None,
+      HeaderMap::new(),
+    )
+    .await?;
+
+    // One delivery for the creation and one per webhook for the deletion.
+    assert_eq!(deliver_pending_webhooks(&state).await?, 2);
+    assert_eq!(deliver_pending_webhooks(&state).await?, 0);
+
+    let received = received.lock().unwrap().clone();
+    assert_eq!(received.len(), 2);
+    for (headers, body) in &received {
+      assert_eq!(
+        headers.get(SIGNATURE_HEADER).unwrap().to_str()?,
+        sign_payload("secret", body)
+      );
+      assert!(headers.get(DELIVERY_HEADER).is_some());
+    }
+
+    // Deliveries are sent concurrently, i.e. they may arrive in any order.
+    let mut events: Vec<serde_json::Value> = received
+      .iter()
+      .map(|(_headers, body)| serde_json::from_str(body))
+      .collect::<Result<_, _>>()?;
+    events.sort_by_key(|e| e["event"].as_str().unwrap().to_string());
+    assert_eq!(events[0]["event"], "delete");
+    assert_eq!(events[1]["event"], "insert");
+    for event in &events {
+      assert_eq!(event["webhook"], "receiver");
+      assert_eq!(event["api"], "notes_api");
+      assert_eq!(
+        event["record"],
+        serde_json::json!({"id": 1, "body": "first"})
+      );
+    }
+
+    // Failed deliveries are rescheduled with backoff.
+    let row = query_one_row(
+      conn,
+      &format!(
+        "SELECT status, attempts, last_status_code, next_attempt - updated FROM {WEBHOOK_DELIVERY_TABLE} WHERE webhook = 'failing'"
+      ),
+      (),
+    )
+    .await?;
+    assert_eq!(row.get::<String>(0)?, "pending");
+    assert_eq!(row.get::<i64>(1)?, 1);
+    assert_eq!(row.get::<i64>(2)?, 503);
+    assert!((row.get::<i64>(3)? - INITIAL_BACKOFF_SEC).abs() <= 1);
+
+    // Until they run out of attempts.
+    conn
+      .execute(
+        &format!(
+          "UPDATE {WEBHOOK_DELIVERY_TABLE} SET attempts = {attempts}, next_attempt = 0 WHERE webhook = 'failing'",
+          attempts = MAX_ATTEMPTS - 1
+        ),
+        (),
+      )
+      .await?;
+    assert_eq!(deliver_pending_webhooks(&state).await?, 0);
+    let status = query_one_row(
+      conn,
+      &format!("SELECT status FROM {WEBHOOK_DELIVERY_TABLE} WHERE webhook = 'failing'"),
+      (),
+    )
+    .await?
+    .get::<String>(0)?;
+    assert_eq!(status, "failed");
+
+    // Settled deliveries are purged once they're past retention, pending ones are kept.
+    assert_eq!(
+      purge_webhook_deliveries(&state, chrono::Duration::hours(1)).await?,
+      0
+    );
+    conn
+      .execute(
+        &format!("UPDATE {WEBHOOK_DELIVERY_TABLE} SET updated = updated - 7200"),
+        (),
+      )
+      .await?;
+    conn
+      .execute(
+        &format!(
+          "UPDATE {WEBHOOK_DELIVERY_TABLE} SET status = 'pending' WHERE webhook = 'failing'"
+        ),
+        (),
+      )
+      .await?;
+    assert_eq!(
+      purge_webhook_deliveries(&state, chrono::Duration::hours(1)).await?,
+      2
+    );
+    let remaining = query_one_row(
+      conn,
+      &format!("SELECT COUNT(*) FROM {WEBHOOK_DELIVERY_TABLE}"),
+      (),
+    )
+    .await?
+    .get::<i64>(0)?;
+    assert_eq!(remaining, 1);
+
+    return Ok(());
+  }
+}
@@ -5,7 +5,13 @@ use rusqlite::{Connection, DatabaseName};
 use std::future::Future;
 
 use crate::app_state::AppState;
-use crate::constants::{DEFAULT_REFRESH_TOKEN_TTL, LOGS_RETENTION_DEFAULT, SESSION_TABLE};
+use crate::constants::{
+  DEFAULT_REFRESH_TOKEN_TTL, LOGS_RETENTION_DEFAULT, ORPHANED_FILES_GRACE_PERIOD, SESSION_TABLE,
+  WEBHOOK_DELIVERY_RETENTION,
+};
+use crate::records::files::delete_orphaned_files;
+use crate::records::soft_delete::purge_soft_deleted_records;
+use crate::records::webhooks::{deliver_pending_webhooks, purge_webhook_deliveries};
 
 #[derive(Default)]
 pub struct AbortOnDrop {
@@ -122,6 +128,60 @@ pub(super) fn start_periodic_tasks(app_state: &AppState) -> AbortOnDrop {
     })
   });
 
+  // Soft-deleted records cleaner.
+  let state = app_state.clone();
+  tasks.add_periodic_task(Duration::hours(1), move || {
+    let state = state.clone();
+
+    tokio::spawn(async move {
+      match purge_soft_deleted_records(&state).await {
+        Ok(count) => info!("Successfully purged {count} soft-deleted records."),
+        Err(err) => warn!("Failed to purge soft-deleted records: {err}"),
+      };
+    })
+  });
+
+  // Orphaned files cleaner, e.g. for files of rows deleted using raw SQL.
+  let state = app_state.clone();
+  tasks.add_periodic_task(Duration::hours(6), move || {
+    let state = state.clone();
+
+    tokio::spawn(async move {
+      match delete_orphaned_files(&state, ORPHANED_FILES_GRACE_PERIOD).await {
+        Ok(orphans) if orphans.is_empty() => {}
+        Ok(orphans) => info!("Successfully deleted {} orphaned files.", orphans.len()),
+        Err(err) => warn!("Failed to delete orphaned files: {err}"),
+      };
+    })
+  });
+
+  // Webhook deliveries.
+  let state = app_state.clone();
+  tasks.add_periodic_task(Duration::seconds(5), move || {
+    let state = state.clone();
+
+    tokio::spawn(async move {
+      match deliver_pending_webhooks(&state).await {
+        Ok(0) => {}
+        Ok(count) => info!("Successfully delivered {count} webhook events."),
+        Err(err) => warn!("Failed to deliver webhook events: {err}"),
+      };
+    })
+  });
+
+  // Webhook deliveries cleaner.
+  let state = app_state.clone();
+  tasks.add_periodic_task(Duration::hours(2), move || {
+    let state = state.clone();
+
+    tokio::spawn(async move {
+      match purge_webhook_deliveries(&state, WEBHOOK_DELIVERY_RETENTION).await {
+        Ok(count) => info!("Successfully purged {count} webhook deliveries."),
+        Err(err) => warn!("Failed to purge webhook deliveries: {err}"),
+      };
+    })
+  });
+
   // Optimizer
   let conn = app_state.conn().clone();
   tasks.add_periodic_task(Duration::hours(24), move || {
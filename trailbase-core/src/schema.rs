@@ -255,7 +255,7 @@ pub enum ColumnDataType {
 }
 
 impl ColumnDataType {
-  fn from_type_name(type_name: &str) -> Option<Self> {
+  pub(crate) fn from_type_name(type_name: &str) -> Option<Self> {
     return Some(match type_name.to_uppercase().as_str() {
       "UNSPECIFIED" => ColumnDataType::Null,
       "ANY" => ColumnDataType::Any,
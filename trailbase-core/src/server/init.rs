@@ -10,6 +10,8 @@ use crate::config::load_or_init_config_textproto;
 use crate::constants::USER_TABLE;
 use crate::migrations::{apply_logs_migrations, apply_main_migrations};
 use crate::rand::generate_random_string;
+use crate::records::geo::sync_geo_indexes;
+use crate::records::search::sync_search_tables;
 use crate::server::DataDir;
 use crate::table_metadata::TableMetadataCache;
 
@@ -65,6 +67,8 @@ pub async fn init_app_state(
     (conn, new_db)
   };
 
+  let write_conn = connect_sqlite(Some(data_dir.main_db_path()), None).await?;
+
   let table_metadata = TableMetadataCache::new(main_conn.clone()).await?;
 
   // Read config or write default one.
@@ -120,12 +124,17 @@ pub async fn init_app_state(
     table_metadata,
     config,
     conn: main_conn.clone(),
+    write_conn,
     logs_conn,
     jwt,
     object_store,
     js_runtime_threads: args.js_runtime_threads,
   });
 
+  sync_search_tables(app_state.conn(), &app_state.get_config()).await?;
+  sync_geo_indexes(app_state.conn(), &app_state.get_config()).await?;
+  app_state.table_metadata().invalidate_all().await?;
+
   if new_db {
     let num_admins: i64 = query_one_row(
       app_state.user_conn(),
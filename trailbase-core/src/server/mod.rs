@@ -1,8 +1,9 @@
 mod init;
 
+use axum::body::Body;
 use axum::extract::{DefaultBodyLimit, Request, State};
 use axum::handler::HandlerWithoutStateExt;
-use axum::http::{HeaderValue, StatusCode};
+use axum::http::{header, HeaderValue, StatusCode};
 use axum::middleware::{self, Next};
 use axum::response::{IntoResponse, Response};
 use axum::routing::get;
@@ -12,7 +13,7 @@ use std::path::PathBuf;
 use tokio::signal;
 use tokio::task::JoinSet;
 use tower_cookies::CookieManagerLayer;
-use tower_http::{cors, limit::RequestBodyLimitLayer, services::ServeDir, trace::TraceLayer};
+use tower_http::{cors, services::ServeDir, trace::TraceLayer};
 use tracing_subscriber::{filter, prelude::*};
 
 use crate::admin;
@@ -20,8 +21,12 @@ use crate::app_state::AppState;
 use crate::assets::AssetService;
 use crate::auth::util::is_admin;
 use crate::auth::{self, AuthError, User};
-use crate::constants::{AUTH_API_PATH, HEADER_CSRF_TOKEN, QUERY_API_PATH, RECORD_API_PATH};
+use crate::constants::{
+  AUTH_API_PATH, HEADER_CSRF_TOKEN, MAX_REQUEST_BODY_SIZE, QUERY_API_PATH, RECORD_API_PATH,
+  TRANSACTION_API_PATH,
+};
 use crate::data_dir::DataDir;
+use crate::extract::is_multipart;
 use crate::logging;
 use crate::scheduler;
 
@@ -247,8 +252,18 @@ impl Server {
   ) -> (String, Router<()>) {
     let mut router = Router::new()
       // Public, stable and versioned APIs.
-      .nest(&format!("/{RECORD_API_PATH}"), crate::records::router())
+      .nest(
+        &format!("/{RECORD_API_PATH}"),
+        crate::records::router().route_layer(middleware::from_fn_with_state(
+          state.clone(),
+          crate::records::files::attach_file_upload_target,
+        )),
+      )
       .nest(&format!("/{QUERY_API_PATH}"), crate::query::router())
+      .nest(
+        &format!("/{TRANSACTION_API_PATH}"),
+        crate::records::transaction_router(),
+      )
       .nest(&format!("/{AUTH_API_PATH}"), auth::router())
       .route("/api/healthcheck", get(healthcheck_handler));
 
@@ -298,9 +313,9 @@ impl Server {
           .on_request(logging::sqlite_logger_on_request)
           .on_response(logging::sqlite_logger_on_response),
       )
-      // Default is only 2MB Increase to 10MB.
+      // Default is only 2MB. Instead, apply our own limit, which exempts streamed uploads.
       .layer(DefaultBodyLimit::disable())
-      .layer(RequestBodyLimitLayer::new(10 * 1024 * 1024))
+      .layer(middleware::from_fn(limit_request_body))
       .with_state(state.clone());
   }
 }
@@ -313,6 +328,39 @@ fn has_indepenedent_admin_router(opts: &ServerOptions) -> bool {
   };
 }
 
+/// Caps request bodies at [MAX_REQUEST_BODY_SIZE].
+///
+/// Multipart requests to record APIs are deferred to [crate::records::files::attach_file_upload_target],
+/// which only lifts the limit if uploaded files get streamed to the object store subject to the
+/// targeted API's file upload limits.
+async fn limit_request_body(req: Request, next: Next) -> Response {
+  if is_multipart(req.headers())
+    && req
+      .uri()
+      .path()
+      .starts_with(&format!("/{RECORD_API_PATH}/"))
+  {
+    return next.run(req).await;
+  }
+
+  return match apply_request_body_limit(req) {
+    Ok(req) => next.run(req).await,
+    Err(response) => response,
+  };
+}
+
+pub(crate) fn apply_request_body_limit(req: Request) -> Result<Request, Response> {
+  let content_length = req
+    .headers()
+    .get(header::CONTENT_LENGTH)
+    .and_then(|v| v.to_str().ok()?.parse::<usize>().ok());
+  if content_length.is_some_and(|l| l > MAX_REQUEST_BODY_SIZE) {
+    return Err((StatusCode::PAYLOAD_TOO_LARGE, "Payload too large").into_response());
+  }
+
+  return Ok(req.map(|body| Body::new(http_body_util::Limited::new(body, MAX_REQUEST_BODY_SIZE))));
+}
+
 async fn healthcheck_handler() -> Response {
   return (StatusCode::OK, "Ok").into_response();
 }
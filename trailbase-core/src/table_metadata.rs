@@ -79,7 +79,6 @@ pub struct TableMetadata {
   pub file_uploads_columns: Vec<usize>,
 
   // Only non-composite keys.
-  #[allow(unused)]
   foreign_ids: Vec<(usize, ForeignKey)>,
   // TODO: Add triggers once sqlparser supports a sqlite "CREATE TRIGGER" statements.
 }
@@ -143,6 +142,15 @@ impl TableMetadata {
       })
       .collect();
 
+    // Table constraints, i.e. "FOREIGN KEY(col) REFERENCES ...", for single columns.
+    for fk in &table.foreign_keys {
+      if let [column] = fk.columns.as_slice() {
+        if let Some(index) = name_to_index.get(column) {
+          foreign_ids.push((*index, fk.clone()));
+        }
+      }
+    }
+
     let record_pk_column = find_record_pk_column_index(&table.columns, tables);
     let user_id_columns = find_user_id_foreign_key_columns(&table.columns);
 
@@ -173,6 +181,15 @@ impl TableMetadata {
     let index = self.column_index_by_name(key)?;
     return Some((&self.schema.columns[index], &self.metadata[index]));
   }
+
+  /// Returns the non-composite foreign key for the given column, if any.
+  pub fn foreign_key_by_column_name(&self, key: &str) -> Option<&ForeignKey> {
+    let index = self.column_index_by_name(key)?;
+    return self
+      .foreign_ids
+      .iter()
+      .find_map(|(i, fk)| if *i == index { Some(fk) } else { None });
+  }
 }
 
 /// A data class describing a sqlite View and future, additional meta data useful for TrailBase.
@@ -502,6 +519,10 @@ impl TableMetadataCache {
     self.state.views.read().get(view_name).cloned()
   }
 
+  pub fn tables(&self) -> Vec<Arc<TableMetadata>> {
+    self.state.tables.read().values().cloned().collect()
+  }
+
   pub async fn invalidate_all(&self) -> Result<(), TableLookupError> {
     debug!("Rebuilding TableMetadataCache");
     let (table_map, tables) = Self::build_tables(&self.state.conn).await?;
@@ -683,7 +704,7 @@ pub enum JsonSchemaMode {
   Update,
 }
 
-fn column_data_type_to_json_type(data_type: ColumnDataType) -> Value {
+pub(crate) fn column_data_type_to_json_type(data_type: ColumnDataType) -> Value {
   return match data_type {
     ColumnDataType::Null => Value::String("null".into()),
     ColumnDataType::Any => Value::Array(vec![
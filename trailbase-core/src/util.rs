@@ -1,4 +1,6 @@
 use base64::prelude::*;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -35,6 +37,18 @@ pub fn urlencode(s: &str) -> String {
   return form_urlencoded::byte_serialize(s.as_bytes()).collect();
 }
 
+/// HMAC-SHA256 as per RFC 2104.
+pub(crate) fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+  // NOTE: HMAC accepts keys of any size.
+  let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("any key size");
+  mac.update(message);
+  return mac.finalize().into_bytes().into();
+}
+
+pub(crate) fn hex_encode(bytes: &[u8]) -> String {
+  return bytes.iter().map(|b| format!("{b:02x}")).collect();
+}
+
 #[cfg(debug_assertions)]
 #[inline(always)]
 pub(crate) fn assert_uuidv7(id: &[u8; 16]) {
@@ -55,3 +69,24 @@ pub(crate) fn assert_uuidv7_version(uuid: &Uuid) {
 
 #[cfg(not(debug_assertions))]
 pub(crate) fn assert_uuidv7_version(_uuid: &Uuid) {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_hmac_sha256() {
+    // Test cases 2 and 6 from RFC 4231.
+    assert_eq!(
+      hex_encode(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+      "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+    assert_eq!(
+      hex_encode(&hmac_sha256(
+        &[0xaa; 131],
+        b"Test Using Larger Than Block-Size Key - Hash Key First"
+      )),
+      "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+    );
+  }
+}
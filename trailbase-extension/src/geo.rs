@@ -0,0 +1,101 @@
+use sqlite_loadable::prelude::*;
+use sqlite_loadable::{api, Error};
+
+/// Mean earth radius in meters.
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Great-circle distance in meters between two points given in degrees using the haversine
+/// formula.
+pub fn haversine_distance(lat0: f64, lng0: f64, lat1: f64, lng1: f64) -> f64 {
+  let (lat0, lat1) = (lat0.to_radians(), lat1.to_radians());
+  let d_lat = lat1 - lat0;
+  let d_lng = (lng1 - lng0).to_radians();
+
+  let a = (d_lat / 2.0).sin().powi(2) + lat0.cos() * lat1.cos() * (d_lng / 2.0).sin().powi(2);
+  return 2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin();
+}
+
+/// Error message of `geo_distance` for non-numeric arguments, e.g. TEXT values.
+pub const NON_NUMERIC_COORDINATES: &str = "geo_distance: expected numeric coordinates";
+
+/// SQL function `geo_distance(lat0, lng0, lat1, lng1)` returning the distance in meters or NULL if
+/// any of the coordinates is NULL.
+pub(super) fn geo_distance(
+  context: *mut sqlite3_context,
+  values: &[*mut sqlite3_value],
+) -> Result<(), Error> {
+  if values.len() != 4 {
+    return Err(Error::new_message("Expected 4 arguments"));
+  }
+
+  let mut coordinates = [0.0; 4];
+  for (coordinate, value) in coordinates.iter_mut().zip(values) {
+    *coordinate = match api::value_type(value) {
+      api::ValueType::Null => {
+        api::result_null(context);
+        return Ok(());
+      }
+      api::ValueType::Integer | api::ValueType::Float => api::value_double(value),
+      _ => {
+        return Err(Error::new_message(NON_NUMERIC_COORDINATES));
+      }
+    };
+  }
+
+  let [lat0, lng0, lat1, lng1] = coordinates;
+  api::result_double(context, haversine_distance(lat0, lng0, lat1, lng1));
+
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::query_row;
+
+  #[test]
+  fn test_haversine_distance() {
+    assert_eq!(haversine_distance(52.5, 13.4, 52.5, 13.4), 0.0);
+
+    // Berlin to Paris is roughly 878km.
+    let d = haversine_distance(52.5200, 13.4050, 48.8566, 2.3522);
+    assert!((d - 877_500.0).abs() < 2_000.0, "{d}");
+
+    // Symmetric and well-defined across the anti-meridian.
+    assert_eq!(
+      haversine_distance(0.0, 179.5, 0.0, -179.5),
+      haversine_distance(0.0, -179.5, 0.0, 179.5)
+    );
+    assert!(haversine_distance(0.0, 179.5, 0.0, -179.5) < 112_000.0);
+  }
+
+  #[tokio::test]
+  async fn test_geo_distance() {
+    let conn = crate::connect().await.unwrap();
+
+    let d: f64 = query_row(
+      &conn,
+      "SELECT geo_distance(52.52, 13.405, 48.8566, 2.3522)",
+      (),
+    )
+    .await
+    .unwrap()
+    .unwrap()
+    .get(0)
+    .unwrap();
+    assert_eq!(d, haversine_distance(52.52, 13.405, 48.8566, 2.3522));
+
+    let d: Option<f64> = query_row(&conn, "SELECT geo_distance(NULL, 13, 48, 2)", ())
+      .await
+      .unwrap()
+      .unwrap()
+      .get(0)
+      .unwrap();
+    assert_eq!(d, None);
+
+    assert!(query_row(&conn, "SELECT geo_distance('a', 13, 48, 2)", ())
+      .await
+      .is_err());
+  }
+}
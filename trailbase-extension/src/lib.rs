@@ -4,6 +4,7 @@ use sqlite_loadable::prelude::*;
 use sqlite_loadable::{define_scalar_function, define_scalar_void_function};
 use uuid::*;
 
+pub mod geo;
 pub mod jsonschema;
 pub mod maxminddb;
 pub mod password;
@@ -115,6 +116,14 @@ pub fn sqlite3_extension_init(db: *mut sqlite3) -> Result<(), sqlite_loadable::E
     validators::is_json,
     FunctionFlags::UTF8 | FunctionFlags::DETERMINISTIC | FunctionFlags::INNOCUOUS,
   )?;
+  // Geospatial, e.g. geo_distance(lat0, lng0, lat1, lng1) in meters.
+  define_scalar_function(
+    db,
+    "geo_distance",
+    4,
+    geo::geo_distance,
+    FunctionFlags::UTF8 | FunctionFlags::DETERMINISTIC | FunctionFlags::INNOCUOUS,
+  )?;
   define_scalar_function(
     db,
     "geoip_country",
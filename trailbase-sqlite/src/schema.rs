@@ -94,6 +94,10 @@ impl FileUpload {
     self.content_type.as_deref()
   }
 
+  pub fn mime_type(&self) -> Option<&str> {
+    self.mime_type.as_deref()
+  }
+
   pub fn original_filename(&self) -> Option<&str> {
     self.filename.as_deref()
   }
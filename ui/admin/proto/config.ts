@@ -137,7 +137,7 @@ export enum PermissionFlag {
   PERMISSION_FLAG_UNDEFINED = 0,
   /** CREATE - Database record insert. */
   CREATE = 1,
-  /** READ - Database record read/list, i.e. select. */
+  /** READ - Database record read, i.e. select. */
   READ = 2,
   /** UPDATE - Database record update. */
   UPDATE = 4,
@@ -145,6 +145,11 @@ export enum PermissionFlag {
   DELETE = 8,
   /** SCHEMA - / Lookup JSON schema for the given record api . */
   SCHEMA = 16,
+  /**
+   * LIST - Database record listing, i.e. select of multiple records. Configs
+   * predating LIST are migrated by granting LIST wherever READ is granted.
+   */
+  LIST = 32,
   UNRECOGNIZED = -1,
 }
 
@@ -168,6 +173,9 @@ export function permissionFlagFromJSON(object: any): PermissionFlag {
     case 16:
     case "SCHEMA":
       return PermissionFlag.SCHEMA;
+    case 32:
+    case "LIST":
+      return PermissionFlag.LIST;
     case -1:
     case "UNRECOGNIZED":
     default:
@@ -189,6 +197,8 @@ export function permissionFlagToJSON(object: PermissionFlag): string {
       return "DELETE";
     case PermissionFlag.SCHEMA:
       return "SCHEMA";
+    case PermissionFlag.LIST:
+      return "LIST";
     case PermissionFlag.UNRECOGNIZED:
     default:
       return "UNRECOGNIZED";
@@ -279,6 +289,51 @@ export function queryApiAclToJSON(object: QueryApiAcl): string {
   }
 }
 
+export enum WebhookEvent {
+  WEBHOOK_EVENT_UNDEFINED = 0,
+  RECORD_CREATED = 1,
+  RECORD_UPDATED = 2,
+  RECORD_DELETED = 3,
+  UNRECOGNIZED = -1,
+}
+
+export function webhookEventFromJSON(object: any): WebhookEvent {
+  switch (object) {
+    case 0:
+    case "WEBHOOK_EVENT_UNDEFINED":
+      return WebhookEvent.WEBHOOK_EVENT_UNDEFINED;
+    case 1:
+    case "RECORD_CREATED":
+      return WebhookEvent.RECORD_CREATED;
+    case 2:
+    case "RECORD_UPDATED":
+      return WebhookEvent.RECORD_UPDATED;
+    case 3:
+    case "RECORD_DELETED":
+      return WebhookEvent.RECORD_DELETED;
+    case -1:
+    case "UNRECOGNIZED":
+    default:
+      return WebhookEvent.UNRECOGNIZED;
+  }
+}
+
+export function webhookEventToJSON(object: WebhookEvent): string {
+  switch (object) {
+    case WebhookEvent.WEBHOOK_EVENT_UNDEFINED:
+      return "WEBHOOK_EVENT_UNDEFINED";
+    case WebhookEvent.RECORD_CREATED:
+      return "RECORD_CREATED";
+    case WebhookEvent.RECORD_UPDATED:
+      return "RECORD_UPDATED";
+    case WebhookEvent.RECORD_DELETED:
+      return "RECORD_DELETED";
+    case WebhookEvent.UNRECOGNIZED:
+    default:
+      return "UNRECOGNIZED";
+  }
+}
+
 export interface EmailTemplate {
   subject?: string | undefined;
   body?: string | undefined;
@@ -348,6 +403,56 @@ export interface ServerConfig {
   backupIntervalSec?: number | undefined;
 }
 
+/**
+ * Column-level access control for record APIs. A column can be read
+ * (written) if it's in the allow-list, or the allow-list is empty, and it's
+ * not in the deny-list. Hidden columns, i.e. ones prefixed with "_", can never
+ * be read.
+ */
+export interface ColumnAcl {
+  readAllow: string[];
+  readDeny: string[];
+  writeAllow: string[];
+  writeDeny: string[];
+}
+
+/**
+ * Named SQL expression, e.g. `_ROW_.first || ' ' || _ROW_.last`, evaluated
+ * over `_ROW_` and `_USER_` and appended to read and listed records.
+ */
+export interface ComputedField {
+  name?: string | undefined;
+  expression?:
+    | string
+    | undefined;
+  /**
+   * SQLite type of the result, e.g. "TEXT" or "INTEGER", which is reflected
+   * in the JSON schema. Unconstrained if unset.
+   */
+  type?: string | undefined;
+}
+
+/** Constraints for files uploaded to `std.FileUpload(s)` columns. */
+export interface FileUploadLimits {
+  /**
+   * Column the limits apply to. Applies to all file columns of the API if
+   * unset. Unset fields of per-column limits fall back to the API-wide ones.
+   */
+  column?:
+    | string
+    | undefined;
+  /** Max size of an individual file in bytes. Default: 10MB. */
+  maxSize?:
+    | number
+    | undefined;
+  /**
+   * Allowed MIME types, e.g. "video/mp4" or "image/*", as inferred from the
+   * file contents rather than provided by the client. Any type is allowed if
+   * empty.
+   */
+  allowedMimeTypes: string[];
+}
+
 export interface RecordApiConfig {
   name?: string | undefined;
   tableName?: string | undefined;
@@ -362,7 +467,70 @@ export interface RecordApiConfig {
   readAccessRule?: string | undefined;
   updateAccessRule?: string | undefined;
   deleteAccessRule?: string | undefined;
-  schemaAccessRule?: string | undefined;
+  schemaAccessRule?:
+    | string
+    | undefined;
+  /** Used to filter listed records. Falls back to the read access rule if unset. */
+  listAccessRule?:
+    | string
+    | undefined;
+  /**
+   * Column, e.g. a version counter or last-updated timestamp, from which
+   * record ETags are derived. Defaults to a hash over the entire row.
+   */
+  etagColumn?:
+    | string
+    | undefined;
+  /**
+   * Columns indexed for full-text search when listing records via `?q=`.
+   * Backed by an FTS5 table, which is kept in sync using triggers.
+   */
+  searchColumns: string[];
+  /**
+   * Latitude and longitude columns, in degrees, indexed for geospatial
+   * `near` and `bbox` filters when listing records. Backed by an R*-tree
+   * table, which is kept in sync using triggers.
+   */
+  geoLatitudeColumn?: string | undefined;
+  geoLongitudeColumn?:
+    | string
+    | undefined;
+  /**
+   * Tombstone column, e.g. `deleted INTEGER`, enabling soft-deletes. Deleting
+   * a record sets the column to the current UNIX timestamp instead of removing
+   * the row. Soft-deleted records are hidden from reads and listings and can
+   * be restored until they're purged. The column cannot be written by clients.
+   */
+  softDeleteColumn?:
+    | string
+    | undefined;
+  /**
+   * Max age of soft-deleted records before they and their files are purged.
+   * Must be positive. Default: 30 days.
+   */
+  softDeleteRetentionSec?:
+    | number
+    | undefined;
+  /**
+   * Records changes made through the API, i.e. the old and new values, the
+   * acting user and a timestamp. Exposed via `GET /<name>/<record>/history`.
+   */
+  enableHistory?:
+    | boolean
+    | undefined;
+  /**
+   * Column-level access control applying to everyone and to authenticated
+   * users, respectively. Authenticated users are subject to
+   * `column_acl_authenticated` if set and `column_acl_world` otherwise.
+   */
+  columnAclWorld?: ColumnAcl | undefined;
+  columnAclAuthenticated?:
+    | ColumnAcl
+    | undefined;
+  /** Derived values appended to read and listed records. */
+  computedFields: ComputedField[];
+  /** Size and type constraints for uploaded files. */
+  fileUploadLimits: FileUploadLimits[];
 }
 
 export interface QueryApiParameter {
@@ -399,7 +567,32 @@ export interface JsonSchemaConfig {
   schema?: string | undefined;
 }
 
+export interface WebhookConfig {
+  /** / Name of the record API, whose record changes trigger the webhook. */
+  apiName?:
+    | string
+    | undefined;
+  /** / Events triggering the webhook. Defaults to all events if empty. */
+  events: WebhookEvent[];
+  /** / URL events get POSTed to. */
+  url?:
+    | string
+    | undefined;
+  /**
+   * / Secret for signing payloads with HMAC-SHA256. If set, the hex-encoded
+   * / signature is sent as `X-TrailBase-Signature: sha256=<signature>` header.
+   */
+  secret?: string | undefined;
+}
+
 export interface Config {
+  /**
+   * Version of the config format, which lets TrailBase migrate configs written
+   * by prior versions. Unset for configs predating versioning.
+   */
+  version?:
+    | number
+    | undefined;
   /**
    * NOTE: These top-level fields currently have to be `required` due to the
    * overly simple approach on how we do config merging (from env vars and
@@ -411,6 +604,13 @@ export interface Config {
   recordApis: RecordApiConfig[];
   queryApis: QueryApiConfig[];
   schemas: JsonSchemaConfig[];
+  /** Webhooks keyed by name. */
+  webhooks: { [key: string]: WebhookConfig };
+}
+
+export interface Config_WebhooksEntry {
+  key: string;
+  value: WebhookConfig | undefined;
 }
 
 function createBaseEmailTemplate(): EmailTemplate {
@@ -1184,68 +1384,31 @@ export const ServerConfig: MessageFns<ServerConfig> = {
   },
 };
 
-function createBaseRecordApiConfig(): RecordApiConfig {
-  return {
-    name: "",
-    tableName: "",
-    conflictResolution: 0,
-    autofillMissingUserIdColumns: false,
-    aclWorld: [],
-    aclAuthenticated: [],
-    createAccessRule: "",
-    readAccessRule: "",
-    updateAccessRule: "",
-    deleteAccessRule: "",
-    schemaAccessRule: "",
-  };
+function createBaseColumnAcl(): ColumnAcl {
+  return { readAllow: [], readDeny: [], writeAllow: [], writeDeny: [] };
 }
 
-export const RecordApiConfig: MessageFns<RecordApiConfig> = {
-  encode(message: RecordApiConfig, writer: BinaryWriter = new BinaryWriter()): BinaryWriter {
-    if (message.name !== undefined && message.name !== "") {
-      writer.uint32(10).string(message.name);
-    }
-    if (message.tableName !== undefined && message.tableName !== "") {
-      writer.uint32(18).string(message.tableName);
-    }
-    if (message.conflictResolution !== undefined && message.conflictResolution !== 0) {
-      writer.uint32(40).int32(message.conflictResolution);
-    }
-    if (message.autofillMissingUserIdColumns !== undefined && message.autofillMissingUserIdColumns !== false) {
-      writer.uint32(48).bool(message.autofillMissingUserIdColumns);
-    }
-    writer.uint32(58).fork();
-    for (const v of message.aclWorld) {
-      writer.int32(v);
-    }
-    writer.join();
-    writer.uint32(66).fork();
-    for (const v of message.aclAuthenticated) {
-      writer.int32(v);
-    }
-    writer.join();
-    if (message.createAccessRule !== undefined && message.createAccessRule !== "") {
-      writer.uint32(90).string(message.createAccessRule);
-    }
-    if (message.readAccessRule !== undefined && message.readAccessRule !== "") {
-      writer.uint32(98).string(message.readAccessRule);
+export const ColumnAcl: MessageFns<ColumnAcl> = {
+  encode(message: ColumnAcl, writer: BinaryWriter = new BinaryWriter()): BinaryWriter {
+    for (const v of message.readAllow) {
+      writer.uint32(10).string(v!);
     }
-    if (message.updateAccessRule !== undefined && message.updateAccessRule !== "") {
-      writer.uint32(106).string(message.updateAccessRule);
+    for (const v of message.readDeny) {
+      writer.uint32(18).string(v!);
     }
-    if (message.deleteAccessRule !== undefined && message.deleteAccessRule !== "") {
-      writer.uint32(114).string(message.deleteAccessRule);
+    for (const v of message.writeAllow) {
+      writer.uint32(26).string(v!);
     }
-    if (message.schemaAccessRule !== undefined && message.schemaAccessRule !== "") {
-      writer.uint32(122).string(message.schemaAccessRule);
+    for (const v of message.writeDeny) {
+      writer.uint32(34).string(v!);
     }
     return writer;
   },
 
-  decode(input: BinaryReader | Uint8Array, length?: number): RecordApiConfig {
+  decode(input: BinaryReader | Uint8Array, length?: number): ColumnAcl {
     const reader = input instanceof BinaryReader ? input : new BinaryReader(input);
     let end = length === undefined ? reader.len : reader.pos + length;
-    const message = createBaseRecordApiConfig();
+    const message = createBaseColumnAcl();
     while (reader.pos < end) {
       const tag = reader.uint32();
       switch (tag >>> 3) {
@@ -1254,7 +1417,7 @@ export const RecordApiConfig: MessageFns<RecordApiConfig> = {
             break;
           }
 
-          message.name = reader.string();
+          message.readAllow.push(reader.string());
           continue;
         }
         case 2: {
@@ -1262,99 +1425,126 @@ export const RecordApiConfig: MessageFns<RecordApiConfig> = {
             break;
           }
 
-          message.tableName = reader.string();
+          message.readDeny.push(reader.string());
           continue;
         }
-        case 5: {
-          if (tag !== 40) {
+        case 3: {
+          if (tag !== 26) {
             break;
           }
 
-          message.conflictResolution = reader.int32() as any;
+          message.writeAllow.push(reader.string());
           continue;
         }
-        case 6: {
-          if (tag !== 48) {
+        case 4: {
+          if (tag !== 34) {
             break;
           }
 
-          message.autofillMissingUserIdColumns = reader.bool();
+          message.writeDeny.push(reader.string());
           continue;
         }
-        case 7: {
-          if (tag === 56) {
-            message.aclWorld.push(reader.int32() as any);
-
-            continue;
-          }
-
-          if (tag === 58) {
-            const end2 = reader.uint32() + reader.pos;
-            while (reader.pos < end2) {
-              message.aclWorld.push(reader.int32() as any);
-            }
-
-            continue;
-          }
-
-          break;
-        }
-        case 8: {
-          if (tag === 64) {
-            message.aclAuthenticated.push(reader.int32() as any);
+      }
+      if ((tag & 7) === 4 || tag === 0) {
+        break;
+      }
+      reader.skip(tag & 7);
+    }
+    return message;
+  },
 
-            continue;
-          }
+  fromJSON(object: any): ColumnAcl {
+    return {
+      readAllow: globalThis.Array.isArray(object?.readAllow)
+        ? object.readAllow.map((e: any) => globalThis.String(e))
+        : [],
+      readDeny: globalThis.Array.isArray(object?.readDeny) ? object.readDeny.map((e: any) => globalThis.String(e)) : [],
+      writeAllow: globalThis.Array.isArray(object?.writeAllow)
+        ? object.writeAllow.map((e: any) => globalThis.String(e))
+        : [],
+      writeDeny: globalThis.Array.isArray(object?.writeDeny)
+        ? object.writeDeny.map((e: any) => globalThis.String(e))
+        : [],
+    };
+  },
 
-          if (tag === 66) {
-            const end2 = reader.uint32() + reader.pos;
-            while (reader.pos < end2) {
-              message.aclAuthenticated.push(reader.int32() as any);
-            }
+  toJSON(message: ColumnAcl): unknown {
+    const obj: any = {};
+    if (message.readAllow?.length) {
+      obj.readAllow = message.readAllow;
+    }
+    if (message.readDeny?.length) {
+      obj.readDeny = message.readDeny;
+    }
+    if (message.writeAllow?.length) {
+      obj.writeAllow = message.writeAllow;
+    }
+    if (message.writeDeny?.length) {
+      obj.writeDeny = message.writeDeny;
+    }
+    return obj;
+  },
 
-            continue;
-          }
+  create<I extends Exact<DeepPartial<ColumnAcl>, I>>(base?: I): ColumnAcl {
+    return ColumnAcl.fromPartial(base ?? ({} as any));
+  },
+  fromPartial<I extends Exact<DeepPartial<ColumnAcl>, I>>(object: I): ColumnAcl {
+    const message = createBaseColumnAcl();
+    message.readAllow = object.readAllow?.map((e) => e) || [];
+    message.readDeny = object.readDeny?.map((e) => e) || [];
+    message.writeAllow = object.writeAllow?.map((e) => e) || [];
+    message.writeDeny = object.writeDeny?.map((e) => e) || [];
+    return message;
+  },
+};
 
-          break;
-        }
-        case 11: {
-          if (tag !== 90) {
-            break;
-          }
+function createBaseComputedField(): ComputedField {
+  return { name: "", expression: "", type: "" };
+}
 
-          message.createAccessRule = reader.string();
-          continue;
-        }
-        case 12: {
-          if (tag !== 98) {
-            break;
-          }
+export const ComputedField: MessageFns<ComputedField> = {
+  encode(message: ComputedField, writer: BinaryWriter = new BinaryWriter()): BinaryWriter {
+    if (message.name !== undefined && message.name !== "") {
+      writer.uint32(10).string(message.name);
+    }
+    if (message.expression !== undefined && message.expression !== "") {
+      writer.uint32(18).string(message.expression);
+    }
+    if (message.type !== undefined && message.type !== "") {
+      writer.uint32(26).string(message.type);
+    }
+    return writer;
+  },
 
-          message.readAccessRule = reader.string();
-          continue;
-        }
-        case 13: {
-          if (tag !== 106) {
+  decode(input: BinaryReader | Uint8Array, length?: number): ComputedField {
+    const reader = input instanceof BinaryReader ? input : new BinaryReader(input);
+    let end = length === undefined ? reader.len : reader.pos + length;
+    const message = createBaseComputedField();
+    while (reader.pos < end) {
+      const tag = reader.uint32();
+      switch (tag >>> 3) {
+        case 1: {
+          if (tag !== 10) {
             break;
           }
 
-          message.updateAccessRule = reader.string();
+          message.name = reader.string();
           continue;
         }
-        case 14: {
-          if (tag !== 114) {
+        case 2: {
+          if (tag !== 18) {
             break;
           }
 
-          message.deleteAccessRule = reader.string();
+          message.expression = reader.string();
           continue;
         }
-        case 15: {
-          if (tag !== 122) {
+        case 3: {
+          if (tag !== 26) {
             break;
           }
 
-          message.schemaAccessRule = reader.string();
+          message.type = reader.string();
           continue;
         }
       }
@@ -1366,12 +1556,466 @@ export const RecordApiConfig: MessageFns<RecordApiConfig> = {
     return message;
   },
 
-  fromJSON(object: any): RecordApiConfig {
+  fromJSON(object: any): ComputedField {
     return {
       name: isSet(object.name) ? globalThis.String(object.name) : "",
-      tableName: isSet(object.tableName) ? globalThis.String(object.tableName) : "",
-      conflictResolution: isSet(object.conflictResolution)
-        ? conflictResolutionStrategyFromJSON(object.conflictResolution)
+      expression: isSet(object.expression) ? globalThis.String(object.expression) : "",
+      type: isSet(object.type) ? globalThis.String(object.type) : "",
+    };
+  },
+
+  toJSON(message: ComputedField): unknown {
+    const obj: any = {};
+    if (message.name !== undefined && message.name !== "") {
+      obj.name = message.name;
+    }
+    if (message.expression !== undefined && message.expression !== "") {
+      obj.expression = message.expression;
+    }
+    if (message.type !== undefined && message.type !== "") {
+      obj.type = message.type;
+    }
+    return obj;
+  },
+
+  create<I extends Exact<DeepPartial<ComputedField>, I>>(base?: I): ComputedField {
+    return ComputedField.fromPartial(base ?? ({} as any));
+  },
+  fromPartial<I extends Exact<DeepPartial<ComputedField>, I>>(object: I): ComputedField {
+    const message = createBaseComputedField();
+    message.name = object.name ?? "";
+    message.expression = object.expression ?? "";
+    message.type = object.type ?? "";
+    return message;
+  },
+};
+
+function createBaseFileUploadLimits(): FileUploadLimits {
+  return { column: "", maxSize: 0, allowedMimeTypes: [] };
+}
+
+export const FileUploadLimits: MessageFns<FileUploadLimits> = {
+  encode(message: FileUploadLimits, writer: BinaryWriter = new BinaryWriter()): BinaryWriter {
+    if (message.column !== undefined && message.column !== "") {
+      writer.uint32(10).string(message.column);
+    }
+    if (message.maxSize !== undefined && message.maxSize !== 0) {
+      writer.uint32(16).uint64(message.maxSize);
+    }
+    for (const v of message.allowedMimeTypes) {
+      writer.uint32(26).string(v!);
+    }
+    return writer;
+  },
+
+  decode(input: BinaryReader | Uint8Array, length?: number): FileUploadLimits {
+    const reader = input instanceof BinaryReader ? input : new BinaryReader(input);
+    let end = length === undefined ? reader.len : reader.pos + length;
+    const message = createBaseFileUploadLimits();
+    while (reader.pos < end) {
+      const tag = reader.uint32();
+      switch (tag >>> 3) {
+        case 1: {
+          if (tag !== 10) {
+            break;
+          }
+
+          message.column = reader.string();
+          continue;
+        }
+        case 2: {
+          if (tag !== 16) {
+            break;
+          }
+
+          message.maxSize = longToNumber(reader.uint64());
+          continue;
+        }
+        case 3: {
+          if (tag !== 26) {
+            break;
+          }
+
+          message.allowedMimeTypes.push(reader.string());
+          continue;
+        }
+      }
+      if ((tag & 7) === 4 || tag === 0) {
+        break;
+      }
+      reader.skip(tag & 7);
+    }
+    return message;
+  },
+
+  fromJSON(object: any): FileUploadLimits {
+    return {
+      column: isSet(object.column) ? globalThis.String(object.column) : "",
+      maxSize: isSet(object.maxSize) ? globalThis.Number(object.maxSize) : 0,
+      allowedMimeTypes: globalThis.Array.isArray(object?.allowedMimeTypes)
+        ? object.allowedMimeTypes.map((e: any) => globalThis.String(e))
+        : [],
+    };
+  },
+
+  toJSON(message: FileUploadLimits): unknown {
+    const obj: any = {};
+    if (message.column !== undefined && message.column !== "") {
+      obj.column = message.column;
+    }
+    if (message.maxSize !== undefined && message.maxSize !== 0) {
+      obj.maxSize = Math.round(message.maxSize);
+    }
+    if (message.allowedMimeTypes?.length) {
+      obj.allowedMimeTypes = message.allowedMimeTypes;
+    }
+    return obj;
+  },
+
+  create<I extends Exact<DeepPartial<FileUploadLimits>, I>>(base?: I): FileUploadLimits {
+    return FileUploadLimits.fromPartial(base ?? ({} as any));
+  },
+  fromPartial<I extends Exact<DeepPartial<FileUploadLimits>, I>>(object: I): FileUploadLimits {
+    const message = createBaseFileUploadLimits();
+    message.column = object.column ?? "";
+    message.maxSize = object.maxSize ?? 0;
+    message.allowedMimeTypes = object.allowedMimeTypes?.map((e) => e) || [];
+    return message;
+  },
+};
+
+function createBaseRecordApiConfig(): RecordApiConfig {
+  return {
+    name: "",
+    tableName: "",
+    conflictResolution: 0,
+    autofillMissingUserIdColumns: false,
+    aclWorld: [],
+    aclAuthenticated: [],
+    createAccessRule: "",
+    readAccessRule: "",
+    updateAccessRule: "",
+    deleteAccessRule: "",
+    schemaAccessRule: "",
+    listAccessRule: "",
+    etagColumn: "",
+    searchColumns: [],
+    geoLatitudeColumn: "",
+    geoLongitudeColumn: "",
+    softDeleteColumn: "",
+    softDeleteRetentionSec: 0,
+    enableHistory: false,
+    columnAclWorld: undefined,
+    columnAclAuthenticated: undefined,
+    computedFields: [],
+    fileUploadLimits: [],
+  };
+}
+
+export const RecordApiConfig: MessageFns<RecordApiConfig> = {
+  encode(message: RecordApiConfig, writer: BinaryWriter = new BinaryWriter()): BinaryWriter {
+    if (message.name !== undefined && message.name !== "") {
+      writer.uint32(10).string(message.name);
+    }
+    if (message.tableName !== undefined && message.tableName !== "") {
+      writer.uint32(18).string(message.tableName);
+    }
+    if (message.conflictResolution !== undefined && message.conflictResolution !== 0) {
+      writer.uint32(40).int32(message.conflictResolution);
+    }
+    if (message.autofillMissingUserIdColumns !== undefined && message.autofillMissingUserIdColumns !== false) {
+      writer.uint32(48).bool(message.autofillMissingUserIdColumns);
+    }
+    writer.uint32(58).fork();
+    for (const v of message.aclWorld) {
+      writer.int32(v);
+    }
+    writer.join();
+    writer.uint32(66).fork();
+    for (const v of message.aclAuthenticated) {
+      writer.int32(v);
+    }
+    writer.join();
+    if (message.createAccessRule !== undefined && message.createAccessRule !== "") {
+      writer.uint32(90).string(message.createAccessRule);
+    }
+    if (message.readAccessRule !== undefined && message.readAccessRule !== "") {
+      writer.uint32(98).string(message.readAccessRule);
+    }
+    if (message.updateAccessRule !== undefined && message.updateAccessRule !== "") {
+      writer.uint32(106).string(message.updateAccessRule);
+    }
+    if (message.deleteAccessRule !== undefined && message.deleteAccessRule !== "") {
+      writer.uint32(114).string(message.deleteAccessRule);
+    }
+    if (message.schemaAccessRule !== undefined && message.schemaAccessRule !== "") {
+      writer.uint32(122).string(message.schemaAccessRule);
+    }
+    if (message.listAccessRule !== undefined && message.listAccessRule !== "") {
+      writer.uint32(130).string(message.listAccessRule);
+    }
+    if (message.etagColumn !== undefined && message.etagColumn !== "") {
+      writer.uint32(138).string(message.etagColumn);
+    }
+    for (const v of message.searchColumns) {
+      writer.uint32(146).string(v!);
+    }
+    if (message.geoLatitudeColumn !== undefined && message.geoLatitudeColumn !== "") {
+      writer.uint32(154).string(message.geoLatitudeColumn);
+    }
+    if (message.geoLongitudeColumn !== undefined && message.geoLongitudeColumn !== "") {
+      writer.uint32(162).string(message.geoLongitudeColumn);
+    }
+    if (message.softDeleteColumn !== undefined && message.softDeleteColumn !== "") {
+      writer.uint32(170).string(message.softDeleteColumn);
+    }
+    if (message.softDeleteRetentionSec !== undefined && message.softDeleteRetentionSec !== 0) {
+      writer.uint32(176).int64(message.softDeleteRetentionSec);
+    }
+    if (message.enableHistory !== undefined && message.enableHistory !== false) {
+      writer.uint32(184).bool(message.enableHistory);
+    }
+    if (message.columnAclWorld !== undefined) {
+      ColumnAcl.encode(message.columnAclWorld, writer.uint32(194).fork()).join();
+    }
+    if (message.columnAclAuthenticated !== undefined) {
+      ColumnAcl.encode(message.columnAclAuthenticated, writer.uint32(202).fork()).join();
+    }
+    for (const v of message.computedFields) {
+      ComputedField.encode(v!, writer.uint32(210).fork()).join();
+    }
+    for (const v of message.fileUploadLimits) {
+      FileUploadLimits.encode(v!, writer.uint32(218).fork()).join();
+    }
+    return writer;
+  },
+
+  decode(input: BinaryReader | Uint8Array, length?: number): RecordApiConfig {
+    const reader = input instanceof BinaryReader ? input : new BinaryReader(input);
+    let end = length === undefined ? reader.len : reader.pos + length;
+    const message = createBaseRecordApiConfig();
+    while (reader.pos < end) {
+      const tag = reader.uint32();
+      switch (tag >>> 3) {
+        case 1: {
+          if (tag !== 10) {
+            break;
+          }
+
+          message.name = reader.string();
+          continue;
+        }
+        case 2: {
+          if (tag !== 18) {
+            break;
+          }
+
+          message.tableName = reader.string();
+          continue;
+        }
+        case 5: {
+          if (tag !== 40) {
+            break;
+          }
+
+          message.conflictResolution = reader.int32() as any;
+          continue;
+        }
+        case 6: {
+          if (tag !== 48) {
+            break;
+          }
+
+          message.autofillMissingUserIdColumns = reader.bool();
+          continue;
+        }
+        case 7: {
+          if (tag === 56) {
+            message.aclWorld.push(reader.int32() as any);
+
+            continue;
+          }
+
+          if (tag === 58) {
+            const end2 = reader.uint32() + reader.pos;
+            while (reader.pos < end2) {
+              message.aclWorld.push(reader.int32() as any);
+            }
+
+            continue;
+          }
+
+          break;
+        }
+        case 8: {
+          if (tag === 64) {
+            message.aclAuthenticated.push(reader.int32() as any);
+
+            continue;
+          }
+
+          if (tag === 66) {
+            const end2 = reader.uint32() + reader.pos;
+            while (reader.pos < end2) {
+              message.aclAuthenticated.push(reader.int32() as any);
+            }
+
+            continue;
+          }
+
+          break;
+        }
+        case 11: {
+          if (tag !== 90) {
+            break;
+          }
+
+          message.createAccessRule = reader.string();
+          continue;
+        }
+        case 12: {
+          if (tag !== 98) {
+            break;
+          }
+
+          message.readAccessRule = reader.string();
+          continue;
+        }
+        case 13: {
+          if (tag !== 106) {
+            break;
+          }
+
+          message.updateAccessRule = reader.string();
+          continue;
+        }
+        case 14: {
+          if (tag !== 114) {
+            break;
+          }
+
+          message.deleteAccessRule = reader.string();
+          continue;
+        }
+        case 15: {
+          if (tag !== 122) {
+            break;
+          }
+
+          message.schemaAccessRule = reader.string();
+          continue;
+        }
+        case 16: {
+          if (tag !== 130) {
+            break;
+          }
+
+          message.listAccessRule = reader.string();
+          continue;
+        }
+        case 17: {
+          if (tag !== 138) {
+            break;
+          }
+
+          message.etagColumn = reader.string();
+          continue;
+        }
+        case 18: {
+          if (tag !== 146) {
+            break;
+          }
+
+          message.searchColumns.push(reader.string());
+          continue;
+        }
+        case 19: {
+          if (tag !== 154) {
+            break;
+          }
+
+          message.geoLatitudeColumn = reader.string();
+          continue;
+        }
+        case 20: {
+          if (tag !== 162) {
+            break;
+          }
+
+          message.geoLongitudeColumn = reader.string();
+          continue;
+        }
+        case 21: {
+          if (tag !== 170) {
+            break;
+          }
+
+          message.softDeleteColumn = reader.string();
+          continue;
+        }
+        case 22: {
+          if (tag !== 176) {
+            break;
+          }
+
+          message.softDeleteRetentionSec = longToNumber(reader.int64());
+          continue;
+        }
+        case 23: {
+          if (tag !== 184) {
+            break;
+          }
+
+          message.enableHistory = reader.bool();
+          continue;
+        }
+        case 24: {
+          if (tag !== 194) {
+            break;
+          }
+
+          message.columnAclWorld = ColumnAcl.decode(reader, reader.uint32());
+          continue;
+        }
+        case 25: {
+          if (tag !== 202) {
+            break;
+          }
+
+          message.columnAclAuthenticated = ColumnAcl.decode(reader, reader.uint32());
+          continue;
+        }
+        case 26: {
+          if (tag !== 210) {
+            break;
+          }
+
+          message.computedFields.push(ComputedField.decode(reader, reader.uint32()));
+          continue;
+        }
+        case 27: {
+          if (tag !== 218) {
+            break;
+          }
+
+          message.fileUploadLimits.push(FileUploadLimits.decode(reader, reader.uint32()));
+          continue;
+        }
+      }
+      if ((tag & 7) === 4 || tag === 0) {
+        break;
+      }
+      reader.skip(tag & 7);
+    }
+    return message;
+  },
+
+  fromJSON(object: any): RecordApiConfig {
+    return {
+      name: isSet(object.name) ? globalThis.String(object.name) : "",
+      tableName: isSet(object.tableName) ? globalThis.String(object.tableName) : "",
+      conflictResolution: isSet(object.conflictResolution)
+        ? conflictResolutionStrategyFromJSON(object.conflictResolution)
         : 0,
       autofillMissingUserIdColumns: isSet(object.autofillMissingUserIdColumns)
         ? globalThis.Boolean(object.autofillMissingUserIdColumns)
@@ -1387,6 +2031,26 @@ export const RecordApiConfig: MessageFns<RecordApiConfig> = {
       updateAccessRule: isSet(object.updateAccessRule) ? globalThis.String(object.updateAccessRule) : "",
       deleteAccessRule: isSet(object.deleteAccessRule) ? globalThis.String(object.deleteAccessRule) : "",
       schemaAccessRule: isSet(object.schemaAccessRule) ? globalThis.String(object.schemaAccessRule) : "",
+      listAccessRule: isSet(object.listAccessRule) ? globalThis.String(object.listAccessRule) : "",
+      etagColumn: isSet(object.etagColumn) ? globalThis.String(object.etagColumn) : "",
+      searchColumns: globalThis.Array.isArray(object?.searchColumns)
+        ? object.searchColumns.map((e: any) => globalThis.String(e))
+        : [],
+      geoLatitudeColumn: isSet(object.geoLatitudeColumn) ? globalThis.String(object.geoLatitudeColumn) : "",
+      geoLongitudeColumn: isSet(object.geoLongitudeColumn) ? globalThis.String(object.geoLongitudeColumn) : "",
+      softDeleteColumn: isSet(object.softDeleteColumn) ? globalThis.String(object.softDeleteColumn) : "",
+      softDeleteRetentionSec: isSet(object.softDeleteRetentionSec) ? globalThis.Number(object.softDeleteRetentionSec) : 0,
+      enableHistory: isSet(object.enableHistory) ? globalThis.Boolean(object.enableHistory) : false,
+      columnAclWorld: isSet(object.columnAclWorld) ? ColumnAcl.fromJSON(object.columnAclWorld) : undefined,
+      columnAclAuthenticated: isSet(object.columnAclAuthenticated)
+        ? ColumnAcl.fromJSON(object.columnAclAuthenticated)
+        : undefined,
+      computedFields: globalThis.Array.isArray(object?.computedFields)
+        ? object.computedFields.map((e: any) => ComputedField.fromJSON(e))
+        : [],
+      fileUploadLimits: globalThis.Array.isArray(object?.fileUploadLimits)
+        ? object.fileUploadLimits.map((e: any) => FileUploadLimits.fromJSON(e))
+        : [],
     };
   },
 
@@ -1425,6 +2089,42 @@ export const RecordApiConfig: MessageFns<RecordApiConfig> = {
     if (message.schemaAccessRule !== undefined && message.schemaAccessRule !== "") {
       obj.schemaAccessRule = message.schemaAccessRule;
     }
+    if (message.listAccessRule !== undefined && message.listAccessRule !== "") {
+      obj.listAccessRule = message.listAccessRule;
+    }
+    if (message.etagColumn !== undefined && message.etagColumn !== "") {
+      obj.etagColumn = message.etagColumn;
+    }
+    if (message.searchColumns?.length) {
+      obj.searchColumns = message.searchColumns;
+    }
+    if (message.geoLatitudeColumn !== undefined && message.geoLatitudeColumn !== "") {
+      obj.geoLatitudeColumn = message.geoLatitudeColumn;
+    }
+    if (message.geoLongitudeColumn !== undefined && message.geoLongitudeColumn !== "") {
+      obj.geoLongitudeColumn = message.geoLongitudeColumn;
+    }
+    if (message.softDeleteColumn !== undefined && message.softDeleteColumn !== "") {
+      obj.softDeleteColumn = message.softDeleteColumn;
+    }
+    if (message.softDeleteRetentionSec !== undefined && message.softDeleteRetentionSec !== 0) {
+      obj.softDeleteRetentionSec = Math.round(message.softDeleteRetentionSec);
+    }
+    if (message.enableHistory !== undefined && message.enableHistory !== false) {
+      obj.enableHistory = message.enableHistory;
+    }
+    if (message.columnAclWorld !== undefined) {
+      obj.columnAclWorld = ColumnAcl.toJSON(message.columnAclWorld);
+    }
+    if (message.columnAclAuthenticated !== undefined) {
+      obj.columnAclAuthenticated = ColumnAcl.toJSON(message.columnAclAuthenticated);
+    }
+    if (message.computedFields?.length) {
+      obj.computedFields = message.computedFields.map((e) => ComputedField.toJSON(e));
+    }
+    if (message.fileUploadLimits?.length) {
+      obj.fileUploadLimits = message.fileUploadLimits.map((e) => FileUploadLimits.toJSON(e));
+    }
     return obj;
   },
 
@@ -1444,6 +2144,23 @@ export const RecordApiConfig: MessageFns<RecordApiConfig> = {
     message.updateAccessRule = object.updateAccessRule ?? "";
     message.deleteAccessRule = object.deleteAccessRule ?? "";
     message.schemaAccessRule = object.schemaAccessRule ?? "";
+    message.listAccessRule = object.listAccessRule ?? "";
+    message.etagColumn = object.etagColumn ?? "";
+    message.searchColumns = object.searchColumns?.map((e) => e) || [];
+    message.geoLatitudeColumn = object.geoLatitudeColumn ?? "";
+    message.geoLongitudeColumn = object.geoLongitudeColumn ?? "";
+    message.softDeleteColumn = object.softDeleteColumn ?? "";
+    message.softDeleteRetentionSec = object.softDeleteRetentionSec ?? 0;
+    message.enableHistory = object.enableHistory ?? false;
+    message.columnAclWorld = (object.columnAclWorld !== undefined && object.columnAclWorld !== null)
+      ? ColumnAcl.fromPartial(object.columnAclWorld)
+      : undefined;
+    message.columnAclAuthenticated =
+      (object.columnAclAuthenticated !== undefined && object.columnAclAuthenticated !== null)
+        ? ColumnAcl.fromPartial(object.columnAclAuthenticated)
+        : undefined;
+    message.computedFields = object.computedFields?.map((e) => ComputedField.fromPartial(e)) || [];
+    message.fileUploadLimits = object.fileUploadLimits?.map((e) => FileUploadLimits.fromPartial(e)) || [];
     return message;
   },
 };
@@ -1726,12 +2443,144 @@ export const JsonSchemaConfig: MessageFns<JsonSchemaConfig> = {
   },
 };
 
+function createBaseWebhookConfig(): WebhookConfig {
+  return { apiName: "", events: [], url: "", secret: "" };
+}
+
+export const WebhookConfig: MessageFns<WebhookConfig> = {
+  encode(message: WebhookConfig, writer: BinaryWriter = new BinaryWriter()): BinaryWriter {
+    if (message.apiName !== undefined && message.apiName !== "") {
+      writer.uint32(10).string(message.apiName);
+    }
+    writer.uint32(18).fork();
+    for (const v of message.events) {
+      writer.int32(v);
+    }
+    writer.join();
+    if (message.url !== undefined && message.url !== "") {
+      writer.uint32(26).string(message.url);
+    }
+    if (message.secret !== undefined && message.secret !== "") {
+      writer.uint32(34).string(message.secret);
+    }
+    return writer;
+  },
+
+  decode(input: BinaryReader | Uint8Array, length?: number): WebhookConfig {
+    const reader = input instanceof BinaryReader ? input : new BinaryReader(input);
+    let end = length === undefined ? reader.len : reader.pos + length;
+    const message = createBaseWebhookConfig();
+    while (reader.pos < end) {
+      const tag = reader.uint32();
+      switch (tag >>> 3) {
+        case 1: {
+          if (tag !== 10) {
+            break;
+          }
+
+          message.apiName = reader.string();
+          continue;
+        }
+        case 2: {
+          if (tag === 16) {
+            message.events.push(reader.int32() as any);
+
+            continue;
+          }
+
+          if (tag === 18) {
+            const end2 = reader.uint32() + reader.pos;
+            while (reader.pos < end2) {
+              message.events.push(reader.int32() as any);
+            }
+
+            continue;
+          }
+
+          break;
+        }
+        case 3: {
+          if (tag !== 26) {
+            break;
+          }
+
+          message.url = reader.string();
+          continue;
+        }
+        case 4: {
+          if (tag !== 34) {
+            break;
+          }
+
+          message.secret = reader.string();
+          continue;
+        }
+      }
+      if ((tag & 7) === 4 || tag === 0) {
+        break;
+      }
+      reader.skip(tag & 7);
+    }
+    return message;
+  },
+
+  fromJSON(object: any): WebhookConfig {
+    return {
+      apiName: isSet(object.apiName) ? globalThis.String(object.apiName) : "",
+      events: globalThis.Array.isArray(object?.events) ? object.events.map((e: any) => webhookEventFromJSON(e)) : [],
+      url: isSet(object.url) ? globalThis.String(object.url) : "",
+      secret: isSet(object.secret) ? globalThis.String(object.secret) : "",
+    };
+  },
+
+  toJSON(message: WebhookConfig): unknown {
+    const obj: any = {};
+    if (message.apiName !== undefined && message.apiName !== "") {
+      obj.apiName = message.apiName;
+    }
+    if (message.events?.length) {
+      obj.events = message.events.map((e) => webhookEventToJSON(e));
+    }
+    if (message.url !== undefined && message.url !== "") {
+      obj.url = message.url;
+    }
+    if (message.secret !== undefined && message.secret !== "") {
+      obj.secret = message.secret;
+    }
+    return obj;
+  },
+
+  create<I extends Exact<DeepPartial<WebhookConfig>, I>>(base?: I): WebhookConfig {
+    return WebhookConfig.fromPartial(base ?? ({} as any));
+  },
+  fromPartial<I extends Exact<DeepPartial<WebhookConfig>, I>>(object: I): WebhookConfig {
+    const message = createBaseWebhookConfig();
+    message.apiName = object.apiName ?? "";
+    message.events = object.events?.map((e) => e) || [];
+    message.url = object.url ?? "";
+    message.secret = object.secret ?? "";
+    return message;
+  },
+};
+
 function createBaseConfig(): Config {
-  return { email: undefined, server: undefined, auth: undefined, recordApis: [], queryApis: [], schemas: [] };
+  return {
+    version: 0,
+    email: undefined,
+    server: undefined,
+    auth: undefined,
+    recordApis: [],
+    queryApis: [],
+    schemas: [],
+    webhooks: {},
+  };
 }
 
 export const Config: MessageFns<Config> = {
   encode(message: Config, writer: BinaryWriter = new BinaryWriter()): BinaryWriter {
+    if (message.version !== undefined && message.version !== 0) {
+      writer.uint32(8).uint32(message.version);
+    }
     if (message.email !== undefined) {
       EmailConfig.encode(message.email, writer.uint32(18).fork()).join();
     }
@@ -1750,6 +2599,9 @@ export const Config: MessageFns<Config> = {
     for (const v of message.schemas) {
       JsonSchemaConfig.encode(v!, writer.uint32(170).fork()).join();
     }
+    Object.entries(message.webhooks).forEach(([key, value]) => {
+      Config_WebhooksEntry.encode({ key: key as any, value }, writer.uint32(178).fork()).join();
+    });
     return writer;
   },
 
@@ -1760,6 +2612,14 @@ export const Config: MessageFns<Config> = {
     while (reader.pos < end) {
       const tag = reader.uint32();
       switch (tag >>> 3) {
+        case 1: {
+          if (tag !== 8) {
+            break;
+          }
+
+          message.version = reader.uint32();
+          continue;
+        }
         case 2: {
           if (tag !== 18) {
             break;
@@ -1808,6 +2668,17 @@ export const Config: MessageFns<Config> = {
           message.schemas.push(JsonSchemaConfig.decode(reader, reader.uint32()));
           continue;
         }
+        case 22: {
+          if (tag !== 178) {
+            break;
+          }
+
+          const entry22 = Config_WebhooksEntry.decode(reader, reader.uint32());
+          if (entry22.value !== undefined) {
+            message.webhooks[entry22.key] = entry22.value;
+          }
+          continue;
+        }
       }
       if ((tag & 7) === 4 || tag === 0) {
         break;
@@ -1819,6 +2690,7 @@ export const Config: MessageFns<Config> = {
 
   fromJSON(object: any): Config {
     return {
+      version: isSet(object.version) ? globalThis.Number(object.version) : 0,
       email: isSet(object.email) ? EmailConfig.fromJSON(object.email) : undefined,
       server: isSet(object.server) ? ServerConfig.fromJSON(object.server) : undefined,
       auth: isSet(object.auth) ? AuthConfig.fromJSON(object.auth) : undefined,
@@ -1831,11 +2703,20 @@ export const Config: MessageFns<Config> = {
       schemas: globalThis.Array.isArray(object?.schemas)
         ? object.schemas.map((e: any) => JsonSchemaConfig.fromJSON(e))
         : [],
+      webhooks: isObject(object.webhooks)
+        ? Object.entries(object.webhooks).reduce<{ [key: string]: WebhookConfig }>((acc, [key, value]) => {
+          acc[key] = WebhookConfig.fromJSON(value);
+          return acc;
+        }, {})
+        : {},
     };
   },
 
   toJSON(message: Config): unknown {
     const obj: any = {};
+    if (message.version !== undefined && message.version !== 0) {
+      obj.version = Math.round(message.version);
+    }
     if (message.email !== undefined) {
       obj.email = EmailConfig.toJSON(message.email);
     }
@@ -1854,6 +2735,15 @@ export const Config: MessageFns<Config> = {
     if (message.schemas?.length) {
       obj.schemas = message.schemas.map((e) => JsonSchemaConfig.toJSON(e));
     }
+    if (message.webhooks) {
+      const entries = Object.entries(message.webhooks);
+      if (entries.length > 0) {
+        obj.webhooks = {};
+        entries.forEach(([k, v]) => {
+          obj.webhooks[k] = WebhookConfig.toJSON(v);
+        });
+      }
+    }
     return obj;
   },
 
@@ -1862,6 +2752,7 @@ export const Config: MessageFns<Config> = {
   },
   fromPartial<I extends Exact<DeepPartial<Config>, I>>(object: I): Config {
     const message = createBaseConfig();
+    message.version = object.version ?? 0;
     message.email = (object.email !== undefined && object.email !== null)
       ? EmailConfig.fromPartial(object.email)
       : undefined;
@@ -1874,6 +2765,93 @@ export const Config: MessageFns<Config> = {
     message.recordApis = object.recordApis?.map((e) => RecordApiConfig.fromPartial(e)) || [];
     message.queryApis = object.queryApis?.map((e) => QueryApiConfig.fromPartial(e)) || [];
     message.schemas = object.schemas?.map((e) => JsonSchemaConfig.fromPartial(e)) || [];
+    message.webhooks = Object.entries(object.webhooks ?? {}).reduce<{ [key: string]: WebhookConfig }>(
+      (acc, [key, value]) => {
+        if (value !== undefined) {
+          acc[key] = WebhookConfig.fromPartial(value);
+        }
+        return acc;
+      },
+      {},
+    );
+    return message;
+  },
+};
+
+function createBaseConfig_WebhooksEntry(): Config_WebhooksEntry {
+  return { key: "", value: undefined };
+}
+
+export const Config_WebhooksEntry: MessageFns<Config_WebhooksEntry> = {
+  encode(message: Config_WebhooksEntry, writer: BinaryWriter = new BinaryWriter()): BinaryWriter {
+    if (message.key !== "") {
+      writer.uint32(10).string(message.key);
+    }
+    if (message.value !== undefined) {
+      WebhookConfig.encode(message.value, writer.uint32(18).fork()).join();
+    }
+    return writer;
+  },
+
+  decode(input: BinaryReader | Uint8Array, length?: number): Config_WebhooksEntry {
+    const reader = input instanceof BinaryReader ? input : new BinaryReader(input);
+    let end = length === undefined ? reader.len : reader.pos + length;
+    const message = createBaseConfig_WebhooksEntry();
+    while (reader.pos < end) {
+      const tag = reader.uint32();
+      switch (tag >>> 3) {
+        case 1: {
+          if (tag !== 10) {
+            break;
+          }
+
+          message.key = reader.string();
+          continue;
+        }
+        case 2: {
+          if (tag !== 18) {
+            break;
+          }
+
+          message.value = WebhookConfig.decode(reader, reader.uint32());
+          continue;
+        }
+      }
+      if ((tag & 7) === 4 || tag === 0) {
+        break;
+      }
+      reader.skip(tag & 7);
+    }
+    return message;
+  },
+
+  fromJSON(object: any): Config_WebhooksEntry {
+    return {
+      key: isSet(object.key) ? globalThis.String(object.key) : "",
+      value: isSet(object.value) ? WebhookConfig.fromJSON(object.value) : undefined,
+    };
+  },
+
+  toJSON(message: Config_WebhooksEntry): unknown {
+    const obj: any = {};
+    if (message.key !== "") {
+      obj.key = message.key;
+    }
+    if (message.value !== undefined) {
+      obj.value = WebhookConfig.toJSON(message.value);
+    }
+    return obj;
+  },
+
+  create<I extends Exact<DeepPartial<Config_WebhooksEntry>, I>>(base?: I): Config_WebhooksEntry {
+    return Config_WebhooksEntry.fromPartial(base ?? ({} as any));
+  },
+  fromPartial<I extends Exact<DeepPartial<Config_WebhooksEntry>, I>>(object: I): Config_WebhooksEntry {
+    const message = createBaseConfig_WebhooksEntry();
+    message.key = object.key ?? "";
+    message.value = (object.value !== undefined && object.value !== null)
+      ? WebhookConfig.fromPartial(object.value)
+      : undefined;
     return message;
   },
 };
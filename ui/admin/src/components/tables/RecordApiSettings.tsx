@@ -40,6 +40,7 @@ import { tableType } from "@/lib/schema";
 const tablePermissions = {
   Create: PermissionFlag.CREATE,
   Read: PermissionFlag.READ,
+  List: PermissionFlag.LIST,
   Update: PermissionFlag.UPDATE,
   Delete: PermissionFlag.DELETE,
   Schema: PermissionFlag.SCHEMA,
@@ -47,6 +48,7 @@ const tablePermissions = {
 
 const viewPermissions = {
   Read: PermissionFlag.READ,
+  List: PermissionFlag.LIST,
   Schema: PermissionFlag.SCHEMA,
 } as const;
 
@@ -62,8 +64,8 @@ function AclForm(props: {
   return (
     <div class="flex">
       <div
-        class="grid items-end gap-2 w-[300px]"
-        style="grid-template-columns: auto 1fr 1fr 1fr 1fr 1fr"
+        class="grid items-end gap-2 w-[340px]"
+        style="grid-template-columns: auto 1fr 1fr 1fr 1fr 1fr 1fr"
       >
         {props.showHeader && (
           <For
@@ -125,6 +127,12 @@ const tableAccessRules: AccessRule[] = [
     description:
       'Row- and request-level read access (_user_, _row_, _req_): If the table has an "owner"\'s column containing binary user ids, access could be rstricted to the owner by setting \'_row_.owner = _user_\' here. Or if the table as a foreign key to a "group" and a relationship defined in a "membership" table: \'(SELECT 1 FROM membership WHERE group = _row_.group AND user = _user_)\'',
   },
+  {
+    field: "listAccessRule",
+    label: "List access:",
+    description:
+      "Row-level filter for listed records based on _USER_, _ROW_. Falls back to the read access rule if unset.",
+  },
   {
     field: "createAccessRule",
     label: "Create access:",
@@ -157,6 +165,12 @@ const viewAccessRules: AccessRule[] = [
     description:
       'Row- and request-level read access (_user_, _row_, _req_): If the table has an "owner"\'s column containing binary user ids, access could be rstricted to the owner by setting \'_row_.owner = _user_\' here. Or if the table as a foreign key to a "group" and a relationship defined in a "membership" table: \'(SELECT 1 FROM membership WHERE group = _row_.group AND user = _user_)\'',
   },
+  {
+    field: "listAccessRule",
+    label: "List access:",
+    description:
+      "Row-level filter for listed records based on _USER_, _ROW_. Falls back to the read access rule if unset.",
+  },
   {
     field: "schemaAccessRule",
     label: "Schema Access",